@@ -0,0 +1,198 @@
+//! Checking that inputs refer to the same genome assembly
+//!
+//! Annotations, tracks and sequences from different assemblies, or with different sequence
+//! naming conventions, silently give empty or wrong results when combined. [`check_compatibility`]
+//! compares the sequence dictionaries of two inputs before they are used together.
+
+use indexmap::IndexMap;
+
+use crate::genome::SeqId;
+use crate::sequence::Genome;
+
+/// The length, and optionally the MD5 digest, of a sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceInfo {
+    pub len: u64,
+    pub md5: Option<String>,
+}
+
+/// The sequences of an input, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceDictionary {
+    pub sequences: IndexMap<SeqId, SequenceInfo>,
+}
+
+impl SequenceDictionary {
+    /// Makes a dictionary from sequence lengths, with no digests
+    pub fn from_lengths(seq_lens: &IndexMap<SeqId, u64>) -> SequenceDictionary {
+        SequenceDictionary {
+            sequences: seq_lens.iter().map(|(seqid, len)| (seqid.clone(), SequenceInfo { len: *len, md5: None })).collect(),
+        }
+    }
+
+    /// Makes a dictionary from the sequences of a genome, with MD5 digests if they have been computed
+    pub fn from_genome(genome: &Genome) -> SequenceDictionary {
+        SequenceDictionary {
+            sequences: genome.seqids()
+                .map(|seqid| (seqid.clone(), SequenceInfo {
+                    len: genome.seq_len(seqid).unwrap(),
+                    md5: genome.digests(seqid).map(|d| d.md5.clone()),
+                }))
+                .collect(),
+        }
+    }
+}
+
+/// Whether two inputs can be used together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The same sequences with the same names
+    Identical,
+    /// The same sequences, some with different names, which can be used together after renaming
+    AliasCompatible,
+    /// Different sequences
+    Incompatible,
+}
+
+/// A sequence with the same name in both inputs but different contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub seqid: SeqId,
+    pub first: SequenceInfo,
+    pub second: SequenceInfo,
+}
+
+/// The result of comparing the sequence dictionaries of two inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    pub verdict: Verdict,
+    /// Sequences in the first input matched to differently named sequences in the second
+    pub aliases: IndexMap<SeqId, SeqId>,
+    pub mismatches: Vec<Mismatch>,
+    /// Sequences of the first input with no match in the second
+    pub only_in_first: Vec<SeqId>,
+    /// Sequences of the second input with no match in the first
+    pub only_in_second: Vec<SeqId>,
+}
+
+/// Compares the sequence dictionaries of two inputs
+///
+/// Sequences with the same name should have the same length, and the same MD5 digest where both
+/// have one. Sequences with different names are matched as aliases when their digests are equal or,
+/// without digests, when their length is unique among the unmatched sequences of both inputs.
+/// With `subset_ok`, sequences of either input without a match don't make the inputs incompatible,
+/// as when an annotation has no features on some sequences, and the verdict is for the shared sequences.
+pub fn check_compatibility(first: &SequenceDictionary, second: &SequenceDictionary, subset_ok: bool) -> CompatibilityReport {
+    let mut mismatches = Vec::new();
+    let mut unmatched_first = Vec::new();
+    for (seqid, info) in &first.sequences {
+        match second.sequences.get(seqid) {
+            Some(other) => {
+                let same_md5 = match (&info.md5, &other.md5) {
+                    (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                    _ => true,
+                };
+                if info.len != other.len || !same_md5 {
+                    mismatches.push(Mismatch { seqid: seqid.clone(), first: info.clone(), second: other.clone() });
+                }
+            }
+            None => unmatched_first.push(seqid),
+        }
+    }
+    let mut unmatched_second: Vec<&SeqId> = second.sequences.keys().filter(|s| !first.sequences.contains_key(*s)).collect();
+
+    let mut aliases = IndexMap::new();
+    let mut only_in_first = Vec::new();
+    for seqid in unmatched_first {
+        let info = &first.sequences[seqid];
+        let by_md5 = info.md5.as_ref().and_then(|md5| {
+            unmatched_second.iter().position(|s| second.sequences[*s].md5.as_ref().is_some_and(|m| m.eq_ignore_ascii_case(md5)))
+        });
+        let by_len = || {
+            let candidates: Vec<usize> = unmatched_second.iter().enumerate()
+                .filter(|(_, s)| {
+                    let other = &second.sequences[**s];
+                    other.len == info.len && (info.md5.is_none() || other.md5.is_none())
+                })
+                .map(|(i, _)| i)
+                .collect();
+            let same_len_in_first = first.sequences.iter()
+                .filter(|(s, other)| !second.sequences.contains_key(*s) && other.len == info.len)
+                .count();
+            (candidates.len() == 1 && same_len_in_first == 1).then(|| candidates[0])
+        };
+        match by_md5.or_else(by_len) {
+            Some(i) => {
+                aliases.insert(seqid.clone(), unmatched_second.remove(i).clone());
+            }
+            None => only_in_first.push(seqid.clone()),
+        }
+    }
+    let only_in_second: Vec<SeqId> = unmatched_second.into_iter().cloned().collect();
+
+    let complete = subset_ok || (only_in_first.is_empty() && only_in_second.is_empty());
+    let nothing_shared = only_in_first.len() == first.sequences.len() && !(first.sequences.is_empty() && second.sequences.is_empty());
+    let verdict = if !mismatches.is_empty() || !complete || nothing_shared {
+        Verdict::Incompatible
+    } else if aliases.is_empty() {
+        Verdict::Identical
+    } else {
+        Verdict::AliasCompatible
+    };
+    CompatibilityReport {
+        verdict,
+        aliases,
+        mismatches,
+        only_in_first,
+        only_in_second,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(seq_lens: &[(&str, u64)]) -> SequenceDictionary {
+        SequenceDictionary::from_lengths(&seq_lens.iter().map(|(seqid, len)| (SeqId::from(*seqid), *len)).collect())
+    }
+
+    #[test]
+    fn matches_renamed_sequences_by_unique_length() {
+        let ucsc = dictionary(&[("chr1", 100), ("chr2", 200), ("chrM", 16)]);
+        let ensembl = dictionary(&[("1", 100), ("2", 200), ("MT", 16)]);
+        assert_eq!(check_compatibility(&ucsc, &ucsc, false).verdict, Verdict::Identical);
+        let report = check_compatibility(&ucsc, &ensembl, false);
+        assert_eq!(report.verdict, Verdict::AliasCompatible);
+        assert_eq!(report.aliases.get(&SeqId::from("chrM")), Some(&SeqId::from("MT")));
+
+        let ambiguous = check_compatibility(&dictionary(&[("a", 100), ("b", 100)]), &dictionary(&[("x", 100), ("y", 100)]), false);
+        assert_eq!((ambiguous.verdict, ambiguous.aliases.len()), (Verdict::Incompatible, 0));
+    }
+
+    #[test]
+    fn reports_mismatches_and_missing_sequences() {
+        let first = dictionary(&[("chr1", 100), ("chr2", 200), ("chrM", 16)]);
+        let report = check_compatibility(&first, &dictionary(&[("chr1", 101), ("chr2", 200), ("chrM", 16)]), true);
+        assert_eq!((report.verdict, report.mismatches[0].seqid.to_string()), (Verdict::Incompatible, "chr1".to_string()));
+
+        let subset = dictionary(&[("chr1", 100), ("chr2", 200)]);
+        let report = check_compatibility(&first, &subset, false);
+        assert_eq!((report.verdict, report.only_in_first.clone()), (Verdict::Incompatible, vec![SeqId::from("chrM")]));
+        assert_eq!(check_compatibility(&first, &subset, true).verdict, Verdict::Identical);
+    }
+
+    #[test]
+    fn matches_sequences_by_digest() {
+        let mut first = Genome::default();
+        first.insert("chr1", b"ACGT".to_vec());
+        first.insert("chr2", b"GGGG".to_vec());
+        first.compute_digests();
+        let mut second = Genome::default();
+        second.insert("1", b"GGGG".to_vec());
+        second.insert("2", b"ACGT".to_vec());
+        second.compute_digests();
+        let report = check_compatibility(&SequenceDictionary::from_genome(&first), &SequenceDictionary::from_genome(&second), false);
+        assert_eq!(report.verdict, Verdict::AliasCompatible);
+        assert_eq!(report.aliases[&SeqId::from("chr1")], SeqId::from("2"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Channels of records between threads
+//!
+//! [`record_channel`] connects the stages of a pipeline running on different threads, such as a
+//! parser feeding an annotation step. The channel is bounded, so a fast producer waits for a slow
+//! consumer rather than holding the whole file in memory. Errors are sent down the channel like
+//! records, so the consumer sees a parse error where it happened in the stream.
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
+use crate::genome::Error;
+
+/// Creates a channel holding at most `capacity` records in flight
+///
+/// A capacity of 0 makes every send wait until the record is received.
+pub fn record_channel<T>(capacity: usize) -> (RecordSender<T>, RecordReceiver<T>) {
+    let (sender, receiver) = sync_channel(capacity);
+    (RecordSender(sender), RecordReceiver(receiver))
+}
+
+/// The sending end of a [`record_channel`], which can be cloned for several producers
+///
+/// The channel is closed once every sender is dropped.
+#[derive(Debug)]
+pub struct RecordSender<T>(SyncSender<Result<T, Error>>);
+
+impl<T> Clone for RecordSender<T> {
+    fn clone(&self) -> Self {
+        RecordSender(self.0.clone())
+    }
+}
+
+impl<T> RecordSender<T> {
+    /// Sends a record, waiting while the channel is full
+    ///
+    /// Fails if the receiver has been dropped.
+    pub fn send(&self, record: T) -> Result<(), Error> {
+        self.send_result(Ok(record))
+    }
+
+    /// Sends a record or an error, waiting while the channel is full
+    pub fn send_result(&self, result: Result<T, Error>) -> Result<(), Error> {
+        self.0.send(result).map_err(|_| Error::InvalidArguments("record receiver was dropped".to_string()))
+    }
+
+    /// Sends every record or error of an iterator, such as a parser, stopping early if the receiver has been dropped
+    pub fn send_all<I: IntoIterator<Item = Result<T, Error>>>(&self, results: I) -> Result<(), Error> {
+        results.into_iter().try_for_each(|result| self.send_result(result))
+    }
+}
+
+/// The receiving end of a [`record_channel`], iterating over the records until every sender is dropped
+#[derive(Debug)]
+pub struct RecordReceiver<T>(Receiver<Result<T, Error>>);
+
+impl<T> RecordReceiver<T> {
+    /// Receives the next record or error, waiting until one is sent, or `None` once every sender is dropped
+    pub fn recv(&self) -> Option<Result<T, Error>> {
+        self.0.recv().ok()
+    }
+
+    /// Receives the next record or error if one is waiting, without blocking
+    pub fn try_recv(&self) -> Option<Result<T, Error>> {
+        self.0.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for RecordReceiver<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn passes_records_and_errors_in_order_between_threads() {
+        let (sender, receiver) = record_channel(1);
+        let producer = thread::spawn(move || {
+            let results = (1..=3).map(Ok).chain([Err(Error::InvalidArguments("bad line".to_string()))]);
+            sender.send_all(results)
+        });
+        let received: Vec<_> = receiver.map(|r| r.map_err(|e| e.to_string())).collect();
+        producer.join().unwrap().unwrap();
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[..3], [Ok(1), Ok(2), Ok(3)]);
+        assert!(received[3].as_ref().unwrap_err().contains("bad line"));
+    }
+
+    #[test]
+    fn closes_when_either_end_is_dropped() {
+        let (sender, receiver) = record_channel::<u32>(2);
+        let other = sender.clone();
+        sender.send(1).unwrap();
+        drop(sender);
+        other.send(2).unwrap();
+        drop(other);
+        assert_eq!(receiver.try_recv().map(|r| r.unwrap()), Some(1));
+        assert_eq!(receiver.recv().map(|r| r.unwrap()), Some(2));
+        assert!(receiver.recv().is_none());
+
+        let (sender, receiver) = record_channel::<u32>(2);
+        drop(receiver);
+        assert!(sender.send(1).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Copy-number segmentation of binned coverage
+//!
+//! Log2 ratios of coverage in bins, e.g. tumour over normal, are split into segments of constant mean
+//! with the PELT changepoint method, under a Gaussian model of the ratios. The segments are a lightweight
+//! copy-number call, and can be written as BedGraph.
+
+use indexmap::IndexMap;
+use num_traits::{NumOps, ToPrimitive};
+
+use crate::format::{BedGraphRow, DataInterval};
+use crate::genome::{Error, GenomicRange, SeqId};
+
+/// A segment of bins with the same copy number
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyNumberSegment {
+    pub range: GenomicRange,
+    /// The number of bins in the segment
+    pub bins: usize,
+    /// The mean of the values of the bins
+    pub mean: f64,
+}
+
+impl CopyNumberSegment {
+    pub fn to_bedgraph_row(&self) -> BedGraphRow<f64> {
+        let range = self.range.range_0halfopen();
+        BedGraphRow {
+            chrom: self.range.seqid().clone(),
+            chrom_start: range.start,
+            chrom_end: range.end,
+            data_value: self.mean,
+        }
+    }
+}
+
+/// The median of the values, which are reordered
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] }
+}
+
+/// Finds the starts of the segments of the values by PELT, with the cost of a segment of constant mean
+fn pelt(values: &[f64], penalty: f64, min_bins: usize) -> Vec<usize> {
+    let n = values.len();
+    let mut prefix = vec![0.0; n + 1];
+    for (i, value) in values.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + value;
+    }
+    // the sum of squares is left out of the cost, as it is the same for any segmentation
+    let cost = |s: usize, t: usize| -(prefix[t] - prefix[s]).powi(2) / (t - s) as f64;
+    let mut best = vec![f64::INFINITY; n + 1];
+    let mut last = vec![0; n + 1];
+    best[0] = -penalty;
+    let mut candidates = vec![0];
+    for t in min_bins..=n {
+        for s in candidates.iter().copied().filter(|s| t - s >= min_bins) {
+            let total = best[s] + cost(s, t) + penalty;
+            if total < best[t] {
+                best[t] = total;
+                last[t] = s;
+            }
+        }
+        candidates.retain(|s| t - s < min_bins || best[*s] + cost(*s, t) <= best[t]);
+        if best[t].is_finite() {
+            candidates.push(t);
+        }
+    }
+    let mut starts = Vec::new();
+    let mut t = n;
+    while t > 0 {
+        t = last[t];
+        starts.push(t);
+    }
+    starts.reverse();
+    starts
+}
+
+/// Segments the values in `column` of binned log2 ratios, on each sequence separately
+///
+/// Bins with no value are skipped, and the bins of each sequence are sorted by position. A changepoint
+/// costs `penalty` × σ² × ln(n), where n is the number of bins of the sequence and σ is estimated from
+/// the median absolute difference of neighbouring bins, so a penalty of 2 is a BIC-like default and higher
+/// penalties give fewer segments. Segments have at least `min_bins` bins, unless the sequence has fewer.
+/// Each segment spans from the start of its first bin to the end of its last.
+pub fn segment_copy_number<T, I>(bins: I, column: usize, penalty: f64, min_bins: usize) -> Result<Vec<CopyNumberSegment>, Error>
+where
+    T: NumOps + Copy + ToPrimitive,
+    I: IntoIterator<Item = DataInterval<T>>,
+{
+    if penalty < 0.0 {
+        return Err(Error::InvalidArguments(format!("penalty should be at least 0, not {}", penalty)));
+    }
+    let mut by_seqid: IndexMap<SeqId, Vec<(u64, u64, f64)>> = IndexMap::new();
+    for bin in bins {
+        let Some(value) = bin.values().get(column).copied().flatten() else {
+            continue;
+        };
+        let value = value.to_f64()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| Error::InvalidArguments(format!("value of bin at {}:{} should be a finite number", bin.range().seqid(), bin.range().range_0halfopen().start)))?;
+        let range = bin.range().range_0halfopen();
+        by_seqid.entry(bin.range().seqid().clone()).or_default().push((range.start, range.end, value));
+    }
+
+    let mut segments = Vec::new();
+    for (seqid, mut seq_bins) in by_seqid {
+        seq_bins.sort_by_key(|(start, end, _)| (*start, *end));
+        let values: Vec<f64> = seq_bins.iter().map(|(_, _, value)| *value).collect();
+        let mut differences: Vec<f64> = values.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+        let sigma = if differences.is_empty() { 0.0 } else { median(&mut differences) / (0.6745 * 2f64.sqrt()) };
+        let beta = penalty * sigma.powi(2) * (values.len() as f64).ln().max(1.0);
+        let min_bins = min_bins.clamp(1, values.len());
+        let starts = pelt(&values, beta, min_bins);
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(values.len());
+            let mean = values[*start..end].iter().sum::<f64>() / (end - start) as f64;
+            segments.push(CopyNumberSegment {
+                range: GenomicRange::from_0halfopen(seqid.clone(), seq_bins[*start].0..seq_bins[end - 1].1)?,
+                bins: end - start,
+                mean,
+            });
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bins(seqid: &str, values: &[f64]) -> Vec<DataInterval<f64>> {
+        values.iter().enumerate()
+            .map(|(i, value)| DataInterval::new(GenomicRange::from_0halfopen(seqid, i as u64 * 100..(i as u64 + 1) * 100).unwrap(), vec![Some(*value)]))
+            .collect()
+    }
+
+    /// Values with alternating noise of ±0.05 around the given levels
+    fn noisy(levels: &[(f64, usize)]) -> Vec<f64> {
+        levels.iter()
+            .flat_map(|(level, n)| std::iter::repeat_n(*level, *n))
+            .enumerate()
+            .map(|(i, level)| if i % 2 == 0 { level + 0.05 } else { level - 0.05 })
+            .collect()
+    }
+
+    #[test]
+    fn pelt_finds_exact_changepoints() {
+        assert_eq!(pelt(&[0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0], 0.1, 1), [0, 3, 7]);
+        assert_eq!(pelt(&[0.0, 0.0, 5.0, 0.0, 0.0], 0.1, 1), [0, 2, 3]);
+        assert_eq!(pelt(&[0.0, 0.0, 5.0, 0.0, 0.0], 15.0, 1), [0]);
+    }
+
+    #[test]
+    fn segments_steps_in_noisy_ratios() {
+        let mut input = bins("chr1", &noisy(&[(0.0, 20), (1.0, 20)]));
+        input.extend(bins("chr2", &noisy(&[(-0.5, 30)])));
+        let segments = segment_copy_number(input, 0, 2.0, 3).unwrap();
+        let summary: Vec<_> = segments.iter().map(|s| (s.range.to_string(), s.bins, (s.mean * 100.0).round() / 100.0)).collect();
+        assert_eq!(summary, [
+            ("chr1:1-2000".to_string(), 20, 0.0),
+            ("chr1:2001-4000".to_string(), 20, 1.0),
+            ("chr2:1-3000".to_string(), 30, -0.5),
+        ]);
+        assert_eq!(segments[1].to_bedgraph_row().chrom_start, 2000);
+    }
+
+    #[test]
+    fn negative_penalties_are_an_error() {
+        assert!(segment_copy_number(bins("chr1", &[0.0]), 0, -1.0, 1).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Codon usage of coding sequences
+//!
+//! Codon usage tables are used for codon optimisation of expressed genes, and as a check on
+//! annotations, where CDS in the wrong frame have unusual codon usage. The table gives the
+//! frequency of each codon and its relative synonymous codon usage (RSCU), the ratio of its count
+//! to the mean count of the codons for the same amino acid.
+
+use std::fmt;
+use serde::Serialize;
+
+use crate::genome::Error;
+use crate::models::Gene;
+use crate::sequence::{translate_codon, Genome};
+
+const BASES: &[u8; 4] = b"TCAG";
+
+/// Codon counts over a set of coding sequences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodonUsage {
+    /// Counts of the 64 codons, indexed in `TCAG` order
+    counts: [u64; 64],
+    /// The number of coding sequences counted
+    pub sequences: usize,
+    /// The number of codons with ambiguous bases, which are not counted
+    pub ambiguous: u64,
+}
+
+impl Default for CodonUsage {
+    fn default() -> Self {
+        CodonUsage {
+            counts: [0; 64],
+            sequences: 0,
+            ambiguous: 0,
+        }
+    }
+}
+
+/// One row of a codon usage table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodonUsageRow {
+    /// The codon in DNA bases
+    pub codon: String,
+    /// The amino acid in the standard genetic code, or `*` for stop
+    pub amino_acid: char,
+    pub count: u64,
+    /// Occurrences per thousand codons
+    pub per_thousand: f64,
+    /// The fraction of the codons for the amino acid
+    pub fraction: Option<f64>,
+    /// Relative synonymous codon usage, 1 when the synonymous codons are used equally
+    pub rscu: Option<f64>,
+}
+
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    let index = |base: u8| match base.to_ascii_uppercase() {
+        b'U' => Some(0),
+        base => BASES.iter().position(|b| *b == base),
+    };
+    match codon {
+        [a, b, c] => Some(index(*a)? * 16 + index(*b)? * 4 + index(*c)?),
+        _ => None,
+    }
+}
+
+fn codon_at(index: usize) -> [u8; 3] {
+    [BASES[index / 16], BASES[index / 4 % 4], BASES[index % 4]]
+}
+
+impl CodonUsage {
+    /// Adds the codons of a coding sequence, from its first base, ignoring any incomplete final codon
+    pub fn add_sequence(&mut self, sequence: &[u8]) {
+        for codon in sequence.chunks_exact(3) {
+            match codon_index(codon) {
+                Some(i) => self.counts[i] += 1,
+                None => self.ambiguous += 1,
+            }
+        }
+        self.sequences += 1;
+    }
+
+    /// Counts the codons of the CDS of every coding transcript
+    ///
+    /// Alternative isoforms are all counted, so genes with several isoforms have more weight;
+    /// use [`CodonUsage::add_sequence`] with e.g. the longest isoforms to avoid this.
+    pub fn from_gene_models<T>(genes: &[Gene<T>], genome: &Genome) -> Result<CodonUsage, Error> {
+        let mut usage = CodonUsage::default();
+        for transcript in genes.iter().flat_map(|g| &g.transcripts).filter(|t| t.is_coding()) {
+            usage.add_sequence(&transcript.cds_sequence(genome)?);
+        }
+        Ok(usage)
+    }
+
+    /// Returns the count of a codon, in DNA or RNA bases
+    pub fn count(&self, codon: &str) -> Option<u64> {
+        codon_index(codon.as_bytes()).map(|i| self.counts[i])
+    }
+
+    /// The total number of codons counted
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Adds the counts of another table
+    pub fn combine(&mut self, other: &CodonUsage) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.sequences += other.sequences;
+        self.ambiguous += other.ambiguous;
+    }
+
+    /// Returns the codon usage table, with codons in the standard `TCAG` order
+    pub fn table(&self) -> Vec<CodonUsageRow> {
+        let total = self.total();
+        let amino_acids: Vec<u8> = (0..64).map(|i| translate_codon(&codon_at(i))).collect();
+        (0..64)
+            .map(|i| {
+                let synonymous: Vec<usize> = (0..64).filter(|&j| amino_acids[j] == amino_acids[i]).collect();
+                let amino_acid_total: u64 = synonymous.iter().map(|&j| self.counts[j]).sum();
+                let fraction = (amino_acid_total > 0).then(|| self.counts[i] as f64 / amino_acid_total as f64);
+                CodonUsageRow {
+                    codon: String::from_utf8_lossy(&codon_at(i)).into_owned(),
+                    amino_acid: amino_acids[i] as char,
+                    count: self.counts[i],
+                    per_thousand: if total > 0 { self.counts[i] as f64 * 1000.0 / total as f64 } else { 0.0 },
+                    fraction,
+                    rscu: fraction.map(|f| f * synonymous.len() as f64),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Writes the table as tab-separated values, with a header
+impl fmt::Display for CodonUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "codon\tamino_acid\tcount\tper_thousand\tfraction\trscu")?;
+        let optional = |value: Option<f64>| value.map_or_else(|| ".".to_string(), |v| format!("{:.2}", v));
+        for row in self.table() {
+            writeln!(f, "{}\t{}\t{}\t{:.2}\t{}\t{}", row.codon, row.amino_acid, row.count, row.per_thousand, optional(row.fraction), optional(row.rscu))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_codons_and_synonymous_usage() {
+        let mut usage = CodonUsage::default();
+        usage.add_sequence(b"ATGGCTGCTgcaAANTAAGC");
+        assert_eq!((usage.total(), usage.ambiguous, usage.sequences), (5, 1, 1));
+        assert_eq!((usage.count("AUG"), usage.count("GCT"), usage.count("GC")), (Some(1), Some(2), None));
+
+        let table = usage.table();
+        assert_eq!(table.len(), 64);
+        let gct = table.iter().find(|row| row.codon == "GCT").unwrap();
+        assert_eq!((gct.amino_acid, gct.per_thousand), ('A', 400.0));
+        assert_eq!(gct.fraction, Some(2.0 / 3.0));
+        assert_eq!(gct.rscu, Some(8.0 / 3.0));
+        let taa = table.iter().find(|row| row.codon == "TAA").unwrap();
+        assert_eq!((taa.amino_acid, taa.rscu), ('*', Some(3.0)));
+
+        let mut combined = usage.clone();
+        combined.combine(&usage);
+        assert_eq!((combined.count("GCT"), combined.sequences), (Some(4), 2));
+
+        let text = usage.to_string();
+        assert_eq!(text.lines().nth(1), Some("TTT\tF\t0\t0.00\t.\t."));
+        assert_eq!(text.lines().count(), 65);
+    }
+}
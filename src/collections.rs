@@ -0,0 +1,725 @@
+//! Collections of genomic ranges
+//!
+//! A [`GenomicRangeSet`] holds any number of ranges, which may overlap, sorted by seqid and position.
+//! It is the starting point for bedtools-style operations such as merging. Once merged, ranges can be
+//! held as [`NormalizedRanges`], whose invariant is guaranteed by construction. Set operations between
+//! collections return the merged ranges as [`NormalizedRanges`]. [`closest`] finds the nearest
+//! reference ranges to each query, as `bedtools closest`, and [`neighborhood`] the features around a
+//! locus, in order. [`shuffle`] places ranges at random, for null distributions.
+
+use indexmap::IndexMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::format::Strand;
+use crate::genome::{ChromSizes, Error, GenomicRange, NormalizedRanges, SeqId, StrandedGenomicRange};
+
+/// Ranges sorted by seqid and position, keeping overlapping and repeated ranges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GenomicRangeSet(Vec<GenomicRange>);
+
+impl GenomicRangeSet {
+    pub fn new() -> GenomicRangeSet {
+        GenomicRangeSet::default()
+    }
+
+    /// Adds a range, after any equal ranges
+    pub fn insert(&mut self, range: GenomicRange) {
+        let i = self.0.partition_point(|r| *r <= range);
+        self.0.insert(i, range);
+    }
+
+    pub fn as_slice(&self) -> &[GenomicRange] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<GenomicRange> {
+        self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, GenomicRange> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The seqids of the ranges, in order
+    pub fn seqids(&self) -> Vec<&SeqId> {
+        let mut seqids: Vec<&SeqId> = self.0.iter().map(|r| r.seqid()).collect();
+        seqids.dedup();
+        seqids
+    }
+
+    /// The ranges on a sequence
+    pub fn on_seqid(&self, seqid: &SeqId) -> &[GenomicRange] {
+        let first = self.0.partition_point(|r| r.seqid() < seqid);
+        let last = self.0.partition_point(|r| r.seqid() <= seqid);
+        &self.0[first..last]
+    }
+
+    /// Merges ranges that overlap, are adjacent, or are separated by at most `min_gap` bases
+    ///
+    /// Empty ranges are removed, as for [`NormalizedRanges`].
+    pub fn merge(&self, min_gap: u64) -> GenomicRangeSet {
+        let mut merged: Vec<GenomicRange> = Vec::with_capacity(self.0.len());
+        for range in self.0.iter().filter(|r| !r.is_empty()) {
+            match merged.last_mut() {
+                Some(last) if last.seqid() == range.seqid() && range.range_0halfopen().start <= last.range_0halfopen().end.saturating_add(min_gap) => {
+                    *last = last.combine(range).unwrap();
+                }
+                _ => merged.push(range.clone()),
+            }
+        }
+        GenomicRangeSet(merged)
+    }
+
+    /// Assigns each range a cluster, numbered from 0, in the order of the set
+    ///
+    /// Ranges on the same seqid that overlap, are adjacent, or are separated by at most `max_gap`
+    /// bases are in the same cluster, as with `bedtools cluster -d`. Unlike [`merge`](Self::merge),
+    /// empty ranges are kept, each in a cluster.
+    pub fn cluster(&self, max_gap: u64) -> Vec<usize> {
+        let mut labels = Vec::with_capacity(self.0.len());
+        // the seqid and end of the current cluster
+        let mut current: Option<(&SeqId, u64)> = None;
+        for range in &self.0 {
+            let interval = range.range_0halfopen();
+            let joins = match &mut current {
+                Some((seqid, end)) if *seqid == range.seqid() && interval.start <= end.saturating_add(max_gap) => {
+                    *end = (*end).max(interval.end);
+                    true
+                }
+                _ => {
+                    current = Some((range.seqid(), interval.end));
+                    false
+                }
+            };
+            labels.push(labels.last().map_or(0, |label| label + !joins as usize));
+        }
+        labels
+    }
+
+    /// The extent of each cluster of [`cluster`](Self::cluster), indexed by cluster
+    pub fn cluster_extents(&self, max_gap: u64) -> Vec<GenomicRange> {
+        let mut extents: Vec<GenomicRange> = Vec::new();
+        for (range, label) in self.0.iter().zip(self.cluster(max_gap)) {
+            match extents.get_mut(label) {
+                Some(extent) => *extent = extent.combine(range).unwrap(),
+                None => extents.push(range.clone()),
+            }
+        }
+        extents
+    }
+
+    /// Splits the ranges into disjoint segments, at every start and end, with the indexes of the ranges covering each segment
+    ///
+    /// This is the disjoin of the ranges, as with `GenomicRanges::disjoin`: every segment is covered by
+    /// the same ranges throughout, and bases covered by no range have no segment. Segments are in order,
+    /// and empty ranges cover no segment.
+    pub fn partition(&self) -> Vec<(GenomicRange, Vec<usize>)> {
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < self.0.len() {
+            let seqid = self.0[i].seqid();
+            let seqid_end = i + self.0[i..].partition_point(|r| r.seqid() == seqid);
+            // the starts and ends on the seqid, with the index of the range
+            let mut boundaries: Vec<(u64, bool, usize)> = Vec::new();
+            for j in (i..seqid_end).filter(|j| !self.0[*j].is_empty()) {
+                let interval = self.0[j].range_0halfopen();
+                boundaries.push((interval.start, true, j));
+                boundaries.push((interval.end, false, j));
+            }
+            boundaries.sort_unstable();
+            let mut covering: Vec<usize> = Vec::new();
+            let mut k = 0;
+            while k < boundaries.len() {
+                let pos = boundaries[k].0;
+                while k < boundaries.len() && boundaries[k].0 == pos {
+                    let (_, is_start, j) = boundaries[k];
+                    if is_start {
+                        covering.push(j);
+                    } else {
+                        covering.retain(|c| *c != j);
+                    }
+                    k += 1;
+                }
+                if let (Some(next), false) = (boundaries.get(k), covering.is_empty()) {
+                    let mut sources = covering.clone();
+                    sources.sort_unstable();
+                    segments.push((GenomicRange::from_0halfopen(seqid.clone(), pos..next.0).unwrap(), sources));
+                }
+            }
+            i = seqid_end;
+        }
+        segments
+    }
+
+    /// The number of bases covered by any of the ranges, counting overlapping bases once
+    pub fn total_covered_bases(&self) -> u64 {
+        self.merge(0).0.iter().map(|r| r.len()).sum()
+    }
+}
+
+impl GenomicRangeSet {
+    fn normalized(&self) -> NormalizedRanges {
+        self.0.iter().cloned().collect()
+    }
+
+    /// The bases covered by either set
+    pub fn union(&self, other: &GenomicRangeSet) -> NormalizedRanges {
+        self.0.iter().chain(&other.0).cloned().collect()
+    }
+
+    /// The bases covered by both sets
+    pub fn intersection(&self, other: &GenomicRangeSet) -> NormalizedRanges {
+        let (first, second) = (self.normalized(), other.normalized());
+        let (first, second) = (first.as_slice(), second.as_slice());
+        let mut shared = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < first.len() && j < second.len() {
+            let (a, b) = (&first[i], &second[j]);
+            if let Some(overlap) = a.intersect(b) {
+                shared.push(overlap);
+            }
+            // advance the range that ends first, in the order of the sets
+            let a_end = (a.seqid(), a.range_0halfopen().end);
+            let b_end = (b.seqid(), b.range_0halfopen().end);
+            if a_end <= b_end { i += 1 } else { j += 1 }
+        }
+        shared.into()
+    }
+
+    /// The bases covered by this set but not the other
+    pub fn difference(&self, other: &GenomicRangeSet) -> NormalizedRanges {
+        let other = other.normalized();
+        let mut remaining = Vec::new();
+        for range in self.normalized() {
+            let mut pieces = vec![range.clone()];
+            for subtracted in other.overlapping(&range) {
+                pieces = pieces.iter().flat_map(|piece| piece.subtract(subtracted)).collect();
+            }
+            remaining.extend(pieces);
+        }
+        remaining.into()
+    }
+
+    /// The Jaccard index of the sets: the bases covered by both over the bases covered by either
+    ///
+    /// This is 0 if neither set covers any bases, as with `bedtools jaccard`.
+    pub fn jaccard(&self, other: &GenomicRangeSet) -> f64 {
+        let union: u64 = self.union(other).iter().map(|r| r.len()).sum();
+        let intersection: u64 = self.intersection(other).iter().map(|r| r.len()).sum();
+        if union == 0 { 0.0 } else { intersection as f64 / union as f64 }
+    }
+
+    /// The pairs of ranges of this set and the other, by their indexes, that overlap by at least
+    /// `fraction` of the range of this set, and if `reciprocal` of the other range too
+    ///
+    /// Pairs are in the order of this set, then the other. See [`GenomicRange::overlaps_by`].
+    pub fn overlap_pairs(&self, other: &GenomicRangeSet, fraction: f64, reciprocal: bool) -> Vec<(usize, usize)> {
+        let longest = other.0.iter().map(|r| r.len()).max().unwrap_or(0);
+        let mut pairs = Vec::new();
+        for (i, range) in self.0.iter().enumerate() {
+            let interval = range.range_0halfopen();
+            // ranges of the other set starting too far before this one can't reach it
+            let first = other.0.partition_point(|r| (r.seqid(), r.range_0halfopen().start.saturating_add(longest)) <= (range.seqid(), interval.start));
+            let last = other.0.partition_point(|r| (r.seqid(), r.range_0halfopen().start) < (range.seqid(), interval.end));
+            pairs.extend((first..last).filter(|j| range.overlaps_by(&other.0[*j], fraction, reciprocal)).map(|j| (i, j)));
+        }
+        pairs
+    }
+
+    /// The bases of the sequences not covered by the set
+    ///
+    /// Fails if a range is on a sequence without a length. Ranges beyond the end of their sequence are
+    /// truncated.
+    pub fn complement(&self, seq_lens: &IndexMap<SeqId, u64>) -> Result<NormalizedRanges, Error> {
+        if let Some(range) = self.0.iter().find(|r| !seq_lens.contains_key(r.seqid())) {
+            return Err(Error::InvalidArguments(format!("sequence {} has no length", range.seqid())));
+        }
+        let covered = self.normalized();
+        let mut gaps = Vec::new();
+        for (seqid, len) in seq_lens {
+            let mut start = 0;
+            for range in covered.on_seqid(seqid) {
+                let interval = range.range_0halfopen();
+                let gap_end = interval.start.min(*len);
+                if gap_end > start {
+                    gaps.push(GenomicRange::from_0halfopen(seqid.clone(), start..gap_end)?);
+                }
+                start = start.max(interval.end);
+            }
+            if start < *len {
+                gaps.push(GenomicRange::from_0halfopen(seqid.clone(), start..*len)?);
+            }
+        }
+        Ok(gaps.into())
+    }
+}
+
+/// Sorts the ranges
+impl FromIterator<GenomicRange> for GenomicRangeSet {
+    fn from_iter<I: IntoIterator<Item = GenomicRange>>(iter: I) -> Self {
+        let mut ranges: Vec<GenomicRange> = iter.into_iter().collect();
+        ranges.sort();
+        GenomicRangeSet(ranges)
+    }
+}
+
+impl From<Vec<GenomicRange>> for GenomicRangeSet {
+    fn from(ranges: Vec<GenomicRange>) -> Self {
+        ranges.into_iter().collect()
+    }
+}
+
+impl From<NormalizedRanges> for GenomicRangeSet {
+    fn from(ranges: NormalizedRanges) -> Self {
+        GenomicRangeSet(ranges.into_vec())
+    }
+}
+
+impl From<GenomicRangeSet> for NormalizedRanges {
+    fn from(set: GenomicRangeSet) -> Self {
+        set.0.into_iter().collect()
+    }
+}
+
+impl Extend<GenomicRange> for GenomicRangeSet {
+    fn extend<I: IntoIterator<Item = GenomicRange>>(&mut self, iter: I) {
+        self.0.extend(iter);
+        self.0.sort();
+    }
+}
+
+impl IntoIterator for GenomicRangeSet {
+    type Item = GenomicRange;
+    type IntoIter = std::vec::IntoIter<GenomicRange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a GenomicRangeSet {
+    type Item = &'a GenomicRange;
+    type IntoIter = std::slice::Iter<'a, GenomicRange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl AsRef<[GenomicRange]> for GenomicRangeSet {
+    fn as_ref(&self) -> &[GenomicRange] {
+        &self.0
+    }
+}
+
+/// Which reference ranges can be closest to a query, by strand
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StrandRule {
+    #[default]
+    Ignore,
+    Same,
+    Opposite,
+}
+
+/// Options for [`closest`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClosestOptions {
+    pub strand: StrandRule,
+    /// Whether to report every reference range tied for closest, or only the first by position
+    pub all_ties: bool,
+    /// Whether to skip overlapping reference ranges, to find the closest that don't overlap
+    pub ignore_overlaps: bool,
+}
+
+/// A reference range closest to a query, by their indexes in the inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closest {
+    pub query: usize,
+    /// The reference range, or `None` if there is none on the sequence of the query
+    pub reference: Option<usize>,
+    /// The distance relative to the strand of the query, negative when the reference is upstream of it
+    /// and 0 when they overlap or are adjacent
+    pub distance: Option<i64>,
+}
+
+/// Finds the closest reference ranges to each query, in the order of the queries
+///
+/// Queries on no strand are oriented as on the plus strand.
+pub fn closest(queries: &[StrandedGenomicRange], references: &[StrandedGenomicRange], options: &ClosestOptions) -> Vec<Closest> {
+    // the reference indexes on each seqid, sorted by start and by end, with the longest range
+    let mut by_seqid: IndexMap<&SeqId, (Vec<usize>, Vec<usize>, u64)> = IndexMap::new();
+    for (i, reference) in references.iter().enumerate() {
+        let (by_start, by_end, longest) = by_seqid.entry(reference.range.seqid()).or_default();
+        by_start.push(i);
+        by_end.push(i);
+        *longest = (*longest).max(reference.range.len());
+    }
+    for (by_start, by_end, _) in by_seqid.values_mut() {
+        by_start.sort_by_key(|i| references[*i].range.range_0halfopen().start);
+        by_end.sort_by_key(|i| references[*i].range.range_0halfopen().end);
+    }
+
+    let mut results = Vec::new();
+    for (q, query) in queries.iter().enumerate() {
+        let allowed = |i: &usize| match options.strand {
+            StrandRule::Ignore => true,
+            StrandRule::Same => references[*i].strand == query.strand,
+            StrandRule::Opposite => references[*i].strand == query.strand.flip() && query.strand != Strand::None,
+        };
+        let interval = query.range.range_0halfopen();
+        let mut candidates: Vec<usize> = Vec::new();
+        if let Some((by_start, by_end, longest)) = by_seqid.get(query.range.seqid()) {
+            let start_of = |i: &usize| references[*i].range.range_0halfopen().start;
+            let end_of = |i: &usize| references[*i].range.range_0halfopen().end;
+            // the first allowed ranges starting at or after the end of the query, with any ties
+            let after = by_start.partition_point(|i| start_of(i) < interval.end);
+            if let Some(offset) = by_start[after..].iter().position(allowed) {
+                let first = by_start[after + offset];
+                candidates.extend(by_start[after + offset..].iter().take_while(|i| start_of(i) == start_of(&first)).filter(|i| allowed(i)));
+            }
+            // the last allowed ranges ending at or before the start of the query, with any ties
+            let before = by_end.partition_point(|i| end_of(i) <= interval.start);
+            if let Some(offset) = by_end[..before].iter().rev().position(allowed) {
+                let last = by_end[before - offset - 1];
+                candidates.extend(by_end[..before - offset].iter().rev().take_while(|i| end_of(i) == end_of(&last)).filter(|i| allowed(i)));
+            }
+            if !options.ignore_overlaps {
+                let first = by_start.partition_point(|i| start_of(i).saturating_add(*longest) <= interval.start);
+                candidates.extend(by_start[first..after].iter().filter(|i| allowed(i) && end_of(i) > interval.start));
+                // empty queries overlap nothing, but are closest to the ranges containing them
+                if interval.is_empty() {
+                    candidates.extend(by_start[first..after].iter().filter(|i| allowed(i) && end_of(i) == interval.start && start_of(i) < interval.start));
+                }
+            }
+        }
+        let distance = |i: usize| {
+            let distance = query.range.signed_distance(&references[i].range).unwrap();
+            if query.strand == Strand::Minus { -distance } else { distance }
+        };
+        let Some(min) = candidates.iter().map(|i| distance(*i).unsigned_abs()).min() else {
+            results.push(Closest { query: q, reference: None, distance: None });
+            continue;
+        };
+        let mut closest: Vec<usize> = candidates.into_iter().filter(|i| distance(*i).unsigned_abs() == min).collect();
+        closest.sort_by_key(|i| (&references[*i].range, *i));
+        closest.dedup();
+        if !options.all_ties {
+            closest.truncate(1);
+        }
+        results.extend(closest.into_iter().map(|i| Closest { query: q, reference: Some(i), distance: Some(distance(i)) }));
+    }
+    results
+}
+
+/// How far around a locus to take features for [`neighborhood`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborhoodExtent {
+    /// The nearest features on each side
+    Features(usize),
+    /// Features within this many bases of the locus
+    Distance(u64),
+}
+
+/// A feature around a locus, relative to the strand of the locus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbor {
+    /// The index of the feature in the input
+    pub feature: usize,
+    /// The position of the feature in order from the locus, negative upstream, positive downstream and
+    /// 0 for features overlapping the locus
+    pub offset: i64,
+    /// The distance from the locus, negative upstream, as [`Closest::distance`]
+    pub distance: i64,
+    /// [`Strand::Plus`] if the feature is on the same strand as the locus, [`Strand::Minus`] if it is on
+    /// the opposite strand, and [`Strand::None`] if either is on no strand
+    pub relative_strand: Strand,
+}
+
+/// Finds the features around a locus, in order from upstream to downstream
+///
+/// A locus on no strand is oriented as on the plus strand. Features overlapping the locus have an offset
+/// of 0, and features at the same distance on a side share the same offset.
+pub fn neighborhood(locus: &StrandedGenomicRange, features: &[StrandedGenomicRange], extent: NeighborhoodExtent) -> Vec<Neighbor> {
+    let reversed = locus.strand == Strand::Minus;
+    let mut neighbors: Vec<Neighbor> = features.iter()
+        .enumerate()
+        .filter_map(|(i, feature)| {
+            let distance = locus.range.signed_distance(&feature.range)?;
+            let overlaps = feature.range.overlaps(&locus.range) || feature.range.is_empty() && locus.range.contains(&feature.range);
+            let relative_strand = match (locus.strand, feature.strand) {
+                (Strand::None, _) | (_, Strand::None) => Strand::None,
+                (a, b) if a == b => Strand::Plus,
+                _ => Strand::Minus,
+            };
+            // a provisional offset giving the side, as adjacent features are at a distance of 0
+            let after = feature.range.range_0halfopen().start >= locus.range.range_0halfopen().end;
+            let (offset, distance) = match (overlaps, reversed) {
+                (true, _) => (0, 0),
+                (false, true) => (if after { -1 } else { 1 }, -distance),
+                (false, false) => (if after { 1 } else { -1 }, distance),
+            };
+            Some(Neighbor { feature: i, offset, distance, relative_strand })
+        })
+        .collect();
+    neighbors.sort_by_key(|n| (n.offset, n.distance, &features[n.feature].range, n.feature));
+
+    // number the features on each side outwards from the locus
+    let (upstream, rest): (Vec<Neighbor>, Vec<Neighbor>) = neighbors.into_iter().partition(|n| n.offset < 0);
+    let (overlapping, downstream): (Vec<Neighbor>, Vec<Neighbor>) = rest.into_iter().partition(|n| n.offset == 0);
+    let number = |side: Vec<Neighbor>, sign: i64| -> Vec<Neighbor> {
+        let mut numbered: Vec<Neighbor> = Vec::with_capacity(side.len());
+        for mut neighbor in side {
+            neighbor.offset = match numbered.last() {
+                Some(last) if last.distance == neighbor.distance => last.offset,
+                Some(last) => last.offset + sign,
+                None => sign,
+            };
+            numbered.push(neighbor);
+        }
+        numbered.retain(|n| match extent {
+            NeighborhoodExtent::Features(n_features) => n.offset.unsigned_abs() <= n_features as u64,
+            NeighborhoodExtent::Distance(max) => n.distance.unsigned_abs() <= max,
+        });
+        numbered
+    };
+    let mut upstream = number(upstream.into_iter().rev().collect(), -1);
+    upstream.reverse();
+    upstream.extend(overlapping);
+    upstream.extend(number(downstream, 1));
+    upstream
+}
+
+/// Options for [`shuffle`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShuffleOptions {
+    /// Whether each range stays on its own sequence
+    pub same_seqid: bool,
+    /// Regions the shuffled ranges may not overlap, such as assembly gaps or blacklisted regions
+    pub exclude: Option<NormalizedRanges>,
+}
+
+/// The positions where a range of a length fits in the allowed intervals
+struct Placements<'a> {
+    intervals: Vec<&'a GenomicRange>,
+    /// The number of starts in each interval and those before it
+    cumulative: Vec<u64>,
+    len: u64,
+}
+
+impl<'a> Placements<'a> {
+    fn new(allowed: &'a [GenomicRange], len: u64) -> Placements<'a> {
+        let intervals: Vec<&GenomicRange> = allowed.iter().filter(|i| i.len() >= len).collect();
+        let cumulative = intervals.iter()
+            .scan(0, |total, interval| {
+                *total += interval.len() - len + 1;
+                Some(*total)
+            })
+            .collect();
+        Placements { intervals, cumulative, len }
+    }
+
+    /// Picks a position uniformly, or `None` if there are none
+    fn pick(&self, rng: &mut StdRng) -> Option<GenomicRange> {
+        let pick = rng.random_range(0..*self.cumulative.last()?);
+        let i = self.cumulative.partition_point(|c| *c <= pick);
+        let before = if i == 0 { 0 } else { self.cumulative[i - 1] };
+        let start = self.intervals[i].range_0halfopen().start + pick - before;
+        Some(GenomicRange::from_0halfopen(self.intervals[i].seqid().clone(), start..start + self.len).unwrap())
+    }
+}
+
+/// Places each range at a random position in the genome, keeping its length, as `bedtools shuffle`
+///
+/// Every position where a range fits without leaving its sequence or overlapping the excluded regions is
+/// equally likely, so sequences are chosen in proportion to their length. Shuffled ranges may overlap
+/// each other. The ranges are returned in the order given, and the same seed gives the same placement.
+/// Fails if a range fits nowhere, or is on a sequence with no length when kept on its sequence.
+pub fn shuffle(ranges: &[GenomicRange], chrom_sizes: &ChromSizes, options: &ShuffleOptions, seed: u64) -> Result<Vec<GenomicRange>, Error> {
+    let allowed = match &options.exclude {
+        Some(exclude) => GenomicRangeSet::from(exclude.clone()).complement(chrom_sizes)?,
+        None => chrom_sizes.iter()
+            .filter(|(_, len)| **len > 0)
+            .map(|(seqid, len)| GenomicRange::from_0halfopen(seqid.clone(), 0..*len).unwrap())
+            .collect(),
+    };
+    // the placements for each length, by seqid or genome-wide
+    let mut placements: IndexMap<(Option<&SeqId>, u64), Placements> = IndexMap::new();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut shuffled = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        if options.same_seqid && !chrom_sizes.contains_key(range.seqid()) {
+            return Err(Error::InvalidArguments(format!("sequence {} has no length", range.seqid())));
+        }
+        let scope = options.same_seqid.then(|| range.seqid());
+        let len = range.len();
+        let placement = placements.entry((scope, len))
+            .or_insert_with(|| match scope {
+                Some(seqid) => Placements::new(allowed.on_seqid(seqid), len),
+                None => Placements::new(allowed.as_slice(), len),
+            })
+            .pick(&mut rng)
+            .ok_or_else(|| Error::InvalidArguments(format!("{} of length {} fits nowhere in the allowed regions", range, len)))?;
+        shuffled.push(placement);
+    }
+    Ok(shuffled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[&str]) -> GenomicRangeSet {
+        ranges.iter().map(|r| r.parse::<GenomicRange>().unwrap()).collect()
+    }
+
+    fn strings<'a, I: IntoIterator<Item = &'a GenomicRange>>(ranges: I) -> Vec<String> {
+        ranges.into_iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn sets_are_sorted_and_merge_within_a_gap() {
+        let mut ranges = set(&["chr2:1-10", "chr1:21-30", "chr1:1-10"]);
+        ranges.insert("chr1:5-15".parse().unwrap());
+        assert_eq!(strings(&ranges), ["chr1:1-10", "chr1:5-15", "chr1:21-30", "chr2:1-10"]);
+        assert_eq!(ranges.seqids(), [&SeqId::from("chr1"), &SeqId::from("chr2")]);
+        assert_eq!(ranges.on_seqid(&SeqId::from("chr2")).len(), 1);
+        assert_eq!(strings(&ranges.merge(0)), ["chr1:1-15", "chr1:21-30", "chr2:1-10"]);
+        assert_eq!(strings(&ranges.merge(5)), ["chr1:1-30", "chr2:1-10"]);
+        assert_eq!(ranges.total_covered_bases(), 35);
+    }
+
+    #[test]
+    fn set_operations_return_merged_ranges() {
+        let a = set(&["chr1:1-20", "chr1:41-60"]);
+        let b = set(&["chr1:11-50", "chr2:1-10"]);
+        assert_eq!(strings(&a.union(&b)), ["chr1:1-60", "chr2:1-10"]);
+        assert_eq!(strings(&a.intersection(&b)), ["chr1:11-20", "chr1:41-50"]);
+        assert_eq!(strings(&a.difference(&b)), ["chr1:1-10", "chr1:51-60"]);
+        let seq_lens = IndexMap::from([(SeqId::from("chr1"), 100), (SeqId::from("chr2"), 10)]);
+        assert_eq!(strings(&a.complement(&seq_lens).unwrap()), ["chr1:21-40", "chr1:61-100", "chr2:1-10"]);
+        assert!(b.complement(&IndexMap::from([(SeqId::from("chr1"), 100)])).is_err());
+    }
+
+    fn stranded(range: &str, strand: Strand) -> StrandedGenomicRange {
+        StrandedGenomicRange::new(range.parse().unwrap(), strand)
+    }
+
+    #[test]
+    fn closest_distances_are_relative_to_the_query_strand() {
+        let references = [
+            stranded("chr1:101-110", Strand::Plus),
+            stranded("chr1:131-140", Strand::Minus),
+            stranded("chr1:81-90", Strand::Minus),
+            stranded("chr1:115-125", Strand::Plus),
+        ];
+        let queries = [stranded("chr1:111-112", Strand::Plus), stranded("chr1:120-121", Strand::Minus), stranded("chr2:1-10", Strand::Plus)];
+        let found = |options: ClosestOptions| -> Vec<(usize, Option<usize>, Option<i64>)> {
+            closest(&queries, &references, &options).iter().map(|c| (c.query, c.reference, c.distance)).collect()
+        };
+        assert_eq!(found(ClosestOptions::default()), [(0, Some(0), Some(0)), (1, Some(3), Some(0)), (2, None, None)]);
+
+        // without the overlapping range, the ranges 9 bases to either side are tied
+        let options = ClosestOptions { ignore_overlaps: true, all_ties: true, ..Default::default() };
+        assert_eq!(found(options)[1..3], [(1, Some(0), Some(9)), (1, Some(1), Some(-9))]);
+        assert_eq!(found(ClosestOptions { strand: StrandRule::Same, ..Default::default() })[1], (1, Some(1), Some(-9)));
+        assert_eq!(found(ClosestOptions { strand: StrandRule::Opposite, ..Default::default() })[1], (1, Some(3), Some(0)));
+    }
+
+    #[test]
+    fn closest_skips_nearer_ranges_on_the_wrong_strand() {
+        let references = [
+            stranded("chr1:221-230", Strand::Minus),
+            stranded("chr1:301-310", Strand::Plus),
+            stranded("chr1:181-190", Strand::Minus),
+            stranded("chr1:101-110", Strand::Plus),
+        ];
+        let same = ClosestOptions { strand: StrandRule::Same, all_ties: true, ..Default::default() };
+        let found: Vec<_> = closest(&[stranded("chr1:201-210", Strand::Plus)], &references, &same).iter().map(|c| (c.reference, c.distance)).collect();
+        assert_eq!(found, [(Some(3), Some(-90)), (Some(1), Some(90))]);
+        let opposite = ClosestOptions { strand: StrandRule::Opposite, ..Default::default() };
+        let found = closest(&[stranded("chr1:191-192", Strand::Minus)], &references, &opposite);
+        assert_eq!((found[0].reference, found[0].distance), (Some(3), Some(80)));
+    }
+
+    #[test]
+    fn clusters_ranges_within_a_gap() {
+        let ranges = set(&["chr1:1-10", "chr1:5-15", "chr1:21-30", "chr2:1-10"]);
+        assert_eq!(ranges.cluster(0), [0, 0, 1, 2]);
+        assert_eq!(ranges.cluster(5), [0, 0, 0, 1]);
+        assert_eq!(strings(&ranges.cluster_extents(0)), ["chr1:1-15", "chr1:21-30", "chr2:1-10"]);
+    }
+
+    #[test]
+    fn overlaps_by_jaccard_and_fraction() {
+        let a = set(&["chr1:1-20", "chr1:41-60"]);
+        let b = set(&["chr1:11-50", "chr2:1-10"]);
+        assert_eq!(a.jaccard(&b), 20.0 / 70.0);
+        assert_eq!(GenomicRangeSet::new().jaccard(&GenomicRangeSet::new()), 0.0);
+        assert_eq!(a.overlap_pairs(&b, 0.5, false), [(0, 0), (1, 0)]);
+        assert!(a.overlap_pairs(&b, 0.5, true).is_empty());
+        assert!(a.overlap_pairs(&b, 0.6, false).is_empty());
+    }
+
+    #[test]
+    fn partitions_into_disjoint_segments() {
+        let ranges = set(&["chr1:1-10", "chr1:5-15", "chr1:21-30", "chr2:1-10", "chr2:1-10"]);
+        let segments: Vec<_> = ranges.partition().into_iter().map(|(range, sources)| (range.to_string(), sources)).collect();
+        assert_eq!(segments, [
+            ("chr1:1-4".to_string(), vec![0]),
+            ("chr1:5-10".to_string(), vec![0, 1]),
+            ("chr1:11-15".to_string(), vec![1]),
+            ("chr1:21-30".to_string(), vec![2]),
+            ("chr2:1-10".to_string(), vec![3, 4]),
+        ]);
+    }
+
+    #[test]
+    fn neighborhoods_are_ordered_along_the_locus_strand() {
+        let locus = stranded("chr1:101-200", Strand::Minus);
+        let features = [
+            stranded("chr1:51-60", Strand::Plus),
+            stranded("chr1:151-160", Strand::Minus),
+            stranded("chr1:221-230", Strand::Plus),
+            stranded("chr1:241-250", Strand::None),
+            stranded("chr1:11-20", Strand::Plus),
+            stranded("chr2:1-10", Strand::Plus),
+        ];
+        let found = |extent| -> Vec<(usize, i64, i64, Strand)> {
+            neighborhood(&locus, &features, extent).iter().map(|n| (n.feature, n.offset, n.distance, n.relative_strand)).collect()
+        };
+        assert_eq!(found(NeighborhoodExtent::Features(2)), [
+            (3, -2, -40, Strand::None),
+            (2, -1, -20, Strand::Minus),
+            (1, 0, 0, Strand::Plus),
+            (0, 1, 40, Strand::Minus),
+            (4, 2, 80, Strand::Minus),
+        ]);
+        let within = found(NeighborhoodExtent::Distance(20));
+        assert_eq!(within.iter().map(|n| n.0).collect::<Vec<_>>(), [2, 1]);
+    }
+
+    #[test]
+    fn shuffled_ranges_avoid_excluded_regions() {
+        let chrom_sizes: ChromSizes = [(SeqId::from("chr1"), 100), (SeqId::from("chr2"), 50)].into_iter().collect();
+        let ranges: Vec<GenomicRange> = ["chr1:1-10", "chr2:1-20"].iter().map(|r| r.parse().unwrap()).collect();
+        let options = ShuffleOptions { same_seqid: true, exclude: Some(set(&["chr1:11-90"]).into()) };
+        for seed in 0..20 {
+            let shuffled = shuffle(&ranges, &chrom_sizes, &options, seed).unwrap();
+            assert!(["chr1:1-10", "chr1:91-100"].contains(&shuffled[0].to_string().as_str()));
+            assert_eq!((shuffled[1].seqid().as_str(), shuffled[1].len()), ("chr2", 20));
+            assert!(shuffled[1].range_0halfopen().end <= 50);
+            assert_eq!(shuffled, shuffle(&ranges, &chrom_sizes, &options, seed).unwrap());
+        }
+        let too_long: Vec<GenomicRange> = vec!["chr1:1-30".parse().unwrap()];
+        assert!(shuffle(&too_long, &chrom_sizes, &options, 0).is_err());
+        assert!(shuffle(&too_long, &chrom_sizes, &ShuffleOptions::default(), 0).is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+//! Columnar storage of GFF3 rows
+//!
+//! Large annotations such as GENCODE have millions of rows, most of which repeat the same
+//! seqids, sources, feature types and attributes. [`ColumnarGff3`] stores each field in its own
+//! vector, with every string interned, including attribute keys and values, which uses much less
+//! memory than a `Vec<Gff3Row>` and makes scans over coordinates faster.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{GenomicRange, SeqId};
+
+/// A table of distinct values, indexed in the order they were first seen
+#[derive(Debug, Clone)]
+struct Interner<V> {
+    values: Vec<V>,
+    indexes: HashMap<V, u32>,
+}
+
+impl<V> Default for Interner<V> {
+    fn default() -> Self {
+        Interner {
+            values: Vec::new(),
+            indexes: HashMap::new(),
+        }
+    }
+}
+
+impl<V: Clone + Eq + std::hash::Hash> Interner<V> {
+    fn intern(&mut self, value: &V) -> u32 {
+        if let Some(i) = self.indexes.get(value) {
+            return *i;
+        }
+        let i = self.values.len() as u32;
+        self.values.push(value.clone());
+        self.indexes.insert(value.clone(), i);
+        i
+    }
+}
+
+impl Interner<Arc<str>> {
+    /// Interns a string, which is stored once however many times it is seen
+    fn intern_str(&mut self, value: &str) -> u32 {
+        if let Some(i) = self.indexes.get(value) {
+            return *i;
+        }
+        let value: Arc<str> = Arc::from(value);
+        let i = self.values.len() as u32;
+        self.values.push(value.clone());
+        self.indexes.insert(value, i);
+        i
+    }
+
+    fn get(&self, i: u32) -> &str {
+        &self.values[i as usize]
+    }
+}
+
+/// GFF3 rows stored as columns, with seqids and every string field interned
+///
+/// The attributes of all the rows are in one column of interned key and value pairs.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnarGff3 {
+    seqids: Interner<SeqId>,
+    strings: Interner<Arc<str>>,
+    seqid: Vec<u32>,
+    source: Vec<u32>,
+    feature_type: Vec<u32>,
+    start: Vec<u64>,
+    end: Vec<u64>,
+    score: Vec<u32>,
+    strand: Vec<Strand>,
+    phase: Vec<u32>,
+    /// The end of the attributes of each row in `attributes`
+    attribute_ends: Vec<u32>,
+    attributes: Vec<(u32, u32)>,
+}
+
+impl ColumnarGff3 {
+    pub fn new() -> ColumnarGff3 {
+        ColumnarGff3::default()
+    }
+
+    pub fn push<T: AsRef<str>>(&mut self, row: Gff3Row<T>) {
+        self.seqid.push(self.seqids.intern(&row.seqid));
+        self.source.push(self.strings.intern_str(&row.source));
+        self.feature_type.push(self.strings.intern_str(row.feature_type.as_ref()));
+        self.start.push(row.start);
+        self.end.push(row.end);
+        self.score.push(self.strings.intern_str(&row.score));
+        self.strand.push(row.strand);
+        self.phase.push(self.strings.intern_str(&row.phase));
+        for (key, value) in &row.attributes {
+            let pair = (self.strings.intern_str(key), self.strings.intern_str(value));
+            self.attributes.push(pair);
+        }
+        self.attribute_ends.push(self.attributes.len() as u32);
+    }
+
+    fn attribute_pairs(&self, i: usize) -> &[(u32, u32)] {
+        let start = if i == 0 { 0 } else { self.attribute_ends[i - 1] as usize };
+        &self.attributes[start..self.attribute_ends[i] as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.start.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start.is_empty()
+    }
+
+    /// Returns a copy of a row
+    pub fn get(&self, i: usize) -> Option<Gff3Row<String>> {
+        (i < self.len()).then(|| Gff3Row {
+            seqid: self.seqids.values[self.seqid[i] as usize].clone(),
+            source: self.strings.get(self.source[i]).to_string(),
+            feature_type: self.feature_type(i).to_string(),
+            start: self.start[i],
+            end: self.end[i],
+            score: self.strings.get(self.score[i]).to_string(),
+            strand: self.strand[i],
+            phase: self.strings.get(self.phase[i]).to_string(),
+            attributes: self.attributes(i).map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+        })
+    }
+
+    /// Returns copies of the rows, in order
+    pub fn rows(&self) -> impl Iterator<Item = Gff3Row<String>> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    pub fn seqid(&self, i: usize) -> &SeqId {
+        &self.seqids.values[self.seqid[i] as usize]
+    }
+
+    pub fn feature_type(&self, i: usize) -> &str {
+        self.strings.get(self.feature_type[i])
+    }
+
+    /// The attributes of a row as key and value pairs, in order
+    pub fn attributes(&self, i: usize) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.attribute_pairs(i).iter().map(|(key, value)| (self.strings.get(*key), self.strings.get(*value)))
+    }
+
+    /// The value of an attribute of a row
+    pub fn attribute(&self, i: usize, key: &str) -> Option<&str> {
+        let key = *self.strings.indexes.get(key)?;
+        self.attribute_pairs(i).iter().find(|(k, _)| *k == key).map(|(_, value)| self.strings.get(*value))
+    }
+
+    /// The 1-based start of every row
+    pub fn starts(&self) -> &[u64] {
+        &self.start
+    }
+
+    /// The 1-based, inclusive end of every row
+    pub fn ends(&self) -> &[u64] {
+        &self.end
+    }
+
+    pub fn strands(&self) -> &[Strand] {
+        &self.strand
+    }
+
+    /// The indexes of the rows of a feature type
+    pub fn indexes_of_type<'a>(&'a self, feature_type: &str) -> impl Iterator<Item = usize> + 'a {
+        let code = self.strings.indexes.get(feature_type).copied();
+        self.feature_type.iter().enumerate()
+            .filter(move |(_, t)| Some(**t) == code)
+            .map(|(i, _)| i)
+    }
+
+    /// The indexes of the rows that overlap a range, found by scanning the coordinates
+    pub fn indexes_overlapping<'a>(&'a self, range: &GenomicRange) -> impl Iterator<Item = usize> + 'a {
+        let code = self.seqids.indexes.get(range.seqid()).copied();
+        let interval = range.range_0halfopen();
+        (0..self.len()).filter(move |&i| Some(self.seqid[i]) == code && self.start[i] <= interval.end && interval.start < self.end[i])
+    }
+}
+
+impl<T: AsRef<str>> FromIterator<Gff3Row<T>> for ColumnarGff3 {
+    fn from_iter<I: IntoIterator<Item = Gff3Row<T>>>(iter: I) -> Self {
+        let mut table = ColumnarGff3::new();
+        for row in iter {
+            table.push(row);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    #[test]
+    fn rows_round_trip() {
+        let gff = "\
+chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;gene_type=protein_coding
+chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1;gene_type=protein_coding
+chr2\t.\tgene\t50\t80\t3.5\t-\t.\tID=g2;gene_type=lncRNA
+";
+        let rows = parse_gff3(gff, ParseMode::Strict).unwrap();
+        let table: ColumnarGff3 = rows.clone().into_iter().collect();
+        for (row, copy) in rows.iter().zip(table.rows()) {
+            assert_eq!(format!("{:?}", row), format!("{:?}", copy));
+        }
+        assert_eq!(table.attribute(1, "Parent"), Some("g1"));
+        assert_eq!(table.attribute(2, "Parent"), None);
+        assert_eq!(table.indexes_of_type("gene").collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(table.indexes_overlapping(&"chr2:1-50".parse().unwrap()).collect::<Vec<_>>(), [2]);
+        // protein_coding is stored once
+        assert_eq!(table.strings.values.iter().filter(|s| &***s == "protein_coding").count(), 1);
+    }
+}
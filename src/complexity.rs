@@ -0,0 +1,142 @@
+//! Homopolymer and low-complexity regions
+//!
+//! Variant calls in homopolymer runs and low-complexity sequence are unreliable, so these regions
+//! are often masked. Regions are found in the sequences of a [`Genome`] and returned as BED rows.
+
+use crate::format::{Bed6Row, Strand};
+use crate::genome::SeqId;
+use crate::sequence::Genome;
+
+fn bed_row(seqid: &SeqId, start: usize, end: usize, name: String) -> Bed6Row {
+    Bed6Row {
+        chrom: seqid.clone(),
+        chrom_start: start as u64,
+        chrom_end: end as u64,
+        name,
+        score: 0,
+        strand: Strand::None,
+    }
+}
+
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// Finds runs of at least `min_len` of the same base, ignoring case, named by the base and length e.g. `A12`
+///
+/// Runs of ambiguous bases such as `N` are skipped.
+pub fn homopolymers(genome: &Genome, min_len: usize) -> Vec<Bed6Row> {
+    let mut rows = Vec::new();
+    for seqid in genome.seqids() {
+        let sequence = genome.get(seqid).unwrap_or_default();
+        let mut start = 0;
+        while start < sequence.len() {
+            let base = sequence[start].to_ascii_uppercase();
+            let len = sequence[start..].iter().take_while(|b| b.to_ascii_uppercase() == base).count();
+            if len >= min_len.max(1) && base_index(base).is_some() {
+                rows.push(bed_row(seqid, start, start + len, format!("{}{}", base as char, len)));
+            }
+            start += len;
+        }
+    }
+    rows
+}
+
+/// Finds low-complexity regions, where windows have a Shannon entropy of k-mers below `max_entropy`
+///
+/// The entropy is in bits, so is at most 2k e.g. 2 for single bases and 4 for dinucleotides, which
+/// detects dinucleotide repeats. `k` should be 1 to 4. K-mers with ambiguous bases aren't counted,
+/// and windows with no counted k-mers are skipped. Overlapping and adjacent low-complexity windows are
+/// merged, and the regions are named `low_complexity`.
+pub fn low_complexity(genome: &Genome, window: usize, k: usize, max_entropy: f64) -> Vec<Bed6Row> {
+    let k = k.clamp(1, 4);
+    let mut rows = Vec::new();
+    if window < k {
+        return rows;
+    }
+    for seqid in genome.seqids() {
+        let sequence = genome.get(seqid).unwrap_or_default();
+        if sequence.len() < window {
+            continue;
+        }
+        // the k-mer starting at each position, or None if it has an ambiguous base
+        let kmers: Vec<Option<usize>> = (0..=sequence.len() - k)
+            .map(|i| sequence[i..i + k].iter().try_fold(0, |code, base| base_index(*base).map(|b| code * 4 + b)))
+            .collect();
+        let kmers_per_window = window - k + 1;
+        let mut counts = vec![0usize; 1 << (2 * k)];
+        let mut total = 0;
+        for kmer in kmers[..kmers_per_window].iter().flatten() {
+            counts[*kmer] += 1;
+            total += 1;
+        }
+        let mut region: Option<(usize, usize)> = None;
+        for start in 0..=(sequence.len() - window) {
+            if start > 0 {
+                if let Some(kmer) = kmers[start - 1] {
+                    counts[kmer] -= 1;
+                    total -= 1;
+                }
+                if let Some(kmer) = kmers[start + kmers_per_window - 1] {
+                    counts[kmer] += 1;
+                    total += 1;
+                }
+            }
+            let entropy: f64 = counts.iter()
+                .filter(|c| **c > 0)
+                .map(|c| {
+                    let p = *c as f64 / total as f64;
+                    -p * p.log2()
+                })
+                .sum();
+            if total == 0 || entropy >= max_entropy {
+                continue;
+            }
+            region = match region {
+                Some((s, e)) if start <= e => Some((s, start + window)),
+                Some((s, e)) => {
+                    rows.push(bed_row(seqid, s, e, "low_complexity".to_string()));
+                    Some((start, start + window))
+                }
+                None => Some((start, start + window)),
+            };
+        }
+        if let Some((s, e)) = region {
+            rows.push(bed_row(seqid, s, e, "low_complexity".to_string()));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(rows: &[Bed6Row]) -> Vec<(u64, u64, &str)> {
+        rows.iter().map(|row| (row.chrom_start, row.chrom_end, row.name.as_str())).collect()
+    }
+
+    #[test]
+    fn finds_homopolymers_ignoring_case() {
+        let mut genome = Genome::default();
+        genome.insert("chr1", b"ACaaAaGNNNNNTTT".to_vec());
+        assert_eq!(ranges(&homopolymers(&genome, 3)), [(2, 6, "A4"), (12, 15, "T3")]);
+    }
+
+    #[test]
+    fn finds_dinucleotide_repeats() {
+        let mut genome = Genome::default();
+        genome.insert("chr1", ["ACGTTGCAAGCTTCGATGCA", &"CA".repeat(10), "GATCCTAGGCTAACGTTGCA"].concat().into_bytes());
+        let rows = low_complexity(&genome, 10, 2, 1.5);
+        // windows overlapping the repeat by most of their length are also low complexity
+        assert_eq!(ranges(&rows), [(17, 41, "low_complexity")]);
+        // the repeat has an entropy of 1 bit in single bases
+        assert!(low_complexity(&genome, 10, 1, 1.0).is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+//! Coverage of ranges and alignments
+//!
+//! Depth of coverage is computed as runs of constant depth, as [`DataInterval`]s ready to be written
+//! as BedGraph. Spliced alignments, such as RNA-seq reads from `minimap2 -x splice`, cover only their
+//! aligned blocks: the skipped introns (`N` in the CIGAR) are counted as junctions instead. Stranded
+//! data can be given a track per strand, or their difference.
+
+use std::collections::HashSet;
+use indexmap::IndexMap;
+
+use crate::format::{Bed6Row, DataInterval, Strand};
+use crate::genome::{Error, GenomicRange, SeqId, StrandedGenomicRange};
+use crate::paf::{parse_cigar, PafRecord};
+
+/// The reference ranges of an alignment, split at introns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplicedAlignment {
+    /// The aligned blocks, in order of position
+    pub blocks: Vec<GenomicRange>,
+    /// The skipped introns, in order of position
+    pub junctions: Vec<GenomicRange>,
+    pub strand: Strand,
+}
+
+impl SplicedAlignment {
+    /// Splits an alignment starting at the 0-based reference position by its CIGAR string
+    ///
+    /// Matches (`M`, `=` and `X`) are aligned blocks, joined across insertions. Deletions (`D`) split
+    /// blocks without being covered, and skips (`N`) are junctions. Clipping and padding are ignored.
+    pub fn from_cigar<T: Into<SeqId>>(seqid: T, start: u64, cigar: &str, strand: Strand) -> Result<SplicedAlignment, Error> {
+        let seqid = seqid.into();
+        let mut alignment = SplicedAlignment { blocks: Vec::new(), junctions: Vec::new(), strand };
+        let mut pos = start;
+        let mut block_start: Option<u64> = None;
+        for (len, op) in parse_cigar(cigar)? {
+            match op {
+                'M' | '=' | 'X' => {
+                    block_start.get_or_insert(pos);
+                    pos += len;
+                }
+                'I' | 'S' | 'H' | 'P' => {}
+                'D' | 'N' => {
+                    if let Some(block_start) = block_start.take() {
+                        alignment.blocks.push(GenomicRange::from_0halfopen(seqid.clone(), block_start..pos)?);
+                    }
+                    if op == 'N' {
+                        alignment.junctions.push(GenomicRange::from_0halfopen(seqid.clone(), pos..pos + len)?);
+                    }
+                    pos += len;
+                }
+                _ => return Err(Error::InvalidArguments(format!("unsupported CIGAR operation {} in {}", op, cigar))),
+            }
+        }
+        if let Some(block_start) = block_start {
+            alignment.blocks.push(GenomicRange::from_0halfopen(seqid, block_start..pos)?);
+        }
+        Ok(alignment)
+    }
+
+    /// Splits a PAF alignment by its `cg` tag, on the target
+    ///
+    /// The strand is the transcript strand given by a minimap2 `ts` tag, relative to the alignment, or
+    /// otherwise the strand of the alignment.
+    pub fn from_paf(record: &PafRecord) -> Result<SplicedAlignment, Error> {
+        let cigar = record.tags.get("cg")
+            .ok_or_else(|| Error::InvalidArguments(format!("alignment of {} to {} has no cg tag", record.query_name, record.target_name)))?;
+        let strand = match record.tags.get("ts").map(|ts| ts.as_str()) {
+            Some("-") => record.strand.flip(),
+            Some("?") => Strand::None,
+            _ => record.strand,
+        };
+        let alignment = SplicedAlignment::from_cigar(record.target_name.clone(), record.target_start, cigar, strand)?;
+        let end = alignment.blocks.last().map_or(record.target_start, |b| b.range_0halfopen().end);
+        if end != record.target_end {
+            return Err(Error::InvalidArguments(format!("cg tag of alignment of {} to {} doesn't match its coordinates", record.query_name, record.target_name)));
+        }
+        Ok(alignment)
+    }
+}
+
+/// Sums the weights of the ranges at each base, as runs of constant value by seqid, in the order first seen
+///
+/// Runs with a total of 0 are left out.
+fn weighted_runs<'a, I: IntoIterator<Item = (&'a GenomicRange, i64)>>(ranges: I) -> Vec<DataInterval<i64>> {
+    let mut events: IndexMap<SeqId, Vec<(u64, i64)>> = IndexMap::new();
+    for (range, weight) in ranges {
+        let interval = range.range_0halfopen();
+        if interval.is_empty() {
+            continue;
+        }
+        let seqid_events = events.entry(range.seqid().clone()).or_default();
+        seqid_events.push((interval.start, weight));
+        seqid_events.push((interval.end, -weight));
+    }
+    let mut runs = Vec::new();
+    let mut push_run = |seqid: &SeqId, start: u64, end: u64, value: i64| {
+        runs.push(DataInterval::new(GenomicRange::from_0halfopen(seqid.clone(), start..end).unwrap(), vec![Some(value)]));
+    };
+    for (seqid, mut seqid_events) in events {
+        seqid_events.sort_unstable();
+        let mut total = 0;
+        let mut run: Option<(u64, u64, i64)> = None;
+        let mut i = 0;
+        while i < seqid_events.len() {
+            let pos = seqid_events[i].0;
+            while i < seqid_events.len() && seqid_events[i].0 == pos {
+                total += seqid_events[i].1;
+                i += 1;
+            }
+            let Some(next) = seqid_events.get(i).map(|e| e.0) else { break };
+            if total == 0 {
+                continue;
+            }
+            run = match run {
+                Some((start, end, value)) if end == pos && value == total => Some((start, next, value)),
+                Some((start, end, value)) => {
+                    push_run(&seqid, start, end, value);
+                    Some((pos, next, total))
+                }
+                None => Some((pos, next, total)),
+            };
+        }
+        if let Some((start, end, value)) = run {
+            push_run(&seqid, start, end, value);
+        }
+    }
+    runs
+}
+
+fn to_depth(runs: Vec<DataInterval<i64>>) -> Vec<DataInterval<u32>> {
+    runs.into_iter()
+        .map(|run| DataInterval::new(run.range().clone(), vec![run.values()[0].map(|v| v as u32)]))
+        .collect()
+}
+
+/// Computes the depth of coverage of the ranges, as runs of constant depth
+///
+/// Bases with no coverage are left out. Runs are sorted by seqid, in the order first seen, and position.
+pub fn depth<'a, I: IntoIterator<Item = &'a GenomicRange>>(ranges: I) -> Vec<DataInterval<u32>> {
+    to_depth(weighted_runs(ranges.into_iter().map(|range| (range, 1))))
+}
+
+/// Computes the mean depth of coverage of the ranges in bins of `bin_size` tiling each sequence, rounded to the nearest integer
+///
+/// Every bin is reported, including those with no coverage, and the last bin of each sequence is
+/// truncated at its end. Bins are in the order of `seq_lens`. Fails if a range is on a sequence
+/// with no length or beyond its end.
+pub fn binned_depth<'a, I: IntoIterator<Item = &'a GenomicRange>>(ranges: I, bin_size: u64, seq_lens: &IndexMap<SeqId, u64>) -> Result<Vec<DataInterval<u32>>, Error> {
+    if bin_size == 0 {
+        return Err(Error::InvalidArguments("bin size should be at least 1".to_string()));
+    }
+    let mut runs: IndexMap<SeqId, Vec<DataInterval<i64>>> = IndexMap::new();
+    for run in weighted_runs(ranges.into_iter().map(|range| (range, 1))) {
+        let seq_len = seq_lens.get(run.range().seqid())
+            .ok_or_else(|| Error::InvalidArguments(format!("no length for sequence {}", run.range().seqid())))?;
+        if run.range().range_0halfopen().end > *seq_len {
+            return Err(Error::InvalidArguments(format!("{} is beyond the end of its sequence", run.range())));
+        }
+        runs.entry(run.range().seqid().clone()).or_default().push(run);
+    }
+    let mut bins = Vec::new();
+    for (seqid, seq_len) in seq_lens {
+        let seqid_runs = runs.get(seqid).map_or(&[][..], |r| r.as_slice());
+        let mut i = 0;
+        for bin_start in (0..*seq_len).step_by(bin_size as usize) {
+            let bin_end = (bin_start + bin_size).min(*seq_len);
+            let mut covered = 0;
+            // runs are sorted and disjoint, so only those reaching past the bin are kept for the next
+            while let Some(run) = seqid_runs.get(i) {
+                let interval = run.range().range_0halfopen();
+                if interval.start >= bin_end {
+                    break;
+                }
+                let overlap = interval.end.min(bin_end).saturating_sub(interval.start.max(bin_start));
+                covered += overlap * run.values()[0].unwrap_or(0) as u64;
+                if interval.end > bin_end {
+                    break;
+                }
+                i += 1;
+            }
+            let len = bin_end - bin_start;
+            let mean = ((covered + len / 2) / len) as u32;
+            bins.push(DataInterval::new(GenomicRange::from_0halfopen(seqid.clone(), bin_start..bin_end)?, vec![Some(mean)]));
+        }
+    }
+    Ok(bins)
+}
+
+/// The depth of coverage of each strand, for stranded data such as RNA-seq or GRO-seq
+#[derive(Default)]
+pub struct StrandedDepth {
+    pub plus: Vec<DataInterval<u32>>,
+    pub minus: Vec<DataInterval<u32>>,
+}
+
+impl StrandedDepth {
+    /// Computes the depth of each strand as [`depth`], leaving out ranges with no strand
+    pub fn from_ranges<'a, I: IntoIterator<Item = &'a StrandedGenomicRange>>(ranges: I) -> StrandedDepth {
+        let (plus, minus): (Vec<_>, Vec<_>) = ranges.into_iter()
+            .filter(|range| range.strand != Strand::None)
+            .partition(|range| range.strand == Strand::Plus);
+        StrandedDepth {
+            plus: depth(plus.iter().map(|range| &range.range)),
+            minus: depth(minus.iter().map(|range| &range.range)),
+        }
+    }
+
+    /// The minus strand depth as negative values, to overlay on the plus strand as in genome browsers
+    pub fn negated_minus(&self) -> Vec<DataInterval<i64>> {
+        self.minus.iter()
+            .map(|run| DataInterval::new(run.range().clone(), vec![run.values()[0].map(|v| -(v as i64))]))
+            .collect()
+    }
+
+    /// The plus strand depth minus the minus strand depth, leaving out bases where they are equal
+    pub fn difference(&self) -> Vec<DataInterval<i64>> {
+        let plus = self.plus.iter().map(|run| (run.range(), run.values()[0].unwrap_or(0) as i64));
+        let minus = self.minus.iter().map(|run| (run.range(), -(run.values()[0].unwrap_or(0) as i64)));
+        weighted_runs(plus.chain(minus))
+    }
+}
+
+/// Extends reads from their 5' end to the fragment length in the 3' direction, as for single-end ChIP-seq
+///
+/// Reads longer than the fragment are truncated to it, and reads with no strand are left as they are.
+/// Fragments are clamped at 0 and, given the sequence lengths, at the end of their sequence.
+pub fn extend_fragments(reads: &[StrandedGenomicRange], fragment_len: u64, seq_lens: Option<&IndexMap<SeqId, u64>>) -> Result<Vec<StrandedGenomicRange>, Error> {
+    reads.iter()
+        .map(|read| {
+            let five_prime = read.five_prime();
+            let fragment = match read.strand {
+                Strand::Plus => five_prime..five_prime + fragment_len,
+                Strand::Minus => (five_prime + 1).saturating_sub(fragment_len)..five_prime + 1,
+                Strand::None => return Ok(read.clone()),
+            };
+            let range = GenomicRange::from_0halfopen(read.range.seqid().clone(), fragment)?.expand(0, 0, seq_lens)?;
+            Ok(StrandedGenomicRange::new(range, read.strand))
+        })
+        .collect()
+}
+
+/// What makes two records duplicates: the same range and strand, and the same barcode if given
+///
+/// For single-cell fragments files, giving the cell barcode keeps fragments from different cells.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DuplicateKey {
+    pub range: StrandedGenomicRange,
+    pub barcode: Option<String>,
+}
+
+/// Records split into the first of each set of duplicates, and the rest
+#[derive(Debug, Clone)]
+pub struct DedupOutput<T> {
+    pub kept: Vec<T>,
+    pub duplicates: Vec<T>,
+}
+
+impl<T> DedupOutput<T> {
+    /// The fraction of records that are duplicates
+    pub fn duplication_rate(&self) -> f64 {
+        let total = self.kept.len() + self.duplicates.len();
+        if total == 0 { 0.0 } else { self.duplicates.len() as f64 / total as f64 }
+    }
+}
+
+/// Splits out duplicate records by their key, keeping the first of each, with both sets in their original order
+pub fn dedup<T, I, F>(records: I, mut key: F) -> DedupOutput<T>
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(&T) -> DuplicateKey,
+{
+    let mut seen = HashSet::new();
+    let mut output = DedupOutput { kept: Vec::new(), duplicates: Vec::new() };
+    for record in records {
+        if seen.insert(key(&record)) {
+            output.kept.push(record);
+        } else {
+            output.duplicates.push(record);
+        }
+    }
+    output
+}
+
+/// The coverage of one exon by spliced alignments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExonCoverage {
+    pub range: GenomicRange,
+    /// The number of alignments with a block overlapping the exon
+    pub reads: u32,
+    /// The number of aligned bases within the exon, summed over the alignments
+    pub bases: u64,
+}
+
+impl ExonCoverage {
+    pub fn mean_depth(&self) -> f64 {
+        let len = self.range.range_0halfopen().end - self.range.range_0halfopen().start;
+        if len == 0 { 0.0 } else { self.bases as f64 / len as f64 }
+    }
+}
+
+/// Exon coverage and junction counts of spliced alignments
+#[derive(Debug, Clone, Default)]
+pub struct SplicedCoverage {
+    /// The coverage of each exon, in the order given
+    pub exons: Vec<ExonCoverage>,
+    /// The number of alignments spanning each intron on the strand of the alignment, in the order first seen
+    pub junctions: IndexMap<StrandedGenomicRange, u32>,
+}
+
+/// Computes the coverage of the exons by the aligned blocks of spliced alignments, and counts their junctions
+pub fn spliced_coverage(alignments: &[SplicedAlignment], exons: &[GenomicRange]) -> SplicedCoverage {
+    let mut coverage = SplicedCoverage {
+        exons: exons.iter().map(|range| ExonCoverage { range: range.clone(), reads: 0, bases: 0 }).collect(),
+        junctions: IndexMap::new(),
+    };
+    // the exons of each seqid sorted by start, with the longest exon to bound the search for overlaps
+    let mut by_seqid: IndexMap<&SeqId, (Vec<usize>, u64)> = IndexMap::new();
+    for (i, exon) in exons.iter().enumerate() {
+        let interval = exon.range_0halfopen();
+        let (indexes, longest) = by_seqid.entry(exon.seqid()).or_default();
+        indexes.push(i);
+        *longest = (*longest).max(interval.end - interval.start);
+    }
+    for (indexes, _) in by_seqid.values_mut() {
+        indexes.sort_by_key(|i| exons[*i].range_0halfopen().start);
+    }
+
+    for alignment in alignments {
+        let mut overlapped = Vec::new();
+        for block in &alignment.blocks {
+            let Some((indexes, longest)) = by_seqid.get(block.seqid()) else { continue };
+            let interval = block.range_0halfopen();
+            let last = indexes.partition_point(|i| exons[*i].range_0halfopen().start < interval.end);
+            let first = indexes.partition_point(|i| exons[*i].range_0halfopen().start + longest <= interval.start);
+            for i in &indexes[first..last] {
+                let bases = block.overlap_len(&exons[*i]);
+                if bases > 0 {
+                    coverage.exons[*i].bases += bases;
+                    overlapped.push(*i);
+                }
+            }
+        }
+        overlapped.sort_unstable();
+        overlapped.dedup();
+        for i in overlapped {
+            coverage.exons[i].reads += 1;
+        }
+        for junction in &alignment.junctions {
+            *coverage.junctions.entry(StrandedGenomicRange::new(junction.clone(), alignment.strand)).or_default() += 1;
+        }
+    }
+    coverage
+}
+
+/// Writes junctions as BED rows of their introns on their strands, named `JUNC<n>` with the count as the score, capped at 1000
+pub fn junctions_to_bed(junctions: &IndexMap<StrandedGenomicRange, u32>) -> Vec<Bed6Row> {
+    junctions.iter()
+        .enumerate()
+        .map(|(i, (intron, count))| Bed6Row {
+            chrom: intron.range.seqid().clone(),
+            chrom_start: intron.range.range_0halfopen().start,
+            chrom_end: intron.range.range_0halfopen().end,
+            name: format!("JUNC{}", i + 1),
+            score: (*count).min(1000) as u16,
+            strand: intron.strand,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::NumOps;
+
+    fn ranges(ranges: &[&str]) -> Vec<GenomicRange> {
+        ranges.iter().map(|r| r.parse().unwrap()).collect()
+    }
+
+    fn runs<T: NumOps + Copy>(intervals: &[DataInterval<T>]) -> Vec<(String, T)> {
+        intervals.iter().map(|run| (run.range().to_string(), run.values()[0].unwrap())).collect()
+    }
+
+    #[test]
+    fn depth_is_in_runs_of_constant_coverage() {
+        let reads = ranges(&["chr1:1-10", "chr1:6-15", "chr2:1-5", "chr1:16-20"]);
+        assert_eq!(runs(&depth(&reads)), [
+            ("chr1:1-5".to_string(), 1), ("chr1:6-10".to_string(), 2), ("chr1:11-20".to_string(), 1), ("chr2:1-5".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn spliced_alignments_cover_blocks_and_count_junctions() {
+        let first = SplicedAlignment::from_cigar("chr1", 100, "5S10M2I5M3D10M50N20M", Strand::Plus).unwrap();
+        assert_eq!(first.blocks, ranges(&["chr1:101-115", "chr1:119-128", "chr1:179-198"]));
+        assert_eq!(first.junctions, ranges(&["chr1:129-178"]));
+        let second = SplicedAlignment::from_cigar("chr1", 120, "8M50N10M", Strand::Plus).unwrap();
+        assert!(SplicedAlignment::from_cigar("chr1", 0, "10Z", Strand::Plus).is_err());
+
+        let coverage = spliced_coverage(&[first, second], &ranges(&["chr1:101-128", "chr1:179-200", "chr1:301-400"]));
+        let exons: Vec<_> = coverage.exons.iter().map(|exon| (exon.reads, exon.bases)).collect();
+        assert_eq!(exons, [(2, 33), (2, 30), (0, 0)]);
+        assert_eq!(coverage.exons[1].mean_depth(), 30.0 / 22.0);
+
+        let bed = junctions_to_bed(&coverage.junctions);
+        assert_eq!((bed[0].chrom_start, bed[0].chrom_end, bed[0].name.as_str(), bed[0].score), (128, 178, "JUNC1", 2));
+    }
+
+    #[test]
+    fn junctions_are_counted_by_strand() {
+        let plus = SplicedAlignment::from_cigar("chr1", 100, "28M50N12M", Strand::Plus).unwrap();
+        let record = PafRecord::try_from("read1\t40\t0\t40\t+\tchr1\t1000\t100\t190\t40\t90\t60\tts:A:-\tcg:Z:28M50N12M").unwrap();
+        let minus = SplicedAlignment::from_paf(&record).unwrap();
+        assert_eq!(minus.strand, Strand::Minus);
+
+        let coverage = spliced_coverage(&[plus.clone(), minus, plus], &[]);
+        let bed: Vec<_> = junctions_to_bed(&coverage.junctions).iter().map(|row| (row.chrom_start, row.chrom_end, row.score, row.strand)).collect();
+        assert_eq!(bed, [(128, 178, 2, Strand::Plus), (128, 178, 1, Strand::Minus)]);
+    }
+
+    fn stranded(range: &str, strand: Strand) -> StrandedGenomicRange {
+        StrandedGenomicRange::new(range.parse().unwrap(), strand)
+    }
+
+    #[test]
+    fn stranded_depth_overlays_and_differences() {
+        let reads = [
+            stranded("chr1:1-10", Strand::Plus),
+            stranded("chr1:6-15", Strand::Plus),
+            stranded("chr1:6-10", Strand::Minus),
+            stranded("chr1:1-100", Strand::None),
+        ];
+        let depth = StrandedDepth::from_ranges(&reads);
+        assert_eq!(runs(&depth.plus).len(), 3);
+        assert_eq!(runs(&depth.negated_minus()), [("chr1:6-10".to_string(), -1)]);
+        // the difference is 1 throughout, so it is a single run
+        assert_eq!(runs(&depth.difference()), [("chr1:1-15".to_string(), 1)]);
+    }
+
+    #[test]
+    fn reads_extend_in_the_3_prime_direction() {
+        let reads = [
+            stranded("chr1:11-20", Strand::Plus),
+            stranded("chr1:11-20", Strand::Minus),
+            stranded("chr1:101-200", Strand::Plus),
+            stranded("chr1:11-20", Strand::None),
+        ];
+        let fragments: Vec<_> = extend_fragments(&reads, 50, None).unwrap().iter().map(|f| f.range.to_string()).collect();
+        assert_eq!(fragments, ["chr1:11-60", "chr1:1-20", "chr1:101-150", "chr1:11-20"]);
+
+        let seq_lens = IndexMap::from([(SeqId::from("chr1"), 40)]);
+        let clamped = extend_fragments(&reads[..1], 50, Some(&seq_lens)).unwrap();
+        assert_eq!(clamped[0].range.to_string(), "chr1:11-40");
+    }
+
+    #[test]
+    fn duplicates_share_range_strand_and_barcode() {
+        let fragments = [
+            ("chr1:1-10", Strand::Plus, "AAAC"),
+            ("chr1:1-10", Strand::Plus, "AAAC"),
+            ("chr1:1-10", Strand::Minus, "AAAC"),
+            ("chr1:1-10", Strand::Plus, "GGGT"),
+        ];
+        let key = |barcodes: bool| move |(range, strand, barcode): &(&str, Strand, &str)| DuplicateKey {
+            range: stranded(range, *strand),
+            barcode: barcodes.then(|| barcode.to_string()),
+        };
+        let by_cell = dedup(fragments, key(true));
+        assert_eq!((by_cell.kept.len(), by_cell.duplicates.len(), by_cell.duplication_rate()), (3, 1, 0.25));
+        let pooled = dedup(fragments, key(false));
+        assert_eq!(pooled.duplicates, [fragments[1], fragments[3]]);
+        assert_eq!(dedup(Vec::<u8>::new(), |_| unreachable!()).duplication_rate(), 0.0);
+    }
+
+    #[test]
+    fn binned_depth_reports_every_bin() {
+        let seq_lens = IndexMap::from([(SeqId::from("chr1"), 25), (SeqId::from("chr2"), 10)]);
+        let reads = ranges(&["chr1:1-10", "chr1:6-15"]);
+        let bins = binned_depth(&reads, 10, &seq_lens).unwrap();
+        assert_eq!(runs(&bins), [
+            ("chr1:1-10".to_string(), 2), ("chr1:11-20".to_string(), 1), ("chr1:21-25".to_string(), 0), ("chr2:1-10".to_string(), 0),
+        ]);
+        assert!(binned_depth(&reads, 0, &seq_lens).is_err());
+        assert!(binned_depth(&ranges(&["chr3:1-10"]), 10, &seq_lens).is_err());
+        assert!(binned_depth(&ranges(&["chr2:5-20"]), 10, &seq_lens).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! CpG island detection
+//!
+//! CpG islands are found with the sliding-window method of Takai and Jones (2002): windows that meet
+//! the length, GC content and observed/expected CpG criteria are merged, and each merged region is
+//! trimmed until it meets the criteria as a whole. The criteria of Gardiner-Garden and Frommer (1987)
+//! can be used instead.
+
+use crate::format::{Bed6Row, Strand};
+use crate::genome::{GenomicRange, SeqId};
+use crate::sequence::Genome;
+
+/// The criteria for a CpG island
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpgIslandCriteria {
+    /// The window size, which is also the minimum island length
+    pub min_len: u64,
+    /// The minimum fraction of G and C
+    pub min_gc: f64,
+    /// The minimum ratio of observed to expected CpG, where expected is C × G / length
+    pub min_obs_exp: f64,
+}
+
+impl CpgIslandCriteria {
+    /// At least 200 bases, 50% GC and 0.6 observed/expected CpG
+    pub fn gardiner_garden() -> CpgIslandCriteria {
+        CpgIslandCriteria { min_len: 200, min_gc: 0.5, min_obs_exp: 0.6 }
+    }
+
+    /// At least 500 bases, 55% GC and 0.65 observed/expected CpG, which excludes most Alu repeats
+    pub fn takai_jones() -> CpgIslandCriteria {
+        CpgIslandCriteria { min_len: 500, min_gc: 0.55, min_obs_exp: 0.65 }
+    }
+}
+
+impl Default for CpgIslandCriteria {
+    fn default() -> Self {
+        CpgIslandCriteria::takai_jones()
+    }
+}
+
+/// A CpG island with its statistics
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpgIsland {
+    pub range: GenomicRange,
+    pub cpg_count: u64,
+    pub gc_fraction: f64,
+    pub obs_exp: f64,
+}
+
+impl CpgIsland {
+    /// A BED row named `CpG:<count>`, as in the UCSC CpG island track
+    pub fn to_bed_row(&self) -> Bed6Row {
+        let range = self.range.range_0halfopen();
+        Bed6Row {
+            chrom: self.range.seqid().clone(),
+            chrom_start: range.start,
+            chrom_end: range.end,
+            name: format!("CpG:{}", self.cpg_count),
+            score: 0,
+            strand: Strand::None,
+        }
+    }
+}
+
+/// Cumulative counts of C, G and CpG, so that the counts of any interval are found by subtraction
+struct Counts {
+    c: Vec<u64>,
+    g: Vec<u64>,
+    cpg: Vec<u64>,
+}
+
+impl Counts {
+    fn new(sequence: &[u8]) -> Counts {
+        let mut counts = Counts { c: vec![0], g: vec![0], cpg: vec![0] };
+        for (i, base) in sequence.iter().enumerate() {
+            let base = base.to_ascii_uppercase();
+            counts.c.push(counts.c[i] + (base == b'C') as u64);
+            counts.g.push(counts.g[i] + (base == b'G') as u64);
+            // a CpG is counted at the position of its G
+            let cpg = base == b'G' && i > 0 && sequence[i - 1].eq_ignore_ascii_case(&b'C');
+            counts.cpg.push(counts.cpg[i] + cpg as u64);
+        }
+        counts
+    }
+
+    /// The C, G and CpG counts of a 0-based, half-open interval
+    fn stats(&self, start: usize, end: usize) -> (u64, u64, u64) {
+        let cpg_start = (start + 1).min(end);
+        (self.c[end] - self.c[start], self.g[end] - self.g[start], self.cpg[end] - self.cpg[cpg_start])
+    }
+
+    fn passes(&self, start: usize, end: usize, criteria: &CpgIslandCriteria) -> bool {
+        let (c, g, cpg) = self.stats(start, end);
+        let len = (end - start) as f64;
+        c > 0 && g > 0
+            && (c + g) as f64 >= criteria.min_gc * len
+            && cpg as f64 * len >= criteria.min_obs_exp * (c * g) as f64
+    }
+}
+
+/// Finds the CpG islands of a sequence
+pub fn find_cpg_islands_in<T: Into<SeqId>>(seqid: T, sequence: &[u8], criteria: &CpgIslandCriteria) -> Vec<CpgIsland> {
+    let seqid = seqid.into();
+    let window = criteria.min_len.max(2) as usize;
+    if sequence.len() < window {
+        return Vec::new();
+    }
+    let counts = Counts::new(sequence);
+
+    // merge the passing windows
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for start in 0..=(sequence.len() - window) {
+        if counts.passes(start, start + window, criteria) {
+            match regions.last_mut() {
+                Some(last) if start <= last.1 => last.1 = start + window,
+                _ => regions.push((start, start + window)),
+            }
+        }
+    }
+
+    // trim the ends of each region until it passes as a whole
+    let mut islands = Vec::new();
+    for (mut start, mut end) in regions {
+        let mut trim_start = true;
+        while end - start >= window && !counts.passes(start, end, criteria) {
+            if trim_start { start += 1 } else { end -= 1 }
+            trim_start = !trim_start;
+        }
+        if end - start < window {
+            continue;
+        }
+        let (c, g, cpg) = counts.stats(start, end);
+        let len = (end - start) as f64;
+        islands.push(CpgIsland {
+            range: GenomicRange::from_0halfopen(seqid.clone(), start as u64..end as u64).unwrap(),
+            cpg_count: cpg,
+            gc_fraction: (c + g) as f64 / len,
+            obs_exp: cpg as f64 * len / (c * g) as f64,
+        });
+    }
+    islands
+}
+
+/// Finds the CpG islands of every sequence in the genome
+pub fn find_cpg_islands(genome: &Genome, criteria: &CpgIslandCriteria) -> Vec<CpgIsland> {
+    genome.seqids()
+        .flat_map(|seqid| find_cpg_islands_in(seqid.clone(), genome.get(seqid).unwrap_or_default(), criteria))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRITERIA: CpgIslandCriteria = CpgIslandCriteria { min_len: 20, min_gc: 0.5, min_obs_exp: 0.6 };
+
+    #[test]
+    fn finds_islands_of_merged_windows() {
+        let sequence = ["A".repeat(50), "CG".repeat(25), "A".repeat(50)].concat();
+        let islands = find_cpg_islands_in("chr1", sequence.as_bytes(), &CRITERIA);
+        assert_eq!(islands.len(), 1);
+        // windows with up to half their bases outside the CpGs still pass
+        assert_eq!(islands[0].range.to_string(), "chr1:41-110");
+        assert_eq!((islands[0].cpg_count, islands[0].gc_fraction, islands[0].obs_exp), (25, 50.0 / 70.0, 2.8));
+        assert_eq!(islands[0].to_bed_row().name, "CpG:25");
+    }
+
+    #[test]
+    fn gc_rich_sequence_without_cpg_is_not_an_island() {
+        let mut genome = Genome::default();
+        genome.insert("chr1", ["G".repeat(100), "C".repeat(100)].concat().into_bytes());
+        genome.insert("chr2", b"CGCG".to_vec());
+        assert!(find_cpg_islands(&genome, &CRITERIA).is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Protein domains projected onto the genome
+//!
+//! Domain annotation from InterProScan (or the HMMER searches it runs) is in protein coordinates.
+//! [`project_domains`] maps each domain through the CDS of its transcript onto the genome, giving
+//! GFF3 `protein_match` features that are split across introns like CDS.
+
+use indexmap::IndexMap;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{Error, GenomicRange};
+use crate::liftover::CoordinateMap;
+use crate::models::{Gene, Transcript};
+use crate::transcript::TranscriptMap;
+
+/// A domain hit from the TSV output of InterProScan
+///
+/// Coordinates are 1-based, closed positions in the protein. Optional columns are `None` when missing or `-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainHit {
+    pub protein_id: String,
+    pub protein_len: u64,
+    /// The member database e.g. Pfam
+    pub analysis: String,
+    pub signature_accession: String,
+    pub signature_description: Option<String>,
+    pub start: u64,
+    pub end: u64,
+    /// The E-value or score, as given
+    pub score: Option<String>,
+    pub interpro_accession: Option<String>,
+    pub interpro_description: Option<String>,
+    /// GO terms, separated by `|`
+    pub go_terms: Option<String>,
+}
+
+/// Parses the TSV output of InterProScan, which has 11 to 15 columns
+pub fn parse_interproscan_tsv(text: &str) -> Result<Vec<DomainHit>, Error> {
+    let mut hits = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() < 11 {
+            return Err(Error::InvalidArguments(format!("line {}: InterProScan lines should have at least 11 fields", line_num + 1)));
+        }
+        let num = |i: usize| fields[i].trim().parse::<u64>()
+            .map_err(|_| Error::InvalidArguments(format!("line {}: field {} should be an unsigned integer, not {}", line_num + 1, i + 1, fields[i])));
+        let optional = |i: usize| fields.get(i).map(|f| f.trim()).filter(|f| !f.is_empty() && *f != "-").map(|f| f.to_string());
+        let hit = DomainHit {
+            protein_id: fields[0].to_string(),
+            protein_len: num(2)?,
+            analysis: fields[3].to_string(),
+            signature_accession: fields[4].to_string(),
+            signature_description: optional(5),
+            start: num(6)?,
+            end: num(7)?,
+            score: optional(8),
+            interpro_accession: optional(11),
+            interpro_description: optional(12),
+            go_terms: optional(13),
+        };
+        if hit.start == 0 || hit.start > hit.end || hit.end > hit.protein_len {
+            return Err(Error::InvalidArguments(format!("line {}: domain {}-{} should be within the protein of length {}", line_num + 1, hit.start, hit.end, hit.protein_len)));
+        }
+        hits.push(hit);
+    }
+    Ok(hits)
+}
+
+/// The projected domains, with the hits that couldn't be projected
+#[derive(Debug, Clone, Default)]
+pub struct DomainProjection {
+    pub rows: Vec<Gff3Row<String>>,
+    /// Hits to proteins that aren't a coding transcript of the gene models
+    pub unmatched: Vec<DomainHit>,
+}
+
+/// Projects protein domains onto the genome through the CDS of the transcripts named by the protein ids
+///
+/// Each domain becomes one `protein_match` row per CDS segment it spans, sharing an `ID` of
+/// `<transcript>.<analysis>.<n>`, with the transcript as `Parent`. The signature is the `Name`, and
+/// the protein range is in a `Target` attribute. InterPro accessions become `Dbxref`s and GO terms
+/// `Ontology_term`s. Protein coordinates are counted from the first complete codon of the CDS.
+pub fn project_domains<T>(hits: Vec<DomainHit>, genes: &[Gene<T>], source: &str) -> Result<DomainProjection, Error> {
+    let transcripts: IndexMap<&str, &Transcript<T>> = genes.iter()
+        .flat_map(|g| &g.transcripts)
+        .filter(|t| t.is_coding())
+        .filter_map(|t| t.id().map(|id| (id, t)))
+        .collect();
+    let mut projection = DomainProjection::default();
+    let mut counts: IndexMap<(String, String), usize> = IndexMap::new();
+    for hit in hits {
+        let Some(transcript) = transcripts.get(hit.protein_id.as_str()) else {
+            projection.unmatched.push(hit);
+            continue;
+        };
+        let mut cds = transcript.cds_ranges();
+        // skip the bases before the first complete codon, given by the phase of the 5' segment
+        let (first, first_row) = match transcript.strand() {
+            Strand::Minus => (cds.len() - 1, transcript.cds.last()),
+            _ => (0, transcript.cds.first()),
+        };
+        let phase = first_row.and_then(|row| row.phase.parse::<u64>().ok()).unwrap_or(0);
+        if phase > 0 {
+            let segment = cds[first].range_0halfopen();
+            let trimmed = match transcript.strand() {
+                Strand::Minus => segment.start..segment.end.saturating_sub(phase).max(segment.start),
+                _ => (segment.start + phase).min(segment.end)..segment.end,
+            };
+            cds[first] = GenomicRange::from_0halfopen(cds[first].seqid().clone(), trimmed)?;
+        }
+        let map = TranscriptMap::new(hit.protein_id.as_str(), transcript.strand(), &cds)?.in_codons();
+        let protein_range = GenomicRange::from_1closed(hit.protein_id.as_str(), hit.start..=hit.end)?;
+        let pieces = map.inverse().map_pieces(&protein_range);
+        if pieces.is_empty() {
+            projection.unmatched.push(hit);
+            continue;
+        }
+
+        let count = counts.entry((hit.protein_id.clone(), hit.analysis.clone())).or_default();
+        *count += 1;
+        let mut attributes = IndexMap::new();
+        attributes.insert("ID".to_string(), format!("{}.{}.{}", hit.protein_id, hit.analysis, count));
+        attributes.insert("Parent".to_string(), hit.protein_id.clone());
+        attributes.insert("Name".to_string(), hit.signature_accession.clone());
+        attributes.insert("Target".to_string(), format!("{} {} {}", hit.protein_id, hit.start, hit.end));
+        attributes.insert("analysis".to_string(), hit.analysis.clone());
+        if let Some(description) = &hit.signature_description {
+            attributes.insert("signature_desc".to_string(), description.clone());
+        }
+        if let Some(interpro) = &hit.interpro_accession {
+            attributes.insert("Dbxref".to_string(), format!("InterPro:{}", interpro));
+        }
+        if let Some(go_terms) = &hit.go_terms {
+            let terms: Vec<&str> = go_terms.split('|').map(|t| t.split('(').next().unwrap_or(t)).collect();
+            attributes.insert("Ontology_term".to_string(), terms.join(","));
+        }
+        for piece in pieces {
+            let range = piece.range.range_1closed();
+            projection.rows.push(Gff3Row {
+                seqid: piece.range.seqid().clone(),
+                source: source.to_string(),
+                feature_type: "protein_match".to_string(),
+                start: *range.start(),
+                end: *range.end(),
+                score: hit.score.clone().unwrap_or_else(|| ".".to_string()),
+                strand: transcript.strand(),
+                phase: ".".to_string(),
+                attributes: attributes.clone(),
+            });
+        }
+    }
+    projection.rows.sort_by_key(GenomicRange::from_gff_row);
+    Ok(projection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    const TSV: &str = "\
+t1\tabc\t20\tPfam\tPF00001\tDomain\t8\t12\t1e-5\tT\t01-01-2024\tIPR000001\tSome domain\tGO:0001(InterPro)|GO:0002(PANTHER)
+t2\tabc\t10\tSMART\tSM00001\t-\t1\t2\t-\tT\t01-01-2024
+x\tabc\t10\tPfam\tPF00002\t-\t1\t5\t-\tT\t01-01-2024
+";
+
+    #[test]
+    fn parses_interproscan_tsv() {
+        let hits = parse_interproscan_tsv(TSV).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!((hits[0].start, hits[0].end, hits[0].score.as_deref()), (8, 12, Some("1e-5")));
+        assert_eq!((hits[1].signature_description.as_deref(), hits[1].interpro_accession.as_deref()), (None, None));
+        assert!(parse_interproscan_tsv("t1\tabc\t10\tPfam\tPF00001\t-\t5\t11\t-\tT\t01-01-2024\n").is_err());
+    }
+
+    #[test]
+    fn splits_domains_across_introns() {
+        let gff = "\
+chr1\t.\tgene\t101\t230\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t101\t230\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\tCDS\t101\t130\t.\t+\t0\tParent=t1
+chr1\t.\tCDS\t201\t230\t.\t+\t0\tParent=t1
+chr1\t.\tgene\t501\t530\t.\t-\t.\tID=g2
+chr1\t.\tmRNA\t501\t530\t.\t-\t.\tID=t2;Parent=g2
+chr1\t.\tCDS\t501\t530\t.\t-\t0\tParent=t2
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let projection = project_domains(parse_interproscan_tsv(TSV).unwrap(), &genes, "InterProScan").unwrap();
+        let rows: Vec<_> = projection.rows.iter().map(|r| (r.start, r.end, r.attributes["ID"].as_str())).collect();
+        assert_eq!(rows, [(122, 130, "t1.Pfam.1"), (201, 206, "t1.Pfam.1"), (525, 530, "t2.SMART.1")]);
+        assert_eq!(projection.rows[0].attributes["Ontology_term"], "GO:0001,GO:0002");
+        assert_eq!(projection.rows[0].attributes["Dbxref"], "InterPro:IPR000001");
+        assert_eq!(projection.unmatched.len(), 1);
+        assert_eq!(projection.unmatched[0].protein_id, "x");
+    }
+}
@@ -0,0 +1,630 @@
+//! Feature tables of GenBank and EMBL flat files
+//!
+//! Gene models are written as the features of a flat file, with locations such as
+//! `complement(join(100..200,300..400))` in 1-based, closed coordinates, for submission
+//! to NCBI or ENA.
+//!
+//! Flat files can also be read, converting their features into gene models and their
+//! sequences into a [`Genome`], since many microbial genomes are only distributed this way.
+
+use std::fmt;
+use indexmap::IndexMap;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{Error, SeqId};
+use crate::models::{Gene, Transcript};
+use crate::sequence::Genome;
+
+/// The layout of feature table lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlatFileStyle {
+    /// Lines are indented by 5 spaces
+    GenBank,
+    /// Lines start with `FT`
+    Embl,
+}
+
+impl FlatFileStyle {
+    fn prefix(&self) -> &'static str {
+        match self {
+            FlatFileStyle::GenBank => "     ",
+            FlatFileStyle::Embl => "FT   ",
+        }
+    }
+}
+
+/// The location of a feature, as one or more 1-based, closed spans on one strand
+///
+/// Partial features extend beyond the first (`<`) or last (`>`) span, in sequence order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub spans: Vec<(u64, u64)>,
+    pub complement: bool,
+    pub partial_start: bool,
+    pub partial_end: bool,
+}
+
+impl Location {
+    /// The location of GFF3 rows e.g. the exons of a transcript, which are sorted by position
+    pub fn from_rows<T>(rows: &[Gff3Row<T>], strand: Strand) -> Location {
+        let mut spans: Vec<_> = rows.iter().map(|r| (r.start, r.end)).collect();
+        spans.sort();
+        Location {
+            spans,
+            complement: strand == Strand::Minus,
+            partial_start: false,
+            partial_end: false,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let last = self.spans.len().saturating_sub(1);
+        let spans: Vec<_> = self.spans.iter().enumerate()
+            .map(|(i, (start, end))| {
+                let start = if i == 0 && self.partial_start { format!("<{}", start) } else { start.to_string() };
+                let end = if i == last && self.partial_end { format!(">{}", end) } else { end.to_string() };
+                if start == end { start } else { format!("{}..{}", start, end) }
+            })
+            .collect();
+        let joined = if spans.len() > 1 { format!("join({})", spans.join(",")) } else { spans.join(",") };
+        if self.complement {
+            write!(f, "complement({})", joined)
+        } else {
+            write!(f, "{}", joined)
+        }
+    }
+}
+
+/// A feature of a feature table, with its qualifiers in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+    pub key: String,
+    pub location: Location,
+    pub qualifiers: Vec<(String, Option<String>)>,
+}
+
+impl Feature {
+    /// Writes the feature in the style of a flat file, wrapping lines at 79 characters
+    pub fn to_lines(&self, style: FlatFileStyle) -> Vec<String> {
+        let indent = format!("{}{:16}", style.prefix(), "");
+        let mut lines = Vec::new();
+        // the location wraps after a comma
+        let mut line = format!("{}{:16}", style.prefix(), self.key);
+        for (i, part) in self.location.to_string().split_inclusive(',').enumerate() {
+            if i > 0 && line.len() + part.len() > 79 {
+                lines.push(line);
+                line = indent.clone();
+            }
+            line.push_str(part);
+        }
+        lines.push(line);
+        for (name, value) in &self.qualifiers {
+            let text = match value {
+                Some(value) if name == "codon_start" => format!("/{}={}", name, value),
+                Some(value) => format!("/{}=\"{}\"", name, value.replace('"', "\"\"")),
+                None => format!("/{}", name),
+            };
+            let chars: Vec<char> = text.chars().collect();
+            for chunk in chars.chunks(79 - indent.len()) {
+                lines.push(format!("{}{}", indent, chunk.iter().collect::<String>()));
+            }
+        }
+        lines
+    }
+}
+
+/// The qualifiers shared by the features of a gene, taken from its GFF3 attributes
+fn gene_qualifiers<T>(row: &Gff3Row<T>) -> Vec<(String, Option<String>)> {
+    let mut qualifiers = Vec::new();
+    if let Some(name) = row.attributes.get("gene").or(row.attributes.get("Name")) {
+        qualifiers.push(("gene".to_string(), Some(name.clone())));
+    }
+    if let Some(tag) = row.attributes.get("locus_tag") {
+        qualifiers.push(("locus_tag".to_string(), Some(tag.clone())));
+    }
+    qualifiers
+}
+
+fn transcript_features<T: AsRef<str>>(gene: &Gene<T>, transcript: &Transcript<T>) -> Vec<Feature> {
+    let strand = transcript.strand();
+    let qualifiers = gene_qualifiers(&gene.row);
+    let product = transcript.row.attributes.get("product").cloned();
+    let mut features = Vec::new();
+    if !transcript.exons.is_empty() {
+        let mut mrna_qualifiers = qualifiers.clone();
+        if let Some(product) = &product {
+            mrna_qualifiers.push(("product".to_string(), Some(product.clone())));
+        }
+        features.push(Feature {
+            key: transcript.row.feature_type.as_ref().to_string(),
+            location: Location::from_rows(&transcript.exons, strand),
+            qualifiers: mrna_qualifiers,
+        });
+    }
+    if !transcript.cds.is_empty() {
+        let mut cds_qualifiers = qualifiers;
+        let first = if strand == Strand::Minus { transcript.cds.last() } else { transcript.cds.first() };
+        let phase = first.and_then(|r| r.phase.parse::<u64>().ok()).unwrap_or(0);
+        if phase > 0 {
+            cds_qualifiers.push(("codon_start".to_string(), Some((phase + 1).to_string())));
+        }
+        let cds_product = transcript.cds[0].attributes.get("product").cloned().or(product);
+        if let Some(product) = cds_product {
+            cds_qualifiers.push(("product".to_string(), Some(product)));
+        }
+        let mut location = Location::from_rows(&transcript.cds, strand);
+        // a CDS that doesn't start at phase 0 is 5' partial
+        if phase > 0 {
+            if strand == Strand::Minus {
+                location.partial_end = true;
+            } else {
+                location.partial_start = true;
+            }
+        }
+        features.push(Feature {
+            key: "CDS".to_string(),
+            location,
+            qualifiers: cds_qualifiers,
+        });
+    }
+    features
+}
+
+/// Converts gene models into flat file features: a `gene` for each gene, then an mRNA
+/// (or other transcript type) joining the exons and a `CDS` joining the CDS segments of each transcript
+///
+/// Qualifiers are taken from the `Name` (or `gene`), `locus_tag` and `product` attributes, and
+/// `/codon_start` is added for CDS with a non-zero phase.
+pub fn gene_features<T: AsRef<str>>(genes: &[Gene<T>]) -> Vec<Feature> {
+    let mut features = Vec::new();
+    for gene in genes {
+        features.push(Feature {
+            key: "gene".to_string(),
+            location: Location {
+                spans: vec![(gene.row.start, gene.row.end)],
+                complement: gene.strand() == Strand::Minus,
+                partial_start: false,
+                partial_end: false,
+            },
+            qualifiers: gene_qualifiers(&gene.row),
+        });
+        for transcript in &gene.transcripts {
+            features.extend(transcript_features(gene, transcript));
+        }
+    }
+    features
+}
+
+/// Writes the features of the gene models as the lines of a flat file feature table, without the `FEATURES` header
+pub fn feature_table<T: AsRef<str>>(genes: &[Gene<T>], style: FlatFileStyle) -> String {
+    let mut table = String::new();
+    for feature in gene_features(genes) {
+        for line in feature.to_lines(style) {
+            table.push_str(&line);
+            table.push('\n');
+        }
+    }
+    table
+}
+
+impl TryFrom<&str> for Location {
+    type Error = Error;
+
+    /// Parses a location, including `join`, `order` and `complement` e.g. `complement(join(<1..100,200..>300))`
+    ///
+    /// Spans that are complemented individually, as in `join(complement(200..300),complement(1..100))`,
+    /// must all be on the same strand. References to other entries and between-base locations (`1^2`)
+    /// aren't supported.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let text: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut spans = Vec::new();
+        parse_location(&text, false, &mut spans)?;
+        let complement = spans.first().is_some_and(|s| s.3);
+        if spans.iter().any(|s| s.3 != complement) {
+            return Err(Error::InvalidArguments(format!("location {} is on both strands", value)));
+        }
+        spans.sort_by_key(|s| s.0);
+        let partial_start = spans.first().is_some_and(|s| s.2 .0);
+        let partial_end = spans.last().is_some_and(|s| s.2 .1);
+        Ok(Location {
+            spans: spans.into_iter().map(|s| (s.0, s.1)).collect(),
+            complement,
+            partial_start,
+            partial_end,
+        })
+    }
+}
+
+type ParsedSpan = (u64, u64, (bool, bool), bool);
+
+fn parse_location(text: &str, complement: bool, spans: &mut Vec<ParsedSpan>) -> Result<(), Error> {
+    let invalid = || Error::InvalidArguments(format!("invalid location {}", text));
+    for (name, inner_complement) in [("complement(", true), ("join(", false), ("order(", false)] {
+        if let Some(inner) = text.strip_prefix(name) {
+            let inner = inner.strip_suffix(')').ok_or_else(invalid)?;
+            let complement = complement ^ inner_complement;
+            for part in split_top_level(inner) {
+                parse_location(part, complement, spans)?;
+            }
+            return Ok(());
+        }
+    }
+    if text.contains(':') || text.contains('^') {
+        return Err(Error::InvalidArguments(format!("unsupported location {}", text)));
+    }
+    let (start, end) = text.split_once("..").unwrap_or((text, text));
+    let partial_start = start.starts_with('<');
+    let partial_end = end.starts_with('>');
+    let start = start.trim_start_matches('<').parse::<u64>().map_err(|_| invalid())?;
+    let end = end.trim_start_matches('>').parse::<u64>().map_err(|_| invalid())?;
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    spans.push((start, end, (partial_start, partial_end), complement));
+    Ok(())
+}
+
+/// Splits on the commas that aren't within brackets
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// The features and sequence of one entry of a flat file
+#[derive(Debug, Clone)]
+pub struct FlatFileEntry {
+    /// The name from the `LOCUS` or `ID` line
+    pub seqid: SeqId,
+    pub features: Vec<Feature>,
+    pub sequence: Vec<u8>,
+}
+
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Features,
+    Sequence,
+}
+
+/// Parses the entries of a GenBank or EMBL flat file, each ending with `//`
+pub fn parse_flat_file(text: &str) -> Result<Vec<FlatFileEntry>, Error> {
+    let mut entries = Vec::new();
+    let mut entry: Option<FlatFileEntry> = None;
+    let mut section = Section::Header;
+    // the lines of each feature, with continuation lines joined by newlines
+    let mut feature_lines: Vec<String> = Vec::new();
+
+    for line in text.lines() {
+        if line.starts_with("LOCUS") || line.starts_with("ID   ") {
+            let name = line.split_whitespace().nth(1).unwrap_or_default().trim_end_matches(';');
+            entry = Some(FlatFileEntry { seqid: SeqId::from(name), features: Vec::new(), sequence: Vec::new() });
+            section = Section::Header;
+        } else if line.starts_with("//") {
+            let mut done = entry.take().ok_or_else(|| Error::InvalidArguments("// without an entry".to_string()))?;
+            done.features = features_from_lines(&std::mem::take(&mut feature_lines))?;
+            entries.push(done);
+            section = Section::Header;
+        } else if line.starts_with("FEATURES") || line.starts_with("FH") {
+            section = Section::Features;
+        } else if line.starts_with("ORIGIN") || line.starts_with("SQ") {
+            section = Section::Sequence;
+        } else if section == Section::Features && (line.starts_with("FT") || line.starts_with("     ")) {
+            let content = line.get(5..).unwrap_or_default();
+            if !content.get(..16).unwrap_or(content).trim().is_empty() {
+                feature_lines.push(content.to_string());
+            } else if let Some(last) = feature_lines.last_mut() {
+                last.push('\n');
+                last.push_str(content.trim());
+            }
+        } else if section == Section::Features {
+            section = Section::Header;
+        } else if section == Section::Sequence {
+            if let Some(entry) = entry.as_mut() {
+                entry.sequence.extend(line.bytes().filter(|b| b.is_ascii_alphabetic()));
+            }
+        }
+    }
+    if entry.is_some() {
+        return Err(Error::InvalidArguments("flat file entry doesn't end with //".to_string()));
+    }
+    Ok(entries)
+}
+
+/// Parses features from their lines, each starting with the key followed by continuation lines
+fn features_from_lines(lines: &[String]) -> Result<Vec<Feature>, Error> {
+    let mut features = Vec::new();
+    for lines in lines {
+        let mut parts = lines.split('\n');
+        let first = parts.next().unwrap_or_default();
+        let (key, location) = first.split_at(16.min(first.len()));
+        let key = key.trim().to_string();
+        let mut location = location.trim().to_string();
+        let mut qualifiers: Vec<(String, Option<String>)> = Vec::new();
+        let mut in_quotes = false;
+        for part in parts {
+            if !in_quotes && part.starts_with('/') {
+                let (name, value) = match part[1..].split_once('=') {
+                    Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                    None => (part[1..].to_string(), None),
+                };
+                in_quotes = value.as_ref().is_some_and(|v| v.starts_with('"') && (v.len() == 1 || !v.ends_with('"')));
+                qualifiers.push((name, value));
+            } else if let Some((name, Some(value))) = qualifiers.last_mut() {
+                // translations are wrapped without spaces
+                if name != "translation" {
+                    value.push(' ');
+                }
+                value.push_str(part);
+                in_quotes = !part.ends_with('"');
+            } else {
+                location.push_str(part);
+            }
+        }
+        for (_, value) in qualifiers.iter_mut() {
+            if let Some(v) = value {
+                if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') {
+                    *v = v[1..v.len() - 1].replace("\"\"", "\"");
+                }
+            }
+        }
+        features.push(Feature {
+            key,
+            location: Location::try_from(location.as_str())?,
+            qualifiers,
+        });
+    }
+    Ok(features)
+}
+
+impl Feature {
+    /// Returns the value of the first qualifier with the name
+    pub fn qualifier(&self, name: &str) -> Option<&str> {
+        self.qualifiers.iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+}
+
+const TRANSCRIPT_KEYS: [&str; 6] = ["mRNA", "ncRNA", "tRNA", "rRNA", "tmRNA", "misc_RNA"];
+
+fn feature_row(seqid: &SeqId, feature_type: &str, start: u64, end: u64, strand: Strand, attributes: Vec<(&str, String)>) -> Gff3Row<String> {
+    Gff3Row {
+        seqid: seqid.clone(),
+        source: "flatfile".to_string(),
+        feature_type: feature_type.to_string(),
+        start,
+        end,
+        score: ".".to_string(),
+        strand,
+        phase: ".".to_string(),
+        attributes: attributes.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+    }
+}
+
+/// Converts the features of an entry into gene models
+///
+/// Features are grouped into genes by their `/locus_tag`, or else their `/gene`. Transcript features
+/// (mRNA, ncRNA, tRNA, rRNA etc.) become transcripts, with exons from their location, and each CDS
+/// is added to the corresponding mRNA, in order, or else becomes a transcript of its own. Genes without
+/// a `gene` feature are given the bounds of their transcripts. Phases are derived from `/codon_start`.
+///
+/// Genes are given their locus tag (or gene name) as their `ID`, and their parts derived IDs.
+pub fn gene_models_from_entry(entry: &FlatFileEntry) -> Vec<Gene<String>> {
+    let mut groups: IndexMap<String, Vec<&Feature>> = IndexMap::new();
+    for (i, feature) in entry.features.iter().enumerate() {
+        if feature.key != "gene" && feature.key != "CDS" && !TRANSCRIPT_KEYS.contains(&feature.key.as_str()) {
+            continue;
+        }
+        let key = feature.qualifier("locus_tag")
+            .or(feature.qualifier("gene"))
+            .map(|k| k.to_string())
+            .unwrap_or_else(|| format!("{}_{}", entry.seqid, i + 1));
+        groups.entry(key).or_default().push(feature);
+    }
+
+    let mut genes = Vec::new();
+    for (gene_id, features) in groups {
+        let strand = if features[0].location.complement { Strand::Minus } else { Strand::Plus };
+        let mut gene_attributes = vec![("ID", gene_id.clone())];
+        if let Some(name) = features.iter().find_map(|f| f.qualifier("gene")) {
+            gene_attributes.push(("Name", name.to_string()));
+        }
+        if let Some(tag) = features.iter().find_map(|f| f.qualifier("locus_tag")) {
+            gene_attributes.push(("locus_tag", tag.to_string()));
+        }
+        let bounds = |location: &Location| (location.spans[0].0, location.spans.iter().map(|s| s.1).max().unwrap());
+        let gene_feature = features.iter().find(|f| f.key == "gene");
+        let (start, end) = gene_feature.map(|f| bounds(&f.location)).unwrap_or((0, 0));
+        let mut gene = Gene {
+            row: feature_row(&entry.seqid, "gene", start, end, strand, gene_attributes),
+            transcripts: Vec::new(),
+        };
+
+        let transcripts: Vec<_> = features.iter().filter(|f| TRANSCRIPT_KEYS.contains(&f.key.as_str())).collect();
+        let cds: Vec<_> = features.iter().filter(|f| f.key == "CDS").collect();
+        let mut mrna_count = 0;
+        for (i, feature) in transcripts.iter().enumerate() {
+            let tx_id = format!("{}.t{}", gene_id, i + 1);
+            let (start, end) = bounds(&feature.location);
+            let mut attributes = vec![("ID", tx_id.clone())];
+            if let Some(product) = feature.qualifier("product") {
+                attributes.push(("product", product.to_string()));
+            }
+            // exons are numbered in the direction of transcription
+            let spans = &feature.location.spans;
+            let exons = spans.iter().enumerate()
+                .map(|(n, (s, e))| {
+                    let exon_num = if strand == Strand::Minus { spans.len() - n } else { n + 1 };
+                    feature_row(&entry.seqid, "exon", *s, *e, strand, vec![("ID", format!("{}.exon{}", tx_id, exon_num))])
+                })
+                .collect();
+            let mut transcript = Transcript {
+                row: feature_row(&entry.seqid, &feature.key, start, end, strand, attributes),
+                exons,
+                cds: Vec::new(),
+                others: Vec::new(),
+            };
+            if feature.key == "mRNA" {
+                if let Some(cds) = cds.get(mrna_count) {
+                    transcript.cds = cds_rows(&entry.seqid, cds, &tx_id, strand);
+                }
+                mrna_count += 1;
+            }
+            gene.transcripts.push(transcript);
+        }
+        for cds in cds.iter().skip(mrna_count) {
+            let tx_id = format!("{}.t{}", gene_id, gene.transcripts.len() + 1);
+            let (start, end) = bounds(&cds.location);
+            let mut attributes = vec![("ID", tx_id.clone())];
+            if let Some(product) = cds.qualifier("product") {
+                attributes.push(("product", product.to_string()));
+            }
+            gene.transcripts.push(Transcript {
+                row: feature_row(&entry.seqid, "mRNA", start, end, strand, attributes),
+                exons: Vec::new(),
+                cds: cds_rows(&entry.seqid, cds, &tx_id, strand),
+                others: Vec::new(),
+            });
+        }
+        if gene_feature.is_some() {
+            // keep the bounds of the gene feature
+            for transcript in &mut gene.transcripts {
+                transcript.rederive();
+                transcript.row.attributes.insert("Parent".to_string(), gene_id.clone());
+            }
+        } else {
+            gene.rederive();
+        }
+        genes.push(gene);
+    }
+    genes
+}
+
+fn cds_rows(seqid: &SeqId, cds: &Feature, tx_id: &str, strand: Strand) -> Vec<Gff3Row<String>> {
+    let mut attributes = vec![("ID", format!("{}.cds", tx_id))];
+    if let Some(product) = cds.qualifier("product") {
+        attributes.push(("product", product.to_string()));
+    }
+    let mut rows: Vec<_> = cds.location.spans.iter()
+        .map(|(s, e)| feature_row(seqid, "CDS", *s, *e, strand, attributes.clone()))
+        .collect();
+    let phase = cds.qualifier("codon_start").and_then(|c| c.parse::<u64>().ok()).unwrap_or(1).saturating_sub(1);
+    let first = if strand == Strand::Minus { rows.last_mut() } else { rows.first_mut() };
+    if let Some(first) = first {
+        first.phase = phase.to_string();
+    }
+    rows
+}
+
+/// Reads the gene models and sequences of all the entries of a flat file
+pub fn read_flat_file(text: &str) -> Result<(Vec<Gene<String>>, Genome), Error> {
+    let mut genes = Vec::new();
+    let mut genome = Genome::default();
+    for entry in parse_flat_file(text)? {
+        genes.extend(gene_models_from_entry(&entry));
+        if !entry.sequence.is_empty() {
+            genome.insert(entry.seqid.clone(), entry.sequence);
+        }
+    }
+    Ok((genes, genome))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    const GFF: &str = "\
+chr1\t.\tgene\t100\t900\t.\t-\t.\tID=g1;Name=abcA;locus_tag=ABC_00010
+chr1\t.\tmRNA\t100\t900\t.\t-\t.\tID=t1;Parent=g1;product=ABC transporter
+chr1\t.\texon\t100\t300\t.\t-\t.\tParent=t1
+chr1\t.\texon\t700\t900\t.\t-\t.\tParent=t1
+chr1\t.\tCDS\t200\t300\t.\t-\t1\tID=cds1;Parent=t1
+chr1\t.\tCDS\t700\t850\t.\t-\t2\tID=cds1;Parent=t1
+";
+
+    fn genes() -> Vec<Gene<String>> {
+        build_gene_models(parse_gff3(GFF, ParseMode::Strict).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_feature_tables() {
+        let table = feature_table(&genes(), FlatFileStyle::GenBank);
+        let expected = [
+            "     gene            complement(100..900)",
+            "                     /gene=\"abcA\"",
+            "                     /locus_tag=\"ABC_00010\"",
+            "     mRNA            complement(join(100..300,700..900))",
+            "                     /gene=\"abcA\"",
+            "                     /locus_tag=\"ABC_00010\"",
+            "                     /product=\"ABC transporter\"",
+            "     CDS             complement(join(200..300,700..>850))",
+            "                     /gene=\"abcA\"",
+            "                     /locus_tag=\"ABC_00010\"",
+            "                     /codon_start=3",
+            "                     /product=\"ABC transporter\"",
+        ];
+        assert_eq!(table.lines().collect::<Vec<_>>(), expected);
+        assert!(feature_table(&genes(), FlatFileStyle::Embl).starts_with("FT   gene            complement(100..900)\n"));
+    }
+
+    #[test]
+    fn long_locations_wrap_after_commas() {
+        let location = Location { spans: (1..=20).map(|i| (i * 1000, i * 1000 + 500)).collect(), complement: false, partial_start: false, partial_end: false };
+        let feature = Feature { key: "mRNA".to_string(), location, qualifiers: Vec::new() };
+        let lines = feature.to_lines(FlatFileStyle::GenBank);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= 79));
+        assert!(lines[..lines.len() - 1].iter().all(|l| l.ends_with(',')));
+    }
+
+    #[test]
+    fn parses_locations() {
+        let location = Location::try_from("join(complement(200..>300), complement(<1..100))").unwrap();
+        assert_eq!(location, Location { spans: vec![(1, 100), (200, 300)], complement: true, partial_start: true, partial_end: true });
+        assert_eq!(Location::try_from("complement(join(1..100,200..300))").unwrap().to_string(), "complement(join(1..100,200..300))");
+        assert!(Location::try_from("join(1..100,complement(200..300))").is_err());
+        assert!(Location::try_from("J00194.1:100..202").is_err());
+    }
+
+    #[test]
+    fn reads_written_gene_models() {
+        let text = format!(
+            "LOCUS       chr1                    1000 bp    DNA     linear   BCT\nFEATURES             Location/Qualifiers\n{}ORIGIN\n        1 {}\n//\n",
+            feature_table(&genes(), FlatFileStyle::GenBank),
+            "acgt".repeat(250),
+        );
+        let (genes, genome) = read_flat_file(&text).unwrap();
+        assert_eq!(genome.seq_len(&SeqId::from("chr1")), Some(1000));
+        let gene = &genes[0];
+        assert_eq!((gene.id(), gene.row.start, gene.row.end, gene.strand()), (Some("ABC_00010"), 100, 900, Strand::Minus));
+        let transcript = &gene.transcripts[0];
+        assert_eq!(transcript.row.attributes["product"], "ABC transporter");
+        let exons: Vec<_> = transcript.exons.iter().map(|e| (e.start, e.end, e.attributes["ID"].as_str())).collect();
+        assert_eq!(exons, [(100, 300, "ABC_00010.t1.exon2"), (700, 900, "ABC_00010.t1.exon1")]);
+        let cds: Vec<_> = transcript.cds.iter().map(|c| (c.start, c.end, c.phase.as_str())).collect();
+        assert_eq!(cds, [(200, 300, "1"), (700, 850, "2")]);
+    }
+
+    #[test]
+    fn unterminated_entries_are_an_error() {
+        assert!(parse_flat_file("LOCUS       chr1 10 bp\nORIGIN\n        1 acgt\n").is_err());
+    }
+}
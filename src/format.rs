@@ -8,7 +8,7 @@ use indexmap::IndexMap;
 use num_traits::NumOps;
 use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::genome::{GenomicRange, SeqId};
+use crate::genome::{Error, GenomicRange, NormalizedRanges, SeqId, SeqIdAliasMap, SeqIdFilter, SeqIdInterner};
 
 /// The standard fields of GFF3
 ///
@@ -17,7 +17,7 @@ use crate::genome::{GenomicRange, SeqId};
 /// - Percent encodings are not converted on deserialize and are not used in serialize
 /// - Validation is limited to type e.g. String, u64 etc.
 ///     - [seqid](Gff3Row::seqid), [score](Gff3Row::score), and [phase](Gff3Row::phase) allow any string
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Gff3Row<T> {
     pub seqid: SeqId,
     pub source: String,
@@ -48,8 +48,336 @@ where
     Ok(map)
 }
 
+/// How strictly text is parsed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Reject any deviation from the specification, for validation
+    #[default]
+    Strict,
+    /// Coerce common deviations, for ingesting files as they are found
+    Lenient,
+}
+
+/// Options for parsing GFF3, with the fields to parse
+///
+/// Fields that aren't projected are skipped, and left empty in the parsed rows: empty text, 0 for the
+/// coordinates, `.` for the score and phase, no strand and no attributes. Skipping the attributes in
+/// particular speeds up reading when only coordinates are needed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParserOptions {
+    pub mode: ParseMode,
+    projection: Option<Vec<&'static str>>,
+    seqid_filter: Option<SeqIdFilter>,
+    regions: Option<NormalizedRanges>,
+    aliases: Option<SeqIdAliasMap>,
+}
+
+impl ParserOptions {
+    /// Options to parse every field
+    pub fn new(mode: ParseMode) -> ParserOptions {
+        ParserOptions { mode, projection: None, seqid_filter: None, regions: None, aliases: None }
+    }
+
+    /// Parses only the named fields of [`Gff3Row`], by their names in [`Schema::fields`] e.g. `seqid` or `start`
+    ///
+    /// `type` is accepted for `feature_type`. The start and end are always parsed together, so that they
+    /// can be validated. Fails if a name isn't a field.
+    pub fn project(self, fields: &[&str]) -> Result<ParserOptions, Error> {
+        let schema = Gff3Row::<String>::fields();
+        let mut projection = Vec::new();
+        for field in fields {
+            let field = if *field == "type" { "feature_type" } else { *field };
+            let Some(descriptor) = schema.iter().find(|d| d.name == field) else {
+                return Err(Error::InvalidArguments(format!("{} is not a field of GFF3 rows", field)));
+            };
+            projection.push(descriptor.name);
+            if matches!(field, "start" | "end") {
+                projection.extend(["start", "end"]);
+            }
+        }
+        Ok(ParserOptions { projection: Some(projection), ..self })
+    }
+
+    /// Skips rows on sequences that the filter excludes, before the rest of the row is parsed
+    pub fn filter_seqids(self, filter: SeqIdFilter) -> ParserOptions {
+        ParserOptions { seqid_filter: Some(filter), ..self }
+    }
+
+    /// Skips rows that don't overlap the regions, before the rest of the row is parsed, to work on a few loci
+    pub fn within(self, regions: NormalizedRanges) -> ParserOptions {
+        ParserOptions { regions: Some(regions), ..self }
+    }
+
+    /// Renames the seqid of each row to its canonical name, leaving unknown names unchanged
+    ///
+    /// The seqid filter and regions are matched against the canonical names.
+    pub fn rename_seqids(self, aliases: SeqIdAliasMap) -> ParserOptions {
+        ParserOptions { aliases: Some(aliases), ..self }
+    }
+
+    /// The seqid of a row, renamed to its canonical name, and otherwise interned
+    fn seqid(&self, name: &str, interner: &mut SeqIdInterner) -> SeqId {
+        match self.aliases.as_ref().and_then(|aliases| aliases.resolve(name)) {
+            Some(canonical) => canonical.clone(),
+            None => interner.get_or_intern(name),
+        }
+    }
+
+    /// Whether a line passes the seqid filter and regions, by its first columns
+    ///
+    /// Lines whose coordinates can't be read are passed on, so that parsing reports the error.
+    fn includes(&self, line: &str) -> bool {
+        if self.seqid_filter.is_none() && self.regions.is_none() {
+            return true;
+        }
+        let fields: Vec<&str> = if line.contains('\t') { line.splitn(6, '\t').collect() } else { line.split_whitespace().take(6).collect() };
+        let seqid = self.aliases.as_ref().and_then(|aliases| aliases.resolve(fields[0])).map_or(fields[0], |canonical| canonical.as_str());
+        if self.seqid_filter.as_ref().is_some_and(|filter| !filter.matches(seqid)) {
+            return false;
+        }
+        let Some(regions) = &self.regions else {
+            return true;
+        };
+        let coordinate = |i: usize| fields.get(i).and_then(|f| parse_coordinate(f, "", self.mode).ok());
+        match (coordinate(3), coordinate(4)) {
+            (Some(start), Some(end)) => {
+                let (start, end) = (start.min(end), start.max(end));
+                regions.overlaps_any(&GenomicRange::from_0halfopen(seqid, start.saturating_sub(1)..end).unwrap())
+            }
+            _ => true,
+        }
+    }
+
+    fn parses(&self, field: &str) -> bool {
+        self.projection.as_ref().is_none_or(|projection| projection.contains(&field))
+    }
+}
+
+fn parse_line_error(line_num: usize, message: String) -> Error {
+    Error::InvalidArguments(format!("line {} of GFF3: {}", line_num + 1, message))
+}
+
+/// Parses a GFF3 coordinate, which leniently may be written as a whole number with a decimal point e.g. `1.0`
+fn parse_coordinate(value: &str, name: &str, mode: ParseMode) -> Result<u64, String> {
+    let parsed = match mode {
+        ParseMode::Strict => value.parse::<u64>().ok(),
+        ParseMode::Lenient => value.trim().parse::<u64>().ok().or_else(|| {
+            value.trim().parse::<f64>().ok().filter(|v| v.fract() == 0.0 && *v >= 0.0).map(|v| v as u64)
+        }),
+    };
+    parsed.ok_or_else(|| format!("{} should be an unsigned integer", name))
+}
+
+impl Gff3Row<String> {
+    /// Parses a GFF3 feature line
+    ///
+    /// In [`ParseMode::Strict`], the line should have exactly 9 tab-separated columns, with a start of at least 1
+    /// and no more than the end, a numeric score or `.`, a strand of `+`, `-`, `.` or `?`, a phase for CDS,
+    /// and `key=value` attributes. [`ParseMode::Lenient`] also accepts:
+    /// - columns separated by spaces, where the line has no tabs
+    /// - a missing attribute column
+    /// - coordinates written with a decimal point, and reversed coordinates, which are swapped
+    /// - any score, strand or phase, with unknown values treated as missing
+    /// - attributes without a value, which are skipped
+    ///
+    /// `?` is read as [`Strand::None`].
+    pub fn from_line(line: &str, mode: ParseMode) -> Result<Gff3Row<String>, Error> {
+        parse_row(line, &ParserOptions::new(mode), &mut SeqIdInterner::new()).map_err(Error::InvalidArguments)
+    }
+}
+
+fn parse_row(line: &str, options: &ParserOptions, interner: &mut SeqIdInterner) -> Result<Gff3Row<String>, String> {
+    let mode = options.mode;
+    let fields: Vec<&str> = match mode {
+        ParseMode::Lenient if !line.contains('\t') => {
+            let mut fields: Vec<&str> = Vec::new();
+            let mut rest = line.trim();
+            while fields.len() < 8 && !rest.is_empty() {
+                let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                fields.push(field);
+                rest = tail.trim_start();
+            }
+            if !rest.is_empty() {
+                fields.push(rest);
+            }
+            fields
+        }
+        _ => line.trim_end_matches(['\r', '\n']).split('\t').collect(),
+    };
+    match (fields.len(), mode) {
+        (9, _) | (8, ParseMode::Lenient) => {}
+        (n, _) => return Err(format!("expected 9 columns but found {}", n)),
+    }
+
+    let (mut start, mut end) = (0, 0);
+    if options.parses("start") {
+        start = parse_coordinate(fields[3], "start", mode)?;
+        end = parse_coordinate(fields[4], "end", mode)?;
+        if start > end && mode == ParseMode::Lenient {
+            std::mem::swap(&mut start, &mut end);
+        }
+        if start == 0 || start > end {
+            return Err(format!("start {} and end {} should be 1-based, with the start no more than the end", start, end));
+        }
+    }
+
+    let score = match fields[5] {
+        _ if !options.parses("score") => ".".to_string(),
+        "." => ".".to_string(),
+        score if score.parse::<f64>().is_ok() => score.to_string(),
+        _ if mode == ParseMode::Lenient => ".".to_string(),
+        score => return Err(format!("score {} should be a number or .", score)),
+    };
+    let strand = match fields[6] {
+        _ if !options.parses("strand") => Strand::None,
+        "+" => Strand::Plus,
+        "-" => Strand::Minus,
+        "." | "?" => Strand::None,
+        _ if mode == ParseMode::Lenient => Strand::None,
+        strand => return Err(format!("strand {} should be +, -, . or ?", strand)),
+    };
+    let phase = match (fields[7], fields[2]) {
+        _ if !options.parses("phase") => ".".to_string(),
+        (phase @ ("0" | "1" | "2"), _) => phase.to_string(),
+        (".", "CDS") if mode == ParseMode::Strict => return Err("CDS should have a phase".to_string()),
+        (".", _) => ".".to_string(),
+        _ if mode == ParseMode::Lenient => ".".to_string(),
+        (phase, _) => return Err(format!("phase {} should be 0, 1, 2 or .", phase)),
+    };
+
+    let mut attributes = IndexMap::new();
+    let raw = if options.parses("attributes") { fields.get(8).map_or("", |a| a.trim_end_matches(';')) } else { "" };
+    for kv in raw.split(';').filter(|kv| !kv.trim().is_empty() && *kv != ".") {
+        match kv.split_once('=') {
+            Some((key, value)) => {
+                let key = if mode == ParseMode::Lenient { key.trim() } else { key };
+                attributes.insert(key.to_string(), value.to_string());
+            }
+            None if mode == ParseMode::Lenient => {}
+            None => return Err(format!("attribute {} should be in the form key=value", kv)),
+        }
+    }
+
+    let text = |i: usize, name: &str| if options.parses(name) { fields[i].to_string() } else { String::new() };
+    Ok(Gff3Row {
+        seqid: if options.parses("seqid") { options.seqid(fields[0], interner) } else { SeqId::from("") },
+        source: text(1, "source"),
+        feature_type: text(2, "feature_type"),
+        start,
+        end,
+        score,
+        strand,
+        phase,
+        attributes,
+    })
+}
+
+/// Parses the feature lines in the text of a GFF3 file
+///
+/// Comments and directives are skipped, and parsing stops at a `##FASTA` directive.
+pub fn parse_gff3(text: &str, mode: ParseMode) -> Result<Vec<Gff3Row<String>>, Error> {
+    parse_gff3_with(text, &ParserOptions::new(mode))
+}
+
+/// Parses the feature lines in the text of a GFF3 file with the options, as [`parse_gff3`]
+pub fn parse_gff3_with(text: &str, options: &ParserOptions) -> Result<Vec<Gff3Row<String>>, Error> {
+    parse_gff3_interned(text, options, &mut SeqIdInterner::new())
+}
+
+/// Parses the feature lines in the text of a GFF3 file with the options, as [`parse_gff3`], interning
+/// the seqids in a pool that can be shared between files
+pub fn parse_gff3_interned(text: &str, options: &ParserOptions, interner: &mut SeqIdInterner) -> Result<Vec<Gff3Row<String>>, Error> {
+    parse_feature_lines(text, options, interner, |row, _| row, parse_line_error)
+}
+
+/// Parses the feature lines that the options include, passing each row with its 0-based line number to `record`
+fn parse_feature_lines<R, F, E>(text: &str, options: &ParserOptions, interner: &mut SeqIdInterner, mut record: F, error: E) -> Result<Vec<R>, Error>
+where
+    F: FnMut(Gff3Row<String>, usize) -> R,
+    E: Fn(usize, String) -> Error,
+{
+    let mut rows = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !options.includes(line) {
+            continue;
+        }
+        let row = parse_row(line, options, interner).map_err(|e| error(line_num, e))?;
+        rows.push(record(row, line_num));
+    }
+    Ok(rows)
+}
+
+/// Where a record was parsed from, so that findings can point back to the input
+#[cfg(feature = "source-location")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLocation {
+    /// The file name, shared by the records from the same file
+    pub file: Option<std::sync::Arc<str>>,
+    /// The 1-based line number
+    pub line: usize,
+}
+
+#[cfg(feature = "source-location")]
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file, self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+/// A record with the location it was parsed from, which is kept through filtering and merging
+#[cfg(feature = "source-location")]
+#[derive(Debug, Clone)]
+pub struct Located<R> {
+    pub record: R,
+    pub location: SourceLocation,
+}
+
+#[cfg(feature = "source-location")]
+impl<R> std::ops::Deref for Located<R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.record
+    }
+}
+
+#[cfg(feature = "source-location")]
+impl<R> std::ops::DerefMut for Located<R> {
+    fn deref_mut(&mut self) -> &mut R {
+        &mut self.record
+    }
+}
+
+/// Parses the feature lines in the text of a GFF3 file with the options, as [`parse_gff3`], recording the line of each row
+///
+/// Errors give the file name, if known, as well as the line.
+#[cfg(feature = "source-location")]
+pub fn parse_gff3_located(text: &str, file: Option<&str>, options: &ParserOptions) -> Result<Vec<Located<Gff3Row<String>>>, Error> {
+    let file: Option<std::sync::Arc<str>> = file.map(std::sync::Arc::from);
+    let location = |line_num: usize| SourceLocation {
+        file: file.clone(),
+        line: line_num + 1,
+    };
+    parse_feature_lines(
+        text,
+        options,
+        &mut SeqIdInterner::new(),
+        |record, line_num| Located { record, location: location(line_num) },
+        |line_num, e| Error::InvalidArguments(format!("{}: {}", location(line_num), e)),
+    )
+}
+
 /// The genome strand the annotation is associated with
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Strand {
   #[serde(rename = "+")]
   Plus,
@@ -59,6 +387,19 @@ pub enum Strand {
   None,  
 }
 
+impl Strand {
+    /// Returns the opposite strand, as seen from the reverse complement of the sequence
+    ///
+    /// [`Strand::None`] is unchanged.
+    pub fn flip(&self) -> Strand {
+        match self {
+            Strand::Plus => Strand::Minus,
+            Strand::Minus => Strand::Plus,
+            Strand::None => Strand::None,
+        }
+    }
+}
+
 impl fmt::Display for Strand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
@@ -69,6 +410,89 @@ impl fmt::Display for Strand {
     }
 }
 
+impl<T> Gff3Row<T> {
+    /// Flips the row to the reverse complement of a sequence of length `seq_len`
+    ///
+    /// The coordinates are reversed and the strand is flipped. The phase is unchanged,
+    /// since it is relative to the direction of transcription.
+    pub fn reverse_complement(self, seq_len: u64) -> Result<Gff3Row<T>, Error> {
+        let range = GenomicRange::from_gff_row(&self).reverse_complement(seq_len)?;
+        let range = range.range_1closed();
+        Ok(Gff3Row {
+            start: *range.start(),
+            end: *range.end(),
+            strand: self.strand.flip(),
+            ..self
+        })
+    }
+}
+
+/// The type of the values of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FieldType {
+    Text,
+    Integer,
+    Float,
+    /// `+`, `-` or `.`
+    Strand,
+    /// GFF3 attributes, as key-value pairs
+    Attributes,
+}
+
+/// The description of one field of a record type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDescriptor {
+    /// The name of the field, as serialized
+    pub name: &'static str,
+    pub field_type: FieldType,
+    /// Whether a value can be missing, written as `.` in GFF3
+    pub nullable: bool,
+    /// Whether the field holds any number of values, flattened into columns
+    pub repeated: bool,
+}
+
+impl FieldDescriptor {
+    /// A required field with a single value
+    pub fn new(name: &'static str, field_type: FieldType) -> FieldDescriptor {
+        FieldDescriptor { name, field_type, nullable: false, repeated: false }
+    }
+
+    pub fn nullable(self) -> FieldDescriptor {
+        FieldDescriptor { nullable: true, ..self }
+    }
+}
+
+/// Describes the fields of a record type at runtime, in the order they are serialized
+///
+/// Exporters and column selection can be written once over the descriptors rather than per format.
+pub trait Schema {
+    fn fields() -> Vec<FieldDescriptor>;
+}
+
+impl<T> Schema for Gff3Row<T> {
+    fn fields() -> Vec<FieldDescriptor> {
+        vec![
+            FieldDescriptor::new("seqid", FieldType::Text),
+            FieldDescriptor::new("source", FieldType::Text),
+            FieldDescriptor::new("feature_type", FieldType::Text),
+            FieldDescriptor::new("start", FieldType::Integer),
+            FieldDescriptor::new("end", FieldType::Integer),
+            FieldDescriptor::new("score", FieldType::Float).nullable(),
+            FieldDescriptor::new("strand", FieldType::Strand),
+            FieldDescriptor::new("phase", FieldType::Integer).nullable(),
+            FieldDescriptor::new("attributes", FieldType::Attributes),
+        ]
+    }
+}
+
+fn bed_fields() -> Vec<FieldDescriptor> {
+    vec![
+        FieldDescriptor::new("chrom", FieldType::Text),
+        FieldDescriptor::new("chrom_start", FieldType::Integer),
+        FieldDescriptor::new("chrom_end", FieldType::Integer),
+    ]
+}
+
 /// The standard fields shared by all BED files
 pub trait BedRow {
     fn chrom(&self) -> &SeqId;
@@ -79,7 +503,7 @@ pub trait BedRow {
 /// The standard fields of BED6
 ///
 /// Validation is by type only e.g. u64 or u16.
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Bed6Row {
     pub chrom: SeqId,
     pub chrom_start: u64,
@@ -104,6 +528,17 @@ impl BedRow for Bed6Row {
 }
 
 impl Bed6Row {
+    /// Flips the row to the reverse complement of a sequence of length `seq_len`
+    pub fn reverse_complement(self, seq_len: u64) -> Result<Bed6Row, Error> {
+        let range = GenomicRange::from_bed_row(&self).reverse_complement(seq_len)?;
+        Ok(Bed6Row {
+            chrom_start: range.range_0halfopen().start,
+            chrom_end: range.range_0halfopen().end,
+            strand: self.strand.flip(),
+            ..self
+        })
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -117,6 +552,91 @@ impl Bed6Row {
     }
 }
 
+impl Schema for Bed6Row {
+    fn fields() -> Vec<FieldDescriptor> {
+        let mut fields = bed_fields();
+        fields.extend([
+            FieldDescriptor::new("name", FieldType::Text),
+            FieldDescriptor::new("score", FieldType::Integer),
+            FieldDescriptor::new("strand", FieldType::Strand),
+        ]);
+        fields
+    }
+}
+
+/// The fields of BED12, with exon blocks e.g. transcripts exported from a genome browser
+///
+/// The block sizes and starts are kept as the comma-separated lists of the file, and are parsed by [`Bed12Row::blocks`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Bed12Row {
+    pub chrom: SeqId,
+    pub chrom_start: u64,
+    pub chrom_end: u64,
+    pub name: String,
+    pub score: u16,
+    pub strand: Strand,
+    pub thick_start: u64,
+    pub thick_end: u64,
+    pub item_rgb: String,
+    pub block_count: usize,
+    pub block_sizes: String,
+    pub block_starts: String,
+}
+
+impl BedRow for Bed12Row {
+    fn chrom(&self) -> &SeqId {
+        &self.chrom
+    }
+
+    fn chrom_start(&self) -> u64 {
+        self.chrom_start
+    }
+
+    fn chrom_end(&self) -> u64 {
+        self.chrom_end
+    }
+}
+
+impl Schema for Bed12Row {
+    fn fields() -> Vec<FieldDescriptor> {
+        let mut fields = Bed6Row::fields();
+        fields.extend([
+            FieldDescriptor::new("thick_start", FieldType::Integer),
+            FieldDescriptor::new("thick_end", FieldType::Integer),
+            FieldDescriptor::new("item_rgb", FieldType::Text),
+            FieldDescriptor::new("block_count", FieldType::Integer),
+            FieldDescriptor::new("block_sizes", FieldType::Text),
+            FieldDescriptor::new("block_starts", FieldType::Text),
+        ]);
+        fields
+    }
+}
+
+impl Bed12Row {
+    /// Returns the blocks as 0-based, half-open intervals on the sequence, in order of position
+    ///
+    /// Fails if the lists don't match the block count, or the blocks overlap or extend beyond the row.
+    pub fn blocks(&self) -> Result<Vec<(u64, u64)>, Error> {
+        let parse = |list: &str, name: &str| list.trim_end_matches(',').split(',')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidArguments(format!("BED12 {} of {} should be unsigned integers", name, self.name)));
+        let sizes = parse(&self.block_sizes, "block sizes")?;
+        let starts = parse(&self.block_starts, "block starts")?;
+        if sizes.len() != self.block_count || starts.len() != self.block_count {
+            return Err(Error::InvalidArguments(format!("BED12 {} should have {} block sizes and starts", self.name, self.block_count)));
+        }
+        let blocks: Vec<(u64, u64)> = starts.iter().zip(&sizes)
+            .map(|(start, size)| (self.chrom_start + start, self.chrom_start + start + size))
+            .collect();
+        if blocks.windows(2).any(|w| w[1].0 < w[0].1) || blocks.last().is_some_and(|b| b.1 > self.chrom_end) {
+            return Err(Error::InvalidArguments(format!("BED12 blocks of {} should be in order, not overlap and end within the row", self.name)));
+        }
+        Ok(blocks)
+    }
+}
+
 /// A genomic range with zero or more associated data values
 pub struct DataInterval<T: NumOps + Copy> {
     range: GenomicRange,
@@ -149,6 +669,14 @@ pub struct BedGraphRow<T: NumOps + Copy> {
     pub data_value: T,
 }
 
+impl<T: NumOps + Copy> Schema for BedGraphRow<T> {
+    fn fields() -> Vec<FieldDescriptor> {
+        let mut fields = bed_fields();
+        fields.push(FieldDescriptor::new("data_value", FieldType::Float));
+        fields
+    }
+}
+
 impl<T> From<BedGraphRow<T>> for DataInterval<T> where T: NumOps + Copy {
 
     fn from(row: BedGraphRow<T>) -> Self {
@@ -170,6 +698,14 @@ pub struct BedGraphExtRow<T: NumOps + Copy> {
     pub data_values: Vec<Option<T>>,
 }
 
+impl<T: NumOps + Copy> Schema for BedGraphExtRow<T> {
+    fn fields() -> Vec<FieldDescriptor> {
+        let mut fields = bed_fields();
+        fields.push(FieldDescriptor { repeated: true, ..FieldDescriptor::new("data_values", FieldType::Float).nullable() });
+        fields
+    }
+}
+
 impl<T> From<BedGraphExtRow<T>> for DataInterval<T> where T: NumOps + Copy {
 
     fn from(row: BedGraphExtRow<T>) -> Self {
@@ -179,4 +715,46 @@ impl<T> From<BedGraphExtRow<T>> for DataInterval<T> where T: NumOps + Copy {
             values: row.data_values
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GFF: &str = "\
+##gff-version 3
+chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1
+chrUn_1\t.\tgene\t1\t100\t.\t+\t.\tID=g2
+chr2\t.\tgene\t50\t80\t.\t-\t.\tID=g3
+";
+
+    #[test]
+    fn lenient_mode_coerces_deviations() {
+        assert!(Gff3Row::from_line("chr1 . gene 1 100 . + .", ParseMode::Strict).is_err());
+        let row = Gff3Row::from_line("chr1 . gene 100 1.0 x ? . ID=g1;flag", ParseMode::Lenient).unwrap();
+        assert_eq!((row.start, row.end, row.score.as_str(), row.strand), (1, 100, ".", Strand::None));
+        assert_eq!(row.attributes.len(), 1);
+    }
+
+    #[test]
+    fn options_filter_and_project() {
+        let options = ParserOptions::new(ParseMode::Strict)
+            .filter_seqids(SeqIdFilter::new().exclude("chrUn*"))
+            .project(&["seqid", "start"])
+            .unwrap();
+        let rows = parse_gff3_with(GFF, &options).unwrap();
+        let rows: Vec<_> = rows.iter().map(|r| (r.seqid.as_str(), r.start, r.end, r.attributes.len())).collect();
+        assert_eq!(rows, [("chr1", 1, 100, 0), ("chr2", 50, 80, 0)]);
+    }
+
+    #[cfg(feature = "source-location")]
+    #[test]
+    fn located_rows_use_the_options() {
+        let options = ParserOptions::new(ParseMode::Strict).filter_seqids(SeqIdFilter::new().exclude("chrUn*"));
+        let rows = parse_gff3_located(GFF, Some("a.gff3"), &options).unwrap();
+        let lines: Vec<_> = rows.iter().map(|r| r.location.to_string()).collect();
+        assert_eq!(lines, ["a.gff3:2", "a.gff3:4"]);
+        let error = parse_gff3_located("chr1\t.\tgene\t0\t1\t.\t+\t.\t.\n", Some("a.gff3"), &options).unwrap_err();
+        assert!(error.to_string().contains("a.gff3:1"));
+    }
+}
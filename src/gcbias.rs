@@ -0,0 +1,142 @@
+//! GC bias correction of signal tracks
+//!
+//! Sequencing coverage depends on the GC content of the sequence, which confounds copy-number and
+//! other coverage analyses. [`gc_correct`] bins the genome, fits the signal of the bins against their
+//! GC content with LOWESS, and rescales each bin so that the expected signal is the same at every GC.
+
+use crate::format::DataInterval;
+use crate::genome::{Error, GenomicRange};
+use crate::sequence::Genome;
+use crate::track::ScoreTrack;
+
+/// A bin of the genome with its GC content and signal, before and after correction
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcBin {
+    pub range: GenomicRange,
+    /// The fraction of G and C among the unambiguous bases
+    pub gc: f64,
+    /// The mean signal, with bases that have no score counted as 0
+    pub signal: f64,
+    /// The signal expected at the GC content of the bin, from the fit
+    pub expected: f64,
+    /// The signal scaled by the median signal over the expected signal, or `None` if none is expected
+    pub corrected: Option<f64>,
+}
+
+impl GcBin {
+    /// The corrected signal as an interval, with no value when there is none
+    pub fn to_interval(&self) -> DataInterval<f64> {
+        DataInterval::new(self.range.clone(), vec![self.corrected])
+    }
+}
+
+/// Fits a locally weighted linear regression (LOWESS) with tricube weights, evaluated at `x`
+///
+/// The points are sorted by x. Each fit uses the `k` nearest points.
+fn lowess_at(points: &[(f64, f64)], k: usize, x: f64) -> f64 {
+    // slide a window of k points to the nearest ones
+    let mut lo = points.partition_point(|(px, _)| *px < x).saturating_sub(k).min(points.len() - k);
+    while lo + k < points.len() && x - points[lo].0 > points[lo + k].0 - x {
+        lo += 1;
+    }
+    let window = &points[lo..lo + k];
+    let max_dist = window.iter().map(|(px, _)| (px - x).abs()).fold(0.0, f64::max);
+    let weights: Vec<f64> = window.iter()
+        .map(|(px, _)| if max_dist == 0.0 { 1.0 } else { (1.0 - ((px - x).abs() / (max_dist * 1.000001)).powi(3)).powi(3) })
+        .collect();
+    let sum_w: f64 = weights.iter().sum();
+    let mean_x = window.iter().zip(&weights).map(|((px, _), w)| w * px).sum::<f64>() / sum_w;
+    let mean_y = window.iter().zip(&weights).map(|((_, py), w)| w * py).sum::<f64>() / sum_w;
+    let var_x: f64 = window.iter().zip(&weights).map(|((px, _), w)| w * (px - mean_x).powi(2)).sum();
+    if var_x <= f64::EPSILON * sum_w {
+        return mean_y;
+    }
+    let cov: f64 = window.iter().zip(&weights).map(|((px, py), w)| w * (px - mean_x) * (py - mean_y)).sum();
+    mean_y + cov / var_x * (x - mean_x)
+}
+
+/// Corrects the signal of the track for GC bias, in bins of `bin_size` bases
+///
+/// Bins are tiled along each sequence of the genome, leaving out the last if it is short. Bins where fewer
+/// than half the bases are unambiguous are left out, e.g. in assembly gaps. The fit at each GC uses the
+/// `span` fraction of bins with the nearest GC, so smaller spans follow the data more closely. The bins
+/// are returned in order, and each is rescaled by the median signal over the expected signal.
+pub fn gc_correct(genome: &Genome, track: &ScoreTrack, bin_size: u64, span: f64) -> Result<Vec<GcBin>, Error> {
+    if bin_size == 0 {
+        return Err(Error::InvalidArguments("bin size should be at least 1".to_string()));
+    }
+    if !(span > 0.0 && span <= 1.0) {
+        return Err(Error::InvalidArguments(format!("span should be more than 0 and at most 1, not {}", span)));
+    }
+    let mut bins = Vec::new();
+    for seqid in genome.seqids() {
+        let sequence = genome.get(seqid).unwrap_or_default();
+        for start in (0..sequence.len() as u64 / bin_size).map(|i| i * bin_size) {
+            let bases = &sequence[start as usize..(start + bin_size) as usize];
+            let gc = bases.iter().filter(|b| matches!(b.to_ascii_uppercase(), b'G' | b'C')).count();
+            let unambiguous = bases.iter().filter(|b| matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T')).count();
+            if unambiguous * 2 < bases.len() {
+                continue;
+            }
+            let range = GenomicRange::from_0halfopen(seqid.clone(), start..start + bin_size)?;
+            let signal = track.summary(&range).sum / bin_size as f64;
+            bins.push(GcBin { range, gc: gc as f64 / unambiguous as f64, signal, expected: 0.0, corrected: None });
+        }
+    }
+    if bins.is_empty() {
+        return Ok(bins);
+    }
+
+    let mut points: Vec<(f64, f64)> = bins.iter().map(|bin| (bin.gc, bin.signal)).collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let k = ((span * points.len() as f64).ceil() as usize).clamp(1, points.len());
+    let mut signals: Vec<f64> = points.iter().map(|(_, signal)| *signal).collect();
+    signals.sort_by(f64::total_cmp);
+    let median = signals[signals.len() / 2];
+
+    // fit on a grid of GC contents and interpolate, as many bins share similar GC
+    const GRID: usize = 200;
+    let fitted: Vec<f64> = (0..=GRID).map(|i| lowess_at(&points, k, i as f64 / GRID as f64)).collect();
+    for bin in &mut bins {
+        let pos = bin.gc * GRID as f64;
+        let i = (pos.floor() as usize).min(GRID - 1);
+        let fraction = pos - i as f64;
+        bin.expected = fitted[i] * (1.0 - fraction) + fitted[i + 1] * fraction;
+        bin.corrected = (bin.expected > 0.0).then(|| bin.signal * median / bin.expected);
+    }
+    Ok(bins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowess_follows_a_line_exactly() {
+        let points: Vec<(f64, f64)> = (0..=10).map(|i| (i as f64 / 10.0, 2.0 * i as f64 / 10.0 + 1.0)).collect();
+        assert!((lowess_at(&points, 5, 0.35) - 1.7).abs() < 1e-9);
+        assert!((lowess_at(&points, 11, 1.0) - 3.0).abs() < 1e-9);
+        assert_eq!(lowess_at(&[(0.5, 4.0), (0.5, 6.0)], 2, 0.1), 5.0);
+    }
+
+    #[test]
+    fn corrected_signal_is_flat_across_gc() {
+        // bins with 0 to 10 G, followed by a gap and a short bin
+        let mut sequence: Vec<u8> = (0..=10).flat_map(|i| [b'G'; 10].into_iter().take(i).chain([b'A'; 10].into_iter().take(10 - i))).collect();
+        sequence.extend_from_slice(b"NNNNNNNNNNACG");
+        let mut genome = Genome::default();
+        genome.insert("chr1", sequence);
+        let intervals = (0..=10u64).map(|i| {
+            DataInterval::new(GenomicRange::from_0halfopen("chr1", i * 10..i * 10 + 10).unwrap(), vec![Some(1.0 + 0.2 * i as f64)])
+        });
+        let track = ScoreTrack::from_intervals(intervals, 0).unwrap();
+
+        let bins = gc_correct(&genome, &track, 10, 0.5).unwrap();
+        assert_eq!(bins.len(), 11);
+        assert_eq!((bins[3].gc, bins[3].signal), (0.3, 1.6));
+        assert!(bins.iter().all(|bin| (bin.corrected.unwrap() - 2.0).abs() < 1e-9));
+        assert_eq!(bins[0].to_interval().values()[0], bins[0].corrected);
+        assert!(gc_correct(&genome, &track, 0, 0.5).is_err());
+        assert!(gc_correct(&genome, &track, 10, 0.0).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Gene sets from ranges, for gene ontology and gene set enrichment tools
+//!
+//! A set of ranges, such as the peaks from a ChIP-seq experiment, is turned into the list of genes
+//! that overlap them or whose TSS is nearest to them. Lists are written as plain text, one gene per
+//! line, or as GMT files with one set per line.
+
+use std::fmt::Write;
+
+use indexmap::IndexSet;
+
+use crate::genome::GenomicRange;
+use crate::models::Gene;
+use crate::report::start_sites;
+
+/// How ranges are assigned to genes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneRule {
+    /// Every gene overlapping the range
+    Overlapping,
+    /// The gene with the nearest TSS, if it is within the distance
+    NearestTss { max_distance: u64 },
+    /// Every overlapping gene, or else the gene with the nearest TSS within the distance
+    OverlappingOrNearest { max_distance: u64 },
+}
+
+/// A named list of genes
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GeneSet {
+    pub name: String,
+    pub description: String,
+    pub genes: Vec<String>,
+}
+
+impl GeneSet {
+    /// Writes the genes, one per line
+    pub fn to_gene_list(&self) -> String {
+        self.genes.iter().map(|gene| format!("{}\n", gene)).collect()
+    }
+}
+
+/// The indexes of the genes overlapping the range
+fn overlapping<T>(range: &GenomicRange, genes: &[Gene<T>]) -> Vec<usize> {
+    let interval = range.range_0halfopen();
+    genes.iter()
+        .enumerate()
+        .filter(|(_, g)| g.row.seqid == *range.seqid())
+        .filter(|(_, g)| {
+            let gene = g.range().range_0halfopen();
+            gene.start < interval.end && interval.start < gene.end
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The index of the gene with the TSS nearest to the range, with ties going to the first gene
+fn nearest_tss<T>(range: &GenomicRange, genes: &[Gene<T>], max_distance: u64) -> Option<usize> {
+    let interval = range.range_0halfopen();
+    let mut nearest: Option<(usize, u64)> = None;
+    for (i, gene) in genes.iter().enumerate().filter(|(_, g)| g.row.seqid == *range.seqid()) {
+        for (_, position, _) in start_sites(gene) {
+            let distance = if position < interval.start {
+                interval.start - position
+            } else {
+                (position + 1).saturating_sub(interval.end)
+            };
+            if distance <= max_distance && nearest.is_none_or(|(_, d)| distance < d) {
+                nearest = Some((i, distance));
+            }
+        }
+    }
+    nearest.map(|(i, _)| i)
+}
+
+/// Returns the genes assigned to any of the ranges by the rule, each once, in the order they are first assigned
+///
+/// Genes are named by the `name_attribute` e.g. `Name` or `gene_name`, falling back to their `ID`,
+/// and genes with neither are left out.
+pub fn genes_for_ranges<T>(ranges: &[GenomicRange], genes: &[Gene<T>], rule: GeneRule, name_attribute: Option<&str>) -> Vec<String> {
+    let mut assigned: IndexSet<usize> = IndexSet::new();
+    for range in ranges {
+        let indexes = match rule {
+            GeneRule::Overlapping => overlapping(range, genes),
+            GeneRule::NearestTss { max_distance } => nearest_tss(range, genes, max_distance).into_iter().collect(),
+            GeneRule::OverlappingOrNearest { max_distance } => {
+                let indexes = overlapping(range, genes);
+                if indexes.is_empty() {
+                    nearest_tss(range, genes, max_distance).into_iter().collect()
+                } else {
+                    indexes
+                }
+            }
+        };
+        assigned.extend(indexes);
+    }
+    let mut names: IndexSet<String> = IndexSet::new();
+    for i in assigned {
+        let gene = &genes[i];
+        let name = name_attribute.and_then(|attribute| gene.row.attributes.get(attribute)).map(|n| n.as_str()).or(gene.id());
+        if let Some(name) = name {
+            names.insert(name.to_string());
+        }
+    }
+    names.into_iter().collect()
+}
+
+/// Writes gene sets in the GMT format, with the name, description and genes of each set on a tab-separated line
+///
+/// Empty descriptions are written as `NA`, as the description column is required.
+pub fn to_gmt(sets: &[GeneSet]) -> String {
+    let mut gmt = String::new();
+    for set in sets {
+        let description = if set.description.is_empty() { "NA" } else { set.description.as_str() };
+        write!(gmt, "{}\t{}", set.name, description).unwrap();
+        for gene in &set.genes {
+            write!(gmt, "\t{}", gene).unwrap();
+        }
+        gmt.push('\n');
+    }
+    gmt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    #[test]
+    fn assigns_ranges_to_genes_by_rule() {
+        let gff = "\
+chr1\t.\tgene\t101\t200\t.\t+\t.\tID=g1;Name=ABC
+chr1\t.\tgene\t301\t400\t.\t-\t.\tID=g2;Name=DEF
+chr1\t.\tgene\t1001\t1100\t.\t+\t.\tID=g3
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let ranges: Vec<GenomicRange> = ["chr1:151-160", "chr1:451-460", "chr1:951-960", "chr1:156-165"].iter().map(|r| r.parse().unwrap()).collect();
+        let assign = |rule| genes_for_ranges(&ranges, &genes, rule, Some("Name"));
+        assert_eq!(assign(GeneRule::Overlapping), ["ABC"]);
+        assert_eq!(assign(GeneRule::NearestTss { max_distance: 100 }), ["ABC", "DEF", "g3"]);
+        assert_eq!(assign(GeneRule::NearestTss { max_distance: 45 }), ["g3"]);
+        assert_eq!(assign(GeneRule::OverlappingOrNearest { max_distance: 45 }), ["ABC", "g3"]);
+        assert_eq!(genes_for_ranges(&ranges, &genes, GeneRule::Overlapping, None), ["g1"]);
+    }
+
+    #[test]
+    fn writes_gene_lists_and_gmt() {
+        let sets = [
+            GeneSet { name: "up".to_string(), description: String::new(), genes: vec!["ABC".to_string(), "DEF".to_string()] },
+            GeneSet { name: "down".to_string(), description: "peaks lost".to_string(), genes: vec![] },
+        ];
+        assert_eq!(sets[0].to_gene_list(), "ABC\nDEF\n");
+        assert_eq!(to_gmt(&sets), "up\tNA\tABC\tDEF\ndown\tpeaks lost\n");
+    }
+}
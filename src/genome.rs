@@ -2,10 +2,11 @@
 //! 
 //! Currently, this includes sequence ids (chromosome, scaffold id etc.) and genomic ranges.
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, fmt, ops::{Range, RangeInclusive}};
+use std::{cmp::Ordering, collections::HashSet, fmt, ops::{Range, RangeInclusive}, sync::Arc};
 
-use crate::format::{Gff3Row, BedRow};
+use crate::format::{Gff3Row, BedRow, Strand};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -17,24 +18,75 @@ pub enum Error {
 /// 
 /// Currently, any string is accepted, although strings that can be converted to unsigned integers
 /// are ordered numerically before the rest.
+///
+/// The string is shared, so cloning a `SeqId` doesn't allocate. Use a [`SeqIdInterner`] to share
+/// one string between the many records on the same sequence.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
-pub struct SeqId(String);
+pub struct SeqId(Arc<str>);
 
 impl SeqId {
     pub fn as_str(&self) -> &str {
-        self.0.as_str()
+        &self.0
     }
 }
 
 impl From<&str> for SeqId {
     fn from(value: &str) -> Self {
-        SeqId(value.to_string())
+        SeqId(Arc::from(value))
     }
 }
 
 impl From<String> for SeqId {
     fn from(value: String) -> Self {
-        SeqId(value)
+        SeqId(Arc::from(value))
+    }
+}
+
+/// A pool of [`SeqId`]s, so that records on the same sequence share one string
+///
+/// The GFF3 parsers intern as they read, with a pool per file, or one shared between files with
+/// [`parse_gff3_interned`](crate::format::parse_gff3_interned). Elsewhere, pass each seqid through
+/// [`SeqIdInterner::intern`] e.g. after deserializing a row, and the row's own string is dropped in
+/// favour of the pooled one.
+#[derive(Debug, Clone, Default)]
+pub struct SeqIdInterner {
+    pool: HashSet<Arc<str>>,
+}
+
+impl SeqIdInterner {
+    pub fn new() -> SeqIdInterner {
+        SeqIdInterner::default()
+    }
+
+    /// Returns the pooled `SeqId` for a string, adding it to the pool if needed
+    pub fn get_or_intern(&mut self, value: &str) -> SeqId {
+        match self.pool.get(value) {
+            Some(pooled) => SeqId(pooled.clone()),
+            None => {
+                let pooled: Arc<str> = Arc::from(value);
+                self.pool.insert(pooled.clone());
+                SeqId(pooled)
+            }
+        }
+    }
+
+    /// Replaces a `SeqId` with the pooled one
+    pub fn intern(&mut self, seqid: &mut SeqId) {
+        match self.pool.get(seqid.as_str()) {
+            Some(pooled) => seqid.0 = pooled.clone(),
+            None => {
+                self.pool.insert(seqid.0.clone());
+            }
+        }
+    }
+
+    /// The number of distinct seqids in the pool
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
     }
 }
 
@@ -67,13 +119,593 @@ impl PartialOrd for SeqId {
     }
 }
 
+/// Compares names in natural order, splitting them into runs of digits, compared as numbers, and other characters
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn runs(text: &str) -> Vec<&str> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for (i, c) in text.char_indices().skip(1) {
+            let previous = text[..i].chars().next_back().unwrap();
+            if c.is_ascii_digit() != previous.is_ascii_digit() {
+                runs.push(&text[start..i]);
+                start = i;
+            }
+        }
+        if start < text.len() {
+            runs.push(&text[start..]);
+        }
+        runs
+    }
+    let (a_runs, b_runs) = (runs(a), runs(b));
+    for (x, y) in a_runs.iter().zip(&b_runs) {
+        let ord = match (x.starts_with(|c: char| c.is_ascii_digit()), y.starts_with(|c: char| c.is_ascii_digit())) {
+            (true, true) => {
+                let (x_digits, y_digits) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x_digits.len().cmp(&y_digits.len()).then_with(|| x_digits.cmp(y_digits))
+            }
+            _ => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a_runs.len().cmp(&b_runs.len()).then_with(|| a.cmp(b))
+}
+
+/// The rank of the sex and mitochondrial chromosomes, which conventionally follow the numbered chromosomes
+fn terminal_rank(name: &str) -> Option<u8> {
+    let name = name.strip_prefix("chr").unwrap_or(name);
+    match name {
+        "X" => Some(0),
+        "Y" => Some(1),
+        "M" | "MT" => Some(2),
+        _ => None,
+    }
+}
+
+/// How sequences are ordered when sorting
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SeqIdOrdering {
+    /// The order of [`SeqId`], where only names that are whole numbers are compared numerically, so `chr10` is before `chr2`
+    #[default]
+    Standard,
+    /// Natural order, where runs of digits are compared as numbers, so `chr2` is before `chr10`
+    Natural,
+    /// Natural order with `X`, `Y` and `M` or `MT`, with or without a `chr` prefix, after the numbered
+    /// chromosomes and before other sequences e.g. `chr1`, `chr2`, `chr10`, `chrX`, `chrY`, `chrM`, `chrUn_1`
+    Karyotypic,
+    /// The order of a list of sequences, such as a reference FASTA, with unlisted sequences after them in the [`Standard`](SeqIdOrdering::Standard) order
+    Explicit(SeqIdOrder),
+}
+
+/// An explicit order of sequences, to match the sort order other tools expect e.g. of a SAM header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeqIdOrder(IndexMap<SeqId, usize>);
+
+impl SeqIdOrder {
+    /// Orders the sequences as listed, keeping the first place of any that are repeated
+    pub fn new<T: Into<SeqId>, I: IntoIterator<Item = T>>(seqids: I) -> SeqIdOrder {
+        let mut ranks = IndexMap::new();
+        for seqid in seqids {
+            let rank = ranks.len();
+            ranks.entry(seqid.into()).or_insert(rank);
+        }
+        SeqIdOrder(ranks)
+    }
+
+    /// Orders the sequences as in the `@SQ` lines of a SAM header, by their `SN` tags
+    pub fn from_sam_header(text: &str) -> Result<SeqIdOrder, Error> {
+        let mut seqids = Vec::new();
+        for (line_num, line) in text.lines().enumerate().filter(|(_, line)| line.starts_with("@SQ")) {
+            let name = line.split('\t').find_map(|tag| tag.strip_prefix("SN:"))
+                .ok_or_else(|| Error::InvalidArguments(format!("line {}: @SQ line has no SN tag", line_num + 1)))?;
+            seqids.push(name);
+        }
+        Ok(SeqIdOrder::new(seqids))
+    }
+
+    /// The place of a sequence in the order, from 0, or `None` if it isn't listed
+    pub fn rank(&self, seqid: &SeqId) -> Option<usize> {
+        self.0.get(seqid).copied()
+    }
+
+    pub fn seqids(&self) -> impl Iterator<Item = &SeqId> {
+        self.0.keys()
+    }
+}
+
+/// Orders the sequences as in the lengths
+impl From<&ChromSizes> for SeqIdOrder {
+    fn from(value: &ChromSizes) -> Self {
+        SeqIdOrder::new(value.keys().cloned())
+    }
+}
+
+impl SeqIdOrdering {
+    pub fn compare(&self, a: &SeqId, b: &SeqId) -> Ordering {
+        match self {
+            SeqIdOrdering::Standard => a.cmp(b),
+            SeqIdOrdering::Natural => natural_cmp(a.as_str(), b.as_str()),
+            SeqIdOrdering::Karyotypic => {
+                // numbered chromosomes, then the terminal ones, then the rest
+                let group = |seqid: &SeqId| {
+                    let name = seqid.as_str();
+                    let numbered = name.strip_prefix("chr").unwrap_or(name).parse::<u64>().is_ok();
+                    match terminal_rank(name) {
+                        _ if numbered => (0, 0),
+                        Some(rank) => (1, rank),
+                        None => (2, 0),
+                    }
+                };
+                group(a).cmp(&group(b)).then_with(|| natural_cmp(a.as_str(), b.as_str()))
+            }
+            SeqIdOrdering::Explicit(order) => match (order.rank(a), order.rank(b)) {
+                (Some(a_rank), Some(b_rank)) => a_rank.cmp(&b_rank),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.cmp(b),
+            },
+        }
+    }
+
+    /// Sorts ranges by seqid in this order, then by position
+    pub fn sort_ranges(&self, ranges: &mut [GenomicRange]) {
+        ranges.sort_by(|a, b| self.compare(&a.seqid, &b.seqid).then_with(|| a.cmp(b)));
+    }
+
+    /// Sorts rows by seqid in this order, then by start and end, keeping rows at the same position in order
+    pub fn sort_rows<T>(&self, rows: &mut [Gff3Row<T>]) {
+        rows.sort_by(|a, b| self.compare(&a.seqid, &b.seqid).then_with(|| (a.start, a.end).cmp(&(b.start, b.end))));
+    }
+}
+
+/// Matches text against a glob pattern, where `*` matches any run of characters and `?` any one character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // the position of the last `*` in the pattern, and of the text it was matched from
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Which sequences to include, by glob patterns e.g. `chr*` or `chrUn_*`
+///
+/// A seqid is included if it matches any include pattern, or there are none, and matches no exclude pattern.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeqIdFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl SeqIdFilter {
+    /// A filter that includes every sequence
+    pub fn new() -> SeqIdFilter {
+        SeqIdFilter::default()
+    }
+
+    pub fn include<T: Into<String>>(mut self, pattern: T) -> SeqIdFilter {
+        self.include.push(pattern.into());
+        self
+    }
+
+    pub fn exclude<T: Into<String>>(mut self, pattern: T) -> SeqIdFilter {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Makes a filter from the text of files of patterns to include and exclude, one per line
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Only the first column is used, so a
+    /// `.chrom.sizes` or `.fai` file can be given as a list of sequences.
+    pub fn from_lists(include: &str, exclude: &str) -> SeqIdFilter {
+        let patterns = |text: &str| text.lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_whitespace().next().map(|p| p.to_string()))
+            .collect();
+        SeqIdFilter { include: patterns(include), exclude: patterns(exclude) }
+    }
+
+    pub fn matches(&self, seqid: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, seqid)))
+            && !self.exclude.iter().any(|p| glob_match(p, seqid))
+    }
+}
+
+/// The lengths of the sequences of a genome assembly, in order
+///
+/// Dereferences to the map of lengths, so it can be given wherever sequence lengths are taken e.g.
+/// to clamp ranges, take the complement of a set, or bin coverage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChromSizes(IndexMap<SeqId, u64>);
+
+impl ChromSizes {
+    pub fn new() -> ChromSizes {
+        ChromSizes::default()
+    }
+
+    /// Parses a UCSC `.chrom.sizes` file, with a seqid and length per line
+    ///
+    /// Columns may be separated by tabs or spaces, and blank lines and `#` comments are skipped.
+    pub fn from_chrom_sizes(text: &str) -> Result<ChromSizes, Error> {
+        ChromSizes::parse_lines(text, None, 2, "chrom.sizes")
+    }
+
+    /// Parses a samtools `.fai` index, taking the name and length of each sequence
+    ///
+    /// FASTA indexes have 5 columns and FASTQ indexes 6, separated by tabs.
+    pub fn from_fai(text: &str) -> Result<ChromSizes, Error> {
+        ChromSizes::parse_lines(text, Some('\t'), 5, "fai")
+    }
+
+    /// Parses lines of fields split by `separator`, or whitespace if `None`
+    fn parse_lines(text: &str, separator: Option<char>, min_fields: usize, format: &str) -> Result<ChromSizes, Error> {
+        let mut sizes = ChromSizes::new();
+        for (line_num, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = match separator {
+                Some(separator) => line.split(separator).collect(),
+                None => line.split_whitespace().collect(),
+            };
+            if fields.len() < min_fields {
+                return Err(Error::InvalidArguments(format!("line {}: {} lines should have at least {} fields", line_num + 1, format, min_fields)));
+            }
+            let len = fields[1].trim().parse::<u64>()
+                .map_err(|_| Error::InvalidArguments(format!("line {}: length should be an unsigned integer, not {}", line_num + 1, fields[1])))?;
+            if sizes.0.insert(SeqId::from(fields[0]), len).is_some() {
+                return Err(Error::InvalidArguments(format!("line {}: sequence {} is repeated", line_num + 1, fields[0])));
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Sets the length of a sequence, returning its previous length
+    pub fn insert<T: Into<SeqId>>(&mut self, seqid: T, len: u64) -> Option<u64> {
+        self.0.insert(seqid.into(), len)
+    }
+
+    /// The total length of the sequences
+    pub fn total_len(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    pub fn into_inner(self) -> IndexMap<SeqId, u64> {
+        self.0
+    }
+}
+
+impl std::ops::Deref for ChromSizes {
+    type Target = IndexMap<SeqId, u64>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<IndexMap<SeqId, u64>> for ChromSizes {
+    fn from(value: IndexMap<SeqId, u64>) -> Self {
+        ChromSizes(value)
+    }
+}
+
+impl FromIterator<(SeqId, u64)> for ChromSizes {
+    fn from_iter<I: IntoIterator<Item = (SeqId, u64)>>(iter: I) -> Self {
+        ChromSizes(iter.into_iter().collect())
+    }
+}
+
+/// Writes the lengths as a `.chrom.sizes` file
+impl fmt::Display for ChromSizes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (seqid, len) in &self.0 {
+            writeln!(f, "{}\t{}", seqid, len)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fixed-width bins tiling a genome, numbered from 0 across all the sequences in order, as for a Hi-C contact matrix
+///
+/// The last bin of each sequence is truncated at its end, so bins don't span sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomeBins {
+    chrom_sizes: ChromSizes,
+    bin_size: u64,
+    /// The index of the first bin of each sequence
+    offsets: IndexMap<SeqId, usize>,
+    len: usize,
+}
+
+impl GenomeBins {
+    pub fn new(chrom_sizes: &ChromSizes, bin_size: u64) -> Result<GenomeBins, Error> {
+        if bin_size == 0 {
+            return Err(Error::InvalidArguments("bin size should be at least 1".to_string()));
+        }
+        let mut offsets = IndexMap::new();
+        let mut len = 0;
+        for (seqid, seq_len) in chrom_sizes.iter() {
+            offsets.insert(seqid.clone(), len);
+            len += seq_len.div_ceil(bin_size) as usize;
+        }
+        Ok(GenomeBins { chrom_sizes: chrom_sizes.clone(), bin_size, offsets, len })
+    }
+
+    pub fn bin_size(&self) -> u64 {
+        self.bin_size
+    }
+
+    /// The number of bins
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bin with the index, or `None` if there is no such bin
+    pub fn bin(&self, index: usize) -> Option<GenomicRange> {
+        // the last sequence starting at or before the index
+        let i = self.offsets.as_slice().partition_point(|_, offset| *offset <= index).checked_sub(1)?;
+        let (seqid, offset) = self.offsets.get_index(i)?;
+        let start = (index - offset) as u64 * self.bin_size;
+        let seq_len = self.chrom_sizes[seqid];
+        (start < seq_len).then(|| GenomicRange::from_0halfopen(seqid.clone(), start..(start + self.bin_size).min(seq_len)).unwrap())
+    }
+
+    /// The bins in order
+    pub fn iter(&self) -> impl Iterator<Item = GenomicRange> + '_ {
+        self.chrom_sizes.iter().flat_map(|(seqid, seq_len)| {
+            (0..*seq_len).step_by(self.bin_size as usize)
+                .map(|start| GenomicRange::from_0halfopen(seqid.clone(), start..(start + self.bin_size).min(*seq_len)).unwrap())
+        })
+    }
+
+    /// The index of the bin containing the first base of the range, or `None` if it isn't within a sequence
+    pub fn assign_bin(&self, range: &GenomicRange) -> Option<usize> {
+        let offset = self.offsets.get(range.seqid())?;
+        (range.start < self.chrom_sizes[range.seqid()]).then(|| offset + (range.start / self.bin_size) as usize)
+    }
+
+    /// The indexes of the bins overlapping the range, truncated at the end of its sequence
+    pub fn overlapping_bins(&self, range: &GenomicRange) -> std::ops::Range<usize> {
+        let Some(first) = self.assign_bin(range) else {
+            return 0..0;
+        };
+        let end = range.end.min(self.chrom_sizes[range.seqid()]);
+        first..self.offsets[range.seqid()] + end.div_ceil(self.bin_size) as usize
+    }
+}
+
+/// Fixed-width bins tiling a genome, in order, with the last bin of each sequence truncated at its end
+pub fn bins(chrom_sizes: &ChromSizes, bin_size: u64) -> Result<Vec<GenomicRange>, Error> {
+    Ok(GenomeBins::new(chrom_sizes, bin_size)?.iter().collect())
+}
+
+/// The columns of names in an NCBI assembly report, any of which can be the canonical name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssemblyNaming {
+    /// The submitter's name e.g. `1`
+    SequenceName,
+    /// The GenBank accession e.g. `CM000663.2`
+    GenBank,
+    /// The RefSeq accession e.g. `NC_000001.11`
+    RefSeq,
+    /// The UCSC-style name e.g. `chr1`
+    Ucsc,
+}
+
+/// Maps the alternative names of sequences to a canonical name e.g. `1` and `NC_000001.11` to `chr1`
+///
+/// Names from different conventions give no overlaps when compared, so inputs are normalized to one
+/// convention as they are read, see [`ParserOptions::rename_seqids`](crate::format::ParserOptions::rename_seqids).
+/// To translate on write, use a map whose canonical names are in the convention to write. Canonical names
+/// map to themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeqIdAliasMap(IndexMap<String, SeqId>);
+
+impl SeqIdAliasMap {
+    pub fn new() -> SeqIdAliasMap {
+        SeqIdAliasMap::default()
+    }
+
+    /// Adds an alias of a canonical name, failing if the alias already has a different canonical name
+    pub fn insert<T: Into<SeqId>>(&mut self, alias: &str, canonical: T) -> Result<(), Error> {
+        let canonical = canonical.into();
+        for name in [alias, canonical.as_str()] {
+            match self.0.get(name) {
+                Some(existing) if *existing != canonical => {
+                    return Err(Error::InvalidArguments(format!("{} is an alias of both {} and {}", name, existing, canonical)));
+                }
+                Some(_) => {}
+                None => {
+                    self.0.insert(name.to_string(), canonical.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a UCSC `chromAlias.txt` file
+    ///
+    /// With a `#` header of naming conventions e.g. `# ucsc  assembly  genbank  refseq`, each line has
+    /// the canonical name in the first column followed by its aliases. Without a header, each line has an
+    /// alias, the canonical name and the source of the alias. Empty aliases are skipped.
+    pub fn from_chrom_alias(text: &str) -> Result<SeqIdAliasMap, Error> {
+        let mut aliases = SeqIdAliasMap::new();
+        let wide = text.lines().next().is_some_and(|line| line.starts_with('#'));
+        for (line_num, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(|f| f.trim()).collect();
+            let (canonical, names) = match (wide, fields.as_slice()) {
+                (true, [canonical, names @ ..]) => (*canonical, names.to_vec()),
+                (false, [alias, canonical, ..]) => (*canonical, vec![*alias]),
+                _ => return Err(Error::InvalidArguments(format!("line {}: chromAlias lines should have an alias and a sequence name", line_num + 1))),
+            };
+            for name in names.into_iter().filter(|n| !n.is_empty()) {
+                aliases.insert(name, canonical).map_err(|e| Error::InvalidArguments(format!("line {}: {}", line_num + 1, e)))?;
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// Parses an NCBI `assembly_report.txt`, taking the names in the `canonical` column as canonical
+    ///
+    /// The sequence name, GenBank and RefSeq accessions and UCSC-style name are aliases, except those
+    /// given as `na`. Sequences with no canonical name are skipped.
+    pub fn from_assembly_report(text: &str, canonical: AssemblyNaming) -> Result<SeqIdAliasMap, Error> {
+        let mut aliases = SeqIdAliasMap::new();
+        let column = |naming: AssemblyNaming| match naming {
+            AssemblyNaming::SequenceName => 0,
+            AssemblyNaming::GenBank => 4,
+            AssemblyNaming::RefSeq => 6,
+            AssemblyNaming::Ucsc => 9,
+        };
+        for (line_num, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(|f| f.trim()).collect();
+            if fields.len() < 7 {
+                return Err(Error::InvalidArguments(format!("line {}: assembly report lines should have at least 7 fields", line_num + 1)));
+            }
+            let name = |naming: AssemblyNaming| fields.get(column(naming)).copied().filter(|n| !n.is_empty() && *n != "na");
+            let Some(canonical_name) = name(canonical) else {
+                continue;
+            };
+            let namings = [AssemblyNaming::SequenceName, AssemblyNaming::GenBank, AssemblyNaming::RefSeq, AssemblyNaming::Ucsc];
+            for alias in namings.into_iter().filter_map(name) {
+                aliases.insert(alias, canonical_name).map_err(|e| Error::InvalidArguments(format!("line {}: {}", line_num + 1, e)))?;
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// Parses Ensembl `seq_region` synonyms as TSV, with the Ensembl name as canonical
+    ///
+    /// Each line has a sequence name, a synonym and optionally the database the synonym is from e.g.
+    /// `1  CM000663.2  INSDC`, as exported by joining the `seq_region` and `seq_region_synonym` tables.
+    /// A header line with a `synonym` column and `#` comments are skipped, as are synonyms that are empty
+    /// or the `\N` of a MySQL dump.
+    pub fn from_ensembl_synonyms(text: &str) -> Result<SeqIdAliasMap, Error> {
+        let mut aliases = SeqIdAliasMap::new();
+        for (line_num, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').map(|f| f.trim()).collect();
+            let [name, synonym, ..] = fields.as_slice() else {
+                return Err(Error::InvalidArguments(format!("line {}: synonym lines should have a sequence name and a synonym", line_num + 1)));
+            };
+            if line_num == 0 && *synonym == "synonym" {
+                continue;
+            }
+            if !synonym.is_empty() && *synonym != "\\N" {
+                aliases.insert(synonym, *name).map_err(|e| Error::InvalidArguments(format!("line {}: {}", line_num + 1, e)))?;
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// The canonical name of a sequence, or `None` if the name is unknown
+    pub fn resolve(&self, name: &str) -> Option<&SeqId> {
+        self.0.get(name)
+    }
+
+    /// The canonical name of a sequence, or the name unchanged if it is unknown
+    pub fn normalize(&self, seqid: &SeqId) -> SeqId {
+        self.resolve(seqid.as_str()).unwrap_or(seqid).clone()
+    }
+}
+
+/// What to do when an operation moves a range below 0 or beyond the end of its sequence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Fail with an error
+    #[default]
+    Error,
+    /// Truncate the range at the bounds of the sequence
+    Clamp,
+    /// Drop the range
+    Drop,
+}
+
+impl OverflowPolicy {
+    /// Makes a range from 0-based, half-open coordinates that may be out of bounds
+    ///
+    /// The end is only checked when the sequence length is given. Returns `None` when the range is dropped.
+    /// A range that is entirely out of bounds is clamped to an empty range at the boundary.
+    pub fn apply<T: Into<SeqId>>(self, seqid: T, start: i64, end: i64, seq_len: Option<u64>) -> Result<Option<GenomicRange>, Error> {
+        let seqid = seqid.into();
+        let max = seq_len.map_or(i64::MAX, |len| len.min(i64::MAX as u64) as i64);
+        if start >= 0 && end <= max {
+            return Ok(Some(GenomicRange { seqid, start: start as u64, end: end as u64 }));
+        }
+        match self {
+            OverflowPolicy::Error => Err(Error::InvalidArguments(format!(
+                "range {}:{}-{} is beyond the bounds of the sequence{}", seqid, start, end,
+                seq_len.map_or_else(String::new, |len| format!(" of length {}", len))
+            ))),
+            OverflowPolicy::Clamp => Ok(Some(GenomicRange {
+                seqid,
+                start: start.clamp(0, max) as u64,
+                end: end.clamp(0, max) as u64,
+            })),
+            OverflowPolicy::Drop => Ok(None),
+        }
+    }
+}
+
+/// Which side of a range a flank is on, in sequence coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlankSide {
+    Left,
+    Right,
+    Both,
+}
+
+/// What to do with the last window of a range when it is shorter than the window size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PartialWindow {
+    /// Keep the window, truncated at the end of the range
+    #[default]
+    Truncate,
+    /// Drop the window, so every window has the full size
+    Drop,
+}
+
+/// Looks up the length of the sequence of a range, if lengths are given
+fn lookup_seq_len(seqid: &SeqId, seq_lens: Option<&IndexMap<SeqId, u64>>) -> Result<Option<u64>, Error> {
+    seq_lens.map(|lens| lens.get(seqid).copied()
+        .ok_or_else(|| Error::InvalidArguments(format!("sequence {} has no length", seqid))))
+        .transpose()
+}
+
 /// Stores a genomic range on a specific sequence
 ///
 /// Provides methods for accessing coordinates that require the caller
 /// to be explicit about the type of range required. This is to avoid a 
 /// common source of errors in genomics where different formats and standards
 /// are used to specify ranges.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GenomicRange {
     // these are stored as 0-based, open on the right
     seqid: SeqId,
@@ -81,25 +713,89 @@ pub struct GenomicRange {
     end: u64
 }
 
+/// The coordinate convention of a range written as text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Coordinates {
+    /// 1-based, closed, as in samtools regions and genome browsers e.g. `chr1:1-100` for the first 100 bases
+    #[default]
+    OneClosed,
+    /// 0-based, half-open, as in BED e.g. `chr1:0-100` for the first 100 bases
+    ZeroHalfOpen,
+}
+
 impl TryFrom<&str> for GenomicRange {
     type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let values: Vec<_> = value.split(':').collect();
-        if values.len() == 2 {
-            let seqid = values[0];
-            let range = values[1];
-            let bounds: Vec<_> = range.split('-').collect();
-            if bounds.len() == 2 {
-                let start = bounds[0].parse::<u64>();
-                let end = bounds[1].parse::<u64>();
-                if let (Ok(start), Ok(end)) = (start, end) {
-                    let location = GenomicRange::from_1closed(seqid, start..=end)?;
-                    return Ok(location);
-                }
-            }
-        } 
-        Err(Error::InvalidArguments("location should be in the form <seqid>:<start>-<end>".to_string()))
+        GenomicRange::parse(value, Coordinates::OneClosed)
+    }
+}
+
+/// Parses a range in the form `seqid:start-end`, 1-based and closed e.g. `chr1:1,000,001-2,000,000`
+impl std::str::FromStr for GenomicRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GenomicRange::parse(s, Coordinates::OneClosed)
+    }
+}
+
+/// Writes the range as `seqid:start-end`, 1-based and closed
+impl fmt::Display for GenomicRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.seqid, self.start + 1, self.end)
+    }
+}
+
+/// The fields of a serialized [`GenomicRange`], 0-based and half-open
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "GenomicRange")]
+struct RangeFields {
+    seqid: SeqId,
+    start: u64,
+    end: u64,
+}
+
+/// A serialized [`GenomicRange`] in either form
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RangeForm {
+    Text(String),
+    Fields(RangeFields),
+}
+
+/// Serializes as a struct of the seqid, and the 0-based, half-open start and end
+///
+/// To serialize as text instead, use `#[serde(with = "gannot::genome::range_as_text")]`.
+impl Serialize for GenomicRange {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RangeFields { seqid: self.seqid.clone(), start: self.start, end: self.end }.serialize(serializer)
+    }
+}
+
+/// Deserializes from either a struct, as serialized, or text in the form `seqid:start-end`, 1-based and closed
+impl<'de> Deserialize<'de> for GenomicRange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RangeForm::deserialize(deserializer)? {
+            RangeForm::Text(text) => text.parse().map_err(serde::de::Error::custom),
+            RangeForm::Fields(RangeFields { seqid, start, end }) if start <= end => Ok(GenomicRange { seqid, start, end }),
+            RangeForm::Fields(RangeFields { start, end, .. }) => Err(serde::de::Error::custom(format!("range start {} should be no more than its end {}", start, end))),
+        }
+    }
+}
+
+/// Serializes a [`GenomicRange`] as text in the form `seqid:start-end`, 1-based and closed, for `#[serde(with)]`
+pub mod range_as_text {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::GenomicRange;
+
+    pub fn serialize<S: Serializer>(range: &GenomicRange, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(range)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GenomicRange, D::Error> {
+        GenomicRange::deserialize(deserializer)
     }
 }
 
@@ -124,6 +820,34 @@ impl PartialOrd for GenomicRange {
 }
 
 impl GenomicRange {
+    /// Parses a range in the form `seqid:start-end` in the convention
+    ///
+    /// The seqid may itself contain `:`, as the coordinates follow the last one. Thousands separators
+    /// (`,` or `_`) and whitespace around the coordinates are ignored, as in samtools regions.
+    pub fn parse(text: &str, coordinates: Coordinates) -> Result<GenomicRange, Error> {
+        let error = || Error::InvalidArguments(format!("location {} should be in the form <seqid>:<start>-<end>", text));
+        let (seqid, bounds) = text.rsplit_once(':').ok_or_else(error)?;
+        let (start, end) = bounds.split_once('-').ok_or_else(error)?;
+        let number = |value: &str| value.trim().replace([',', '_'], "").parse::<u64>().map_err(|_| error());
+        let (start, end) = (number(start)?, number(end)?);
+        if seqid.is_empty() {
+            return Err(error());
+        }
+        match coordinates {
+            Coordinates::OneClosed if start <= end.saturating_add(1) => GenomicRange::from_1closed(seqid, start..=end),
+            Coordinates::ZeroHalfOpen if start <= end => GenomicRange::from_0halfopen(seqid, start..end),
+            _ => Err(Error::InvalidArguments(format!("location {} should start no more than its end", text))),
+        }
+    }
+
+    /// Writes the range as `seqid:start-end` in the convention
+    pub fn display(&self, coordinates: Coordinates) -> String {
+        match coordinates {
+            Coordinates::OneClosed => self.to_string(),
+            Coordinates::ZeroHalfOpen => format!("{}:{}-{}", self.seqid, self.start, self.end),
+        }
+    }
+
     pub fn combine(&self, other: &GenomicRange) -> Result<GenomicRange, Error> {
         if self.seqid != other.seqid {
             Err(Error::InvalidArguments("can only combine GenomicRanges with the same seqid".to_string()))
@@ -192,6 +916,551 @@ impl GenomicRange {
         (self.start)..=(self.end - 1) 
     }
 
+    /// The number of bases in the range
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The 0-based position of the middle base, which is the right of the two middle bases for an even length
+    pub fn midpoint(&self) -> u64 {
+        self.start + self.len() / 2
+    }
+
+    /// A range of `width` bases centred on the midpoint, truncated at 0
+    ///
+    /// For an even width, the midpoint is the right of the two middle bases, as for [`GenomicRange::midpoint`].
+    pub fn center_range(&self, width: u64) -> GenomicRange {
+        let start = self.midpoint().saturating_sub(width / 2);
+        GenomicRange {
+            seqid: self.seqid.clone(),
+            start,
+            end: self.midpoint() + (width - width / 2),
+        }
+    }
+
+    /// Maps the range onto the reverse complement of a sequence of length `seq_len`
+    ///
+    /// Fails if the range extends beyond the end of the sequence.
+    pub fn reverse_complement(&self, seq_len: u64) -> Result<GenomicRange, Error> {
+        if self.end > seq_len {
+            return Err(Error::InvalidArguments(format!("range ends at {} beyond the sequence length {}", self.end, seq_len)));
+        }
+        Ok(GenomicRange {
+            seqid: self.seqid.clone(),
+            start: seq_len - self.end,
+            end: seq_len - self.start,
+        })
+    }
+
+    /// Extends the range by `left` bases before its start and `right` bases after its end
+    ///
+    /// The range is clamped at 0 and, given the sequence lengths, at the end of its sequence, which
+    /// must then have a length.
+    pub fn expand(&self, left: u64, right: u64, seq_lens: Option<&IndexMap<SeqId, u64>>) -> Result<GenomicRange, Error> {
+        Ok(self.expand_within(left, right, seq_lens, OverflowPolicy::Clamp)?.expect("clamped ranges aren't dropped"))
+    }
+
+    /// Extends the range as [`GenomicRange::expand`], applying the policy if it extends beyond the bounds of its sequence
+    pub fn expand_within(&self, left: u64, right: u64, seq_lens: Option<&IndexMap<SeqId, u64>>, policy: OverflowPolicy) -> Result<Option<GenomicRange>, Error> {
+        let seq_len = lookup_seq_len(&self.seqid, seq_lens)?;
+        let start = (self.start as i64).saturating_sub_unsigned(left);
+        let end = (self.end as i64).saturating_add_unsigned(right);
+        policy.apply(self.seqid.clone(), start, end, seq_len)
+    }
+
+    /// The `size` bases beside the range on the given side or sides, clamped as [`GenomicRange::expand`]
+    ///
+    /// Flanks that are empty after clamping are left out, so there are 0 to 2 flanks, in order of position.
+    pub fn flank(&self, size: u64, side: FlankSide, seq_lens: Option<&IndexMap<SeqId, u64>>) -> Result<Vec<GenomicRange>, Error> {
+        self.flank_within(size, side, seq_lens, OverflowPolicy::Clamp)
+    }
+
+    /// The flanks as [`GenomicRange::flank`], applying the policy to each flank that extends beyond the bounds of the sequence
+    pub fn flank_within(&self, size: u64, side: FlankSide, seq_lens: Option<&IndexMap<SeqId, u64>>, policy: OverflowPolicy) -> Result<Vec<GenomicRange>, Error> {
+        let seq_len = lookup_seq_len(&self.seqid, seq_lens)?;
+        let (start, end) = (self.start as i64, self.end as i64);
+        let mut flanks = Vec::new();
+        if side != FlankSide::Right {
+            flanks.extend(policy.apply(self.seqid.clone(), start.saturating_sub_unsigned(size), start, seq_len)?);
+        }
+        if side != FlankSide::Left {
+            flanks.extend(policy.apply(self.seqid.clone(), end, end.saturating_add_unsigned(size), seq_len)?);
+        }
+        flanks.retain(|flank| !flank.is_empty());
+        Ok(flanks)
+    }
+
+    /// Moves the range by a signed offset, failing if it would start before 0
+    pub fn shift(&self, offset: i64) -> Result<GenomicRange, Error> {
+        let moved = |pos: u64| pos.checked_add_signed(offset)
+            .ok_or_else(|| Error::InvalidArguments(format!("range {}:{}-{} can't be shifted by {}", self.seqid, self.start, self.end, offset)));
+        Ok(GenomicRange { seqid: self.seqid.clone(), start: moved(self.start)?, end: moved(self.end)? })
+    }
+
+    /// Moves the range by a signed offset, applying the policy if it moves beyond the bounds of its sequence
+    ///
+    /// The end is only checked when the sequence lengths are given, and the sequence must then have a length.
+    pub fn shift_within(&self, offset: i64, seq_lens: Option<&IndexMap<SeqId, u64>>, policy: OverflowPolicy) -> Result<Option<GenomicRange>, Error> {
+        let seq_len = lookup_seq_len(&self.seqid, seq_lens)?;
+        let moved = |pos: u64| (pos as i64).saturating_add(offset);
+        policy.apply(self.seqid.clone(), moved(self.start), moved(self.end), seq_len)
+    }
+
+    /// Tiles the range with windows of `size` bases, starting every `step` bases
+    ///
+    /// Windows overlap when the step is less than the size, and leave gaps when it is more. Windows stop at
+    /// the first that reaches the end of the range, which is truncated or dropped by the policy when it is
+    /// short. The size and step should be at least 1.
+    pub fn windows(&self, size: u64, step: u64, partial: PartialWindow) -> Result<impl Iterator<Item = GenomicRange> + '_, Error> {
+        if size == 0 || step == 0 {
+            return Err(Error::InvalidArguments("window size and step should be at least 1".to_string()));
+        }
+        let mut next = Some(self.start).filter(|start| *start < self.end);
+        Ok(std::iter::from_fn(move || {
+            let start = next?;
+            let end = start.saturating_add(size);
+            next = start.checked_add(step).filter(|next| end < self.end && *next < self.end);
+            if end > self.end && partial == PartialWindow::Drop {
+                return None;
+            }
+            Some(GenomicRange { seqid: self.seqid.clone(), start, end: end.min(self.end) })
+        }))
+    }
+
+    /// Whether the ranges share at least one base
+    ///
+    /// Adjacent ranges, where one ends where the other starts, don't overlap, nor do ranges on
+    /// different seqids or empty ranges.
+    pub fn overlaps(&self, other: &GenomicRange) -> bool {
+        self.overlap_len(other) > 0
+    }
+
+    /// The number of bases shared by the ranges, which is 0 for adjacent ranges or different seqids
+    pub fn overlap_len(&self, other: &GenomicRange) -> u64 {
+        if self.seqid != other.seqid {
+            return 0;
+        }
+        self.end.min(other.end).saturating_sub(self.start.max(other.start))
+    }
+
+    /// The overlap as a fraction of the shorter range, or 0 if either is empty
+    pub fn overlap_fraction(&self, other: &GenomicRange) -> f64 {
+        let shorter = self.len().min(other.len());
+        if shorter == 0 { 0.0 } else { self.overlap_len(other) as f64 / shorter as f64 }
+    }
+
+    /// Whether the overlap is at least `fraction` of this range, and if `reciprocal` of the other too
+    ///
+    /// These are the `-f` and `-r` options of `bedtools intersect`. Ranges must overlap by at least
+    /// one base, even if `fraction` is 0.
+    pub fn overlaps_by(&self, other: &GenomicRange, fraction: f64, reciprocal: bool) -> bool {
+        let overlap = self.overlap_len(other) as f64;
+        overlap > 0.0
+            && overlap >= fraction * self.len() as f64
+            && (!reciprocal || overlap >= fraction * other.len() as f64)
+    }
+
+    /// Whether the range contains the 0-based position
+    pub fn contains_point(&self, pos: u64) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Whether the range contains all of the other range, which must be on the same seqid
+    ///
+    /// An empty range is contained by a range on the same seqid that it is within or at either end of.
+    pub fn contains(&self, other: &GenomicRange) -> bool {
+        self.seqid == other.seqid && self.start <= other.start && other.end <= self.end
+    }
+
+    /// The number of bases between the ranges, which is 0 if they overlap or are adjacent, or `None` on different seqids
+    pub fn distance(&self, other: &GenomicRange) -> Option<u64> {
+        self.signed_distance(other).map(|d| d.unsigned_abs())
+    }
+
+    /// The distance to the other range on the forward strand, positive when it is downstream (after this
+    /// range) and negative when it is upstream, or `None` on different seqids
+    pub fn signed_distance(&self, other: &GenomicRange) -> Option<i64> {
+        if self.seqid != other.seqid {
+            None
+        } else if other.start >= self.end {
+            Some((other.start - self.end) as i64)
+        } else if other.end <= self.start {
+            Some(-((self.start - other.end) as i64))
+        } else {
+            Some(0)
+        }
+    }
+
+    /// The range shared by both ranges, or `None` if they don't overlap
+    ///
+    /// This is the counterpart of [`GenomicRange::combine`].
+    pub fn intersect(&self, other: &GenomicRange) -> Option<GenomicRange> {
+        self.overlaps(other).then(|| GenomicRange {
+            seqid: self.seqid.clone(),
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        })
+    }
+
+    /// The parts of the range not covered by the other range
+    ///
+    /// Returns the range itself if they don't overlap, nothing if it is covered, one piece if it is
+    /// truncated and two if it is split.
+    pub fn subtract(&self, other: &GenomicRange) -> Vec<GenomicRange> {
+        if !self.overlaps(other) {
+            return vec![self.clone()];
+        }
+        let mut pieces = Vec::new();
+        if self.start < other.start {
+            pieces.push(GenomicRange { seqid: self.seqid.clone(), start: self.start, end: other.start });
+        }
+        if other.end < self.end {
+            pieces.push(GenomicRange { seqid: self.seqid.clone(), start: other.end, end: self.end });
+        }
+        pieces
+    }
+
+}
+
+
+
+/// A genomic range on a strand, for operations relative to the direction of a feature
+///
+/// Ranges on [`Strand::None`] are treated as on the plus strand.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StrandedGenomicRange {
+    pub range: GenomicRange,
+    pub strand: Strand,
+}
+
+impl StrandedGenomicRange {
+    pub fn new(range: GenomicRange, strand: Strand) -> StrandedGenomicRange {
+        StrandedGenomicRange { range, strand }
+    }
+
+    pub fn from_gff_row<T>(row: &Gff3Row<T>) -> StrandedGenomicRange {
+        StrandedGenomicRange { range: GenomicRange::from_gff_row(row), strand: row.strand }
+    }
+
+    fn with_range(&self, start: u64, end: u64) -> StrandedGenomicRange {
+        StrandedGenomicRange {
+            range: GenomicRange { seqid: self.range.seqid.clone(), start, end },
+            strand: self.strand,
+        }
+    }
+
+    /// The 0-based position of the 5' base, which is the last base on the minus strand
+    pub fn five_prime(&self) -> u64 {
+        match self.strand {
+            Strand::Minus => self.range.end.saturating_sub(1).max(self.range.start),
+            _ => self.range.start,
+        }
+    }
+
+    /// The 0-based position of the 3' base, which is the first base on the minus strand
+    pub fn three_prime(&self) -> u64 {
+        match self.strand {
+            Strand::Minus => self.range.start,
+            _ => self.range.end.saturating_sub(1).max(self.range.start),
+        }
+    }
+
+    /// The `n` bases before the 5' end, on the same strand
+    ///
+    /// The range is truncated at the start of the sequence, but not at its end, which isn't known.
+    pub fn upstream(&self, n: u64) -> StrandedGenomicRange {
+        match self.strand {
+            Strand::Minus => self.with_range(self.range.end, self.range.end + n),
+            _ => self.with_range(self.range.start.saturating_sub(n), self.range.start),
+        }
+    }
+
+    /// The `n` bases after the 3' end, on the same strand
+    ///
+    /// The range is truncated at the start of the sequence, but not at its end, which isn't known.
+    pub fn downstream(&self, n: u64) -> StrandedGenomicRange {
+        match self.strand {
+            Strand::Minus => self.with_range(self.range.start.saturating_sub(n), self.range.start),
+            _ => self.with_range(self.range.end, self.range.end + n),
+        }
+    }
+}
+
+/// Ranges that are sorted, with overlapping and adjacent ranges merged
+///
+/// The invariant is guaranteed by construction, so algorithms over the ranges can rely on it
+/// without checking. Empty ranges are removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizedRanges(Vec<GenomicRange>);
+
+impl NormalizedRanges {
+    pub fn new() -> NormalizedRanges {
+        NormalizedRanges::default()
+    }
+
+    pub fn as_slice(&self) -> &[GenomicRange] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<GenomicRange> {
+        self.0
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, GenomicRange> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The ranges on a sequence
+    pub fn on_seqid(&self, seqid: &SeqId) -> &[GenomicRange] {
+        let first = self.0.partition_point(|r| r.seqid < *seqid);
+        let last = self.0.partition_point(|r| r.seqid <= *seqid);
+        &self.0[first..last]
+    }
+
+    /// The ranges that overlap a range, found by binary search
+    pub fn overlapping(&self, range: &GenomicRange) -> &[GenomicRange] {
+        let ranges = self.on_seqid(&range.seqid);
+        let first = ranges.partition_point(|r| r.end <= range.start);
+        let last = ranges.partition_point(|r| r.start < range.end);
+        &ranges[first..last.max(first)]
+    }
+
+    /// Whether any of the ranges overlaps the range
+    pub fn overlaps_any(&self, range: &GenomicRange) -> bool {
+        !self.overlapping(range).is_empty()
+    }
+
+    /// Whether a 0-based position on a sequence is covered by the ranges
+    pub fn covers(&self, seqid: &SeqId, pos: u64) -> bool {
+        let ranges = self.on_seqid(seqid);
+        let i = ranges.partition_point(|r| r.end <= pos);
+        ranges.get(i).is_some_and(|r| r.start <= pos)
+    }
+
+    /// The number of bases covered by the ranges
+    pub fn covered_len(&self) -> u64 {
+        self.0.iter().map(|r| r.end - r.start).sum()
+    }
+}
+
+/// Sorts and merges the ranges
+impl FromIterator<GenomicRange> for NormalizedRanges {
+    fn from_iter<I: IntoIterator<Item = GenomicRange>>(iter: I) -> Self {
+        let mut ranges: Vec<GenomicRange> = iter.into_iter().filter(|r| r.end > r.start).collect();
+        ranges.sort_unstable();
+        let mut merged: Vec<GenomicRange> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if last.seqid == range.seqid && range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        NormalizedRanges(merged)
+    }
+}
+
+impl From<Vec<GenomicRange>> for NormalizedRanges {
+    fn from(ranges: Vec<GenomicRange>) -> Self {
+        ranges.into_iter().collect()
+    }
+}
+
+impl IntoIterator for NormalizedRanges {
+    type Item = GenomicRange;
+    type IntoIter = std::vec::IntoIter<GenomicRange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NormalizedRanges {
+    type Item = &'a GenomicRange;
+    type IntoIter = std::slice::Iter<'a, GenomicRange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl AsRef<[GenomicRange]> for NormalizedRanges {
+    fn as_ref(&self) -> &[GenomicRange] {
+        &self.0
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrom_sizes_from_chrom_sizes() {
+        let sizes = ChromSizes::from_chrom_sizes("# comment\nchr2\t200\n\nchr1 100\n").unwrap();
+        assert_eq!(sizes.keys().map(|s| s.as_str()).collect::<Vec<_>>(), vec!["chr2", "chr1"]);
+        assert_eq!(sizes[&SeqId::from("chr1")], 100);
+        assert_eq!(sizes.total_len(), 300);
+        assert_eq!(sizes.to_string(), "chr2\t200\nchr1\t100\n");
+    }
+
+    #[test]
+    fn chrom_sizes_from_fai() {
+        let sizes = ChromSizes::from_fai("chr1\t100\t6\t60\t61\nchr2\t50\t114\t60\t61\n").unwrap();
+        assert_eq!(sizes.total_len(), 150);
+        assert!(ChromSizes::from_fai("chr1\t100\n").is_err());
+    }
+
+    #[test]
+    fn chrom_sizes_errors() {
+        assert!(ChromSizes::from_chrom_sizes("chr1\n").is_err());
+        assert!(ChromSizes::from_chrom_sizes("chr1\t-5\n").is_err());
+        assert!(ChromSizes::from_chrom_sizes("chr1\t5\nchr1\t6\n").is_err());
+    }
+
+    #[test]
+    fn alias_map_from_chrom_alias() {
+        let wide = "# ucsc\tassembly\trefseq\nchr1\t1\tNC_000001.11\nchrM\tMT\t\n";
+        let aliases = SeqIdAliasMap::from_chrom_alias(wide).unwrap();
+        assert_eq!(aliases.resolve("NC_000001.11").map(|s| s.as_str()), Some("chr1"));
+        assert_eq!(aliases.resolve("chr1").map(|s| s.as_str()), Some("chr1"));
+        assert_eq!(aliases.resolve("MT").map(|s| s.as_str()), Some("chrM"));
+        assert_eq!(aliases.resolve(""), None);
+
+        let long = SeqIdAliasMap::from_chrom_alias("1\tchr1\tassembly\n").unwrap();
+        assert_eq!(long.normalize(&SeqId::from("1")).as_str(), "chr1");
+        assert_eq!(long.normalize(&SeqId::from("2")).as_str(), "2");
+    }
+
+    #[test]
+    fn alias_map_conflicts() {
+        let mut aliases = SeqIdAliasMap::new();
+        aliases.insert("1", "chr1").unwrap();
+        aliases.insert("1", "chr1").unwrap();
+        assert!(aliases.insert("1", "chr2").is_err());
+        assert!(SeqIdAliasMap::from_chrom_alias("1\tchr1\n1\tchr2\n").is_err());
+    }
+
+    #[test]
+    fn alias_map_from_assembly_report() {
+        let report = "# Sequence-Name\tSequence-Role\tAssigned-Molecule\tAssigned-Molecule-Location/Type\tGenBank-Accn\tRelationship\tRefSeq-Accn\tAssembly-Unit\tSequence-Length\tUCSC-style-name\n\
+            1\tassembled-molecule\t1\tChromosome\tCM000663.2\t=\tNC_000001.11\tPrimary Assembly\t248956422\tchr1\n\
+            HSCHR1_CTG1\tunlocalized-scaffold\t1\tChromosome\tKI270706.1\t=\tNT_187361.1\tPrimary Assembly\t175055\tna\n";
+        let aliases = SeqIdAliasMap::from_assembly_report(report, AssemblyNaming::Ucsc).unwrap();
+        assert_eq!(aliases.resolve("CM000663.2").map(|s| s.as_str()), Some("chr1"));
+        assert_eq!(aliases.resolve("NC_000001.11").map(|s| s.as_str()), Some("chr1"));
+        assert_eq!(aliases.resolve("KI270706.1"), None);
+
+        let aliases = SeqIdAliasMap::from_assembly_report(report, AssemblyNaming::RefSeq).unwrap();
+        assert_eq!(aliases.resolve("chr1").map(|s| s.as_str()), Some("NC_000001.11"));
+        assert_eq!(aliases.resolve("KI270706.1").map(|s| s.as_str()), Some("NT_187361.1"));
+        assert!(SeqIdAliasMap::from_assembly_report("1\tassembled-molecule\n", AssemblyNaming::Ucsc).is_err());
+    }
+
+    fn sorted(ordering: &SeqIdOrdering, names: &[&str]) -> Vec<String> {
+        let mut seqids: Vec<SeqId> = names.iter().map(|n| SeqId::from(*n)).collect();
+        seqids.sort_by(|a, b| ordering.compare(a, b));
+        seqids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn natural_and_karyotypic_orderings() {
+        let names = ["chrUn_1", "chrM", "chr10", "chrX", "chr2", "chrY", "chr1"];
+        assert_eq!(sorted(&SeqIdOrdering::Natural, &names), vec!["chr1", "chr2", "chr10", "chrM", "chrUn_1", "chrX", "chrY"]);
+        assert_eq!(sorted(&SeqIdOrdering::Karyotypic, &names), vec!["chr1", "chr2", "chr10", "chrX", "chrY", "chrM", "chrUn_1"]);
+        assert_eq!(sorted(&SeqIdOrdering::Karyotypic, &["MT", "X", "10", "2"]), vec!["2", "10", "X", "MT"]);
+    }
+
+    #[test]
+    fn sort_ranges_by_ordering() {
+        let mut ranges: Vec<GenomicRange> = ["chr10:1-5", "chr2:6-9", "chr2:1-5"].iter().map(|r| r.parse().unwrap()).collect();
+        SeqIdOrdering::Natural.sort_ranges(&mut ranges);
+        assert_eq!(ranges.iter().map(|r| r.to_string()).collect::<Vec<_>>(), vec!["chr2:1-5", "chr2:6-9", "chr10:1-5"]);
+    }
+
+    #[test]
+    fn explicit_ordering() {
+        let header = "@HD\tVN:1.6\n@SQ\tSN:chrX\tLN:100\n@SQ\tSN:chr2\tLN:50\n@SQ\tSN:chrX\tLN:100\n";
+        let order = SeqIdOrder::from_sam_header(header).unwrap();
+        assert_eq!(order.rank(&SeqId::from("chr2")), Some(1));
+        assert_eq!(order.seqids().count(), 2);
+        let ordering = SeqIdOrdering::Explicit(order);
+        assert_eq!(sorted(&ordering, &["chr2", "chrB", "chrA", "chrX"]), vec!["chrX", "chr2", "chrA", "chrB"]);
+        assert!(SeqIdOrder::from_sam_header("@SQ\tLN:100\n").is_err());
 
+        let sizes: ChromSizes = [(SeqId::from("b"), 10), (SeqId::from("a"), 20)].into_iter().collect();
+        assert_eq!(SeqIdOrder::from(&sizes), SeqIdOrder::new(["b", "a"]));
+    }
+
+    #[test]
+    fn genome_bins() {
+        let sizes: ChromSizes = [(SeqId::from("chr1"), 25), (SeqId::from("chr2"), 10)].into_iter().collect();
+        let bins = GenomeBins::new(&sizes, 10).unwrap();
+        assert_eq!(bins.len(), 4);
+        assert_eq!(bins.iter().map(|r| r.to_string()).collect::<Vec<_>>(), vec!["chr1:1-10", "chr1:11-20", "chr1:21-25", "chr2:1-10"]);
+        assert_eq!(bins.bin(2).unwrap().to_string(), "chr1:21-25");
+        assert_eq!(bins.bin(3).unwrap().to_string(), "chr2:1-10");
+        assert_eq!(bins.bin(4), None);
+        assert!(GenomeBins::new(&sizes, 0).is_err());
+    }
+
+    #[test]
+    fn genome_bins_assignment() {
+        let sizes: ChromSizes = [(SeqId::from("chr1"), 25), (SeqId::from("chr2"), 10)].into_iter().collect();
+        let bins = GenomeBins::new(&sizes, 10).unwrap();
+        let range = |text: &str| text.parse::<GenomicRange>().unwrap();
+        assert_eq!(bins.assign_bin(&range("chr1:15-30")), Some(1));
+        assert_eq!(bins.assign_bin(&range("chr2:5-6")), Some(3));
+        assert_eq!(bins.assign_bin(&range("chr1:26-30")), None);
+        assert_eq!(bins.assign_bin(&range("chr3:1-5")), None);
+        assert_eq!(bins.overlapping_bins(&range("chr1:15-30")), 1..3);
+        assert_eq!(bins.overlapping_bins(&range("chr1:11-20")), 1..2);
+        assert_eq!(bins.overlapping_bins(&range("chr3:1-5")), 0..0);
+    }
+
+    #[test]
+    fn expand_flank_and_shift_follow_the_overflow_policy() {
+        let seq_lens = IndexMap::from([(SeqId::from("chr1"), 100)]);
+        let range: GenomicRange = "chr1:6-95".parse().unwrap();
+        let expand = |policy| range.expand_within(10, 3, Some(&seq_lens), policy);
+        assert!(expand(OverflowPolicy::Error).is_err());
+        assert_eq!(expand(OverflowPolicy::Clamp).unwrap().map(|r| r.to_string()), Some("chr1:1-98".to_string()));
+        assert_eq!(expand(OverflowPolicy::Drop).unwrap(), None);
+        assert_eq!(range.expand(10, 10, Some(&seq_lens)).unwrap().to_string(), "chr1:1-100");
+        assert_eq!(range.expand_within(5, 5, Some(&seq_lens), OverflowPolicy::Error).unwrap().map(|r| r.to_string()), Some("chr1:1-100".to_string()));
+
+        let flank = |policy| range.flank_within(10, FlankSide::Both, Some(&seq_lens), policy).map(|f| f.iter().map(|r| r.to_string()).collect::<Vec<_>>());
+        assert!(flank(OverflowPolicy::Error).is_err());
+        assert_eq!(flank(OverflowPolicy::Clamp).unwrap(), ["chr1:1-5", "chr1:96-100"]);
+        assert_eq!(flank(OverflowPolicy::Drop).unwrap(), Vec::<String>::new());
+        assert_eq!(range.flank_within(3, FlankSide::Both, Some(&seq_lens), OverflowPolicy::Drop).unwrap().len(), 2);
+        assert_eq!(range.flank(10, FlankSide::Left, None).unwrap()[0].to_string(), "chr1:1-5");
+
+        let shift = |policy| range.shift_within(-10, Some(&seq_lens), policy);
+        assert!(shift(OverflowPolicy::Error).is_err());
+        assert_eq!(shift(OverflowPolicy::Clamp).unwrap().map(|r| r.to_string()), Some("chr1:1-85".to_string()));
+        assert_eq!(shift(OverflowPolicy::Drop).unwrap(), None);
+    }
+
+    #[test]
+    fn alias_map_from_ensembl_synonyms() {
+        let text = "seq_region\tsynonym\tdb\n1\tCM000663.2\tINSDC\n1\tNC_000001.11\tRefSeq_genomic\nMT\t\\N\t\\N\n";
+        let aliases = SeqIdAliasMap::from_ensembl_synonyms(text).unwrap();
+        assert_eq!(aliases.resolve("NC_000001.11").map(|s| s.as_str()), Some("1"));
+        assert_eq!(aliases.resolve("1").map(|s| s.as_str()), Some("1"));
+        assert_eq!(aliases.resolve("MT"), None);
+        assert_eq!(aliases.resolve("synonym"), None);
+        assert!(SeqIdAliasMap::from_ensembl_synonyms("1\n").is_err());
+        assert!(SeqIdAliasMap::from_ensembl_synonyms("1\tCM000663.2\n2\tCM000663.2\n").is_err());
+    }
+}
@@ -0,0 +1,525 @@
+//! Importers for the outputs of common gene, RNA and repeat finders
+//!
+//! Each importer takes the text of an output file and normalizes the predictions into
+//! [`Gff3Row`]s with GFF3 feature types and `ID`/`Parent` attributes, so that they can be
+//! combined with other annotation. Similarity search hits become `match` features, and
+//! transcripts exported as BED12 can be rebuilt into gene, transcript, exon and CDS rows.
+
+use indexmap::IndexMap;
+
+use crate::format::{Bed12Row, Gff3Row, Strand};
+use crate::genome::{Error, SeqId};
+
+fn row(seqid: &str, source: &str, feature_type: &str, (start, end): (u64, u64), score: String, strand: Strand, attributes: IndexMap<String, String>) -> Gff3Row<String> {
+    Gff3Row {
+        seqid: SeqId::from(seqid),
+        source: source.to_string(),
+        feature_type: feature_type.to_string(),
+        start,
+        end,
+        score,
+        strand,
+        phase: ".".to_string(),
+        attributes,
+    }
+}
+
+/// 1-based, closed bounds, given in the order of the strand
+fn oriented_bounds(from: u64, to: u64) -> ((u64, u64), Strand) {
+    if from <= to {
+        ((from, to), Strand::Plus)
+    } else {
+        ((to, from), Strand::Minus)
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(value: &str, name: &str, line_num: usize) -> Result<T, Error> {
+    value.parse::<T>().map_err(|_| Error::InvalidArguments(format!("line {}: invalid {} {}", line_num + 1, name, value)))
+}
+
+/// Imports the default tabular output of tRNAscan-SE
+///
+/// Each tRNA becomes a `tRNA` row with an `ID` of `<seqid>.trna<n>`, and `exon` children either side
+/// of any intron, numbered from the 5' end. The isotype, anticodon and any note (e.g. `pseudo`) are kept as attributes.
+pub fn trnascan(text: &str) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut rows = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        // skip the three header lines
+        if fields.len() < 9 || fields[1].parse::<u64>().is_err() {
+            continue;
+        }
+        let num = |i: usize, name: &str| parse_num::<u64>(fields[i], name, line_num);
+        let (bounds, strand) = oriented_bounds(num(2, "begin")?, num(3, "end")?);
+        let id = format!("{}.trna{}", fields[0], fields[1]);
+        let mut attributes = IndexMap::from([
+            ("ID".to_string(), id.clone()),
+            ("Name".to_string(), format!("tRNA-{}", fields[4])),
+            ("isotype".to_string(), fields[4].to_string()),
+            ("anticodon".to_string(), fields[5].to_string()),
+        ]);
+        if fields.len() > 9 {
+            attributes.insert("Note".to_string(), fields[9..].join(" "));
+        }
+        rows.push(row(fields[0], "tRNAscan-SE", "tRNA", bounds, fields[8].to_string(), strand, attributes));
+
+        let (intron, _) = oriented_bounds(num(6, "intron begin")?, num(7, "intron end")?);
+        let exons = if intron.0 == 0 {
+            vec![bounds]
+        } else {
+            vec![(bounds.0, intron.0 - 1), (intron.1 + 1, bounds.1)]
+        };
+        let count = exons.len();
+        for (i, exon) in exons.into_iter().enumerate() {
+            let n = if strand == Strand::Minus { count - i } else { i + 1 };
+            let attributes = IndexMap::from([
+                ("ID".to_string(), format!("{}.exon{}", id, n)),
+                ("Parent".to_string(), id.clone()),
+            ]);
+            rows.push(row(fields[0], "tRNAscan-SE", "exon", exon, ".".to_string(), strand, attributes));
+        }
+    }
+    Ok(rows)
+}
+
+/// The Infernal program that wrote a tblout file, which sets the order of its columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfernalProgram {
+    /// The sequence is the target and the model the query
+    Cmsearch,
+    /// The model is the target and the sequence the query
+    Cmscan,
+}
+
+/// Imports the `--tblout` output of Infernal cmsearch or cmscan
+///
+/// Each hit becomes an `ncRNA` row named by the model, with the Rfam accession, E-value and
+/// inclusion flag as attributes. With `significant_only`, hits not marked `!` are skipped.
+pub fn infernal_tblout(text: &str, program: InfernalProgram, significant_only: bool) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut rows = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.len() < 17 {
+            return Err(Error::InvalidArguments(format!("line {}: tblout lines should have at least 17 fields", line_num + 1)));
+        }
+        if significant_only && fields[16] != "!" {
+            continue;
+        }
+        let (bounds, _) = oriented_bounds(parse_num(fields[7], "seq from", line_num)?, parse_num(fields[8], "seq to", line_num)?);
+        let strand = match fields[9] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            s => return Err(Error::InvalidArguments(format!("line {}: invalid strand {}", line_num + 1, s))),
+        };
+        // the name and accession of the sequence and of the model
+        let (seqid, (model, accession)) = match program {
+            InfernalProgram::Cmsearch => (fields[0], (fields[2], fields[3])),
+            InfernalProgram::Cmscan => (fields[2], (fields[0], fields[1])),
+        };
+        let mut attributes = IndexMap::from([
+            ("ID".to_string(), format!("{}.{}.{}", model, seqid, rows.len() + 1)),
+            ("Name".to_string(), model.to_string()),
+        ]);
+        if accession != "-" {
+            attributes.insert("rfam".to_string(), accession.to_string());
+        }
+        attributes.insert("evalue".to_string(), fields[15].to_string());
+        attributes.insert("inc".to_string(), fields[16].to_string());
+        rows.push(row(seqid, "Infernal", "ncRNA", bounds, fields[14].to_string(), strand, attributes));
+    }
+    Ok(rows)
+}
+
+/// Which sequence of a BLAST hit the features are placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlastCoordinates {
+    Query,
+    Subject,
+}
+
+/// Imports BLAST or DIAMOND tabular output (`-outfmt 6`, or 7 with comments) with the default columns
+///
+/// Each hit becomes a `match` row on the query or subject, with an `ID` of `<seqid>.hit<n>`, the other
+/// sequence as `Name` and in a `Target` attribute, and the bit score as the score. The percent identity,
+/// alignment length and E-value are kept as `identity`, `length` and `evalue` attributes. The strand is minus
+/// when one of the ranges is reversed, as for hits to the reverse complement of a nucleotide sequence.
+pub fn blast_tabular(text: &str, source: &str, on: BlastCoordinates) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut rows = Vec::new();
+    let mut counts: IndexMap<String, usize> = IndexMap::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() < 12 {
+            return Err(Error::InvalidArguments(format!("line {}: BLAST tabular lines should have at least 12 fields", line_num + 1)));
+        }
+        let (query_bounds, query_strand) = oriented_bounds(parse_num(fields[6], "qstart", line_num)?, parse_num(fields[7], "qend", line_num)?);
+        let (subject_bounds, subject_strand) = oriented_bounds(parse_num(fields[8], "sstart", line_num)?, parse_num(fields[9], "send", line_num)?);
+        let strand = if query_strand == subject_strand { Strand::Plus } else { Strand::Minus };
+        let ((seqid, bounds), (target, target_bounds)) = match on {
+            BlastCoordinates::Query => ((fields[0], query_bounds), (fields[1], subject_bounds)),
+            BlastCoordinates::Subject => ((fields[1], subject_bounds), (fields[0], query_bounds)),
+        };
+        let count = counts.entry(seqid.to_string()).or_default();
+        *count += 1;
+        let attributes = IndexMap::from([
+            ("ID".to_string(), format!("{}.hit{}", seqid, count)),
+            ("Name".to_string(), target.to_string()),
+            ("Target".to_string(), format!("{} {} {} {}", target, target_bounds.0, target_bounds.1, strand)),
+            ("identity".to_string(), fields[2].to_string()),
+            ("length".to_string(), fields[3].to_string()),
+            ("evalue".to_string(), fields[10].to_string()),
+        ]);
+        rows.push(row(seqid, source, "match", bounds, fields[11].trim().to_string(), strand, attributes));
+    }
+    Ok(rows)
+}
+
+/// Imports the `.dat` output of Tandem Repeats Finder
+///
+/// Each repeat becomes a `tandem_repeat` row with an `ID` of `<seqid>.trf<n>`, the TRF score, and the
+/// period, copy number, consensus motif, percent matches and indels, and entropy as attributes.
+/// The sequence is the first word of each `Sequence:` line.
+pub fn trf_dat(text: &str) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut rows = Vec::new();
+    let mut seqid: Option<String> = None;
+    let mut count = 0;
+    for (line_num, line) in text.lines().enumerate() {
+        if let Some(header) = line.strip_prefix("Sequence:") {
+            seqid = header.split_whitespace().next().map(|s| s.to_string());
+            count = 0;
+            continue;
+        }
+        let fields: Vec<_> = line.split_whitespace().collect();
+        // repeat lines start with the coordinates, unlike the headers and parameters
+        if fields.len() < 14 || fields[0].parse::<u64>().is_err() {
+            continue;
+        }
+        let Some(seqid) = &seqid else {
+            return Err(Error::InvalidArguments(format!("line {}: repeat before a Sequence: line", line_num + 1)));
+        };
+        let start = parse_num::<u64>(fields[0], "start", line_num)?;
+        let end = parse_num::<u64>(fields[1], "end", line_num)?;
+        parse_num::<f64>(fields[3], "copy number", line_num)?;
+        count += 1;
+        let attributes = IndexMap::from([
+            ("ID".to_string(), format!("{}.trf{}", seqid, count)),
+            ("period".to_string(), fields[2].to_string()),
+            ("copy_number".to_string(), fields[3].to_string()),
+            ("consensus".to_string(), fields[13].to_string()),
+            ("percent_matches".to_string(), fields[5].to_string()),
+            ("percent_indels".to_string(), fields[6].to_string()),
+            ("entropy".to_string(), fields[12].to_string()),
+        ]);
+        rows.push(row(seqid, "TRF", "tandem_repeat", (start, end), fields[7].to_string(), Strand::None, attributes));
+    }
+    Ok(rows)
+}
+
+/// Imports AUGUSTUS predictions in its default GTF-like output or with `--gff3=on`
+///
+/// The quirks of AUGUSTUS output are normalized:
+/// - `transcript` rows become `mRNA`
+/// - bare `gene` and `transcript` attributes (e.g. `g1.t1`) become `ID` and `Parent`
+/// - GTF `transcript_id` attributes become `Parent`
+/// - `intron` rows are dropped, since they are implied by the exons or CDS
+/// - comment lines, such as the predicted protein sequences, are skipped
+pub fn augustus(text: &str) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut rows = Vec::new();
+    let mut counts: IndexMap<(String, String), usize> = IndexMap::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(Error::InvalidArguments(format!("line {}: AUGUSTUS lines should have 9 fields", line_num + 1)));
+        }
+        let feature_type = match fields[2] {
+            "intron" => continue,
+            "transcript" => "mRNA",
+            t => t,
+        };
+        let strand = match fields[6] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => Strand::None,
+        };
+
+        let raw = fields[8].trim();
+        let mut attributes = IndexMap::new();
+        if raw.contains('=') {
+            for kv in raw.split(';') {
+                if let Some((key, value)) = kv.split_once('=') {
+                    attributes.insert(key.trim().to_string(), value.trim().to_string());
+                }
+            }
+        } else if raw.contains('"') {
+            for kv in raw.split(';') {
+                if let Some((key, value)) = kv.trim().split_once(' ') {
+                    attributes.insert(key.to_string(), value.trim().trim_matches('"').to_string());
+                }
+            }
+        } else if feature_type == "gene" {
+            attributes.insert("ID".to_string(), raw.to_string());
+        } else if feature_type == "mRNA" {
+            attributes.insert("ID".to_string(), raw.to_string());
+            if let Some((gene, _)) = raw.rsplit_once('.') {
+                attributes.insert("Parent".to_string(), gene.to_string());
+            }
+        }
+        if feature_type == "mRNA" && !attributes.contains_key("Parent") {
+            if let Some(gene) = attributes.shift_remove("gene_id") {
+                attributes.insert("Parent".to_string(), gene);
+            }
+        }
+        if let Some(transcript) = attributes.shift_remove("transcript_id") {
+            attributes.shift_remove("gene_id");
+            if feature_type == "mRNA" {
+                attributes.entry("ID".to_string()).or_insert(transcript);
+            } else {
+                attributes.entry("Parent".to_string()).or_insert(transcript);
+            }
+        }
+        if !attributes.contains_key("ID") {
+            if let Some(parent) = attributes.get("Parent").cloned() {
+                let count = counts.entry((parent.clone(), feature_type.to_string())).or_default();
+                *count += 1;
+                attributes.insert("ID".to_string(), format!("{}.{}{}", parent, feature_type, count));
+            }
+        }
+        // keep ID and Parent first, as is conventional
+        attributes.sort_by_cached_key(|key, _| match key.as_str() {
+            "ID" => 0,
+            "Parent" => 1,
+            _ => 2,
+        });
+
+        rows.push(Gff3Row {
+            seqid: SeqId::from(fields[0]),
+            source: fields[1].to_string(),
+            feature_type: feature_type.to_string(),
+            start: parse_num(fields[3], "start", line_num)?,
+            end: parse_num(fields[4], "end", line_num)?,
+            score: fields[5].to_string(),
+            strand,
+            phase: fields[7].to_string(),
+            attributes,
+        });
+    }
+    Ok(rows)
+}
+
+/// Rebuilds gene models from BED12 transcripts, as exported from a genome browser
+///
+/// Each row becomes a `gene` with an `ID` of its name, made unique with a `_<n>` suffix, or `<source><n>`
+/// for unnamed rows. Its transcript has an `ID` of `<gene>.t1`, and exons `<transcript>.exon<n>`, numbered
+/// in the direction of transcription. With `infer_cds`, the thick part of the blocks becomes `CDS` rows
+/// (`<transcript>.cds`) with phases and the transcript is an `mRNA`; otherwise, or when the thick part is
+/// empty, the transcript is a `transcript`.
+pub fn bed12(rows: &[Bed12Row], source: &str, infer_cds: bool) -> Result<Vec<Gff3Row<String>>, Error> {
+    let mut gff_rows = Vec::new();
+    let mut names: IndexMap<String, usize> = IndexMap::new();
+    for (i, bed) in rows.iter().enumerate() {
+        let blocks = bed.blocks()?;
+        let base = if bed.name.is_empty() || bed.name == "." { format!("{}{}", source, i + 1) } else { bed.name.clone() };
+        let count = names.entry(base.clone()).or_default();
+        *count += 1;
+        let gene_id = if *count == 1 { base } else { format!("{}_{}", base, count) };
+        let tx_id = format!("{}.t1", gene_id);
+        let coding = infer_cds && bed.thick_start < bed.thick_end;
+        let seqid = bed.chrom.as_str();
+        let bounds = (bed.chrom_start + 1, bed.chrom_end);
+        let attributes = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<IndexMap<_, _>>();
+        let mut gene_attributes = attributes(&[("ID", &gene_id)]);
+        if !bed.name.is_empty() && bed.name != "." {
+            gene_attributes.insert("Name".to_string(), bed.name.clone());
+        }
+
+        gff_rows.push(row(seqid, source, "gene", bounds, ".".to_string(), bed.strand, gene_attributes));
+        let tx_type = if coding { "mRNA" } else { "transcript" };
+        gff_rows.push(row(seqid, source, tx_type, bounds, bed.score.to_string(), bed.strand, attributes(&[("ID", &tx_id), ("Parent", &gene_id)])));
+        for (n, (start, end)) in blocks.iter().enumerate() {
+            let exon_num = if bed.strand == Strand::Minus { blocks.len() - n } else { n + 1 };
+            let exon_id = format!("{}.exon{}", tx_id, exon_num);
+            gff_rows.push(row(seqid, source, "exon", (start + 1, *end), ".".to_string(), bed.strand, attributes(&[("ID", &exon_id), ("Parent", &tx_id)])));
+        }
+        if coding {
+            let mut segments: Vec<(u64, u64)> = blocks.iter()
+                .map(|(start, end)| ((*start).max(bed.thick_start), (*end).min(bed.thick_end)))
+                .filter(|(start, end)| start < end)
+                .collect();
+            if bed.strand == Strand::Minus {
+                segments.reverse();
+            }
+            let cds_id = format!("{}.cds", tx_id);
+            let mut cds_rows = Vec::new();
+            let mut coding_len = 0;
+            for (start, end) in segments {
+                let mut cds = row(seqid, source, "CDS", (start + 1, end), ".".to_string(), bed.strand, attributes(&[("ID", &cds_id), ("Parent", &tx_id)]));
+                cds.phase = ((3 - coding_len % 3) % 3).to_string();
+                coding_len += end - start;
+                cds_rows.push(cds);
+            }
+            cds_rows.sort_by_key(|cds| cds.start);
+            gff_rows.extend(cds_rows);
+        }
+    }
+    Ok(gff_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trnascan_numbers_exons_from_the_5_prime_end() {
+        let text = "\
+Sequence\ttRNA\tBounds\ttRNA\tAnti\tIntron Bounds\tInf
+Name\ttRNA #\tBegin\tEnd\tType\tCodon\tBegin\tEnd\tScore\tNote
+--------\t------\t-----\t------\t----\t-----\t-----\t----\t------\t------
+chr1\t1\t1000\t920\tTyr\tGTA\t962\t950\t60.1
+";
+        let rows = trnascan(text).unwrap();
+        let exons: Vec<_> = rows[1..].iter().map(|r| (r.attributes["ID"].as_str(), r.start, r.end)).collect();
+        assert_eq!((rows[0].start, rows[0].end, rows[0].strand), (920, 1000, Strand::Minus));
+        assert_eq!(exons, [("chr1.trna1.exon2", 920, 949), ("chr1.trna1.exon1", 963, 1000)]);
+    }
+
+    #[test]
+    fn infernal_columns_depend_on_the_program() {
+        let cmsearch = "chr1 - 5S_rRNA RF00001 cm 1 119 1118 1000 - no 1 0.52 0.0 95.0 1.2e-20 ! -";
+        let cmscan = "5S_rRNA RF00001 chr1 - cm 1 119 1118 1000 - no 1 0.52 0.0 95.0 1.2e-20 ! -";
+        for (text, program) in [(cmsearch, InfernalProgram::Cmsearch), (cmscan, InfernalProgram::Cmscan)] {
+            let rows = infernal_tblout(text, program, true).unwrap();
+            assert_eq!((rows[0].seqid.as_str(), rows[0].start, rows[0].end, rows[0].strand), ("chr1", 1000, 1118, Strand::Minus));
+            assert_eq!(rows[0].attributes["Name"], "5S_rRNA");
+            assert_eq!(rows[0].attributes["rfam"], "RF00001");
+        }
+    }
+
+    #[test]
+    fn blast_hits_are_placed_on_the_query_or_subject() {
+        let text = "\
+# BLASTN 2.15.0+
+q1\tchr1\t98.50\t100\t1\t0\t1\t100\t500\t401\t1e-50\t180
+q1\tchr2\t90.00\t50\t5\t0\t101\t150\t1\t50\t1e-5\t80.5
+";
+        let on_query = blast_tabular(text, "blastn", BlastCoordinates::Query).unwrap();
+        let placed: Vec<_> = on_query.iter().map(|r| (r.seqid.as_str(), r.start, r.end, r.strand, r.attributes["ID"].as_str())).collect();
+        assert_eq!(placed, [("q1", 1, 100, Strand::Minus, "q1.hit1"), ("q1", 101, 150, Strand::Plus, "q1.hit2")]);
+        assert_eq!(on_query[0].attributes["Target"], "chr1 401 500 -");
+        assert_eq!((on_query[0].attributes["identity"].as_str(), on_query[0].attributes["evalue"].as_str()), ("98.50", "1e-50"));
+        assert_eq!((on_query[1].score.as_str(), on_query[1].attributes["Name"].as_str()), ("80.5", "chr2"));
+
+        let on_subject = blast_tabular(text, "blastn", BlastCoordinates::Subject).unwrap();
+        let placed: Vec<_> = on_subject.iter().map(|r| (r.seqid.as_str(), r.start, r.end, r.strand, r.attributes["ID"].as_str())).collect();
+        assert_eq!(placed, [("chr1", 401, 500, Strand::Minus, "chr1.hit1"), ("chr2", 1, 50, Strand::Plus, "chr2.hit1")]);
+        assert_eq!(on_subject[0].attributes["Target"], "q1 1 100 -");
+        assert_eq!(on_subject[0].attributes["length"], "100");
+        assert!(blast_tabular("q1\tchr1\t98.50\n", "blastn", BlastCoordinates::Query).is_err());
+    }
+
+    #[test]
+    fn trf_repeats_keep_their_columns() {
+        let text = "\
+Tandem Repeats Finder Program written by:
+
+Sequence: chr1 test sequence
+
+Parameters: 2 7 7 80 10 50 500
+
+
+10 45 6 6.0 6 100 0 72 50 0 0 50 1.00 TTAGGA TTAGGATTAGGATTAGGATTAGGATTAGGATTAGGA
+";
+        let rows = trf_dat(text).unwrap();
+        assert_eq!(rows.len(), 1);
+        let repeat = &rows[0];
+        assert_eq!((repeat.seqid.as_str(), repeat.start, repeat.end, repeat.score.as_str()), ("chr1", 10, 45, "72"));
+        assert_eq!(repeat.attributes["ID"], "chr1.trf1");
+        assert_eq!((repeat.attributes["period"].as_str(), repeat.attributes["copy_number"].as_str()), ("6", "6.0"));
+        assert_eq!(repeat.attributes["consensus"], "TTAGGA");
+        assert_eq!((repeat.attributes["percent_matches"].as_str(), repeat.attributes["entropy"].as_str()), ("100", "1.00"));
+        assert!(trf_dat("10 45 6 6.0 6 100 0 72 50 0 0 50 1.00 TTAGGA TTAGGA\n").is_err());
+    }
+
+    #[test]
+    fn bed12_rebuilds_minus_strand_gene_models() {
+        let bed = |thick_start, thick_end| Bed12Row {
+            chrom: SeqId::from("chr1"),
+            chrom_start: 100,
+            chrom_end: 200,
+            name: "tx1".to_string(),
+            score: 0,
+            strand: Strand::Minus,
+            thick_start,
+            thick_end,
+            item_rgb: "0".to_string(),
+            block_count: 3,
+            block_sizes: "20,30,20,".to_string(),
+            block_starts: "0,40,80,".to_string(),
+        };
+        let rows = bed12(&[bed(110, 190), bed(100, 100)], "bed", true).unwrap();
+        let summary: Vec<_> = rows.iter()
+            .map(|r| (r.feature_type.as_str(), r.start, r.end, r.phase.as_str(), r.attributes["ID"].as_str()))
+            .collect();
+        assert_eq!(summary, [
+            ("gene", 101, 200, ".", "tx1"),
+            ("mRNA", 101, 200, ".", "tx1.t1"),
+            ("exon", 101, 120, ".", "tx1.t1.exon3"),
+            ("exon", 141, 170, ".", "tx1.t1.exon2"),
+            ("exon", 181, 200, ".", "tx1.t1.exon1"),
+            ("CDS", 111, 120, "2", "tx1.t1.cds"),
+            ("CDS", 141, 170, "2", "tx1.t1.cds"),
+            ("CDS", 181, 190, "0", "tx1.t1.cds"),
+            ("gene", 101, 200, ".", "tx1_2"),
+            ("transcript", 101, 200, ".", "tx1_2.t1"),
+            ("exon", 101, 120, ".", "tx1_2.t1.exon3"),
+            ("exon", 141, 170, ".", "tx1_2.t1.exon2"),
+            ("exon", 181, 200, ".", "tx1_2.t1.exon1"),
+        ]);
+        assert!(rows.iter().all(|r| r.strand == Strand::Minus));
+        assert_eq!(rows[2].attributes["Parent"], "tx1.t1");
+    }
+
+    #[test]
+    fn augustus_quirks_are_normalized() {
+        let gtf = "\
+# start gene g1
+chr1\tAUGUSTUS\tgene\t1\t100\t0.5\t+\t.\tg1
+chr1\tAUGUSTUS\ttranscript\t1\t100\t0.5\t+\t.\tg1.t1
+chr1\tAUGUSTUS\texon\t1\t40\t.\t+\t.\ttranscript_id \"g1.t1\"; gene_id \"g1\";
+chr1\tAUGUSTUS\tintron\t41\t60\t1\t+\t.\ttranscript_id \"g1.t1\"; gene_id \"g1\";
+chr1\tAUGUSTUS\texon\t61\t100\t.\t+\t.\ttranscript_id \"g1.t1\"; gene_id \"g1\";
+chr1\tAUGUSTUS\tCDS\t11\t40\t0.9\t+\t0\ttranscript_id \"g1.t1\"; gene_id \"g1\";
+# protein sequence = [MAAAK]
+";
+        let rows = augustus(gtf).unwrap();
+        let summary: Vec<_> = rows.iter()
+            .map(|r| (r.feature_type.as_str(), r.attributes.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(";")))
+            .collect();
+        assert_eq!(summary, [
+            ("gene", "ID=g1".to_string()),
+            ("mRNA", "ID=g1.t1;Parent=g1".to_string()),
+            ("exon", "ID=g1.t1.exon1;Parent=g1.t1".to_string()),
+            ("exon", "ID=g1.t1.exon2;Parent=g1.t1".to_string()),
+            ("CDS", "ID=g1.t1.CDS1;Parent=g1.t1".to_string()),
+        ]);
+        assert_eq!((rows[4].start, rows[4].end, rows[4].phase.as_str()), (11, 40, "0"));
+
+        let gff3 = "\
+chr1\tAUGUSTUS\tgene\t1\t100\t0.5\t-\t.\tID=g2;
+chr1\tAUGUSTUS\ttranscript\t1\t100\t0.5\t-\t.\tID=g2.t1;Parent=g2
+chr1\tAUGUSTUS\tintron\t41\t60\t1\t-\t.\tParent=g2.t1;
+chr1\tAUGUSTUS\tCDS\t61\t100\t.\t-\t0\tID=g2.t1.cds;Parent=g2.t1
+";
+        let rows = augustus(gff3).unwrap();
+        let summary: Vec<_> = rows.iter().map(|r| (r.feature_type.as_str(), r.attributes["ID"].as_str(), r.strand)).collect();
+        assert_eq!(summary, [("gene", "g2", Strand::Minus), ("mRNA", "g2.t1", Strand::Minus), ("CDS", "g2.t1.cds", Strand::Minus)]);
+        assert_eq!(rows[1].attributes["Parent"], "g2");
+    }
+}
@@ -0,0 +1,113 @@
+//! Longest-isoform sequences of gene models
+//!
+//! Annotation completeness tools such as BUSCO take one protein (or transcript) per gene.
+//! [`longest_isoform_fasta`] selects the longest isoform of each gene and extracts its
+//! spliced transcript and translated CDS in one call.
+
+use crate::format::Gff3Row;
+use crate::genome::Error;
+use crate::models::{Gene, Transcript};
+use crate::sequence::{translate, FastaRecord, Genome};
+
+/// What to do with a stop codon at the end of a translated CDS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCodonPolicy {
+    /// Keep the stop as `*`
+    Keep,
+    /// Remove the stop
+    Trim,
+}
+
+/// The longest isoform of each gene, as FASTA records named by the transcript id
+#[derive(Debug, Clone, Default)]
+pub struct IsoformSequences {
+    pub transcripts: Vec<FastaRecord>,
+    /// Proteins of the coding genes
+    pub proteins: Vec<FastaRecord>,
+}
+
+/// Returns the longest isoform of the gene
+///
+/// Coding transcripts are preferred and are compared by CDS length, then by spliced length.
+/// Ties go to the first transcript.
+pub fn longest_isoform<T>(gene: &Gene<T>) -> Option<&Transcript<T>> {
+    gene.transcripts.iter()
+        .rev()
+        .max_by_key(|t| (t.cds_len(), t.exonic_len()))
+}
+
+/// Translates the CDS of a transcript, applying the stop codon policy
+///
+/// Internal stops are kept as `*`, so that they can be detected.
+pub fn protein<T>(transcript: &Transcript<T>, genome: &Genome, stop_codon: StopCodonPolicy) -> Result<Vec<u8>, Error> {
+    let mut protein = translate(&transcript.cds_sequence(genome)?);
+    if stop_codon == StopCodonPolicy::Trim && protein.last() == Some(&b'*') {
+        protein.pop();
+    }
+    Ok(protein)
+}
+
+/// Extracts the transcript and protein sequences of the longest isoform of each gene
+///
+/// The description of each record is the gene id. Genes with no transcripts are skipped.
+pub fn longest_isoform_fasta<T>(genes: &[Gene<T>], genome: &Genome, stop_codon: StopCodonPolicy) -> Result<IsoformSequences, Error> {
+    let mut sequences = IsoformSequences::default();
+    for gene in genes {
+        let Some(transcript) = longest_isoform(gene) else {
+            continue;
+        };
+        let record = |sequence: Vec<u8>| FastaRecord {
+            id: row_id(&transcript.row),
+            description: gene.id().map(|id| id.to_string()),
+            sequence: String::from_utf8_lossy(&sequence).into_owned(),
+        };
+        sequences.transcripts.push(record(transcript.sequence(genome)?));
+        if transcript.is_coding() {
+            sequences.proteins.push(record(protein(transcript, genome, stop_codon)?));
+        }
+    }
+    Ok(sequences)
+}
+
+fn row_id<T>(row: &Gff3Row<T>) -> String {
+    row.attributes.get("ID").cloned().unwrap_or_else(|| format!("{}:{}-{}", row.seqid, row.start, row.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    const GFF: &str = "\
+chr1\t.\tgene\t1\t12\t.\t+\t.\tID=g1
+chr1\t.\tncRNA\t1\t12\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t12\t.\t+\t.\tParent=t1
+chr1\t.\tmRNA\t1\t10\t.\t+\t.\tID=t2;Parent=g1
+chr1\t.\texon\t1\t10\t.\t+\t.\tParent=t2
+chr1\t.\tCDS\t1\t9\t.\t+\t0\tParent=t2
+chr1\t.\tgene\t13\t24\t.\t-\t.\tID=g2
+chr1\t.\tmRNA\t13\t24\t.\t-\t.\tID=t3;Parent=g2
+chr1\t.\texon\t13\t24\t.\t-\t.\tParent=t3
+chr1\t.\tCDS\t16\t24\t.\t-\t0\tParent=t3
+";
+
+    #[test]
+    fn extracts_the_longest_coding_isoforms() {
+        let genes = build_gene_models(parse_gff3(GFF, ParseMode::Strict).unwrap()).unwrap();
+        let mut genome = Genome::default();
+        genome.insert("chr1", b"ATGAAATAAGGGCCCTTACATCAT".to_vec());
+
+        // a coding transcript is preferred over a longer non-coding one
+        assert_eq!(longest_isoform(&genes[0]).unwrap().id(), Some("t2"));
+
+        let sequences = longest_isoform_fasta(&genes, &genome, StopCodonPolicy::Keep).unwrap();
+        let records: Vec<_> = sequences.transcripts.iter().map(|r| (r.id.as_str(), r.description.as_deref(), r.sequence.as_str())).collect();
+        assert_eq!(records, [("t2", Some("g1"), "ATGAAATAAG"), ("t3", Some("g2"), "ATGATGTAAGGG")]);
+        let proteins: Vec<_> = sequences.proteins.iter().map(|r| r.sequence.as_str()).collect();
+        assert_eq!(proteins, ["MK*", "MM*"]);
+
+        let trimmed = longest_isoform_fasta(&genes, &genome, StopCodonPolicy::Trim).unwrap();
+        assert_eq!(trimmed.proteins[1].sequence, "MM");
+    }
+}
@@ -3,5 +3,42 @@
 #![deny(rustdoc::broken_intra_doc_links)] 
 #![deny(rustdoc::private_intra_doc_links)]
 
+pub mod assembly;
+pub mod cnv;
+pub mod channel;
+pub mod codons;
+pub mod collections;
+pub mod columnar;
+pub mod complexity;
+pub mod coverage;
+pub mod cpg;
+pub mod domains;
+pub mod flatfile;
 pub mod format;
-pub mod genome;
\ No newline at end of file
+pub mod gcbias;
+pub mod genesets;
+pub mod genome;
+pub mod import;
+pub mod isoforms;
+pub mod liftover;
+pub mod mask;
+pub mod merge;
+pub mod models;
+pub mod paf;
+pub mod prokaryote;
+pub mod psl;
+pub mod rename;
+pub mod report;
+pub mod roundtrip;
+pub mod sample;
+pub mod segmentation;
+pub mod seqstats;
+pub mod sequence;
+pub mod simulate;
+pub mod sql;
+pub mod stats;
+pub mod surgery;
+pub mod synteny;
+pub mod track;
+pub mod transcript;
+pub mod transfer;
\ No newline at end of file
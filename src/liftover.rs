@@ -0,0 +1,588 @@
+//! Converting annotations between assemblies using UCSC chain files
+//!
+//! Chains are parsed from the text of a chain file and used to remap [`GenomicRange`]s,
+//! [`Gff3Row`]s and [`Bed6Row`]s from the target (old) assembly to the query (new) assembly,
+//! following the conventions of UCSC liftOver.
+//!
+//! Records that can't be mapped are returned with an [`UnmappedReason`], and
+//! [`LiftoverOutput::unmapped_file`] writes them as the unmapped file of liftOver.
+
+use std::fmt;
+use indexmap::IndexMap;
+
+use crate::format::{Bed6Row, Gff3Row, Strand};
+use crate::genome::{Error, GenomicRange, OverflowPolicy, SeqId};
+
+/// An ungapped aligned block of a [`Chain`]
+///
+/// The query start is on the query strand of the chain i.e. it counts from the end of
+/// the query sequence when the query strand is [`Strand::Minus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainBlock {
+    pub target_start: u64,
+    pub query_start: u64,
+    pub size: u64,
+}
+
+/// A single chain from a UCSC chain file, aligning part of a target sequence to a query sequence
+///
+/// Coordinates are 0-based and half-open, as in the chain file. The target strand is always `+`.
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub score: f64,
+    pub target_seqid: SeqId,
+    pub target_size: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub query_seqid: SeqId,
+    pub query_size: u64,
+    pub query_strand: Strand,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub id: u64,
+    pub blocks: Vec<ChainBlock>,
+}
+
+/// Parses all the chains in the text of a chain file
+pub fn parse_chains(text: &str) -> Result<Vec<Chain>, Error> {
+    let mut chains = Vec::new();
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+    while let Some((line_num, line)) = lines.next() {
+        let mut chain = parse_header(line).map_err(|e| line_error(line_num, e))?;
+        let mut target_pos = chain.target_start;
+        let mut query_pos = chain.query_start;
+        loop {
+            let Some((line_num, line)) = lines.next() else {
+                return Err(Error::InvalidArguments(format!("chain {} has no final block", chain.id)));
+            };
+            let fields = line.split_whitespace()
+                .map(|f| f.parse::<u64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| line_error(line_num, "block fields should be unsigned integers".to_string()))?;
+            let (size, gaps) = match fields[..] {
+                [size] => (size, None),
+                [size, dt, dq] => (size, Some((dt, dq))),
+                _ => return Err(line_error(line_num, "blocks should have either 1 or 3 fields".to_string())),
+            };
+            chain.blocks.push(ChainBlock {
+                target_start: target_pos,
+                query_start: query_pos,
+                size,
+            });
+            target_pos += size;
+            query_pos += size;
+            match gaps {
+                Some((dt, dq)) => {
+                    target_pos += dt;
+                    query_pos += dq;
+                }
+                None => break,
+            }
+        }
+        if target_pos != chain.target_end || query_pos != chain.query_end {
+            return Err(Error::InvalidArguments(format!("the blocks of chain {} don't match its header", chain.id)));
+        }
+        if chain.target_end > chain.target_size || chain.query_end > chain.query_size {
+            return Err(Error::InvalidArguments(format!("chain {} extends beyond the end of its sequences", chain.id)));
+        }
+        chains.push(chain);
+    }
+    Ok(chains)
+}
+
+fn line_error(line_num: usize, message: String) -> Error {
+    Error::InvalidArguments(format!("line {} of chain file: {}", line_num + 1, message))
+}
+
+fn parse_header(line: &str) -> Result<Chain, String> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+    if fields.len() < 12 || fields[0] != "chain" {
+        return Err("chain header should start with 'chain' and have at least 12 fields".to_string());
+    }
+    let num = |i: usize| fields[i].parse::<u64>().map_err(|_| format!("field {} should be an unsigned integer", i + 1));
+    let strand = |i: usize| match fields[i] {
+        "+" => Ok(Strand::Plus),
+        "-" => Ok(Strand::Minus),
+        s => Err(format!("invalid strand {}", s)),
+    };
+    if strand(4)? != Strand::Plus {
+        return Err("the target strand should be +".to_string());
+    }
+    Ok(Chain {
+        score: fields[1].parse::<f64>().map_err(|_| "the score should be a number".to_string())?,
+        target_seqid: SeqId::from(fields[2]),
+        target_size: num(3)?,
+        target_start: num(5)?,
+        target_end: num(6)?,
+        query_seqid: SeqId::from(fields[7]),
+        query_size: num(8)?,
+        query_strand: strand(9)?,
+        query_start: num(10)?,
+        query_end: num(11)?,
+        id: fields.get(12).map(|_| num(12)).transpose()?.unwrap_or_default(),
+        blocks: Vec::new(),
+    })
+}
+
+/// Writes the chain in UCSC chain format, ending with a blank line
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "chain {} {} {} + {} {} {} {} {} {} {} {}",
+            self.score, self.target_seqid, self.target_size, self.target_start, self.target_end,
+            self.query_seqid, self.query_size, self.query_strand, self.query_start, self.query_end, self.id)?;
+        for pair in self.blocks.windows(2) {
+            let (block, next) = (pair[0], pair[1]);
+            writeln!(f, "{}\t{}\t{}", block.size,
+                next.target_start - block.target_start - block.size,
+                next.query_start - block.query_start - block.size)?;
+        }
+        if let Some(last) = self.blocks.last() {
+            writeln!(f, "{}", last.size)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl Chain {
+    /// Maps the part of a 0-based, half-open target interval that is aligned
+    ///
+    /// Returns one piece per block, as 0-based, half-open intervals on the query strand
+    /// of the chain, in target order.
+    fn map_interval(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let first = self.blocks.partition_point(|b| b.target_start + b.size <= start);
+        self.blocks[first..].iter()
+            .take_while(|b| b.target_start < end)
+            .map(|b| {
+                let s = start.max(b.target_start);
+                let e = end.min(b.target_start + b.size);
+                (b.query_start + s - b.target_start, b.query_start + e - b.target_start)
+            })
+            .collect()
+    }
+
+    /// Converts an interval on the query strand of the chain to the forward strand
+    fn to_forward(&self, (start, end): (u64, u64)) -> (u64, u64) {
+        match self.query_strand {
+            Strand::Minus => (self.query_size - end, self.query_size - start),
+            _ => (start, end),
+        }
+    }
+}
+
+/// How to handle a record that is aligned on either side of a gap in its chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapPolicy {
+    /// Map to a single range spanning the aligned parts, as UCSC liftOver does
+    Span,
+    /// Map to one record per aligned block
+    Split,
+    /// Leave the record unmapped
+    Drop,
+}
+
+/// Options for lifting records, with defaults matching UCSC liftOver
+#[derive(Debug, Clone)]
+pub struct LiftoverOptions {
+    /// Minimum fraction of bases that must be aligned for a record to be mapped
+    pub min_match: f64,
+    pub gap_policy: GapPolicy,
+    /// What to do with a record extending beyond the end of its sequence in the old assembly, which
+    /// is [`UnmappedReason::OutOfBoundsInOld`] for [`OverflowPolicy::Error`] and left out of the output
+    /// for [`OverflowPolicy::Drop`]
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for LiftoverOptions {
+    fn default() -> Self {
+        LiftoverOptions {
+            min_match: 0.95,
+            gap_policy: GapPolicy::Span,
+            overflow: OverflowPolicy::Error,
+        }
+    }
+}
+
+/// Why a record couldn't be lifted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappedReason {
+    /// No bases are aligned
+    DeletedInNew,
+    /// Fewer than the minimum fraction of bases are aligned
+    PartiallyDeletedInNew,
+    /// Enough bases are aligned, but only across more than one chain
+    SplitInNew,
+    /// The record is aligned either side of a gap and the gap policy is [`GapPolicy::Drop`]
+    CrossesGapInNew,
+    /// The record extends beyond the end of its sequence and the overflow policy is [`OverflowPolicy::Error`]
+    OutOfBoundsInOld,
+}
+
+impl fmt::Display for UnmappedReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            UnmappedReason::DeletedInNew => "Deleted in new",
+            UnmappedReason::PartiallyDeletedInNew => "Partially deleted in new",
+            UnmappedReason::SplitInNew => "Split in new",
+            UnmappedReason::CrossesGapInNew => "Crosses gap in new",
+            UnmappedReason::OutOfBoundsInOld => "Out of bounds in old",
+        })
+    }
+}
+
+/// A range on the new assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiftedRange {
+    pub range: GenomicRange,
+    /// Whether the range is on the opposite strand to the original, so that the strand of a record should be flipped
+    pub reversed: bool,
+}
+
+/// A record that couldn't be lifted, with the reason
+#[derive(Debug)]
+pub struct Unmapped<R> {
+    pub record: R,
+    pub reason: UnmappedReason,
+}
+
+/// The result of lifting a collection of records
+#[derive(Debug)]
+pub struct LiftoverOutput<R> {
+    pub mapped: Vec<R>,
+    pub unmapped: Vec<Unmapped<R>>,
+}
+
+impl<R> LiftoverOutput<R> {
+    /// Writes the unmapped records as liftOver does, each preceded by a line of `#` and its reason
+    ///
+    /// Each record is written as a line by `write_record` e.g. by returning the line it was parsed from.
+    pub fn unmapped_file<F: Fn(&R) -> String>(&self, write_record: F) -> String {
+        self.unmapped.iter()
+            .map(|unmapped| format!("#{}\n{}\n", unmapped.reason, write_record(&unmapped.record)))
+            .collect()
+    }
+}
+
+/// Records with a location that can be lifted to a new assembly
+pub trait Liftable: Sized {
+    fn genomic_range(&self) -> GenomicRange;
+
+    /// Returns a copy of the record at the new location
+    fn lifted(&self, lifted: &LiftedRange) -> Self;
+}
+
+impl<T: Clone> Liftable for Gff3Row<T> {
+    fn genomic_range(&self) -> GenomicRange {
+        GenomicRange::from_gff_row(self)
+    }
+
+    fn lifted(&self, lifted: &LiftedRange) -> Self {
+        let range = lifted.range.range_1closed();
+        Gff3Row {
+            seqid: lifted.range.seqid().clone(),
+            start: *range.start(),
+            end: *range.end(),
+            strand: if lifted.reversed { self.strand.flip() } else { self.strand },
+            ..self.clone()
+        }
+    }
+}
+
+impl Liftable for Bed6Row {
+    fn genomic_range(&self) -> GenomicRange {
+        GenomicRange::from_bed_row(self)
+    }
+
+    fn lifted(&self, lifted: &LiftedRange) -> Self {
+        let range = lifted.range.range_0halfopen();
+        Bed6Row {
+            chrom: lifted.range.seqid().clone(),
+            chrom_start: range.start,
+            chrom_end: range.end,
+            strand: if lifted.reversed { self.strand.flip() } else { self.strand },
+            ..self.clone()
+        }
+    }
+}
+
+/// Maps ranges from one coordinate system to another e.g. between assemblies or from the genome to a transcript
+///
+/// Implementors only need to map a range to its aligned pieces. Applying the [`LiftoverOptions`]
+/// and lifting records is then shared by all maps.
+pub trait CoordinateMap {
+    /// Maps the aligned parts of `range`, in order along `range`
+    ///
+    /// Each piece is the image of a contiguous part of `range` with no gaps in the alignment.
+    /// There are no pieces if no part of `range` is aligned.
+    fn map_pieces(&self, range: &GenomicRange) -> Vec<LiftedRange>;
+
+    /// The length of a sequence in the coordinates mapped from, if known, to check ranges against
+    fn seq_len(&self, _seqid: &SeqId) -> Option<u64> {
+        None
+    }
+
+    /// Maps a single range, returning more than one range only with [`GapPolicy::Split`]
+    ///
+    /// With [`GapPolicy::Span`], pieces mapping to different sequences or orientations can't be
+    /// spanned and the range is [`UnmappedReason::SplitInNew`]. A range beyond the end of its sequence
+    /// follows the overflow policy, and maps to no ranges when dropped.
+    fn project_range(&self, range: &GenomicRange, options: &LiftoverOptions) -> Result<Vec<LiftedRange>, UnmappedReason> {
+        let Some(range) = within_bounds(self.seq_len(range.seqid()), range, options)? else {
+            return Ok(Vec::new());
+        };
+        project_pieces(self.map_pieces(&range), range.len(), options)
+    }
+
+    /// Maps a record, which is split into several records with [`GapPolicy::Split`]
+    fn project<R: Liftable>(&self, record: &R, options: &LiftoverOptions) -> Result<Vec<R>, UnmappedReason> {
+        let lifted = self.project_range(&record.genomic_range(), options)?;
+        Ok(lifted.iter().map(|l| record.lifted(l)).collect())
+    }
+
+    /// Maps all the records e.g. of a GFF3 or BED file, collecting those that can't be mapped
+    ///
+    /// Records dropped by the overflow policy are in neither the mapped nor the unmapped records.
+    fn project_records<R: Liftable, I: IntoIterator<Item = R>>(&self, records: I, options: &LiftoverOptions) -> LiftoverOutput<R> {
+        let mut output = LiftoverOutput {
+            mapped: Vec::new(),
+            unmapped: Vec::new(),
+        };
+        for record in records {
+            match self.project(&record, options) {
+                Ok(lifted) => output.mapped.extend(lifted),
+                Err(reason) => output.unmapped.push(Unmapped { record, reason }),
+            }
+        }
+        output
+    }
+}
+
+/// Maps from the target to the query of a single chain
+impl CoordinateMap for Chain {
+    fn seq_len(&self, seqid: &SeqId) -> Option<u64> {
+        (*seqid == self.target_seqid).then_some(self.target_size)
+    }
+
+    fn map_pieces(&self, range: &GenomicRange) -> Vec<LiftedRange> {
+        if *range.seqid() != self.target_seqid {
+            return Vec::new();
+        }
+        let interval = range.range_0halfopen();
+        self.map_interval(interval.start, interval.end).into_iter()
+            .map(|piece| {
+                let (start, end) = self.to_forward(piece);
+                LiftedRange {
+                    range: GenomicRange::from_0halfopen(self.query_seqid.clone(), start..end).unwrap(),
+                    reversed: self.query_strand == Strand::Minus,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Lifts ranges from the target to the query assembly of a set of chains
+///
+/// Each range is lifted with the chain that aligns the most of its bases, with ties going
+/// to the highest scoring chain. PAF alignments can be used by converting them with
+/// [`paf_to_chains`](crate::paf::paf_to_chains).
+pub struct Liftover {
+    // chains for each target seqid, sorted by their start
+    chains: IndexMap<SeqId, Vec<Chain>>,
+}
+
+impl Liftover {
+    pub fn new(chains: Vec<Chain>) -> Liftover {
+        let mut by_seqid: IndexMap<SeqId, Vec<Chain>> = IndexMap::new();
+        for chain in chains {
+            by_seqid.entry(chain.target_seqid.clone()).or_default().push(chain);
+        }
+        for chains in by_seqid.values_mut() {
+            chains.sort_by_key(|c| c.target_start);
+        }
+        Liftover { chains: by_seqid }
+    }
+
+    /// Returns the aligned pieces of the best chain for the range, with the bases aligned by all chains
+    fn best_chain(&self, range: &GenomicRange) -> (Vec<LiftedRange>, u64) {
+        let interval = range.range_0halfopen();
+        let mut best: Option<&Chain> = None;
+        let mut pieces = Vec::new();
+        let mut aligned = 0;
+        let mut total_aligned = 0;
+        let Some(chains) = self.chains.get(range.seqid()) else {
+            return (pieces, total_aligned);
+        };
+        for chain in chains.iter().take_while(|c| c.target_start < interval.end) {
+            if chain.target_end <= interval.start {
+                continue;
+            }
+            let chain_pieces = chain.map_pieces(range);
+            let chain_aligned: u64 = chain_pieces.iter().map(|p| p.range.len()).sum();
+            total_aligned += chain_aligned;
+            let better = match best {
+                None => chain_aligned > 0,
+                Some(best) => chain_aligned > aligned || (chain_aligned == aligned && chain.score > best.score),
+            };
+            if better {
+                best = Some(chain);
+                pieces = chain_pieces;
+                aligned = chain_aligned;
+            }
+        }
+        (pieces, total_aligned)
+    }
+}
+
+impl CoordinateMap for Liftover {
+    fn map_pieces(&self, range: &GenomicRange) -> Vec<LiftedRange> {
+        self.best_chain(range).0
+    }
+
+    fn seq_len(&self, seqid: &SeqId) -> Option<u64> {
+        self.chains.get(seqid)?.first().map(|c| c.target_size)
+    }
+
+    /// As for other maps, except that a range is [`UnmappedReason::SplitInNew`] when the best chain
+    /// aligns too few bases, but all the chains together align enough
+    fn project_range(&self, range: &GenomicRange, options: &LiftoverOptions) -> Result<Vec<LiftedRange>, UnmappedReason> {
+        let Some(range) = within_bounds(self.seq_len(range.seqid()), range, options)? else {
+            return Ok(Vec::new());
+        };
+        let (pieces, total_aligned) = self.best_chain(&range);
+        let len = range.len();
+        let aligned: u64 = pieces.iter().map(|p| p.range.len()).sum();
+        if aligned > 0 && (aligned as f64) < options.min_match * len as f64 && (total_aligned as f64) >= options.min_match * len as f64 {
+            return Err(UnmappedReason::SplitInNew);
+        }
+        project_pieces(pieces, len, options)
+    }
+}
+
+/// Applies the overflow policy to a range on a sequence of length `seq_len`, returning `None` if it is dropped
+fn within_bounds(seq_len: Option<u64>, range: &GenomicRange, options: &LiftoverOptions) -> Result<Option<GenomicRange>, UnmappedReason> {
+    let interval = range.range_0halfopen();
+    options.overflow.apply(range.seqid().clone(), interval.start as i64, interval.end as i64, seq_len)
+        .map_err(|_| UnmappedReason::OutOfBoundsInOld)
+}
+
+/// Applies the options to the pieces of a range of length `len`
+pub(crate) fn project_pieces(mut pieces: Vec<LiftedRange>, len: u64, options: &LiftoverOptions) -> Result<Vec<LiftedRange>, UnmappedReason> {
+    if pieces.is_empty() {
+        return Err(UnmappedReason::DeletedInNew);
+    }
+    let aligned: u64 = pieces.iter().map(|p| p.range.len()).sum();
+    if (aligned as f64) < options.min_match * len as f64 {
+        return Err(UnmappedReason::PartiallyDeletedInNew);
+    }
+    let gapped = pieces.len() > 1 || aligned < len;
+    match options.gap_policy {
+        GapPolicy::Drop if gapped => Err(UnmappedReason::CrossesGapInNew),
+        GapPolicy::Split => {
+            pieces.sort_by(|a, b| a.range.cmp(&b.range));
+            Ok(pieces)
+        }
+        _ => {
+            let first = &pieces[0];
+            let mut span = first.range.clone();
+            for piece in &pieces[1..] {
+                if piece.reversed != first.reversed {
+                    return Err(UnmappedReason::SplitInNew);
+                }
+                span = span.combine(&piece.range).map_err(|_| UnmappedReason::SplitInNew)?;
+            }
+            Ok(vec![LiftedRange { range: span, reversed: first.reversed }])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::ParseMode;
+
+    const CHAINS: &str = "\
+chain 1000 chr1 1000 + 0 500 chrA 1000 + 100 550 1
+200\t50\t0
+250
+
+chain 500 chr1 1000 + 600 700 chrB 500 - 0 100 2
+100
+";
+
+    fn range(text: &str) -> GenomicRange {
+        text.parse().unwrap()
+    }
+
+    fn lift(range_text: &str, options: &LiftoverOptions) -> Result<Vec<String>, UnmappedReason> {
+        let liftover = Liftover::new(parse_chains(CHAINS).unwrap());
+        liftover.project_range(&range(range_text), options)
+            .map(|lifted| lifted.iter().map(|l| l.range.to_string()).collect())
+    }
+
+    #[test]
+    fn chains_round_trip() {
+        let chains = parse_chains(CHAINS).unwrap();
+        assert_eq!(chains[0].blocks[1], ChainBlock { target_start: 250, query_start: 300, size: 250 });
+        let text: String = chains.iter().map(|c| c.to_string()).collect();
+        assert_eq!(text, format!("{}\n", CHAINS));
+        assert!(parse_chains("chain 1 chr1 1000 + 0 500 chrA 1000 + 100 550 1\n200\n").is_err());
+    }
+
+    #[test]
+    fn lifts_within_a_block() {
+        assert_eq!(lift("chr1:11-20", &LiftoverOptions::default()), Ok(vec!["chrA:111-120".to_string()]));
+        assert_eq!(lift("chr1:601-610", &LiftoverOptions::default()), Ok(vec!["chrB:491-500".to_string()]));
+        assert_eq!(lift("chr1:551-560", &LiftoverOptions::default()), Err(UnmappedReason::DeletedInNew));
+    }
+
+    #[test]
+    fn gaps_follow_the_policy() {
+        assert_eq!(lift("chr1:191-260", &LiftoverOptions::default()), Err(UnmappedReason::PartiallyDeletedInNew));
+        let options = |gap_policy| LiftoverOptions { min_match: 0.2, gap_policy, ..LiftoverOptions::default() };
+        assert_eq!(lift("chr1:191-260", &options(GapPolicy::Span)), Ok(vec!["chrA:291-310".to_string()]));
+        assert_eq!(lift("chr1:191-260", &options(GapPolicy::Split)), Ok(vec!["chrA:291-300".to_string(), "chrA:301-310".to_string()]));
+        assert_eq!(lift("chr1:191-260", &options(GapPolicy::Drop)), Err(UnmappedReason::CrossesGapInNew));
+    }
+
+    #[test]
+    fn records_on_reversed_chains_flip_strand() {
+        let liftover = Liftover::new(parse_chains(CHAINS).unwrap());
+        let rows = [
+            Gff3Row::from_line("chr1\t.\tgene\t601\t610\t.\t+\t.\tID=g1", ParseMode::Strict).unwrap(),
+            Gff3Row::from_line("chr2\t.\tgene\t1\t10\t.\t+\t.\tID=g2", ParseMode::Strict).unwrap(),
+        ];
+        let output = liftover.project_records(rows, &LiftoverOptions::default());
+        assert_eq!((output.mapped[0].seqid.as_str(), output.mapped[0].start, output.mapped[0].strand), ("chrB", 491, Strand::Minus));
+        assert_eq!(output.unmapped[0].reason, UnmappedReason::DeletedInNew);
+        assert_eq!(output.unmapped[0].record.attributes["ID"], "g2");
+    }
+
+    #[test]
+    fn ranges_beyond_the_sequence_follow_the_overflow_policy() {
+        let liftover = Liftover::new(parse_chains("chain 100 chr1 100 + 50 100 chrA 100 + 0 50 1\n50\n").unwrap());
+        let lift = |overflow| {
+            let options = LiftoverOptions { overflow, ..LiftoverOptions::default() };
+            liftover.project_range(&range("chr1:91-110"), &options).map(|lifted| lifted.iter().map(|l| l.range.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(lift(OverflowPolicy::Error), Err(UnmappedReason::OutOfBoundsInOld));
+        assert_eq!(lift(OverflowPolicy::Clamp), Ok(vec!["chrA:41-50".to_string()]));
+        assert_eq!(lift(OverflowPolicy::Drop), Ok(vec![]));
+
+        let row = Gff3Row::from_line("chr1\t.\tgene\t91\t110\t.\t+\t.\tID=g1", ParseMode::Strict).unwrap();
+        let options = LiftoverOptions { overflow: OverflowPolicy::Drop, ..LiftoverOptions::default() };
+        let output = liftover.project_records([row], &options);
+        assert!(output.mapped.is_empty() && output.unmapped.is_empty());
+    }
+
+    #[test]
+    fn unmapped_file_gives_the_reason_for_each_record() {
+        let liftover = Liftover::new(parse_chains(CHAINS).unwrap());
+        let bed = |chrom: &str, chrom_start, chrom_end, name: &str| Bed6Row {
+            chrom: SeqId::from(chrom), chrom_start, chrom_end, name: name.to_string(), score: 0, strand: Strand::Plus,
+        };
+        let rows = [bed("chr1", 10, 20, "a"), bed("chr1", 550, 560, "b"), bed("chr1", 190, 260, "c")];
+        let output = liftover.project_records(rows, &LiftoverOptions::default());
+        assert_eq!(output.mapped.len(), 1);
+        let text = output.unmapped_file(|row| format!("{}\t{}\t{}\t{}", row.chrom, row.chrom_start, row.chrom_end, row.name));
+        assert_eq!(text, "#Deleted in new\nchr1\t550\t560\tb\n#Partially deleted in new\nchr1\t190\t260\tc\n");
+    }
+}
@@ -0,0 +1,304 @@
+//! Genome masks with one bit per base or per bin
+//!
+//! Masks such as mappability or blacklists cover large parts of a genome. A [`GenomeMask`]
+//! stores them as bits, for fast membership queries and logical combination, and converts
+//! to and from [`NormalizedRanges`]. Masks can also be made from score tracks such as
+//! mappability, and used to filter out or flag records in masked regions.
+
+use indexmap::IndexMap;
+use num_traits::{NumOps, ToPrimitive};
+
+use crate::format::{DataInterval, Gff3Row};
+use crate::genome::{Error, GenomicRange, NormalizedRanges, SeqId};
+
+/// The bits of one sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bits {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bits {
+    fn new(len: usize) -> Bits {
+        Bits {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        i < self.len && self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set_range(&mut self, start: usize, end: usize) {
+        for i in start..end.min(self.len) {
+            self.words[i / 64] |= 1 << (i % 64);
+        }
+    }
+
+    /// Clears any bits beyond the length in the last word
+    fn trim(&mut self) {
+        if self.len % 64 != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << (self.len % 64)) - 1;
+            }
+        }
+    }
+}
+
+/// A mask over the sequences of a genome, with one bit per bin of `bin_size` bases
+///
+/// A bin is set when any of its bases is covered. With a bin size of 1, the mask is exact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomeMask {
+    bin_size: u64,
+    seq_lens: IndexMap<SeqId, u64>,
+    bits: IndexMap<SeqId, Bits>,
+}
+
+impl GenomeMask {
+    /// Makes an empty mask over sequences of the given lengths
+    pub fn new(seq_lens: &IndexMap<SeqId, u64>, bin_size: u64) -> Result<GenomeMask, Error> {
+        if bin_size == 0 {
+            return Err(Error::InvalidArguments("bin size should be above 0".to_string()));
+        }
+        Ok(GenomeMask {
+            bin_size,
+            seq_lens: seq_lens.clone(),
+            bits: seq_lens.iter().map(|(seqid, len)| (seqid.clone(), Bits::new(len.div_ceil(bin_size) as usize))).collect(),
+        })
+    }
+
+    /// Makes a mask covering the ranges
+    pub fn from_ranges(seq_lens: &IndexMap<SeqId, u64>, bin_size: u64, ranges: &NormalizedRanges) -> Result<GenomeMask, Error> {
+        let mut mask = GenomeMask::new(seq_lens, bin_size)?;
+        for range in ranges {
+            mask.insert(range)?;
+        }
+        Ok(mask)
+    }
+
+    /// Makes a mask of the intervals whose value in `column` satisfies the predicate
+    ///
+    /// For example, a mask of low mappability is made from a mappability BedGraph by masking values
+    /// below a threshold. Where bases missing from the track are unmappable, mask the values at or above
+    /// the threshold and take the inverse with [`GenomeMask::not`]. Intervals with no value in the column are skipped.
+    pub fn from_intervals<T, I, F>(seq_lens: &IndexMap<SeqId, u64>, bin_size: u64, intervals: I, column: usize, predicate: F) -> Result<GenomeMask, Error>
+    where
+        T: NumOps + Copy + ToPrimitive,
+        I: IntoIterator<Item = DataInterval<T>>,
+        F: Fn(f64) -> bool,
+    {
+        let mut mask = GenomeMask::new(seq_lens, bin_size)?;
+        for interval in intervals {
+            let Some(value) = interval.values().get(column).copied().flatten() else {
+                continue;
+            };
+            let value = value.to_f64()
+                .ok_or_else(|| Error::InvalidArguments("score can't be converted to f64".to_string()))?;
+            if predicate(value) {
+                mask.insert(interval.range())?;
+            }
+        }
+        Ok(mask)
+    }
+
+    pub fn bin_size(&self) -> u64 {
+        self.bin_size
+    }
+
+    /// Sets the bins overlapping a range, which should be on a sequence of the mask
+    pub fn insert(&mut self, range: &GenomicRange) -> Result<(), Error> {
+        let bits = self.bits.get_mut(range.seqid())
+            .ok_or_else(|| Error::InvalidArguments(format!("sequence {} is not in the mask", range.seqid())))?;
+        let interval = range.range_0halfopen();
+        if interval.end > interval.start {
+            bits.set_range((interval.start / self.bin_size) as usize, interval.end.div_ceil(self.bin_size) as usize);
+        }
+        Ok(())
+    }
+
+    /// Whether the bin containing a 0-based position is set
+    pub fn contains(&self, seqid: &SeqId, pos: u64) -> bool {
+        self.bits.get(seqid).is_some_and(|bits| bits.get((pos / self.bin_size) as usize))
+    }
+
+    /// The fraction of the bases of a range that are in set bins, or 0 for an empty range
+    pub fn masked_fraction(&self, range: &GenomicRange) -> f64 {
+        let interval = range.range_0halfopen();
+        let Some(bits) = self.bits.get(range.seqid()) else {
+            return 0.0;
+        };
+        if interval.end <= interval.start {
+            return 0.0;
+        }
+        let first = interval.start / self.bin_size;
+        let last = interval.end.div_ceil(self.bin_size);
+        let masked: u64 = (first..last)
+            .filter(|bin| bits.get(*bin as usize))
+            .map(|bin| (interval.end.min((bin + 1) * self.bin_size)) - interval.start.max(bin * self.bin_size))
+            .sum();
+        masked as f64 / (interval.end - interval.start) as f64
+    }
+
+    /// The number of bases in the set bins, excluding the part of the last bin beyond the end of each sequence
+    pub fn covered_len(&self) -> u64 {
+        self.to_ranges().covered_len()
+    }
+
+    /// Converts the set bins to ranges, truncated at the end of each sequence
+    pub fn to_ranges(&self) -> NormalizedRanges {
+        let mut ranges = Vec::new();
+        for (seqid, bits) in &self.bits {
+            let seq_len = self.seq_lens[seqid];
+            let mut i = 0;
+            while i < bits.len {
+                if !bits.get(i) {
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < bits.len && bits.get(i) {
+                    i += 1;
+                }
+                let start = start as u64 * self.bin_size;
+                let end = (i as u64 * self.bin_size).min(seq_len);
+                ranges.push(GenomicRange::from_0halfopen(seqid.clone(), start..end).unwrap());
+            }
+        }
+        ranges.into_iter().collect()
+    }
+
+    fn check_compatible(&self, other: &GenomeMask) -> Result<(), Error> {
+        if self.bin_size != other.bin_size || !self.seq_lens.iter().eq(other.seq_lens.iter()) {
+            return Err(Error::InvalidArguments("masks should have the same bin size and sequences, in the same order".to_string()));
+        }
+        Ok(())
+    }
+
+    fn combine(&self, other: &GenomeMask, op: impl Fn(u64, u64) -> u64) -> Result<GenomeMask, Error> {
+        self.check_compatible(other)?;
+        let mut mask = self.clone();
+        for (bits, other) in mask.bits.values_mut().zip(other.bits.values()) {
+            for (word, other) in bits.words.iter_mut().zip(&other.words) {
+                *word = op(*word, *other);
+            }
+        }
+        Ok(mask)
+    }
+
+    /// The bins set in both masks, which should have the same bin size and sequences
+    pub fn and(&self, other: &GenomeMask) -> Result<GenomeMask, Error> {
+        self.combine(other, |a, b| a & b)
+    }
+
+    /// The bins set in either mask, which should have the same bin size and sequences
+    pub fn or(&self, other: &GenomeMask) -> Result<GenomeMask, Error> {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// The bins set in this mask but not the other, which should have the same bin size and sequences
+    pub fn and_not(&self, other: &GenomeMask) -> Result<GenomeMask, Error> {
+        self.combine(other, |a, b| a & !b)
+    }
+
+    /// The bins that aren't set
+    pub fn not(&self) -> GenomeMask {
+        let mut mask = self.clone();
+        for bits in mask.bits.values_mut() {
+            for word in &mut bits.words {
+                *word = !*word;
+            }
+            bits.trim();
+        }
+        mask
+    }
+}
+
+/// Records split by whether too much of them is masked
+#[derive(Debug, Clone)]
+pub struct MaskFilterOutput<R> {
+    pub kept: Vec<R>,
+    pub removed: Vec<R>,
+}
+
+/// Removes the records with more than `max_fraction` of their bases masked e.g. in low mappability regions
+pub fn filter_masked<R, F>(records: Vec<R>, range: F, mask: &GenomeMask, max_fraction: f64) -> MaskFilterOutput<R>
+where
+    F: Fn(&R) -> GenomicRange,
+{
+    let (removed, kept) = records.into_iter().partition(|record| mask.masked_fraction(&range(record)) > max_fraction);
+    MaskFilterOutput { kept, removed }
+}
+
+/// Flags the rows with more than `max_fraction` of their bases masked, setting the attribute to the masked fraction
+///
+/// Returns the number of rows flagged.
+pub fn flag_masked<T>(rows: &mut [Gff3Row<T>], mask: &GenomeMask, max_fraction: f64, attribute: &str) -> usize {
+    let mut flagged = 0;
+    for row in rows {
+        let fraction = mask.masked_fraction(&GenomicRange::from_gff_row(row));
+        if fraction > max_fraction {
+            row.attributes.insert(attribute.to_string(), format!("{:.3}", fraction));
+            flagged += 1;
+        }
+    }
+    flagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    fn seq_lens() -> IndexMap<SeqId, u64> {
+        IndexMap::from([(SeqId::from("chr1"), 150), (SeqId::from("chr2"), 70)])
+    }
+
+    fn mask(bin_size: u64, ranges: &[&str]) -> GenomeMask {
+        let ranges: NormalizedRanges = ranges.iter().map(|r| r.parse::<GenomicRange>().unwrap()).collect();
+        GenomeMask::from_ranges(&seq_lens(), bin_size, &ranges).unwrap()
+    }
+
+    fn strings(ranges: &NormalizedRanges) -> Vec<String> {
+        ranges.into_iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn bins_cover_any_overlapping_base() {
+        let exact = mask(1, &["chr1:11-20", "chr2:66-70"]);
+        assert_eq!(strings(&exact.to_ranges()), ["chr1:11-20", "chr2:66-70"]);
+        assert!(exact.contains(&SeqId::from("chr1"), 10) && !exact.contains(&SeqId::from("chr1"), 20));
+        assert_eq!(exact.masked_fraction(&"chr1:16-25".parse().unwrap()), 0.5);
+
+        let binned = mask(64, &["chr1:11-20", "chr2:66-70"]);
+        // the last bin of chr2 is truncated at its end
+        assert_eq!(strings(&binned.to_ranges()), ["chr1:1-64", "chr2:65-70"]);
+        assert_eq!(binned.covered_len(), 70);
+        assert!(GenomeMask::new(&seq_lens(), 0).is_err());
+        assert!(mask(1, &[]).insert(&"chr3:1-10".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn combines_masks() {
+        let a = mask(1, &["chr1:1-20"]);
+        let b = mask(1, &["chr1:11-30"]);
+        assert_eq!(strings(&a.and(&b).unwrap().to_ranges()), ["chr1:11-20"]);
+        assert_eq!(strings(&a.or(&b).unwrap().to_ranges()), ["chr1:1-30"]);
+        assert_eq!(strings(&a.and_not(&b).unwrap().to_ranges()), ["chr1:1-10"]);
+        assert_eq!(strings(&a.not().to_ranges()), ["chr1:21-150", "chr2:1-70"]);
+        assert!(a.and(&mask(10, &[])).is_err());
+    }
+
+    #[test]
+    fn filters_and_flags_masked_records() {
+        let mask = mask(1, &["chr1:1-20"]);
+        let ranges: Vec<GenomicRange> = ["chr1:1-10", "chr1:16-25", "chr1:31-40"].iter().map(|r| r.parse().unwrap()).collect();
+        let output = filter_masked(ranges, |r| r.clone(), &mask, 0.5);
+        assert_eq!((output.kept.len(), output.removed.len()), (2, 1));
+
+        let mut rows = parse_gff3("chr1\t.\tgene\t16\t25\t.\t+\t.\tID=g1\nchr1\t.\tgene\t31\t40\t.\t+\t.\tID=g2\n", ParseMode::Strict).unwrap();
+        assert_eq!(flag_masked(&mut rows, &mask, 0.1, "masked"), 1);
+        assert_eq!(rows[0].attributes.get("masked").map(String::as_str), Some("0.500"));
+    }
+}
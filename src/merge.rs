@@ -0,0 +1,230 @@
+//! Combining gene models from several evidence sources
+//!
+//! Gene models from different predictors or annotation runs often overlap. [`merge_gene_models`]
+//! keeps one model for each locus, choosing between overlapping models by the priority of their
+//! source or by their score, and reports the decision made for every model.
+
+use crate::format::Strand;
+use crate::genome::{GenomicRange, SeqId};
+use crate::models::Gene;
+
+/// Gene models from one source e.g. a gene predictor or a previous annotation
+#[derive(Debug, Clone)]
+pub struct EvidenceSource<T> {
+    pub name: String,
+    /// Models from sources with a higher priority are preferred
+    pub priority: u32,
+    pub genes: Vec<Gene<T>>,
+}
+
+/// How to choose between overlapping gene models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Prefer the source with the highest priority, then the highest score
+    Priority,
+    /// Prefer the highest score, then the source with the highest priority
+    Score,
+}
+
+/// Options for merging gene models
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    pub resolution: Resolution,
+    /// Whether models on opposite strands are allowed to overlap
+    pub strand_specific: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            resolution: Resolution::Priority,
+            strand_specific: true,
+        }
+    }
+}
+
+/// What happened to a gene model
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Kept, after rejecting the given number of overlapping models
+    Kept { rejected: usize },
+    /// Rejected in favour of an overlapping model
+    Rejected { source: String, gene_id: Option<String> },
+}
+
+/// The decision for one gene model
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub source: String,
+    pub gene_id: Option<String>,
+    pub range: GenomicRange,
+    pub outcome: Outcome,
+}
+
+/// The merged gene models, sorted by position, with the decision for every input model
+#[derive(Debug, Clone)]
+pub struct MergeOutput<T> {
+    pub genes: Vec<Gene<T>>,
+    pub decisions: Vec<Decision>,
+}
+
+struct Candidate<T> {
+    source: usize,
+    priority: u32,
+    score: f64,
+    seqid: SeqId,
+    strand: Strand,
+    start: u64,
+    end: u64,
+    gene: Gene<T>,
+}
+
+/// The score of the gene row, or else the highest transcript score, where `.` is the lowest score
+fn gene_score<T>(gene: &Gene<T>) -> f64 {
+    gene.row.score.parse::<f64>().ok()
+        .or_else(|| gene.transcripts.iter().filter_map(|t| t.row.score.parse::<f64>().ok()).reduce(f64::max))
+        .unwrap_or(f64::NEG_INFINITY)
+}
+
+/// Merges gene models from several sources, resolving overlapping models
+///
+/// Models overlap when their gene ranges overlap on the same sequence (and strand, if strand specific).
+/// Overlapping models are clustered, and within each cluster models are kept in order of preference
+/// unless they overlap a model that has already been kept. Ties go to the source listed first.
+pub fn merge_gene_models<T>(sources: Vec<EvidenceSource<T>>, options: &MergeOptions) -> MergeOutput<T> {
+    let mut names = Vec::new();
+    let mut candidates = Vec::new();
+    for (i, source) in sources.into_iter().enumerate() {
+        names.push(source.name);
+        for gene in source.genes {
+            let range = gene.range().range_0halfopen();
+            candidates.push(Candidate {
+                source: i,
+                priority: source.priority,
+                score: gene_score(&gene),
+                seqid: gene.row.seqid.clone(),
+                strand: if options.strand_specific { gene.strand() } else { Strand::None },
+                start: range.start,
+                end: range.end,
+                gene,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| (&a.seqid, a.strand as u8, a.start).cmp(&(&b.seqid, b.strand as u8, b.start)));
+
+    let mut output = MergeOutput {
+        genes: Vec::new(),
+        decisions: Vec::new(),
+    };
+    let mut cluster: Vec<Candidate<T>> = Vec::new();
+    let mut cluster_end = 0;
+    for candidate in candidates {
+        let overlaps = cluster.last().is_some_and(|last| last.seqid == candidate.seqid && last.strand == candidate.strand && candidate.start < cluster_end);
+        if !overlaps {
+            resolve_cluster(std::mem::take(&mut cluster), &names, options, &mut output);
+            cluster_end = 0;
+        }
+        cluster_end = cluster_end.max(candidate.end);
+        cluster.push(candidate);
+    }
+    resolve_cluster(cluster, &names, options, &mut output);
+
+    output.genes.sort_by_key(|gene| gene.range());
+    output
+}
+
+fn resolve_cluster<T>(mut cluster: Vec<Candidate<T>>, names: &[String], options: &MergeOptions, output: &mut MergeOutput<T>) {
+    cluster.sort_by(|a, b| {
+        let by_priority = b.priority.cmp(&a.priority);
+        let by_score = b.score.total_cmp(&a.score);
+        match options.resolution {
+            Resolution::Priority => by_priority.then(by_score),
+            Resolution::Score => by_score.then(by_priority),
+        }
+        .then(a.source.cmp(&b.source))
+    });
+
+    // decisions of the kept models, with their ranges
+    let mut kept: Vec<(usize, u64, u64)> = Vec::new();
+    let mut decisions: Vec<Decision> = Vec::new();
+    let mut genes = Vec::new();
+    for candidate in cluster {
+        let decision = Decision {
+            source: names[candidate.source].clone(),
+            gene_id: candidate.gene.id().map(|id| id.to_string()),
+            range: candidate.gene.range(),
+            outcome: Outcome::Kept { rejected: 0 },
+        };
+        let conflict = kept.iter().find(|(_, start, end)| candidate.start < *end && *start < candidate.end);
+        match conflict {
+            Some(&(winner, _, _)) => {
+                if let Outcome::Kept { rejected } = &mut decisions[winner].outcome {
+                    *rejected += 1;
+                }
+                decisions.push(Decision {
+                    outcome: Outcome::Rejected {
+                        source: decisions[winner].source.clone(),
+                        gene_id: decisions[winner].gene_id.clone(),
+                    },
+                    ..decision
+                });
+            }
+            None => {
+                kept.push((decisions.len(), candidate.start, candidate.end));
+                decisions.push(decision);
+                genes.push(candidate.gene);
+            }
+        }
+    }
+    output.genes.extend(genes);
+    output.decisions.extend(decisions);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    fn sources() -> Vec<EvidenceSource<String>> {
+        let source = |name: &str, priority, gff: &str| EvidenceSource {
+            name: name.to_string(),
+            priority,
+            genes: build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap(),
+        };
+        vec![
+            source("a", 2, "\
+chr1\t.\tgene\t101\t200\t5\t+\t.\tID=a1
+chr1\t.\tgene\t1001\t1100\t.\t-\t.\tID=a2
+"),
+            source("b", 1, "\
+chr1\t.\tgene\t151\t300\t10\t+\t.\tID=b1
+chr1\t.\tgene\t401\t500\t.\t+\t.\tID=b2
+chr1\t.\tgene\t1051\t1150\t.\t+\t.\tID=b3
+"),
+        ]
+    }
+
+    fn outcomes(output: &MergeOutput<String>) -> Vec<(&str, &Outcome)> {
+        output.decisions.iter().map(|d| (d.gene_id.as_deref().unwrap(), &d.outcome)).collect()
+    }
+
+    #[test]
+    fn prefers_sources_by_priority() {
+        let output = merge_gene_models(sources(), &MergeOptions::default());
+        let ids: Vec<_> = output.genes.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["a1", "b2", "a2", "b3"]);
+        let rejected_b1 = Outcome::Rejected { source: "a".to_string(), gene_id: Some("a1".to_string()) };
+        assert!(outcomes(&output).contains(&("b1", &rejected_b1)));
+        assert!(outcomes(&output).contains(&("a1", &Outcome::Kept { rejected: 1 })));
+    }
+
+    #[test]
+    fn prefers_genes_by_score_and_ignores_strand() {
+        let options = MergeOptions { resolution: Resolution::Score, strand_specific: false };
+        let output = merge_gene_models(sources(), &options);
+        let ids: Vec<_> = output.genes.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["b1", "b2", "a2"]);
+        assert_eq!(output.decisions.len(), 5);
+    }
+}
@@ -0,0 +1,260 @@
+//! Gene models built from the feature hierarchy of GFF3 rows
+//!
+//! Rows are linked by their `ID` and `Parent` attributes into [`Gene`]s, each with one or more
+//! [`Transcript`]s holding their exons, CDS segments and other parts such as UTRs.
+//! Feature types are compared with the GFF3 (Sequence Ontology) names e.g. `exon` and `CDS`.
+
+use indexmap::IndexMap;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{Error, GenomicRange};
+use crate::sequence::{reverse_complement, Genome};
+
+/// A gene with its transcripts
+#[derive(Debug, Clone)]
+pub struct Gene<T> {
+    pub row: Gff3Row<T>,
+    pub transcripts: Vec<Transcript<T>>,
+}
+
+/// A transcript with its parts, each sorted by position
+#[derive(Debug, Clone)]
+pub struct Transcript<T> {
+    pub row: Gff3Row<T>,
+    pub exons: Vec<Gff3Row<T>>,
+    pub cds: Vec<Gff3Row<T>>,
+    /// Any other children e.g. UTRs, start and stop codons
+    pub others: Vec<Gff3Row<T>>,
+}
+
+impl<T> Gene<T> {
+    pub fn id(&self) -> Option<&str> {
+        self.row.attributes.get("ID").map(|id| id.as_str())
+    }
+
+    pub fn range(&self) -> GenomicRange {
+        GenomicRange::from_gff_row(&self.row)
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.row.strand
+    }
+}
+
+impl<T> Transcript<T> {
+    pub fn id(&self) -> Option<&str> {
+        self.row.attributes.get("ID").map(|id| id.as_str())
+    }
+
+    pub fn range(&self) -> GenomicRange {
+        GenomicRange::from_gff_row(&self.row)
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.row.strand
+    }
+
+    /// The exons, or the CDS segments if there are no exons, as is common for prokaryotic annotation
+    pub fn exon_ranges(&self) -> Vec<GenomicRange> {
+        let rows = if self.exons.is_empty() { &self.cds } else { &self.exons };
+        rows.iter().map(GenomicRange::from_gff_row).collect()
+    }
+
+    pub fn cds_ranges(&self) -> Vec<GenomicRange> {
+        self.cds.iter().map(GenomicRange::from_gff_row).collect()
+    }
+
+    /// The ranges between consecutive exons
+    pub fn introns(&self) -> Vec<GenomicRange> {
+        self.exon_ranges()
+            .windows(2)
+            .filter(|w| w[0].range_0halfopen().end < w[1].range_0halfopen().start)
+            .map(|w| GenomicRange::from_0halfopen(w[0].seqid().clone(), w[0].range_0halfopen().end..w[1].range_0halfopen().start).unwrap())
+            .collect()
+    }
+
+    pub fn is_coding(&self) -> bool {
+        !self.cds.is_empty()
+    }
+
+    /// The spliced length of the exons
+    pub fn exonic_len(&self) -> u64 {
+        self.exon_ranges().iter().map(|r| r.range_0halfopen().end - r.range_0halfopen().start).sum()
+    }
+
+    /// The spliced length of the CDS
+    pub fn cds_len(&self) -> u64 {
+        self.cds_ranges().iter().map(|r| r.range_0halfopen().end - r.range_0halfopen().start).sum()
+    }
+
+    /// The lengths of the 5' and 3' UTRs i.e. the exonic bases before and after the CDS
+    ///
+    /// Returns `None` for non-coding transcripts.
+    pub fn utr_lens(&self) -> Option<(u64, u64)> {
+        let cds = self.cds_ranges();
+        let cds_start = cds.iter().map(|r| r.range_0halfopen().start).min()?;
+        let cds_end = cds.iter().map(|r| r.range_0halfopen().end).max()?;
+        let (mut left, mut right) = (0, 0);
+        for exon in self.exon_ranges() {
+            let exon = exon.range_0halfopen();
+            left += exon.end.min(cds_start).saturating_sub(exon.start);
+            right += exon.end.saturating_sub(exon.start.max(cds_end));
+        }
+        Some(match self.strand() {
+            Strand::Minus => (right, left),
+            _ => (left, right),
+        })
+    }
+}
+
+impl<T> Transcript<T> {
+    /// The spliced sequence of the ranges, in the direction of transcription
+    fn spliced(&self, ranges: &[GenomicRange], genome: &Genome) -> Result<Vec<u8>, Error> {
+        let mut sequence = Vec::new();
+        for range in ranges {
+            sequence.extend_from_slice(genome.fetch(range)?);
+        }
+        if self.strand() == Strand::Minus {
+            sequence = reverse_complement(&sequence);
+        }
+        Ok(sequence)
+    }
+
+    /// The spliced sequence of the exons, in the direction of transcription
+    pub fn sequence(&self, genome: &Genome) -> Result<Vec<u8>, Error> {
+        self.spliced(&self.exon_ranges(), genome)
+    }
+
+    /// The spliced sequence of the CDS, in the direction of transcription
+    ///
+    /// Bases before the first complete codon are removed, according to the phase of the first CDS segment,
+    /// so that the sequence is in frame for a 5' partial transcript.
+    pub fn cds_sequence(&self, genome: &Genome) -> Result<Vec<u8>, Error> {
+        let first = match self.strand() {
+            Strand::Minus => self.cds.last(),
+            _ => self.cds.first(),
+        };
+        let phase = first.and_then(|row| row.phase.parse::<usize>().ok()).unwrap_or(0);
+        let sequence = self.spliced(&self.cds_ranges(), genome)?;
+        Ok(sequence.get(phase..).unwrap_or_default().to_vec())
+    }
+}
+
+/// Builds gene models from GFF3 rows
+///
+/// Rows without a `Parent` become genes, their children become transcripts, and the children
+/// of transcripts are assigned to the exons, CDS segments or other parts. Rows with several
+/// parents, such as shared exons, are added to each of them. Genes are in the order of the input.
+/// Rows without a `Parent` or children are only genes if their type ends in `gene` e.g. `pseudogene`,
+/// so that rows such as the `region` rows of RefSeq annotation are left out.
+///
+/// Transcripts without an `ID` are ignored. Fails if the parent of a row is neither a gene nor a transcript.
+pub fn build_gene_models<T, I>(rows: I) -> Result<Vec<Gene<T>>, Error>
+where
+    T: AsRef<str> + Clone,
+    I: IntoIterator<Item = Gff3Row<T>>,
+{
+    let mut genes: IndexMap<String, Gene<T>> = IndexMap::new();
+    let mut children: Vec<(String, Gff3Row<T>)> = Vec::new();
+    for row in rows {
+        match (row.attributes.get("Parent"), row.attributes.get("ID")) {
+            (None, Some(id)) => {
+                genes.insert(id.clone(), Gene { row, transcripts: Vec::new() });
+            }
+            (Some(parents), _) => {
+                for parent in parents.clone().split(',') {
+                    children.push((parent.to_string(), row.clone()));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    // transcript id to the ids of its genes
+    let mut transcript_genes: IndexMap<String, Vec<String>> = IndexMap::new();
+    let mut transcripts: IndexMap<String, Transcript<T>> = IndexMap::new();
+    let mut parts: Vec<(String, Gff3Row<T>)> = Vec::new();
+    for (parent, row) in children {
+        if genes.contains_key(&parent) {
+            if let Some(id) = row.attributes.get("ID").cloned() {
+                transcript_genes.entry(id.clone()).or_default().push(parent);
+                transcripts.entry(id).or_insert(Transcript {
+                    row,
+                    exons: Vec::new(),
+                    cds: Vec::new(),
+                    others: Vec::new(),
+                });
+            }
+        } else {
+            parts.push((parent, row));
+        }
+    }
+
+    for (parent, row) in parts {
+        let transcript = transcripts.get_mut(&parent)
+            .ok_or_else(|| Error::InvalidArguments(format!("parent {} of {} feature is not a transcript", parent, row.feature_type.as_ref())))?;
+        match row.feature_type.as_ref() {
+            "exon" => transcript.exons.push(row),
+            "CDS" => transcript.cds.push(row),
+            _ => transcript.others.push(row),
+        }
+    }
+
+    for (id, mut transcript) in transcripts {
+        transcript.exons.sort_by_key(|r| r.start);
+        transcript.cds.sort_by_key(|r| r.start);
+        transcript.others.sort_by_key(|r| r.start);
+        let (last, others) = transcript_genes[&id].split_last().unwrap();
+        for gene in others {
+            genes.get_mut(gene).unwrap().transcripts.push(transcript.clone());
+        }
+        genes.get_mut(last).unwrap().transcripts.push(transcript);
+    }
+    genes.retain(|_, gene| !gene.transcripts.is_empty() || gene.row.feature_type.as_ref().ends_with("gene"));
+    Ok(genes.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    #[test]
+    fn rows_with_several_parents_are_added_to_each() {
+        let gff = "\
+chr1\t.\tgene\t1\t900\t.\t+\t.\tID=g1
+chr1\t.\tgene\t1\t900\t.\t+\t.\tID=g2
+chr1\t.\tmRNA\t1\t900\t.\t+\t.\tID=t1;Parent=g1,g2
+chr1\t.\tmRNA\t1\t500\t.\t+\t.\tID=t2;Parent=g1
+chr1\t.\texon\t1\t500\t.\t+\t.\tParent=t1,t2
+chr1\t.\texon\t700\t900\t.\t+\t.\tParent=t1
+chr1\t.\tCDS\t1\t300\t.\t+\t0\tParent=t2
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let summary: Vec<Vec<_>> = genes.iter()
+            .map(|g| g.transcripts.iter().map(|t| (t.id().unwrap(), t.exons.len(), t.cds.len())).collect())
+            .collect();
+        assert_eq!(summary, [vec![("t1", 2, 0), ("t2", 1, 1)], vec![("t1", 2, 0)]]);
+        assert_eq!(genes[1].transcripts[0].exonic_len(), 701);
+    }
+
+    #[test]
+    fn parts_of_unknown_parents_are_an_error() {
+        let gff = "chr1\t.\texon\t1\t500\t.\t+\t.\tParent=t1\n";
+        assert!(build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).is_err());
+    }
+
+    #[test]
+    fn region_rows_are_not_genes() {
+        let gff = "\
+NC_000001.11\tRefSeq\tregion\t1\t248956422\t.\t+\t.\tID=NC_000001.11:1..248956422;chromosome=1
+NC_000001.11\tBestRefSeq\tgene\t11874\t14409\t.\t+\t.\tID=gene-DDX11L1
+NC_000001.11\tBestRefSeq\ttranscript\t11874\t14409\t.\t+\t.\tID=rna-NR_046018.2;Parent=gene-DDX11L1
+NC_000001.11\tBestRefSeq\texon\t11874\t12227\t.\t+\t.\tParent=rna-NR_046018.2
+NC_000001.11\tCurated Genomic\tpseudogene\t14696\t24886\t.\t-\t.\tID=gene-WASH7P
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let ids: Vec<_> = genes.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["gene-DDX11L1", "gene-WASH7P"]);
+    }
+}
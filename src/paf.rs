@@ -0,0 +1,192 @@
+//! Pairwise alignments in PAF format, as written by minimap2
+//!
+//! Alignments with a CIGAR string (the `cg` tag, from `minimap2 -c`) can be converted into
+//! UCSC [`Chain`]s, so that an assembly-to-assembly alignment can be used for liftover.
+//! The PAF target (the reference given to minimap2) becomes the chain target, i.e. the assembly
+//! that is lifted from.
+
+use indexmap::IndexMap;
+
+use crate::format::Strand;
+use crate::genome::{Error, SeqId};
+use crate::liftover::{Chain, ChainBlock};
+
+/// The standard fields of PAF, with optional tags
+///
+/// Coordinates are 0-based and half-open. The query coordinates are always on the forward strand.
+/// Tags are keyed by their name, and their values exclude the type e.g. `cg:Z:10M` is stored as `10M`.
+#[derive(Debug, Clone)]
+pub struct PafRecord {
+    pub query_name: SeqId,
+    pub query_len: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub strand: Strand,
+    pub target_name: SeqId,
+    pub target_len: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub matches: u64,
+    pub block_len: u64,
+    pub mapq: u8,
+    pub tags: IndexMap<String, String>,
+}
+
+impl TryFrom<&str> for PafRecord {
+    type Error = Error;
+
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() < 12 {
+            return Err(Error::InvalidArguments(format!("PAF lines should have at least 12 fields, not {}", fields.len())));
+        }
+        let num = |i: usize| fields[i].parse::<u64>()
+            .map_err(|_| Error::InvalidArguments(format!("PAF field {} should be an unsigned integer, not {}", i + 1, fields[i])));
+        let strand = match fields[4] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            s => return Err(Error::InvalidArguments(format!("invalid PAF strand {}", s))),
+        };
+        let mut tags = IndexMap::new();
+        for tag in &fields[12..] {
+            let mut iter = tag.splitn(3, ':');
+            if let (Some(name), Some(_), Some(value)) = (iter.next(), iter.next(), iter.next()) {
+                tags.insert(name.to_string(), value.to_string());
+            }
+        }
+        Ok(PafRecord {
+            query_name: SeqId::from(fields[0]),
+            query_len: num(1)?,
+            query_start: num(2)?,
+            query_end: num(3)?,
+            strand,
+            target_name: SeqId::from(fields[5]),
+            target_len: num(6)?,
+            target_start: num(7)?,
+            target_end: num(8)?,
+            matches: num(9)?,
+            block_len: num(10)?,
+            mapq: fields[11].parse::<u8>()
+                .map_err(|_| Error::InvalidArguments(format!("PAF mapping quality should be 0-255, not {}", fields[11])))?,
+            tags,
+        })
+    }
+}
+
+impl PafRecord {
+    /// Converts the alignment into a chain with the given id
+    ///
+    /// The `cg` tag is required. The score is taken from the `AS` tag when present, and is
+    /// otherwise the number of matching bases.
+    pub fn to_chain(&self, id: u64) -> Result<Chain, Error> {
+        let cigar = self.tags.get("cg")
+            .ok_or_else(|| Error::InvalidArguments(format!("alignment of {} to {} has no cg tag", self.query_name, self.target_name)))?;
+        // on the minus strand, the query is reverse complemented and chain coordinates count from its end
+        let (query_start, query_end) = match self.strand {
+            Strand::Minus => (self.query_len - self.query_end, self.query_len - self.query_start),
+            _ => (self.query_start, self.query_end),
+        };
+        let mut blocks: Vec<ChainBlock> = Vec::new();
+        let mut target_pos = self.target_start;
+        let mut query_pos = query_start;
+        for (len, op) in parse_cigar(cigar)? {
+            match op {
+                'M' | '=' | 'X' => {
+                    match blocks.last_mut() {
+                        Some(last) if last.target_start + last.size == target_pos && last.query_start + last.size == query_pos => last.size += len,
+                        _ => blocks.push(ChainBlock { target_start: target_pos, query_start: query_pos, size: len }),
+                    }
+                    target_pos += len;
+                    query_pos += len;
+                }
+                'I' => query_pos += len,
+                'D' | 'N' => target_pos += len,
+                _ => return Err(Error::InvalidArguments(format!("unsupported CIGAR operation {} in cg tag", op))),
+            }
+        }
+        if target_pos != self.target_end || query_pos != query_end {
+            return Err(Error::InvalidArguments(format!("cg tag of alignment of {} to {} doesn't match its coordinates", self.query_name, self.target_name)));
+        }
+        let score = match self.tags.get("AS") {
+            Some(score) => score.parse::<f64>().map_err(|_| Error::InvalidArguments(format!("invalid AS tag {}", score)))?,
+            None => self.matches as f64,
+        };
+        Ok(Chain {
+            score,
+            target_seqid: self.target_name.clone(),
+            target_size: self.target_len,
+            target_start: self.target_start,
+            target_end: self.target_end,
+            query_seqid: self.query_name.clone(),
+            query_size: self.query_len,
+            query_strand: self.strand,
+            query_start,
+            query_end,
+            id,
+            blocks,
+        })
+    }
+}
+
+pub(crate) fn parse_cigar(cigar: &str) -> Result<Vec<(u64, char)>, Error> {
+    let mut ops = Vec::new();
+    let mut len_start = 0;
+    for (i, c) in cigar.char_indices() {
+        if !c.is_ascii_digit() {
+            let len = cigar[len_start..i].parse::<u64>()
+                .map_err(|_| Error::InvalidArguments(format!("invalid CIGAR string {}", cigar)))?;
+            ops.push((len, c));
+            len_start = i + c.len_utf8();
+        }
+    }
+    if len_start != cigar.len() {
+        return Err(Error::InvalidArguments(format!("CIGAR string {} should end with an operation", cigar)));
+    }
+    Ok(ops)
+}
+
+/// Converts the alignments in the text of a PAF file into chains, numbered from 1
+///
+/// The chains can be written in UCSC chain format with [`Display`](std::fmt::Display).
+pub fn paf_to_chains(text: &str) -> Result<Vec<Chain>, Error> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| PafRecord::try_from(line)?.to_chain(i as u64 + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAF: &str = "q1\t100\t10\t32\t+\tt1\t200\t50\t73\t20\t25\t60\tcg:Z:10M2I5M3D5M\n\
+        q1\t100\t10\t32\t-\tt1\t200\t50\t73\t20\t25\t60\tcg:Z:10M2I5M3D5M\tAS:i:42\n";
+
+    fn blocks(chain: &Chain) -> Vec<(u64, u64, u64)> {
+        chain.blocks.iter().map(|b| (b.target_start, b.query_start, b.size)).collect()
+    }
+
+    #[test]
+    fn alignments_become_chains() {
+        let chains = paf_to_chains(PAF).unwrap();
+        assert_eq!(chains.len(), 2);
+        assert_eq!((chains[0].id, chains[0].score), (1, 20.0));
+        assert_eq!(blocks(&chains[0]), [(50, 10, 10), (60, 22, 5), (68, 27, 5)]);
+
+        // minus strand query coordinates count from the end of the query
+        assert_eq!((chains[1].query_strand, chains[1].query_start, chains[1].query_end), (Strand::Minus, 68, 90));
+        assert_eq!(chains[1].score, 42.0);
+        assert_eq!(blocks(&chains[1]), [(50, 68, 10), (60, 80, 5), (68, 85, 5)]);
+    }
+
+    #[test]
+    fn cigars_must_match_the_coordinates() {
+        let record = PafRecord::try_from("q1\t100\t10\t32\t+\tt1\t200\t50\t73\t20\t25\t60\tcg:Z:10M").unwrap();
+        assert!(record.to_chain(1).is_err());
+        let record = PafRecord::try_from("q1\t100\t10\t32\t+\tt1\t200\t50\t73\t20\t25\t60").unwrap();
+        assert!(record.to_chain(1).is_err());
+        assert!(PafRecord::try_from("q1\t100\t10\t32\t+\tt1").is_err());
+        assert!(parse_cigar("10M5").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Helpers for compact, prokaryotic genomes
+//!
+//! Genes in bacterial and archaeal genomes are densely packed, so analyses of operons,
+//! intergenic regions and promoters need to account for the neighbouring genes.
+//! Sequence lengths are needed for genes on the minus strand near the end of a sequence.
+
+use indexmap::IndexMap;
+
+use crate::format::Strand;
+use crate::genome::{Error, GenomicRange, OverflowPolicy, SeqId};
+use crate::models::Gene;
+
+/// Groups genes into predicted operons: runs of adjacent genes on the same strand separated by at most `max_gap` bases
+///
+/// Genes that overlap are always grouped. An operon is broken by a gene on the other strand.
+/// Operons are returned in order of position, each with the indexes of its genes in order of position.
+pub fn predict_operons<T>(genes: &[Gene<T>], max_gap: u64) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..genes.len()).collect();
+    order.sort_by_key(|&i| genes[i].range());
+    let mut operons: Vec<Vec<usize>> = Vec::new();
+    let mut operon_end = 0;
+    for i in order {
+        let gene = &genes[i];
+        let range = gene.range().range_0halfopen();
+        let joins = operons.last().is_some_and(|operon| {
+            let last = &genes[*operon.last().unwrap()];
+            last.row.seqid == gene.row.seqid
+                && last.strand() == gene.strand()
+                && range.start <= operon_end + max_gap
+        });
+        if joins {
+            operons.last_mut().unwrap().push(i);
+            operon_end = operon_end.max(range.end);
+        } else {
+            operons.push(vec![i]);
+            operon_end = range.end;
+        }
+    }
+    operons
+}
+
+/// For each gene, the nearest boundary of another gene on its 5' side, in 0-based coordinates
+///
+/// For a gene on the minus strand, the boundary is the start of the nearest gene beyond its end,
+/// and otherwise it is the end of the nearest gene before its start. The boundary is `None` when
+/// there is no such gene, and may be within the gene when genes overlap.
+fn upstream_boundaries<T>(genes: &[Gene<T>]) -> Vec<Option<u64>> {
+    let mut by_seqid: IndexMap<&SeqId, Vec<usize>> = IndexMap::new();
+    for (i, gene) in genes.iter().enumerate() {
+        by_seqid.entry(&gene.row.seqid).or_default().push(i);
+    }
+    let ranges: Vec<_> = genes.iter().map(|g| g.range().range_0halfopen()).collect();
+    let mut boundaries = vec![None; genes.len()];
+    for indexes in by_seqid.values() {
+        let mut by_start = indexes.clone();
+        by_start.sort_by_key(|&i| ranges[i].start);
+        // the maximum end of the genes starting before each gene
+        let mut max_end: Option<u64> = None;
+        let mut k = 0;
+        while k < by_start.len() {
+            let start = ranges[by_start[k]].start;
+            let tied = by_start[k..].iter().take_while(|&&i| ranges[i].start == start).count();
+            for &i in &by_start[k..k + tied] {
+                if genes[i].strand() != Strand::Minus {
+                    boundaries[i] = max_end;
+                }
+            }
+            for &i in &by_start[k..k + tied] {
+                max_end = max_end.max(Some(ranges[i].end));
+            }
+            k += tied;
+        }
+
+        let mut by_end = indexes.clone();
+        by_end.sort_by_key(|&i| std::cmp::Reverse(ranges[i].end));
+        // the minimum start of the genes ending after each gene
+        let mut min_start: Option<u64> = None;
+        let mut k = 0;
+        while k < by_end.len() {
+            let end = ranges[by_end[k]].end;
+            let tied = by_end[k..].iter().take_while(|&&i| ranges[i].end == end).count();
+            for &i in &by_end[k..k + tied] {
+                if genes[i].strand() == Strand::Minus {
+                    boundaries[i] = min_start;
+                }
+            }
+            for &i in &by_end[k..k + tied] {
+                min_start = Some(min_start.map_or(ranges[i].start, |s| s.min(ranges[i].start)));
+            }
+            k += tied;
+        }
+    }
+    boundaries
+}
+
+fn seq_len(seq_lens: &IndexMap<SeqId, u64>, seqid: &SeqId) -> Result<u64, Error> {
+    seq_lens.get(seqid).copied()
+        .ok_or_else(|| Error::InvalidArguments(format!("no length for sequence {}", seqid)))
+}
+
+/// Returns the intergenic region upstream of each gene, up to the nearest gene or the end of the sequence
+///
+/// The region is `None` when the gene overlaps, or directly abuts, its upstream neighbour.
+pub fn upstream_intergenic<T>(genes: &[Gene<T>], seq_lens: &IndexMap<SeqId, u64>) -> Result<Vec<Option<GenomicRange>>, Error> {
+    let boundaries = upstream_boundaries(genes);
+    genes.iter().zip(boundaries)
+        .map(|(gene, boundary)| {
+            let range = gene.range().range_0halfopen();
+            let (start, end) = if gene.strand() == Strand::Minus {
+                (range.end, boundary.map_or_else(|| seq_len(seq_lens, &gene.row.seqid), Ok)?)
+            } else {
+                (boundary.unwrap_or(0), range.start)
+            };
+            Ok((start < end).then(|| GenomicRange::from_0halfopen(gene.row.seqid.clone(), start..end).unwrap()))
+        })
+        .collect()
+}
+
+/// Returns the promoter range of each gene, from `upstream` bases before to `downstream` bases after its 5' end
+///
+/// The upstream part is truncated at the nearest gene upstream, so promoters don't extend into the
+/// previous gene. Promoters beyond the bounds of the sequence are handled by the overflow policy,
+/// and are `None` if dropped.
+pub fn promoters<T>(genes: &[Gene<T>], upstream: u64, downstream: u64, seq_lens: &IndexMap<SeqId, u64>, policy: OverflowPolicy) -> Result<Vec<Option<GenomicRange>>, Error> {
+    let boundaries = upstream_boundaries(genes);
+    genes.iter().zip(boundaries)
+        .map(|(gene, boundary)| {
+            let len = seq_len(seq_lens, &gene.row.seqid)?;
+            let range = gene.range().range_0halfopen();
+            let (start, end) = if gene.strand() == Strand::Minus {
+                let tss = range.end as i64;
+                let end = tss + upstream as i64;
+                (tss - downstream as i64, boundary.map_or(end, |b| end.min(b.max(range.end) as i64)))
+            } else {
+                let tss = range.start as i64;
+                let start = tss - upstream as i64;
+                (boundary.map_or(start, |b| start.max(b.min(range.start) as i64)), tss + downstream as i64)
+            };
+            policy.apply(gene.row.seqid.clone(), start, end, Some(len))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    fn genes() -> Vec<Gene<String>> {
+        let gff = "\
+chr1\t.\tgene\t101\t200\t.\t+\t.\tID=g1
+chr1\t.\tgene\t211\t300\t.\t+\t.\tID=g2
+chr1\t.\tgene\t401\t500\t.\t-\t.\tID=g3
+chr1\t.\tgene\t951\t990\t.\t-\t.\tID=g4
+";
+        build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap()
+    }
+
+    fn strings(ranges: Vec<Option<GenomicRange>>) -> Vec<Option<String>> {
+        ranges.into_iter().map(|r| r.map(|r| r.to_string())).collect()
+    }
+
+    #[test]
+    fn groups_close_genes_into_operons() {
+        assert_eq!(predict_operons(&genes(), 20), [vec![0, 1], vec![2], vec![3]]);
+        assert_eq!(predict_operons(&genes(), 5), [vec![0], vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn upstream_regions_stop_at_neighbouring_genes() {
+        let seq_lens = IndexMap::from([(SeqId::from("chr1"), 1000)]);
+        let regions = upstream_intergenic(&genes(), &seq_lens).unwrap();
+        assert_eq!(strings(regions), [Some("chr1:1-100"), Some("chr1:201-210"), Some("chr1:501-950"), Some("chr1:991-1000")].map(|r| r.map(String::from)));
+        assert!(upstream_intergenic(&genes(), &IndexMap::new()).is_err());
+
+        let promoters_with = |policy| promoters(&genes(), 50, 10, &seq_lens, policy);
+        let clamped = promoters_with(OverflowPolicy::Clamp).unwrap();
+        assert_eq!(strings(clamped), [Some("chr1:51-110"), Some("chr1:201-220"), Some("chr1:491-550"), Some("chr1:981-1000")].map(|r| r.map(String::from)));
+        assert_eq!(promoters_with(OverflowPolicy::Drop).unwrap()[3], None);
+        assert!(promoters_with(OverflowPolicy::Error).is_err());
+    }
+}
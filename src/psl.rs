@@ -0,0 +1,262 @@
+//! Alignments in PSL format, as written by BLAT
+//!
+//! Alignments of transcripts, proteins or ESTs to the genome are evidence for annotation.
+//! [`PslRecord`]s can be converted into [`Bed12Row`]s for display, or into GFF3 `match` and
+//! `match_part` features on the target sequence, so that they can be layered with other annotation.
+
+use indexmap::IndexMap;
+
+use crate::format::{Bed12Row, Gff3Row, Strand};
+use crate::genome::{Error, SeqId};
+
+/// An aligned block, with starts as given in the file
+///
+/// Starts on a minus strand are on the reverse complement of the sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PslBlock {
+    pub size: u64,
+    pub query_start: u64,
+    pub target_start: u64,
+}
+
+/// The fields of a PSL line
+///
+/// Coordinates are 0-based and half-open. The query and target ranges of the alignment are on the
+/// forward strand, while the blocks are on the strand of the alignment.
+#[derive(Debug, Clone)]
+pub struct PslRecord {
+    pub matches: u64,
+    pub mismatches: u64,
+    pub rep_matches: u64,
+    pub n_count: u64,
+    pub query_gap_count: u64,
+    pub query_gap_bases: u64,
+    pub target_gap_count: u64,
+    pub target_gap_bases: u64,
+    pub query_strand: Strand,
+    /// The target strand, which is only given for translated alignments and is otherwise plus
+    pub target_strand: Strand,
+    pub query_name: String,
+    pub query_size: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub target_name: SeqId,
+    pub target_size: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub blocks: Vec<PslBlock>,
+}
+
+fn parse_list(value: &str, name: &str) -> Result<Vec<u64>, Error> {
+    value.trim_end_matches(',').split(',')
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<u64>().map_err(|_| Error::InvalidArguments(format!("PSL {} should be unsigned integers, not {}", name, value))))
+        .collect()
+}
+
+impl TryFrom<&str> for PslRecord {
+    type Error = Error;
+
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() != 21 {
+            return Err(Error::InvalidArguments(format!("PSL lines should have 21 fields, not {}", fields.len())));
+        }
+        let num = |i: usize| fields[i].parse::<u64>()
+            .map_err(|_| Error::InvalidArguments(format!("PSL field {} should be an unsigned integer, not {}", i + 1, fields[i])));
+        let strand = |c: char| match c {
+            '+' => Ok(Strand::Plus),
+            '-' => Ok(Strand::Minus),
+            _ => Err(Error::InvalidArguments(format!("invalid PSL strand {}", fields[8]))),
+        };
+        let mut strands = fields[8].chars();
+        let (query_strand, target_strand) = match (strands.next(), strands.next(), strands.next()) {
+            (Some(q), None, _) => (strand(q)?, Strand::Plus),
+            (Some(q), Some(t), None) => (strand(q)?, strand(t)?),
+            _ => return Err(Error::InvalidArguments(format!("invalid PSL strand {}", fields[8]))),
+        };
+        let block_count = num(17)? as usize;
+        let sizes = parse_list(fields[18], "block sizes")?;
+        let query_starts = parse_list(fields[19], "query starts")?;
+        let target_starts = parse_list(fields[20], "target starts")?;
+        if sizes.len() != block_count || query_starts.len() != block_count || target_starts.len() != block_count {
+            return Err(Error::InvalidArguments(format!("PSL lines should have {} block sizes and starts", block_count)));
+        }
+        Ok(PslRecord {
+            matches: num(0)?,
+            mismatches: num(1)?,
+            rep_matches: num(2)?,
+            n_count: num(3)?,
+            query_gap_count: num(4)?,
+            query_gap_bases: num(5)?,
+            target_gap_count: num(6)?,
+            target_gap_bases: num(7)?,
+            query_strand,
+            target_strand,
+            query_name: fields[9].to_string(),
+            query_size: num(10)?,
+            query_start: num(11)?,
+            query_end: num(12)?,
+            target_name: SeqId::from(fields[13]),
+            target_size: num(14)?,
+            target_start: num(15)?,
+            target_end: num(16)?,
+            blocks: sizes.into_iter().zip(query_starts).zip(target_starts)
+                .map(|((size, query_start), target_start)| PslBlock { size, query_start, target_start })
+                .collect(),
+        })
+    }
+}
+
+/// Parses the alignments in the text of a PSL file, skipping any `psLayout` header
+pub fn parse_psl(text: &str) -> Result<Vec<PslRecord>, Error> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| line.split('\t').next().is_some_and(|f| !f.is_empty() && f.bytes().all(|b| b.is_ascii_digit())))
+        .map(|(line_num, line)| PslRecord::try_from(line)
+            .map_err(|e| Error::InvalidArguments(format!("line {} of PSL: {}", line_num + 1, e))))
+        .collect()
+}
+
+impl PslRecord {
+    /// Whether the query is a protein, in which case each block covers three times its size on the target
+    pub fn is_protein(&self) -> bool {
+        let Some(last) = self.blocks.last() else {
+            return false;
+        };
+        let end = last.target_start + 3 * last.size;
+        match self.target_strand {
+            Strand::Minus => self.target_start == self.target_size.saturating_sub(end),
+            _ => self.target_end == end,
+        }
+    }
+
+    /// The strand of the query relative to the forward strand of the target
+    pub fn strand(&self) -> Strand {
+        if self.query_strand == self.target_strand { Strand::Plus } else { Strand::Minus }
+    }
+
+    /// The fraction of aligned bases that match, counting repeat matches
+    pub fn identity(&self) -> f64 {
+        let aligned = self.matches + self.rep_matches + self.mismatches;
+        if aligned == 0 { 0.0 } else { (self.matches + self.rep_matches) as f64 / aligned as f64 }
+    }
+
+    /// The blocks as 0-based, half-open intervals on the forward strand of the target and query, in target order
+    pub fn forward_blocks(&self) -> Vec<((u64, u64), (u64, u64))> {
+        let target_scale = if self.is_protein() { 3 } else { 1 };
+        let mut blocks: Vec<_> = self.blocks.iter()
+            .map(|block| {
+                let target = (block.target_start, block.target_start + block.size * target_scale);
+                let query = (block.query_start, block.query_start + block.size);
+                let target = match self.target_strand {
+                    Strand::Minus => (self.target_size - target.1, self.target_size - target.0),
+                    _ => target,
+                };
+                let query = match self.query_strand {
+                    Strand::Minus => (self.query_size - query.1, self.query_size - query.0),
+                    _ => query,
+                };
+                (target, query)
+            })
+            .collect();
+        blocks.sort_by_key(|(target, _)| *target);
+        blocks
+    }
+
+    /// Converts the alignment into a BED12 row on the target, named by the query, as UCSC `pslToBed` does
+    ///
+    /// The score is the identity scaled to 0-1000, and the whole alignment is thick.
+    pub fn to_bed12(&self) -> Bed12Row {
+        let blocks = self.forward_blocks();
+        let list = |values: Vec<u64>| values.iter().map(|v| format!("{},", v)).collect::<String>();
+        Bed12Row {
+            chrom: self.target_name.clone(),
+            chrom_start: self.target_start,
+            chrom_end: self.target_end,
+            name: self.query_name.clone(),
+            score: (self.identity() * 1000.0).round() as u16,
+            strand: self.strand(),
+            thick_start: self.target_start,
+            thick_end: self.target_end,
+            item_rgb: "0".to_string(),
+            block_count: blocks.len(),
+            block_sizes: list(blocks.iter().map(|((start, end), _)| end - start).collect()),
+            block_starts: list(blocks.iter().map(|((start, _), _)| start - self.target_start).collect()),
+        }
+    }
+
+    /// Converts the alignment into a `match` row with the given `ID` and a `match_part` child for each block
+    ///
+    /// The rows have a `Target` attribute with the aligned query range, and the `match` row has the
+    /// percent identity as its score, as well as `matches` and `mismatches` attributes.
+    pub fn to_gff3(&self, source: &str, id: &str) -> Vec<Gff3Row<String>> {
+        let strand = self.strand();
+        let target_attr = |(start, end): (u64, u64)| format!("{} {} {} {}", self.query_name, start + 1, end, if strand == Strand::Minus { '-' } else { '+' });
+        let row = |feature_type: &str, (start, end): (u64, u64), score: String, attributes: IndexMap<String, String>| Gff3Row {
+            seqid: self.target_name.clone(),
+            source: source.to_string(),
+            feature_type: feature_type.to_string(),
+            start: start + 1,
+            end,
+            score,
+            strand,
+            phase: ".".to_string(),
+            attributes,
+        };
+        let mut attributes = IndexMap::new();
+        attributes.insert("ID".to_string(), id.to_string());
+        attributes.insert("Name".to_string(), self.query_name.clone());
+        attributes.insert("Target".to_string(), target_attr((self.query_start, self.query_end)));
+        attributes.insert("matches".to_string(), self.matches.to_string());
+        attributes.insert("mismatches".to_string(), self.mismatches.to_string());
+        let mut rows = vec![row("match", (self.target_start, self.target_end), format!("{:.2}", self.identity() * 100.0), attributes)];
+        for (n, (target, query)) in self.forward_blocks().into_iter().enumerate() {
+            let mut attributes = IndexMap::new();
+            attributes.insert("ID".to_string(), format!("{}.part{}", id, n + 1));
+            attributes.insert("Parent".to_string(), id.to_string());
+            attributes.insert("Target".to_string(), target_attr(query));
+            rows.push(row("match_part", target, ".".to_string(), attributes));
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PSL: &str = "psLayout version 3\n\nmatch\tmis-\n---------\n\
+        27\t3\t0\t0\t0\t0\t1\t100\t-\tq1\t50\t5\t35\tchr1\t1000\t100\t230\t2\t10,20,\t15,25,\t100,210,\n\
+        10\t0\t0\t0\t0\t0\t0\t0\t++\tp1\t10\t0\t10\tchr1\t1000\t100\t130\t1\t10,\t0,\t100,\n";
+
+    #[test]
+    fn minus_strand_blocks_are_on_the_forward_strand() {
+        let records = parse_psl(PSL).unwrap();
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!((record.strand(), record.identity(), record.is_protein()), (Strand::Minus, 0.9, false));
+        assert_eq!(record.forward_blocks(), [((100, 110), (25, 35)), ((210, 230), (5, 25))]);
+
+        let bed = record.to_bed12();
+        assert_eq!((bed.score, bed.block_sizes.as_str(), bed.block_starts.as_str()), (900, "10,20,", "0,110,"));
+
+        let rows = record.to_gff3("blat", "m1");
+        let summary: Vec<_> = rows.iter().map(|r| (r.feature_type.as_str(), r.start, r.end, r.attributes["Target"].as_str())).collect();
+        assert_eq!(summary, [("match", 101, 230, "q1 6 35 -"), ("match_part", 101, 110, "q1 26 35 -"), ("match_part", 211, 230, "q1 6 25 -")]);
+        assert_eq!(rows[0].score, "90.00");
+    }
+
+    #[test]
+    fn protein_blocks_cover_codons() {
+        let record = &parse_psl(PSL).unwrap()[1];
+        assert!(record.is_protein());
+        assert_eq!(record.forward_blocks(), [((100, 130), (0, 10))]);
+    }
+
+    #[test]
+    fn block_counts_must_match() {
+        assert!(PslRecord::try_from("10\t0\t0\t0\t0\t0\t0\t0\t+\tq1\t10\t0\t10\tchr1\t1000\t100\t110\t2\t10,\t0,\t100,").is_err());
+        assert!(PslRecord::try_from("10\t0\t0\t0\t0\t0\t0\t0\t*\tq1\t10\t0\t10\tchr1\t1000\t100\t110\t1\t10,\t0,\t100,").is_err());
+    }
+}
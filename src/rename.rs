@@ -0,0 +1,409 @@
+//! Systematic renaming of gene models
+//!
+//! Before submission to GenBank or ENA, genes are given systematic locus tags, such as
+//! `ABC_00010`, numbered along each chromosome. [`assign_locus_tags`] numbers the genes and
+//! gives their transcripts and parts IDs derived from the locus tag.
+//!
+//! When an annotation is updated, IDs should stay stable for models that are unchanged or only
+//! slightly changed. [`carry_over_ids`] matches the genes and transcripts of a new annotation to the
+//! old one by the similarity of their exons, gives matches the old IDs, and reports which IDs are
+//! novel and which are retired.
+
+use indexmap::IndexMap;
+
+use std::fmt;
+
+use crate::collections::GenomicRangeSet;
+use crate::format::Strand;
+use crate::genome::{Error, GenomicRange, SeqId};
+use crate::models::{Gene, Transcript};
+
+/// How locus tags are numbered
+#[derive(Debug, Clone)]
+pub struct LocusTagOptions {
+    /// The registered locus tag prefix, without the underscore
+    pub prefix: String,
+    /// The increment between genes, leaving space for genes added later
+    pub step: u64,
+    /// The number of digits, padded with zeros
+    pub width: usize,
+    /// Whether numbering starts again on each sequence
+    pub restart_per_seqid: bool,
+}
+
+impl LocusTagOptions {
+    /// Numbers in steps of 10 with 5 digits, continuing across sequences
+    pub fn new(prefix: &str) -> LocusTagOptions {
+        LocusTagOptions {
+            prefix: prefix.to_string(),
+            step: 10,
+            width: 5,
+            restart_per_seqid: false,
+        }
+    }
+}
+
+/// Assigns locus tags to the genes and IDs derived from them to their transcripts and parts
+///
+/// Genes are numbered in order of position along each sequence, with sequences in the order they
+/// are first seen, and are left in the order given. Each gene gets the locus tag as its `ID` and a `locus_tag` attribute, which is
+/// also added to its transcripts and CDS. Transcripts become `<locus tag>.t<n>`, exons
+/// `<transcript>.exon<n>`, numbered in the direction of transcription, and CDS `<transcript>.cds`.
+/// Other parts keep their IDs.
+///
+/// Returns the new ID of each gene by its old ID, for genes that had one. Fails, leaving the genes
+/// unchanged, if a locus tag would have more than `width` digits.
+pub fn assign_locus_tags<T>(genes: &mut [Gene<T>], options: &LocusTagOptions) -> Result<IndexMap<String, String>, Error> {
+    if options.step == 0 {
+        return Err(Error::InvalidArguments("locus tag step should be above 0".to_string()));
+    }
+    let mut seqid_order: IndexMap<SeqId, usize> = IndexMap::new();
+    for gene in genes.iter() {
+        let n = seqid_order.len();
+        seqid_order.entry(gene.row.seqid.clone()).or_insert(n);
+    }
+    let key = |g: &Gene<T>| (seqid_order[&g.row.seqid], g.row.start, g.row.end);
+    let mut order: Vec<usize> = (0..genes.len()).collect();
+    order.sort_by_key(|&i| key(&genes[i]));
+
+    // all the tags are checked before any gene is changed
+    let mut tags = Vec::with_capacity(genes.len());
+    let mut number = 0;
+    let mut previous_seqid: Option<&SeqId> = None;
+    for &i in &order {
+        let seqid = &genes[i].row.seqid;
+        if options.restart_per_seqid && previous_seqid != Some(seqid) {
+            number = 0;
+        }
+        previous_seqid = Some(seqid);
+        number += options.step;
+        let tag = format!("{}_{:0width$}", options.prefix, number, width = options.width);
+        if tag.len() - options.prefix.len() - 1 > options.width {
+            return Err(Error::InvalidArguments(format!("locus tag {} has more than {} digits", tag, options.width)));
+        }
+        tags.push(tag);
+    }
+
+    let mut renamed = IndexMap::new();
+    for (i, tag) in order.into_iter().zip(tags) {
+        let gene = &mut genes[i];
+        if let Some(old) = gene.row.attributes.insert("ID".to_string(), tag.clone()) {
+            renamed.insert(old, tag.clone());
+        }
+        gene.row.attributes.insert("locus_tag".to_string(), tag.clone());
+        for (i, transcript) in gene.transcripts.iter_mut().enumerate() {
+            let tx_id = format!("{}.t{}", tag, i + 1);
+            transcript.row.attributes.insert("ID".to_string(), tx_id.clone());
+            transcript.row.attributes.insert("locus_tag".to_string(), tag.clone());
+            let exon_count = transcript.exons.len();
+            for (n, exon) in transcript.exons.iter_mut().enumerate() {
+                let exon_num = if transcript.row.strand == Strand::Minus { exon_count - n } else { n + 1 };
+                exon.attributes.insert("ID".to_string(), format!("{}.exon{}", tx_id, exon_num));
+            }
+            for cds in &mut transcript.cds {
+                cds.attributes.insert("ID".to_string(), format!("{}.cds", tx_id));
+                cds.attributes.insert("locus_tag".to_string(), tag.clone());
+            }
+        }
+        gene.rederive();
+    }
+    Ok(renamed)
+}
+
+/// Options for [`carry_over_ids`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StableIdOptions {
+    /// The minimum Jaccard index of the exonic bases of an old and new model for the new one to keep the old ID
+    pub min_similarity: f64,
+}
+
+impl Default for StableIdOptions {
+    fn default() -> Self {
+        StableIdOptions { min_similarity: 0.5 }
+    }
+}
+
+/// Whether an ID mapping is of a gene or a transcript
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureLevel {
+    Gene,
+    Transcript,
+}
+
+/// What happened to an ID between the old and new annotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdStatus {
+    /// The model has the same exons and CDS, and for genes the same transcripts
+    Unchanged,
+    /// The model changed, with the Jaccard index of the exonic bases of the old and new model
+    Modified { similarity: f64 },
+    /// The new model matches no old model, and keeps its ID
+    Novel,
+    /// The old model matches no new model
+    Retired,
+}
+
+impl fmt::Display for IdStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdStatus::Unchanged => write!(f, "unchanged"),
+            IdStatus::Modified { .. } => write!(f, "modified"),
+            IdStatus::Novel => write!(f, "novel"),
+            IdStatus::Retired => write!(f, "retired"),
+        }
+    }
+}
+
+/// How the ID of a gene or transcript was carried over
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdMapping {
+    pub level: FeatureLevel,
+    /// The ID in the old annotation, which a matched new model now has
+    pub old_id: Option<String>,
+    /// The ID the new model had before the update
+    pub new_id: Option<String>,
+    pub status: IdStatus,
+}
+
+/// Writes the mappings as TSV with a header, with the similarity of modified models
+pub fn id_mappings_tsv(mappings: &[IdMapping]) -> String {
+    let mut tsv = "level\told_id\tnew_id\tstatus\tsimilarity\n".to_string();
+    for mapping in mappings {
+        let similarity = match mapping.status {
+            IdStatus::Unchanged => "1.000".to_string(),
+            IdStatus::Modified { similarity } => format!("{:.3}", similarity),
+            _ => ".".to_string(),
+        };
+        tsv.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            if mapping.level == FeatureLevel::Gene { "gene" } else { "transcript" },
+            mapping.old_id.as_deref().unwrap_or("."),
+            mapping.new_id.as_deref().unwrap_or("."),
+            mapping.status,
+            similarity,
+        ));
+    }
+    tsv
+}
+
+/// The span, strand and exonic bases of a model, for matching
+struct Footprint {
+    range: GenomicRange,
+    strand: Strand,
+    exons: GenomicRangeSet,
+}
+
+impl Footprint {
+    fn of_transcripts<'a, T: 'a, I: IntoIterator<Item = &'a Transcript<T>>>(range: GenomicRange, strand: Strand, transcripts: I) -> Footprint {
+        Footprint { range, strand, exons: transcripts.into_iter().flat_map(|t| t.exon_ranges()).collect() }
+    }
+}
+
+/// Matches old and new models one to one, most similar first, returning the pairs with their similarity
+fn match_models(old: &[Footprint], new: &[Footprint], min_similarity: f64) -> Vec<(usize, usize, f64)> {
+    let mut order: Vec<usize> = (0..old.len()).collect();
+    order.sort_by(|a, b| old[*a].range.cmp(&old[*b].range));
+    let longest = old.iter().map(|f| f.range.len()).max().unwrap_or(0);
+    let mut candidates = Vec::new();
+    for (j, footprint) in new.iter().enumerate() {
+        let interval = footprint.range.range_0halfopen();
+        let key = |i: &usize| (old[*i].range.seqid(), old[*i].range.range_0halfopen().start);
+        let first = order.partition_point(|i| (key(i).0, key(i).1.saturating_add(longest)) <= (footprint.range.seqid(), interval.start));
+        let last = order.partition_point(|i| key(i) < (footprint.range.seqid(), interval.end));
+        for &i in &order[first..last] {
+            if old[i].strand != footprint.strand || !old[i].range.overlaps(&footprint.range) {
+                continue;
+            }
+            let similarity = old[i].exons.jaccard(&footprint.exons);
+            if similarity >= min_similarity && similarity > 0.0 {
+                candidates.push((i, j, similarity));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+    let (mut old_matched, mut new_matched) = (vec![false; old.len()], vec![false; new.len()]);
+    candidates.retain(|(i, j, _)| {
+        let free = !old_matched[*i] && !new_matched[*j];
+        if free {
+            old_matched[*i] = true;
+            new_matched[*j] = true;
+        }
+        free
+    });
+    candidates.sort_by_key(|(_, j, _)| *j);
+    candidates
+}
+
+fn unchanged<T, U>(old: &Transcript<T>, new: &Transcript<U>) -> bool {
+    old.exon_ranges() == new.exon_ranges() && old.cds_ranges() == new.cds_ranges()
+}
+
+/// Gives the genes and transcripts of a new annotation the IDs of the old models they match, returning
+/// the mapping of every ID
+///
+/// Genes are matched one to one, most similar first, by the Jaccard index of their exonic bases, and
+/// must be on the same strand. The transcripts of matched genes are then matched in the same way. Models
+/// that match no old model keep their IDs, and are novel. The `Parent` of every transcript and part is
+/// updated, but the IDs of parts are kept. Mappings are in the order of the new genes, with retired genes
+/// at the end. Fails, leaving the models unchanged, if a kept ID is the same as an ID carried over to
+/// another model.
+pub fn carry_over_ids<T, U>(old: &[Gene<T>], new: &mut [Gene<U>], options: &StableIdOptions) -> Result<Vec<IdMapping>, Error> {
+    let id = |row_id: Option<&str>| row_id.map(|id| id.to_string());
+    let mapping = |level, old_id, new_id, status| IdMapping { level, old_id, new_id, status };
+    let old_genes: Vec<Footprint> = old.iter().map(|g| Footprint::of_transcripts(g.range(), g.strand(), &g.transcripts)).collect();
+    let new_genes: Vec<Footprint> = new.iter().map(|g| Footprint::of_transcripts(g.range(), g.strand(), &g.transcripts)).collect();
+    let gene_matches = match_models(&old_genes, &new_genes, options.min_similarity);
+
+    // the IDs are worked out and checked before any model is changed
+    let mut gene_ids: Vec<Option<String>> = new.iter().map(|g| id(g.id())).collect();
+    let mut transcript_ids: Vec<Vec<Option<String>>> = new.iter().map(|g| g.transcripts.iter().map(|t| id(t.id())).collect()).collect();
+    let mut mappings = Vec::new();
+    let mut old_gene_matched = vec![false; old.len()];
+    let mut new_gene_matched = vec![false; new.len()];
+    let mut matches = gene_matches.iter().peekable();
+    for (j, gene) in new.iter().enumerate() {
+        let Some((i, _, similarity)) = matches.next_if(|(_, m, _)| *m == j).copied() else {
+            mappings.push(mapping(FeatureLevel::Gene, None, id(gene.id()), IdStatus::Novel));
+            for transcript in &gene.transcripts {
+                mappings.push(mapping(FeatureLevel::Transcript, None, id(transcript.id()), IdStatus::Novel));
+            }
+            continue;
+        };
+        old_gene_matched[i] = true;
+        new_gene_matched[j] = true;
+        let old_gene = &old[i];
+        let gene_index = mappings.len();
+        mappings.push(mapping(FeatureLevel::Gene, id(old_gene.id()), id(gene.id()), IdStatus::Modified { similarity }));
+
+        let old_transcripts: Vec<Footprint> = old_gene.transcripts.iter().map(|t| Footprint::of_transcripts(t.range(), t.strand(), [t])).collect();
+        let new_transcripts: Vec<Footprint> = gene.transcripts.iter().map(|t| Footprint::of_transcripts(t.range(), t.strand(), [t])).collect();
+        let transcript_matches = match_models(&old_transcripts, &new_transcripts, options.min_similarity);
+        let mut all_unchanged = old_gene.transcripts.len() == gene.transcripts.len();
+        let mut old_transcript_matched = vec![false; old_gene.transcripts.len()];
+        let mut transcript_matches = transcript_matches.iter().peekable();
+        for (n, transcript) in gene.transcripts.iter().enumerate() {
+            let Some((m, _, similarity)) = transcript_matches.next_if(|(_, k, _)| *k == n).copied() else {
+                all_unchanged = false;
+                mappings.push(mapping(FeatureLevel::Transcript, None, id(transcript.id()), IdStatus::Novel));
+                continue;
+            };
+            old_transcript_matched[m] = true;
+            let old_transcript = &old_gene.transcripts[m];
+            let status = if unchanged(old_transcript, transcript) { IdStatus::Unchanged } else { IdStatus::Modified { similarity } };
+            all_unchanged &= status == IdStatus::Unchanged;
+            mappings.push(mapping(FeatureLevel::Transcript, id(old_transcript.id()), id(transcript.id()), status));
+            if let Some(old_id) = old_transcript.id() {
+                transcript_ids[j][n] = Some(old_id.to_string());
+            }
+        }
+        for (old_transcript, _) in old_gene.transcripts.iter().zip(&old_transcript_matched).filter(|(_, matched)| !**matched) {
+            mappings.push(mapping(FeatureLevel::Transcript, id(old_transcript.id()), None, IdStatus::Retired));
+        }
+        if all_unchanged {
+            mappings[gene_index].status = IdStatus::Unchanged;
+        }
+        if let Some(old_id) = old_gene.id() {
+            gene_ids[j] = Some(old_id.to_string());
+        }
+    }
+    for (old_gene, _) in old.iter().zip(&old_gene_matched).filter(|(_, matched)| !**matched) {
+        mappings.push(mapping(FeatureLevel::Gene, id(old_gene.id()), None, IdStatus::Retired));
+        for transcript in &old_gene.transcripts {
+            mappings.push(mapping(FeatureLevel::Transcript, id(transcript.id()), None, IdStatus::Retired));
+        }
+    }
+
+    let mut ids = std::collections::HashSet::new();
+    for id in gene_ids.iter().chain(transcript_ids.iter().flatten()).flatten() {
+        if !ids.insert(id) {
+            return Err(Error::InvalidArguments(format!("ID {} is used by more than one model after carrying over IDs", id)));
+        }
+    }
+
+    for (((gene, gene_id), transcript_ids), matched) in new.iter_mut().zip(gene_ids).zip(transcript_ids).zip(new_gene_matched) {
+        if !matched {
+            continue;
+        }
+        for (transcript, transcript_id) in gene.transcripts.iter_mut().zip(transcript_ids) {
+            if let Some(transcript_id) = transcript_id {
+                transcript.row.attributes.insert("ID".to_string(), transcript_id);
+            }
+        }
+        if let Some(gene_id) = gene_id {
+            gene.row.attributes.insert("ID".to_string(), gene_id);
+        }
+        gene.rederive();
+    }
+    Ok(mappings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    fn genes(gff: &str) -> Vec<Gene<String>> {
+        build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap()
+    }
+
+    const GFF: &str = "\
+chr2\t.\tgene\t100\t200\t.\t-\t.\tID=b
+chr2\t.\tmRNA\t100\t200\t.\t-\t.\tID=b1;Parent=b
+chr2\t.\texon\t100\t120\t.\t-\t.\tParent=b1
+chr2\t.\texon\t180\t200\t.\t-\t.\tParent=b1
+chr1\t.\tgene\t500\t600\t.\t+\t.\tID=c
+chr1\t.\tmRNA\t500\t600\t.\t+\t.\tID=c1;Parent=c
+chr1\t.\texon\t500\t600\t.\t+\t.\tParent=c1
+chr2\t.\tgene\t10\t50\t.\t+\t.\tID=a
+chr2\t.\tmRNA\t10\t50\t.\t+\t.\tID=a1;Parent=a
+chr2\t.\texon\t10\t50\t.\t+\t.\tParent=a1
+";
+
+    #[test]
+    fn locus_tags_follow_position() {
+        let mut genes = genes(GFF);
+        let renamed = assign_locus_tags(&mut genes, &LocusTagOptions::new("ABC")).unwrap();
+        assert_eq!(renamed["a"], "ABC_00010");
+        assert_eq!(renamed["b"], "ABC_00020");
+        assert_eq!(renamed["c"], "ABC_00030");
+        let ids: Vec<_> = genes.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["ABC_00020", "ABC_00030", "ABC_00010"]);
+        let exons: Vec<_> = genes[0].transcripts[0].exons.iter().map(|e| e.attributes["ID"].as_str()).collect();
+        assert_eq!(exons, ["ABC_00020.t1.exon2", "ABC_00020.t1.exon1"]);
+        assert_eq!(genes[0].transcripts[0].exons[0].attributes["Parent"], "ABC_00020.t1");
+    }
+
+    #[test]
+    fn failed_locus_tags_leave_genes_unchanged() {
+        let mut genes = genes(GFF);
+        let options = LocusTagOptions { width: 1, step: 4, ..LocusTagOptions::new("ABC") };
+        assert!(assign_locus_tags(&mut genes, &options).is_err());
+        let ids: Vec<_> = genes.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn ids_are_carried_over_to_matching_models() {
+        let old = genes(GFF);
+        let mut new = genes(&GFF.replace("ID=a", "ID=x").replace("Parent=a", "Parent=x").replace("180\t200", "170\t200"));
+        let mappings = carry_over_ids(&old, &mut new, &StableIdOptions::default()).unwrap();
+        let ids: Vec<_> = new.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["b", "c", "a"]);
+        assert_eq!(new[2].transcripts[0].id(), Some("a1"));
+        assert_eq!(new[2].transcripts[0].exons[0].attributes["Parent"], "a1");
+        let statuses: Vec<_> = mappings.iter().map(|m| m.status.to_string()).collect();
+        assert_eq!(statuses, ["modified", "modified", "unchanged", "unchanged", "unchanged", "unchanged"]);
+    }
+
+    #[test]
+    fn failed_carry_over_leaves_models_unchanged() {
+        let old = genes(GFF);
+        // the novel gene keeps the ID that the matched gene is given
+        let mut new = genes(&format!("{}chr3\t.\tgene\t1\t10\t.\t+\t.\tID=a\n", GFF.replace("ID=a", "ID=x").replace("Parent=a", "Parent=x")));
+        assert!(carry_over_ids(&old, &mut new, &StableIdOptions::default()).is_err());
+        let ids: Vec<_> = new.iter().map(|g| g.id().unwrap()).collect();
+        assert_eq!(ids, ["b", "c", "x", "a"]);
+        assert_eq!(new[2].transcripts[0].id(), Some("x1"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Human-readable reports of regions of interest
+//!
+//! For each region, e.g. a peak, a GWAS locus or a candidate from a screen, a [`RegionReport`]
+//! lists the overlapping genes, the nearest transcription start site and a summary of a score
+//! track. The reports can be written as a Markdown or HTML table for a quick look at each locus.
+
+use std::fmt::Write;
+
+use crate::format::Strand;
+use crate::genome::GenomicRange;
+use crate::models::Gene;
+use crate::track::{ScoreSummary, ScoreTrack};
+
+/// The nearest transcription start site to a region
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearestTss {
+    pub gene_id: Option<String>,
+    pub transcript_id: Option<String>,
+    /// The 0-based position of the TSS
+    pub position: u64,
+    pub strand: Strand,
+    /// The distance from the TSS to the region, negative when the region is upstream of the TSS,
+    /// and 0 when the region contains it
+    pub distance: i64,
+}
+
+/// The summary of one region of interest
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionReport {
+    pub range: GenomicRange,
+    /// The IDs of the overlapping genes, or their ranges for genes without an ID
+    pub overlapping_genes: Vec<String>,
+    pub nearest_tss: Option<NearestTss>,
+    pub signal: Option<ScoreSummary>,
+}
+
+/// Returns the transcription start sites of a gene's transcripts, or of the gene if it has none
+pub(crate) fn start_sites<T>(gene: &Gene<T>) -> Vec<(Option<String>, u64, Strand)> {
+    let tss = |range: &GenomicRange, strand: Strand| match strand {
+        Strand::Minus => range.range_0halfopen().end - 1,
+        _ => range.range_0halfopen().start,
+    };
+    if gene.transcripts.is_empty() {
+        return vec![(None, tss(&gene.range(), gene.strand()), gene.strand())];
+    }
+    gene.transcripts.iter()
+        .map(|t| (t.id().map(|id| id.to_string()), tss(&t.range(), t.strand()), t.strand()))
+        .collect()
+}
+
+/// Builds a report of each region, with a summary of the track if given
+///
+/// Ties for the nearest TSS go to the first gene and transcript.
+pub fn region_reports<T>(ranges: &[GenomicRange], genes: &[Gene<T>], track: Option<&ScoreTrack>) -> Vec<RegionReport> {
+    ranges.iter()
+        .map(|range| {
+            let interval = range.range_0halfopen();
+            let on_seqid = || genes.iter().filter(|g| g.row.seqid == *range.seqid());
+            let overlapping_genes = on_seqid()
+                .filter(|g| {
+                    let gene = g.range().range_0halfopen();
+                    gene.start < interval.end && interval.start < gene.end
+                })
+                .map(|g| g.id().map_or_else(|| g.range().to_string(), |id| id.to_string()))
+                .collect();
+            let mut nearest_tss: Option<NearestTss> = None;
+            for gene in on_seqid() {
+                for (transcript_id, position, strand) in start_sites(gene) {
+                    let offset = if position < interval.start {
+                        (interval.start - position) as i64
+                    } else if position >= interval.end {
+                        -((position - interval.end + 1) as i64)
+                    } else {
+                        0
+                    };
+                    let distance = if strand == Strand::Minus { -offset } else { offset };
+                    if nearest_tss.as_ref().is_none_or(|n| distance.abs() < n.distance.abs()) {
+                        nearest_tss = Some(NearestTss {
+                            gene_id: gene.id().map(|id| id.to_string()),
+                            transcript_id,
+                            position,
+                            strand,
+                            distance,
+                        });
+                    }
+                }
+            }
+            RegionReport {
+                range: range.clone(),
+                overlapping_genes,
+                nearest_tss,
+                signal: track.map(|track| track.summary(range)),
+            }
+        })
+        .collect()
+}
+
+/// The cells of a report row, with the nearest TSS as `<id> (<distance>)`
+fn cells(report: &RegionReport) -> [String; 5] {
+    let format_opt = |value: Option<f64>| value.map_or_else(|| ".".to_string(), |v| format!("{:.3}", v));
+    [
+        report.range.to_string(),
+        report.overlapping_genes.join(", "),
+        report.nearest_tss.as_ref().map_or_else(String::new, |tss| {
+            let id = tss.transcript_id.as_deref().or(tss.gene_id.as_deref()).unwrap_or(".");
+            format!("{} ({:+})", id, tss.distance)
+        }),
+        report.signal.map_or_else(String::new, |s| format_opt(s.mean())),
+        report.signal.map_or_else(String::new, |s| format_opt(s.max)),
+    ]
+}
+
+const HEADERS: [&str; 5] = ["Region", "Overlapping genes", "Nearest TSS (distance)", "Mean signal", "Max signal"];
+
+/// Writes the reports as a Markdown table
+pub fn to_markdown(reports: &[RegionReport]) -> String {
+    let mut markdown = format!("| {} |\n|{}\n", HEADERS.join(" | "), "---|".repeat(HEADERS.len()));
+    for report in reports {
+        let cells = cells(report).map(|cell| cell.replace('|', "\\|"));
+        writeln!(markdown, "| {} |", cells.join(" | ")).unwrap();
+    }
+    markdown
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Writes the reports as an HTML table
+pub fn to_html(reports: &[RegionReport]) -> String {
+    let mut html = String::from("<table>\n<thead>\n<tr>");
+    for header in HEADERS {
+        write!(html, "<th>{}</th>", header).unwrap();
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for report in reports {
+        html.push_str("<tr>");
+        for cell in cells(report) {
+            write!(html, "<td>{}</td>", escape_html(&cell)).unwrap();
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, DataInterval, ParseMode};
+    use crate::models::build_gene_models;
+
+    #[test]
+    fn reports_overlaps_and_signed_tss_distances() {
+        let gff = "\
+chr1\t.\tgene\t101\t200\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t101\t200\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\tgene\t301\t400\t.\t-\t.\tID=g2
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let ranges: Vec<GenomicRange> = ["chr1:51-60", "chr1:391-420", "chr1:451-460"].iter().map(|r| r.parse().unwrap()).collect();
+        let reports = region_reports(&ranges, &genes, None);
+        let nearest: Vec<_> = reports.iter()
+            .map(|r| r.nearest_tss.as_ref().map(|tss| (tss.gene_id.as_deref().unwrap(), tss.transcript_id.as_deref(), tss.distance)).unwrap())
+            .collect();
+        // regions upstream of a TSS have negative distances, on either strand
+        assert_eq!(nearest, [("g1", Some("t1"), -41), ("g2", None, 0), ("g2", None, -51)]);
+        assert_eq!(reports[1].overlapping_genes, ["g2"]);
+        assert!(reports[0].overlapping_genes.is_empty());
+
+        let markdown = to_markdown(&reports);
+        assert_eq!(markdown.lines().nth(3), Some("| chr1:391-420 | g2 | g2 (+0) |  |  |"));
+        assert!(to_html(&reports).contains("<td>t1 (-41)</td>"));
+    }
+
+    #[test]
+    fn summarises_the_track_over_each_region() {
+        let interval = DataInterval::new("chr1:51-55".parse().unwrap(), vec![Some(2.0)]);
+        let track = ScoreTrack::from_intervals([interval], 0).unwrap();
+        let reports = region_reports::<String>(&["chr1:51-60".parse().unwrap()], &[], Some(&track));
+        assert_eq!(reports[0].nearest_tss, None);
+        assert_eq!(to_markdown(&reports).lines().nth(2), Some("| chr1:51-60 |  |  | 2.000 | 2.000 |"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Semantic differences between GFF3 rows, to verify round trips through other formats
+//!
+//! A conversion such as GFF3 → GTF → GFF3 can silently drop attributes or shift coordinates. Converting
+//! the output back and comparing it with [`diff_gff3`] reports what was lost. Rows are compared by
+//! meaning, not text: they are matched by position and type regardless of order, and the values of
+//! an attribute are compared regardless of order.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::SeqId;
+
+/// A column of a row, or an attribute by key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowField {
+    Source,
+    Score,
+    Phase,
+    Attribute(String),
+}
+
+impl fmt::Display for RowField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RowField::Source => write!(f, "source"),
+            RowField::Score => write!(f, "score"),
+            RowField::Phase => write!(f, "phase"),
+            RowField::Attribute(key) => write!(f, "attribute {}", key),
+        }
+    }
+}
+
+/// A difference between the rows before and after a round trip, by their indexes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// A row before with no row after at the same position, of the same type
+    Missing(usize),
+    /// A row after with no row before at the same position, of the same type
+    Extra(usize),
+    /// A field that differs between matched rows, with `None` where an attribute is absent
+    Changed {
+        before: usize,
+        after: usize,
+        field: RowField,
+        before_value: Option<String>,
+        after_value: Option<String>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Missing(i) => write!(f, "row {} is missing", i + 1),
+            Difference::Extra(i) => write!(f, "row {} is extra", i + 1),
+            Difference::Changed { before, after, field, before_value, after_value } => write!(
+                f,
+                "row {} (row {} after) {} changed from {} to {}",
+                before + 1,
+                after + 1,
+                field,
+                before_value.as_deref().unwrap_or("nothing"),
+                after_value.as_deref().unwrap_or("nothing"),
+            ),
+        }
+    }
+}
+
+/// The values of an attribute, in order, for comparison regardless of their order
+fn sorted_values(value: &str) -> Vec<&str> {
+    let mut values: Vec<&str> = value.split(',').collect();
+    values.sort_unstable();
+    values
+}
+
+/// Compares rows before and after a round trip, in the order of the rows before
+///
+/// Rows are matched by seqid, type, start, end and strand, in order where several rows match. The
+/// source, score, phase and attributes of matched rows are compared, and attributes in `ignored`,
+/// such as ones a format adds, are skipped. An empty result means the round trip is lossless.
+pub fn diff_gff3<T: AsRef<str>, U: AsRef<str>>(before: &[Gff3Row<T>], after: &[Gff3Row<U>], ignored: &[&str]) -> Vec<Difference> {
+    let mut unmatched: IndexMap<(&SeqId, &str, u64, u64, Strand), VecDeque<usize>> = IndexMap::new();
+    for (j, row) in after.iter().enumerate() {
+        unmatched.entry((&row.seqid, row.feature_type.as_ref(), row.start, row.end, row.strand)).or_default().push_back(j);
+    }
+    let mut differences = Vec::new();
+    let mut matched = vec![false; after.len()];
+    for (i, row) in before.iter().enumerate() {
+        let key = (&row.seqid, row.feature_type.as_ref(), row.start, row.end, row.strand);
+        let Some(j) = unmatched.get_mut(&key).and_then(|rows| rows.pop_front()) else {
+            differences.push(Difference::Missing(i));
+            continue;
+        };
+        matched[j] = true;
+        let other = &after[j];
+        let mut changed = |field: RowField, before_value: Option<&String>, after_value: Option<&String>| {
+            differences.push(Difference::Changed { before: i, after: j, field, before_value: before_value.cloned(), after_value: after_value.cloned() });
+        };
+        for (field, a, b) in [
+            (RowField::Source, &row.source, &other.source),
+            (RowField::Score, &row.score, &other.score),
+            (RowField::Phase, &row.phase, &other.phase),
+        ] {
+            if a != b {
+                changed(field, Some(a), Some(b));
+            }
+        }
+        let keys = row.attributes.keys().chain(other.attributes.keys().filter(|k| !row.attributes.contains_key(*k)));
+        for key in keys.filter(|k| !ignored.contains(&k.as_str())) {
+            let (a, b) = (row.attributes.get(key), other.attributes.get(key));
+            if a.map(|v| sorted_values(v)) != b.map(|v| sorted_values(v)) {
+                changed(RowField::Attribute(key.clone()), a, b);
+            }
+        }
+    }
+    differences.extend(matched.iter().enumerate().filter(|(_, m)| !**m).map(|(j, _)| Difference::Extra(j)));
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    #[test]
+    fn reports_differences_regardless_of_order() {
+        let before = parse_gff3("\
+chr1\tsrc\tgene\t1\t100\t.\t+\t.\tID=g1;Dbxref=a,b
+chr1\tsrc\texon\t1\t50\t.\t+\t.\tParent=t1
+chr1\tsrc\texon\t1\t50\t.\t+\t.\tParent=t2
+chr1\tsrc\tCDS\t1\t30\t.\t+\t0\tParent=t1
+", ParseMode::Strict).unwrap();
+        let after = parse_gff3("\
+chr1\tsrc\texon\t1\t50\t.\t+\t.\tParent=t1
+chr1\tconv\tgene\t1\t100\t.\t+\t.\tID=g1;Dbxref=b,a;gene_id=g1
+chr1\tsrc\texon\t1\t50\t.\t+\t.\tParent=t3
+chr1\tsrc\tmRNA\t1\t100\t.\t+\t.\tID=t1
+", ParseMode::Strict).unwrap();
+        assert!(diff_gff3(&before, &before, &[]).is_empty());
+
+        let differences = diff_gff3(&before, &after, &["gene_id"]);
+        assert_eq!(differences, [
+            Difference::Changed { before: 0, after: 1, field: RowField::Source, before_value: Some("src".to_string()), after_value: Some("conv".to_string()) },
+            Difference::Changed { before: 2, after: 2, field: RowField::Attribute("Parent".to_string()), before_value: Some("t2".to_string()), after_value: Some("t3".to_string()) },
+            Difference::Missing(3),
+            Difference::Extra(3),
+        ]);
+        assert_eq!(differences[1].to_string(), "row 3 (row 3 after) attribute Parent changed from t2 to t3");
+        assert_eq!(diff_gff3(&before, &after, &[]).len(), 5);
+    }
+}
@@ -0,0 +1,121 @@
+//! Random subsampling of records
+//!
+//! The samplers work on any iterator of records e.g. deserialized [`Gff3Row`](crate::format::Gff3Row)s
+//! or [`GenomicRange`](crate::genome::GenomicRange)s. Each takes a seed so that samples are reproducible,
+//! and the sampled records are returned in their original order.
+
+use std::hash::Hash;
+use indexmap::IndexMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::genome::Error;
+
+/// Samples `n` records uniformly at random using reservoir sampling
+///
+/// Only `n` records are held in memory at any time. If there are fewer than `n` records,
+/// all of them are returned.
+pub fn reservoir<T, I: IntoIterator<Item = T>>(records: I, n: usize, seed: u64) -> Vec<T> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut reservoir = Reservoir::new(n);
+    for (i, record) in records.into_iter().enumerate() {
+        reservoir.offer(i, record, &mut rng);
+    }
+    reservoir.into_sample()
+}
+
+/// Keeps each record independently with probability `fraction`
+///
+/// The number of records returned is not fixed, but the records are streamed rather than held in memory.
+pub fn fraction<T, I: IntoIterator<Item = T>>(records: I, fraction: f64, seed: u64) -> Result<impl Iterator<Item = T>, Error> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(Error::InvalidArguments(format!("fraction should be between 0 and 1, not {}", fraction)));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    Ok(records.into_iter().filter(move |_| rng.random::<f64>() < fraction))
+}
+
+/// Samples up to `n` records from each stratum, as given by `key` e.g. the seqid or feature type
+///
+/// The strata are returned in the order they are first seen.
+pub fn stratified<T, K, I, F>(records: I, n: usize, key: F, seed: u64) -> IndexMap<K, Vec<T>>
+where
+    I: IntoIterator<Item = T>,
+    K: Hash + Eq,
+    F: Fn(&T) -> K,
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut strata: IndexMap<K, Reservoir<T>> = IndexMap::new();
+    for (i, record) in records.into_iter().enumerate() {
+        strata.entry(key(&record))
+            .or_insert_with(|| Reservoir::new(n))
+            .offer(i, record, &mut rng);
+    }
+    strata.into_iter()
+        .map(|(k, reservoir)| (k, reservoir.into_sample()))
+        .collect()
+}
+
+/// Algorithm R, remembering the position of each record so the original order can be restored
+struct Reservoir<T> {
+    capacity: usize,
+    seen: usize,
+    sample: Vec<(usize, T)>,
+}
+
+impl<T> Reservoir<T> {
+    fn new(capacity: usize) -> Reservoir<T> {
+        Reservoir {
+            capacity,
+            seen: 0,
+            sample: Vec::new(),
+        }
+    }
+
+    fn offer(&mut self, position: usize, record: T, rng: &mut StdRng) {
+        if self.seen < self.capacity {
+            self.sample.push((position, record));
+        } else {
+            let j = rng.random_range(0..=self.seen);
+            if j < self.capacity {
+                self.sample[j] = (position, record);
+            }
+        }
+        self.seen += 1;
+    }
+
+    fn into_sample(mut self) -> Vec<T> {
+        self.sample.sort_by_key(|(position, _)| *position);
+        self.sample.into_iter().map(|(_, record)| record).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservoir_samples_are_reproducible_and_in_order() {
+        let sample = reservoir(0..1000, 10, 7);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sample, reservoir(0..1000, 10, 7));
+        assert_ne!(sample, reservoir(0..1000, 10, 8));
+        assert_eq!(reservoir(0..3, 10, 7), [0, 1, 2]);
+    }
+
+    #[test]
+    fn fractions_keep_about_that_many() {
+        let kept = fraction(0..10000, 0.25, 1).unwrap().count();
+        assert!((2300..2700).contains(&kept));
+        assert_eq!(fraction(0..10, 1.0, 1).unwrap().count(), 10);
+        assert!(fraction(0..10, 1.5, 1).is_err());
+    }
+
+    #[test]
+    fn strata_are_sampled_separately() {
+        let strata = stratified(0..100, 3, |i| i % 2 == 0, 3);
+        assert_eq!(strata.keys().copied().collect::<Vec<_>>(), [true, false]);
+        assert!(strata[&true].iter().all(|i| i % 2 == 0) && strata[&true].len() == 3);
+        assert!(strata[&false].iter().all(|i| i % 2 == 1) && strata[&false].len() == 3);
+    }
+}
@@ -0,0 +1,373 @@
+//! Categorical segmentations of the genome e.g. chromatin states
+//!
+//! A [`Segmentation`] labels non-overlapping segments of the genome, such as the states from
+//! chromHMM or Segway. Ranges or bins can be painted with the label of the segments they overlap.
+//! Segmentations are read from the BED files of chromHMM and Segway, optionally renaming the states,
+//! and summarised by the genome coverage of each state. [`Segmentation::enrichment`] tests where a
+//! set of ranges such as peaks falls among the states.
+
+use indexmap::IndexMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::format::Bed6Row;
+use crate::genome::{Error, GenomicRange, SeqId};
+
+/// Labelled segments along each sequence
+#[derive(Debug, Clone, Default)]
+pub struct Segmentation {
+    // 0-based, half-open, sorted and non-overlapping
+    segments: IndexMap<SeqId, Vec<(u64, u64, String)>>,
+}
+
+/// How a range overlapping several segments is labelled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelRule {
+    /// The label covering the most bases of the range, with ties going to the first segment
+    MaxOverlap,
+    /// The first label in the list that overlaps the range, or else the label covering the most bases
+    Priority(Vec<String>),
+}
+
+/// Ranges labelled by a segmentation, with a contingency table of their names against the labels
+#[derive(Debug, Clone, Default)]
+pub struct PaintOutput {
+    /// The input rows, named by their label, or `.` if they don't overlap a segment
+    pub rows: Vec<Bed6Row>,
+    /// The number of rows with each name in the input by label
+    pub table: IndexMap<String, IndexMap<String, usize>>,
+}
+
+/// Summary of one state of a segmentation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateSummary {
+    pub state: String,
+    pub segments: usize,
+    pub bases: u64,
+    /// The fraction of the segmented bases in the state
+    pub coverage: f64,
+    pub mean_len: f64,
+}
+
+/// Enrichment of a set of ranges in one state of a segmentation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateEnrichment {
+    pub state: String,
+    /// The number of ranges labelled with the state
+    pub observed: usize,
+    /// The number of ranges expected from the fraction of the segmented bases in the state
+    pub expected: f64,
+    pub fold_enrichment: f64,
+    /// The hypergeometric probability of at least as many ranges in the state
+    pub p_value: f64,
+    /// The fraction of permutations with at least as many ranges in the state, or `None` without permutations
+    pub permutation_p_value: Option<f64>,
+}
+
+/// The natural log of the binomial coefficient, summed term by term as `k` is small
+fn ln_choose(n: u64, k: u64) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    let k = k.min(n - k);
+    (0..k).map(|i| ((n - i) as f64 / (k - i) as f64).ln()).sum()
+}
+
+/// The probability of at least `k` successes in `n` draws without replacement from `total` with `successes`
+fn hypergeometric_upper_tail(k: u64, n: u64, successes: u64, total: u64) -> f64 {
+    let failures = total - successes;
+    let lowest = n.saturating_sub(failures);
+    let k = k.max(lowest);
+    let highest = n.min(successes);
+    if k > highest {
+        return 0.0;
+    }
+    let mut pmf = (ln_choose(successes, k) + ln_choose(failures, n - k) - ln_choose(total, n)).exp();
+    let mut tail = 0.0;
+    for x in k..=highest {
+        tail += pmf;
+        if x < highest {
+            pmf *= (successes - x) as f64 * (n - x) as f64 / ((x + 1) as f64 * (failures + x + 1 - n) as f64);
+        }
+    }
+    tail.min(1.0)
+}
+
+/// Parses a mapping of state labels to names e.g. `E1` to `TssA`, as two tab-separated columns
+///
+/// Lines starting with `#` are skipped.
+pub fn parse_state_names(text: &str) -> Result<IndexMap<String, String>, Error> {
+    let mut names = IndexMap::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((state, name)) = line.split_once('\t') else {
+            return Err(Error::InvalidArguments(format!("line {}: state names should have 2 tab-separated columns", line_num + 1)));
+        };
+        names.insert(state.trim().to_string(), name.trim().to_string());
+    }
+    Ok(names)
+}
+
+/// Parses a segmentation BED file, as written by chromHMM (`_dense.bed` or `_segments.bed`) or Segway
+///
+/// The state is the name column. `track` and `browser` lines are skipped, as are rows with no length.
+/// States are renamed with `state_names` where given, and others keep their label.
+pub fn parse_segmentation_bed(text: &str, state_names: Option<&IndexMap<String, String>>) -> Result<Segmentation, Error> {
+    let mut segments = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() < 4 {
+            return Err(Error::InvalidArguments(format!("line {}: segmentation BED should have at least 4 columns", line_num + 1)));
+        }
+        let num = |i: usize| fields[i].parse::<u64>()
+            .map_err(|_| Error::InvalidArguments(format!("line {}: field {} should be an unsigned integer, not {}", line_num + 1, i + 1, fields[i])));
+        let (start, end) = (num(1)?, num(2)?);
+        if end <= start {
+            continue;
+        }
+        let state = fields[3].to_string();
+        let label = state_names.and_then(|names| names.get(&state).cloned()).unwrap_or(state);
+        segments.push((GenomicRange::from_0halfopen(fields[0], start..end)?, label));
+    }
+    Segmentation::from_segments(segments)
+}
+
+impl Segmentation {
+    /// Creates a segmentation from labelled ranges, which should not overlap
+    pub fn from_segments<I: IntoIterator<Item = (GenomicRange, String)>>(segments: I) -> Result<Segmentation, Error> {
+        let mut by_seqid: IndexMap<SeqId, Vec<(u64, u64, String)>> = IndexMap::new();
+        for (range, label) in segments {
+            let interval = range.range_0halfopen();
+            by_seqid.entry(range.seqid().clone()).or_default().push((interval.start, interval.end, label));
+        }
+        for (seqid, segments) in by_seqid.iter_mut() {
+            segments.sort_by_key(|(start, end, _)| (*start, *end));
+            if segments.windows(2).any(|w| w[0].1 > w[1].0) {
+                return Err(Error::InvalidArguments(format!("segments on {} overlap", seqid)));
+            }
+        }
+        Ok(Segmentation { segments: by_seqid })
+    }
+
+    /// Iterates over the segments in order, as ranges with their labels
+    pub fn segments(&self) -> impl Iterator<Item = (GenomicRange, &str)> + '_ {
+        self.segments.iter().flat_map(|(seqid, segments)| {
+            segments.iter().map(move |(start, end, label)| (GenomicRange::from_0halfopen(seqid.clone(), *start..*end).unwrap(), label.as_str()))
+        })
+    }
+
+    /// The labels, in the order first seen along the genome
+    pub fn labels(&self) -> Vec<&str> {
+        let mut labels: IndexMap<&str, ()> = IndexMap::new();
+        for segments in self.segments.values() {
+            for (_, _, label) in segments {
+                labels.insert(label, ());
+            }
+        }
+        labels.into_keys().collect()
+    }
+
+    /// Summarises each state, in the order of [`Segmentation::labels`]
+    pub fn state_summary(&self) -> Vec<StateSummary> {
+        let mut totals: IndexMap<&str, (usize, u64)> = self.labels().into_iter().map(|l| (l, (0, 0))).collect();
+        for segments in self.segments.values() {
+            for (start, end, label) in segments {
+                let total = totals.get_mut(label.as_str()).unwrap();
+                total.0 += 1;
+                total.1 += end - start;
+            }
+        }
+        let all_bases: u64 = totals.values().map(|(_, bases)| bases).sum();
+        totals.into_iter()
+            .map(|(state, (segments, bases))| StateSummary {
+                state: state.to_string(),
+                segments,
+                bases,
+                coverage: if all_bases == 0 { 0.0 } else { bases as f64 / all_bases as f64 },
+                mean_len: bases as f64 / segments as f64,
+            })
+            .collect()
+    }
+
+    /// Tests the enrichment of rows, such as peaks, in each state, in the order of [`Segmentation::labels`]
+    ///
+    /// Rows are labelled by the rule, and rows that don't overlap a segment are left out. The
+    /// hypergeometric test treats the labelled rows as positions drawn from the segmented bases. With
+    /// `permutations`, each row is also placed at random positions within the segmented bases, keeping
+    /// its length, and labelled again, which accounts for the lengths of the rows and segments.
+    pub fn enrichment(&self, rows: &[Bed6Row], rule: &LabelRule, permutations: usize, seed: u64) -> Vec<StateEnrichment> {
+        let summary = self.state_summary();
+        let total: u64 = summary.iter().map(|s| s.bases).sum();
+        let count_labels = |ranges: &mut dyn Iterator<Item = GenomicRange>| {
+            let mut counts: IndexMap<&str, usize> = IndexMap::new();
+            for range in ranges {
+                if let Some(label) = self.label(&range, rule) {
+                    *counts.entry(label).or_default() += 1;
+                }
+            }
+            counts
+        };
+        let observed = count_labels(&mut rows.iter()
+            .map(|row| GenomicRange::from_0halfopen(row.chrom.clone(), row.chrom_start..row.chrom_end).unwrap()));
+        let labelled: usize = observed.values().sum();
+
+        // the number of permutations with at least the observed count of each state
+        let mut exceeded = vec![0usize; summary.len()];
+        if permutations > 0 && total > 0 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let segments: Vec<(&SeqId, u64, u64)> = self.segments.iter()
+                .flat_map(|(seqid, segments)| segments.iter().map(move |(start, end, _)| (seqid, *start, *end)))
+                .collect();
+            let mut cumulative = Vec::with_capacity(segments.len());
+            let mut sum = 0;
+            for (_, start, end) in &segments {
+                sum += end - start;
+                cumulative.push(sum);
+            }
+            for _ in 0..permutations {
+                let mut shuffled = rows.iter().map(|row| {
+                    let base = rng.random_range(0..total);
+                    let i = cumulative.partition_point(|c| *c <= base);
+                    let (seqid, _, end) = segments[i];
+                    let position = end - (cumulative[i] - base);
+                    let len = row.chrom_end - row.chrom_start;
+                    let shifted = position.saturating_sub(len / 2);
+                    GenomicRange::from_0halfopen(seqid.clone(), shifted..shifted + len).unwrap()
+                });
+                let counts = count_labels(&mut shuffled);
+                for (state, exceeded) in summary.iter().zip(exceeded.iter_mut()) {
+                    let state = state.state.as_str();
+                    if counts.get(state).copied().unwrap_or(0) >= observed.get(state).copied().unwrap_or(0) {
+                        *exceeded += 1;
+                    }
+                }
+            }
+        }
+
+        summary.iter()
+            .zip(exceeded)
+            .map(|(state, exceeded)| {
+                let count = observed.get(state.state.as_str()).copied().unwrap_or(0);
+                let expected = labelled as f64 * state.coverage;
+                StateEnrichment {
+                    state: state.state.clone(),
+                    observed: count,
+                    expected,
+                    fold_enrichment: if expected > 0.0 { count as f64 / expected } else { 0.0 },
+                    p_value: hypergeometric_upper_tail(count as u64, labelled as u64, state.bases, total),
+                    permutation_p_value: (permutations > 0).then(|| {
+                        (exceeded + 1) as f64 / (permutations + 1) as f64
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// The number of bases of the range overlapped by each label, in order along the range
+    pub fn overlaps(&self, range: &GenomicRange) -> IndexMap<&str, u64> {
+        let interval = range.range_0halfopen();
+        let segments = self.segments.get(range.seqid()).map(|s| s.as_slice()).unwrap_or_default();
+        let first = segments.partition_point(|(_, end, _)| *end <= interval.start);
+        let mut overlaps: IndexMap<&str, u64> = IndexMap::new();
+        for (start, end, label) in segments[first..].iter().take_while(|(start, _, _)| *start < interval.end) {
+            *overlaps.entry(label.as_str()).or_default() += end.min(&interval.end) - start.max(&interval.start);
+        }
+        overlaps
+    }
+
+    /// Returns the label of a range by the rule, or `None` if it doesn't overlap a segment
+    pub fn label(&self, range: &GenomicRange, rule: &LabelRule) -> Option<&str> {
+        let overlaps = self.overlaps(range);
+        if let LabelRule::Priority(priority) = rule {
+            if let Some(label) = priority.iter().find(|p| overlaps.contains_key(p.as_str())) {
+                return overlaps.get_key_value(label.as_str()).map(|(label, _)| *label);
+            }
+        }
+        overlaps.iter()
+            .rev()
+            .max_by_key(|(_, bases)| **bases)
+            .map(|(label, _)| *label)
+    }
+
+    /// Labels each row, such as a bin or a peak, and counts the labels of the rows with each name
+    ///
+    /// For a single table of label counts, give the rows the same name. Fails if a row ends before it starts.
+    pub fn paint(&self, rows: &[Bed6Row], rule: &LabelRule) -> Result<PaintOutput, Error> {
+        let mut output = PaintOutput::default();
+        for row in rows {
+            if row.chrom_start > row.chrom_end {
+                return Err(Error::InvalidArguments(format!("row {} at {}:{}-{} ends before it starts", row.name, row.chrom, row.chrom_start, row.chrom_end)));
+            }
+            let range = GenomicRange::from_0halfopen(row.chrom.clone(), row.chrom_start..row.chrom_end)?;
+            let label = self.label(&range, rule).unwrap_or(".").to_string();
+            *output.table.entry(row.name.clone()).or_default().entry(label.clone()).or_default() += 1;
+            output.rows.push(Bed6Row { name: label, ..row.clone() });
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Strand;
+
+    const BED: &str = "\
+track name=states
+chr1\t0\t100\tE1
+chr1\t100\t400\tE2
+chr1\t400\t500\tE1
+chr1\t500\t500\tE2
+chr2\t0\t500\tE3
+";
+
+    fn segmentation() -> Segmentation {
+        let names = parse_state_names("# state\tname\nE1\tTssA\nE2\tQuies\n").unwrap();
+        parse_segmentation_bed(BED, Some(&names)).unwrap()
+    }
+
+    fn row(chrom: &str, start: u64, end: u64, name: &str) -> Bed6Row {
+        Bed6Row { chrom: SeqId::from(chrom), chrom_start: start, chrom_end: end, name: name.to_string(), score: 0, strand: Strand::None }
+    }
+
+    #[test]
+    fn parses_and_summarises_states() {
+        let segmentation = segmentation();
+        assert_eq!(segmentation.labels(), ["TssA", "Quies", "E3"]);
+        let summary: Vec<_> = segmentation.state_summary().into_iter().map(|s| (s.segments, s.bases, s.coverage)).collect();
+        assert_eq!(summary, [(2, 200, 0.2), (1, 300, 0.3), (1, 500, 0.5)]);
+        assert!(Segmentation::from_segments([("chr1:1-10".parse().unwrap(), "a".to_string()), ("chr1:5-20".parse().unwrap(), "b".to_string())]).is_err());
+    }
+
+    #[test]
+    fn labels_ranges_by_rule() {
+        let segmentation = segmentation();
+        let range: GenomicRange = "chr1:91-120".parse().unwrap();
+        assert_eq!(segmentation.label(&range, &LabelRule::MaxOverlap), Some("Quies"));
+        assert_eq!(segmentation.label(&range, &LabelRule::Priority(vec!["TssA".to_string()])), Some("TssA"));
+        assert_eq!(segmentation.label(&"chr1:81-120".parse().unwrap(), &LabelRule::MaxOverlap), Some("TssA"));
+
+        let output = segmentation.paint(&[row("chr1", 90, 120, "bin"), row("chr1", 600, 610, "bin")], &LabelRule::MaxOverlap).unwrap();
+        let names: Vec<_> = output.rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Quies", "."]);
+        assert_eq!(output.table["bin"]["."], 1);
+        assert!(segmentation.paint(&[row("chr1", 120, 90, "bin")], &LabelRule::MaxOverlap).is_err());
+    }
+
+    #[test]
+    fn tests_enrichment_in_each_state() {
+        assert_eq!(hypergeometric_upper_tail(1, 1, 1, 2), 0.5);
+        let segmentation = segmentation();
+        let peaks: Vec<_> = [10, 20, 30, 40, 410].iter().map(|&start| row("chr1", start, start + 10, "peak")).collect();
+        let enrichment = segmentation.enrichment(&peaks, &LabelRule::MaxOverlap, 99, 1);
+        assert_eq!((enrichment[0].observed, enrichment[0].expected, enrichment[0].fold_enrichment), (5, 1.0, 5.0));
+        assert!(enrichment[0].p_value < 0.001);
+        assert!(enrichment[0].permutation_p_value.unwrap() < 0.05);
+        assert_eq!((enrichment[1].observed, enrichment[1].p_value), (0, 1.0));
+    }
+}
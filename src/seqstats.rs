@@ -0,0 +1,138 @@
+//! Sequence statistics of features
+//!
+//! Simple statistics of the sequence of each feature, such as GC content or the longest ORF,
+//! are useful for filtering and QC of annotation. [`annotate_sequence_stats`] computes them from
+//! the genome and writes them into the attributes of the rows.
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{Error, GenomicRange};
+use crate::sequence::{reverse_complement, translate_codon, Genome};
+
+/// Statistics of a sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceStats {
+    pub len: u64,
+    /// The percentage of G and C among the unambiguous bases, or `None` if there are none
+    pub gc_percent: Option<f64>,
+    /// The number of CpG dinucleotides
+    pub cpg_count: u64,
+    /// The length in bases of the longest open reading frame, from ATG to a stop codon inclusive, on the given strand
+    pub longest_orf: u64,
+}
+
+impl SequenceStats {
+    /// Computes the statistics of a sequence, given in the direction to search for ORFs
+    pub fn from_sequence(sequence: &[u8]) -> SequenceStats {
+        let upper = sequence.to_ascii_uppercase();
+        let gc = upper.iter().filter(|b| matches!(b, b'G' | b'C')).count();
+        let unambiguous = upper.iter().filter(|b| matches!(b, b'A' | b'C' | b'G' | b'T' | b'U')).count();
+        SequenceStats {
+            len: sequence.len() as u64,
+            gc_percent: (unambiguous > 0).then(|| gc as f64 * 100.0 / unambiguous as f64),
+            cpg_count: upper.windows(2).filter(|w| w == b"CG").count() as u64,
+            longest_orf: longest_orf(&upper),
+        }
+    }
+}
+
+/// The length of the longest ORF in any of the three forward frames
+fn longest_orf(sequence: &[u8]) -> u64 {
+    let mut longest = 0;
+    for frame in 0..3 {
+        let codons = sequence.get(frame..).unwrap_or_default().chunks_exact(3);
+        let mut start: Option<usize> = None;
+        for (i, codon) in codons.enumerate() {
+            match translate_codon(codon) {
+                b'M' if start.is_none() => start = Some(i),
+                b'*' => {
+                    if let Some(s) = start.take() {
+                        longest = longest.max((i + 1 - s) as u64 * 3);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    longest
+}
+
+/// Which statistics to write as attributes, by attribute name, or `None` to leave one out
+#[derive(Debug, Clone)]
+pub struct SequenceStatsAttributes {
+    pub len: Option<String>,
+    pub gc_percent: Option<String>,
+    pub cpg_count: Option<String>,
+    pub longest_orf: Option<String>,
+}
+
+impl Default for SequenceStatsAttributes {
+    fn default() -> Self {
+        SequenceStatsAttributes {
+            len: Some("length".to_string()),
+            gc_percent: Some("gc_percent".to_string()),
+            cpg_count: Some("cpg_count".to_string()),
+            longest_orf: Some("longest_orf".to_string()),
+        }
+    }
+}
+
+/// Computes the statistics of the sequence of each row and writes them into its attributes
+///
+/// The sequence of a row on the minus strand is reverse complemented, so ORFs are on the strand of the row.
+/// GC content is written with 2 decimal places. Fails if a row is beyond the end of its sequence.
+pub fn annotate_sequence_stats<T>(rows: &mut [Gff3Row<T>], genome: &Genome, attributes: &SequenceStatsAttributes) -> Result<(), Error> {
+    for row in rows {
+        let sequence = genome.fetch(&GenomicRange::from_gff_row(row))?;
+        let stats = match row.strand {
+            Strand::Minus => SequenceStats::from_sequence(&reverse_complement(sequence)),
+            _ => SequenceStats::from_sequence(sequence),
+        };
+        let values = [
+            (&attributes.len, stats.len.to_string()),
+            (&attributes.gc_percent, stats.gc_percent.map_or_else(|| ".".to_string(), |gc| format!("{:.2}", gc))),
+            (&attributes.cpg_count, stats.cpg_count.to_string()),
+            (&attributes.longest_orf, stats.longest_orf.to_string()),
+        ];
+        for (name, value) in values {
+            if let Some(name) = name {
+                row.attributes.insert(name.clone(), value);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    #[test]
+    fn computes_sequence_stats() {
+        let stats = SequenceStats::from_sequence(b"ccATGAAATGACG");
+        assert_eq!((stats.len, stats.cpg_count, stats.longest_orf), (13, 1, 9));
+        assert_eq!(stats.gc_percent, Some(600.0 / 13.0));
+        assert_eq!(SequenceStats::from_sequence(b"NNNN").gc_percent, None);
+        // an ORF needs a stop codon
+        assert_eq!(SequenceStats::from_sequence(b"ATGAAA").longest_orf, 0);
+    }
+
+    #[test]
+    fn orfs_are_on_the_strand_of_the_row() {
+        let mut genome = Genome::default();
+        genome.insert("chr1", b"CCATGAAATGACGTCATTTCAT".to_vec());
+        let gff = "\
+chr1\t.\tregion\t14\t22\t.\t+\t.\tID=r1
+chr1\t.\tregion\t14\t22\t.\t-\t.\tID=r2
+";
+        let mut rows = parse_gff3(gff, ParseMode::Strict).unwrap();
+        let attributes = SequenceStatsAttributes { cpg_count: None, ..Default::default() };
+        annotate_sequence_stats(&mut rows, &genome, &attributes).unwrap();
+        assert_eq!((rows[0].attributes["longest_orf"].as_str(), rows[1].attributes["longest_orf"].as_str()), ("0", "9"));
+        assert_eq!((rows[0].attributes["length"].as_str(), rows[0].attributes["gc_percent"].as_str()), ("9", "22.22"));
+        assert!(!rows[0].attributes.contains_key("cpg_count"));
+
+        let mut beyond = parse_gff3("chr1\t.\tregion\t20\t30\t.\t+\t.\tID=r3\n", ParseMode::Strict).unwrap();
+        assert!(annotate_sequence_stats(&mut beyond, &genome, &attributes).is_err());
+    }
+}
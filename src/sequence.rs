@@ -0,0 +1,611 @@
+//! Genome sequences and FASTA
+//!
+//! Sequences are held in memory as bytes, as parsed from the text of a FASTA file, and can
+//! be fetched for a [`GenomicRange`]. Helpers are provided for reverse complementing and
+//! translating with the standard genetic code. Extracted sequences can be named with a
+//! [`HeaderTemplate`] and written with any line width, and checksums of the sequences can be
+//! computed for comparing assemblies.
+//!
+//! Genomes can also be read from the bytes of UCSC 2bit files, and sequences read on demand can be
+//! cached with a [`CachedFetcher`].
+
+use std::fmt;
+use indexmap::IndexMap;
+use md5::Md5;
+use sha2::{Digest, Sha512};
+
+use crate::format::{FieldDescriptor, FieldType, Schema, Strand};
+use crate::genome::{ChromSizes, Error, GenomicRange, SeqId};
+
+/// The sequences of a genome assembly, in the order they were added
+#[derive(Debug, Clone, Default)]
+pub struct Genome {
+    sequences: IndexMap<SeqId, Vec<u8>>,
+    digests: IndexMap<SeqId, SequenceDigests>,
+}
+
+/// Checksums of a sequence, for checking that an annotation and reference are the same assembly
+///
+/// As in the GA4GH refget specification, the digests are of the sequence in upper case.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SequenceDigests {
+    /// The MD5 digest in lower-case hex, as used by e.g. SAM `M5` tags and NCBI
+    pub md5: String,
+    /// The first 24 bytes of the SHA-512 digest in lower-case hex
+    pub trunc512: String,
+}
+
+impl SequenceDigests {
+    pub fn from_sequence(sequence: &[u8]) -> SequenceDigests {
+        let upper = sequence.to_ascii_uppercase();
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        SequenceDigests {
+            md5: hex(&Md5::digest(&upper)),
+            trunc512: hex(&Sha512::digest(&upper)[..24]),
+        }
+    }
+
+    /// The GA4GH sequence identifier, `SQ.` followed by the truncated SHA-512 digest in base64url
+    pub fn ga4gh_identifier(&self) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let bytes: Vec<u8> = (0..self.trunc512.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&self.trunc512[i..i + 2], 16).unwrap())
+            .collect();
+        let mut id = String::from("SQ.");
+        // 24 bytes encode to 32 characters with no padding
+        for chunk in bytes.chunks(3) {
+            let n = (chunk[0] as u32) << 16 | (chunk[1] as u32) << 8 | chunk[2] as u32;
+            for shift in [18, 12, 6, 0] {
+                id.push(ALPHABET[(n >> shift & 63) as usize] as char);
+            }
+        }
+        id
+    }
+}
+
+/// Reads the little or big-endian fields of a 2bit file
+struct TwoBitReader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl TwoBitReader<'_> {
+    fn truncated() -> Error {
+        Error::InvalidArguments("2bit file is truncated".to_string())
+    }
+
+    fn slice(&self, pos: usize, len: usize) -> Result<&[u8], Error> {
+        self.bytes.get(pos..pos.checked_add(len).ok_or_else(Self::truncated)?).ok_or_else(Self::truncated)
+    }
+
+    fn u32(&self, pos: usize) -> Result<u32, Error> {
+        let bytes: [u8; 4] = self.slice(pos, 4)?.try_into().unwrap();
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(&self, pos: usize) -> Result<u64, Error> {
+        let bytes: [u8; 8] = self.slice(pos, 8)?.try_into().unwrap();
+        Ok(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+
+    /// The start and length of each block in a list of block starts followed by block sizes
+    fn blocks(&self, pos: usize) -> Result<(Vec<(usize, usize)>, usize), Error> {
+        let count = self.u32(pos)? as usize;
+        let sizes = pos + 4 + count * 4;
+        let blocks = (0..count)
+            .map(|i| Ok((self.u32(pos + 4 + i * 4)? as usize, self.u32(sizes + i * 4)? as usize)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok((blocks, sizes + count * 4))
+    }
+
+    /// Decodes the sequence record at the offset
+    fn sequence(&self, offset: usize) -> Result<Vec<u8>, Error> {
+        const BASES: [u8; 4] = *b"TCAG";
+        let len = self.u32(offset)? as usize;
+        let (n_blocks, pos) = self.blocks(offset + 4)?;
+        let (mask_blocks, pos) = self.blocks(pos)?;
+        // skip the reserved field
+        let packed = self.slice(pos + 4, len.div_ceil(4))?;
+        let mut sequence: Vec<u8> = (0..len).map(|i| BASES[(packed[i / 4] >> (6 - 2 * (i % 4)) & 3) as usize]).collect();
+        for (blocks, mask) in [(n_blocks, false), (mask_blocks, true)] {
+            for (start, size) in blocks {
+                let block = sequence.get_mut(start..start + size)
+                    .ok_or_else(|| Error::InvalidArguments("2bit block extends beyond its sequence".to_string()))?;
+                for base in block {
+                    *base = if mask { base.to_ascii_lowercase() } else { b'N' };
+                }
+            }
+        }
+        Ok(sequence)
+    }
+}
+
+impl Genome {
+    /// Parses the text of a FASTA file
+    ///
+    /// The sequence id is the header up to the first whitespace. Case is preserved, so soft-masking is kept.
+    pub fn from_fasta(text: &str) -> Result<Genome, Error> {
+        let mut genome = Genome::default();
+        for record in parse_fasta(text)? {
+            let seqid = SeqId::from(record.id);
+            if genome.sequences.contains_key(&seqid) {
+                return Err(Error::InvalidArguments(format!("sequence {} is repeated in FASTA", seqid)));
+            }
+            genome.sequences.insert(seqid, record.sequence.into_bytes());
+        }
+        Ok(genome)
+    }
+
+    /// Parses the bytes of a UCSC 2bit file, in either byte order and with 32 or 64-bit offsets
+    ///
+    /// N blocks are read as `N`, and masked blocks are lower case, as in soft-masked FASTA.
+    pub fn from_2bit(bytes: &[u8]) -> Result<Genome, Error> {
+        let mut reader = TwoBitReader { bytes, big_endian: false };
+        match reader.u32(0)? {
+            0x1A412743 => {}
+            0x4327411A => reader.big_endian = true,
+            _ => return Err(Error::InvalidArguments("2bit signature is invalid".to_string())),
+        }
+        let version = reader.u32(4)?;
+        if version > 1 {
+            return Err(Error::InvalidArguments(format!("2bit version {} is not supported", version)));
+        }
+        let seq_count = reader.u32(8)? as usize;
+        let mut genome = Genome::default();
+        let mut pos = 16;
+        for _ in 0..seq_count {
+            let name_len = *bytes.get(pos).ok_or_else(TwoBitReader::truncated)? as usize;
+            let name = std::str::from_utf8(reader.slice(pos + 1, name_len)?)
+                .map_err(|_| Error::InvalidArguments("2bit sequence names should be UTF-8".to_string()))?;
+            pos += 1 + name_len;
+            let offset = if version == 1 { reader.u64(pos)? as usize } else { reader.u32(pos)? as usize };
+            pos += if version == 1 { 8 } else { 4 };
+            let seqid = SeqId::from(name);
+            if genome.sequences.contains_key(&seqid) {
+                return Err(Error::InvalidArguments(format!("sequence {} is repeated in 2bit", seqid)));
+            }
+            genome.sequences.insert(seqid, reader.sequence(offset)?);
+        }
+        Ok(genome)
+    }
+
+    pub fn insert<T: Into<SeqId>>(&mut self, seqid: T, sequence: Vec<u8>) {
+        let seqid = seqid.into();
+        self.digests.shift_remove(&seqid);
+        self.sequences.insert(seqid, sequence);
+    }
+
+    /// Computes the digests of any sequences that don't have them yet
+    pub fn compute_digests(&mut self) {
+        for (seqid, sequence) in &self.sequences {
+            if !self.digests.contains_key(seqid) {
+                self.digests.insert(seqid.clone(), SequenceDigests::from_sequence(sequence));
+            }
+        }
+    }
+
+    /// The digests of a sequence, if they have been computed
+    pub fn digests(&self, seqid: &SeqId) -> Option<&SequenceDigests> {
+        self.digests.get(seqid)
+    }
+
+    pub fn get(&self, seqid: &SeqId) -> Option<&[u8]> {
+        self.sequences.get(seqid).map(|s| s.as_slice())
+    }
+
+    pub fn seq_len(&self, seqid: &SeqId) -> Option<u64> {
+        self.sequences.get(seqid).map(|s| s.len() as u64)
+    }
+
+    pub fn seqids(&self) -> impl Iterator<Item = &SeqId> {
+        self.sequences.keys()
+    }
+
+    /// The lengths of the sequences, in order
+    pub fn chrom_sizes(&self) -> ChromSizes {
+        self.sequences.iter().map(|(seqid, sequence)| (seqid.clone(), sequence.len() as u64)).collect()
+    }
+
+    /// Returns the forward strand sequence of the range
+    pub fn fetch(&self, range: &GenomicRange) -> Result<&[u8], Error> {
+        let sequence = self.get(range.seqid())
+            .ok_or_else(|| Error::InvalidArguments(format!("sequence {} is not in the genome", range.seqid())))?;
+        let interval = range.range_0halfopen();
+        if interval.end > sequence.len() as u64 {
+            return Err(Error::InvalidArguments(format!("range ends at {} beyond the sequence length {}", interval.end, sequence.len())));
+        }
+        Ok(&sequence[interval.start as usize..interval.end as usize])
+    }
+}
+
+/// A least-recently-used cache of sequence blocks over a loader, such as reads from an indexed FASTA file
+///
+/// Sequences are loaded in aligned blocks of `block_size` bases, so repeated small fetches from the same
+/// region, such as the exons of neighbouring genes, are served from memory. The loader is called with
+/// ranges within the sequence lengths, and should return the forward strand sequence of the range.
+pub struct CachedFetcher<F> {
+    loader: F,
+    seq_lens: IndexMap<SeqId, u64>,
+    block_size: u64,
+    capacity: usize,
+    // in order of last use, with the least recently used first
+    blocks: IndexMap<(SeqId, u64), Vec<u8>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<F: FnMut(&GenomicRange) -> Result<Vec<u8>, Error>> CachedFetcher<F> {
+    /// Creates a cache holding up to `capacity` blocks, which should be at least 1, as should the block size
+    pub fn new(loader: F, seq_lens: IndexMap<SeqId, u64>, block_size: u64, capacity: usize) -> Result<CachedFetcher<F>, Error> {
+        if block_size == 0 || capacity == 0 {
+            return Err(Error::InvalidArguments("the block size and capacity of a sequence cache should be at least 1".to_string()));
+        }
+        Ok(CachedFetcher { loader, seq_lens, block_size, capacity, blocks: IndexMap::new(), hits: 0, misses: 0 })
+    }
+
+    /// Returns the forward strand sequence of the range, loading any blocks that aren't cached
+    pub fn fetch(&mut self, range: &GenomicRange) -> Result<Vec<u8>, Error> {
+        let seq_len = *self.seq_lens.get(range.seqid())
+            .ok_or_else(|| Error::InvalidArguments(format!("sequence {} is not in the genome", range.seqid())))?;
+        let interval = range.range_0halfopen();
+        if interval.end > seq_len {
+            return Err(Error::InvalidArguments(format!("range ends at {} beyond the sequence length {}", interval.end, seq_len)));
+        }
+        let mut sequence = Vec::with_capacity((interval.end - interval.start) as usize);
+        if interval.is_empty() {
+            return Ok(sequence);
+        }
+        for block in interval.start / self.block_size..=(interval.end - 1) / self.block_size {
+            let block_start = block * self.block_size;
+            let key = (range.seqid().clone(), block);
+            let bases = match self.blocks.shift_remove(&key) {
+                Some(bases) => {
+                    self.hits += 1;
+                    bases
+                }
+                None => {
+                    self.misses += 1;
+                    let block_end = (block_start + self.block_size).min(seq_len);
+                    let bases = (self.loader)(&GenomicRange::from_0halfopen(range.seqid().clone(), block_start..block_end)?)?;
+                    if bases.len() as u64 != block_end - block_start {
+                        return Err(Error::InvalidArguments(format!("loaded {} bases for a block of {} bases of {}", bases.len(), block_end - block_start, range.seqid())));
+                    }
+                    bases
+                }
+            };
+            let start = interval.start.max(block_start) - block_start;
+            let end = interval.end.min(block_start + bases.len() as u64) - block_start;
+            sequence.extend_from_slice(&bases[start as usize..end as usize]);
+            self.blocks.insert(key, bases);
+            if self.blocks.len() > self.capacity {
+                self.blocks.shift_remove_index(0);
+            }
+        }
+        Ok(sequence)
+    }
+
+    /// The number of blocks served from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of blocks loaded
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+/// A single FASTA record
+///
+/// The id is the header up to the first whitespace, and the description is the rest of the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaRecord {
+    pub id: String,
+    pub description: Option<String>,
+    pub sequence: String,
+}
+
+impl Schema for FastaRecord {
+    fn fields() -> Vec<FieldDescriptor> {
+        vec![
+            FieldDescriptor::new("id", FieldType::Text),
+            FieldDescriptor::new("description", FieldType::Text).nullable(),
+            FieldDescriptor::new("sequence", FieldType::Text),
+        ]
+    }
+}
+
+impl FastaRecord {
+    /// Makes a record from a header, split into the id and description at the first whitespace
+    pub fn from_header(header: &str, sequence: String) -> FastaRecord {
+        let mut parts = header.splitn(2, char::is_whitespace);
+        FastaRecord {
+            id: parts.next().unwrap_or_default().to_string(),
+            description: parts.next().map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+            sequence,
+        }
+    }
+
+    /// Writes the record as FASTA, with the sequence wrapped at `line_width` characters, or on one line if `None`
+    pub fn to_fasta(&self, line_width: Option<usize>) -> String {
+        let mut fasta = match &self.description {
+            Some(description) => format!(">{} {}\n", self.id, description),
+            None => format!(">{}\n", self.id),
+        };
+        match line_width.filter(|w| *w > 0) {
+            Some(width) => {
+                for line in self.sequence.as_bytes().chunks(width) {
+                    fasta.push_str(&String::from_utf8_lossy(line));
+                    fasta.push('\n');
+                }
+            }
+            None if self.sequence.is_empty() => {}
+            None => {
+                fasta.push_str(&self.sequence);
+                fasta.push('\n');
+            }
+        }
+        fasta
+    }
+}
+
+/// Writes the record as FASTA, with the sequence wrapped at 60 characters
+impl fmt::Display for FastaRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_fasta(Some(60)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+/// A template for FASTA headers, with fields in braces e.g. `{gene_id}|{range}|{strand}`
+///
+/// Braces are escaped by doubling them. The id of the record is the rendered header up to the first whitespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl TryFrom<&str> for HeaderTemplate {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        field.push(c);
+                    }
+                    if !closed {
+                        return Err(Error::InvalidArguments(format!("header template {} has an unmatched {{", value)));
+                    }
+                    if field.is_empty() || field.contains('{') {
+                        return Err(Error::InvalidArguments(format!("header template {} has an invalid field", value)));
+                    }
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Field(field));
+                }
+                '}' => return Err(Error::InvalidArguments(format!("header template {} has an unmatched }}", value))),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(HeaderTemplate { parts })
+    }
+}
+
+impl HeaderTemplate {
+    /// Returns the names of the fields in the template
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Field(field) => Some(field.as_str()),
+            TemplatePart::Text(_) => None,
+        })
+    }
+
+    /// Fills in the fields, which are all required
+    pub fn render(&self, fields: &IndexMap<String, String>) -> Result<String, Error> {
+        let mut header = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => header.push_str(text),
+                TemplatePart::Field(field) => header.push_str(fields.get(field)
+                    .ok_or_else(|| Error::InvalidArguments(format!("no value for header field {}", field)))?),
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// Extracts the sequence of a range on a strand as a FASTA record named with a template
+///
+/// The sequence is reverse complemented on the minus strand. Along with the given fields, the template
+/// can use `seqid`, `start` and `end` (1-based), `range` (as `seqid:start-end`) and `strand`.
+pub fn extract_record(genome: &Genome, range: &GenomicRange, strand: Strand, template: &HeaderTemplate, fields: &IndexMap<String, String>) -> Result<FastaRecord, Error> {
+    let sequence = genome.fetch(range)?;
+    let sequence = match strand {
+        Strand::Minus => reverse_complement(sequence),
+        _ => sequence.to_vec(),
+    };
+    let interval = range.range_1closed();
+    let mut fields = fields.clone();
+    for (name, value) in [
+        ("seqid", range.seqid().to_string()),
+        ("start", interval.start().to_string()),
+        ("end", interval.end().to_string()),
+        ("range", range.to_string()),
+        ("strand", strand.to_string()),
+    ] {
+        fields.entry(name.to_string()).or_insert(value);
+    }
+    let header = template.render(&fields)?;
+    let record = FastaRecord::from_header(&header, String::from_utf8_lossy(&sequence).into_owned());
+    if record.id.is_empty() {
+        return Err(Error::InvalidArguments(format!("header {} has no id", header)));
+    }
+    Ok(record)
+}
+
+/// Parses the records in the text of a FASTA file
+pub fn parse_fasta(text: &str) -> Result<Vec<FastaRecord>, Error> {
+    let mut records: Vec<FastaRecord> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('>') {
+            let record = FastaRecord::from_header(header, String::new());
+            if record.id.is_empty() {
+                return Err(Error::InvalidArguments("FASTA header has no id".to_string()));
+            }
+            records.push(record);
+        } else if !line.is_empty() && !line.starts_with(';') {
+            records.last_mut()
+                .ok_or_else(|| Error::InvalidArguments("FASTA should start with a header".to_string()))?
+                .sequence.push_str(line);
+        }
+    }
+    Ok(records)
+}
+
+/// Reverse complements a nucleotide sequence, preserving case and IUPAC ambiguity codes
+pub fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence.iter().rev().map(|base| match base {
+        b'A' => b'T', b'T' => b'A', b'U' => b'A', b'G' => b'C', b'C' => b'G',
+        b'a' => b't', b't' => b'a', b'u' => b'a', b'g' => b'c', b'c' => b'g',
+        b'R' => b'Y', b'Y' => b'R', b'K' => b'M', b'M' => b'K', b'B' => b'V', b'V' => b'B', b'D' => b'H', b'H' => b'D',
+        b'r' => b'y', b'y' => b'r', b'k' => b'm', b'm' => b'k', b'b' => b'v', b'v' => b'b', b'd' => b'h', b'h' => b'd',
+        other => *other,
+    }).collect()
+}
+
+/// Translates a codon with the standard genetic code, giving `*` for stop and `X` for codons with ambiguous bases
+pub fn translate_codon(codon: &[u8]) -> u8 {
+    const AMINO_ACIDS: &[u8; 64] = b"KNKNTTTTRSRSIIMIQHQHPPPPRRRRLLLLEDEDAAAAGGGGVVVV*Y*YSSSS*CWCLFLF";
+    let index = |base: u8| match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' | b'U' => Some(3),
+        _ => None,
+    };
+    match codon {
+        [a, b, c] => match (index(*a), index(*b), index(*c)) {
+            (Some(a), Some(b), Some(c)) => AMINO_ACIDS[a * 16 + b * 4 + c],
+            _ => b'X',
+        },
+        _ => b'X',
+    }
+}
+
+/// Translates a coding sequence from its first base, ignoring any incomplete final codon
+pub fn translate(sequence: &[u8]) -> Vec<u8> {
+    sequence.chunks_exact(3).map(translate_codon).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks as (start, size)
+    type Blocks<'a> = &'a [(u32, u32)];
+
+    /// Encodes sequences as a version 0 2bit file, with their N and mask blocks
+    fn two_bit(records: &[(&str, &str, Blocks, Blocks)], big_endian: bool) -> Vec<u8> {
+        let word = |n: u32| if big_endian { n.to_be_bytes() } else { n.to_le_bytes() };
+        let index_len: usize = records.iter().map(|(name, ..)| 1 + name.len() + 4).sum();
+        let mut bytes: Vec<u8> = [word(0x1A412743), word(0), word(records.len() as u32), word(0)].concat();
+        let mut data = Vec::new();
+        for (name, sequence, n_blocks, mask_blocks) in records {
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&word((16 + index_len + data.len()) as u32));
+            data.extend_from_slice(&word(sequence.len() as u32));
+            for blocks in [n_blocks, mask_blocks] {
+                data.extend_from_slice(&word(blocks.len() as u32));
+                data.extend(blocks.iter().flat_map(|(start, _)| word(*start)));
+                data.extend(blocks.iter().flat_map(|(_, size)| word(*size)));
+            }
+            data.extend_from_slice(&word(0));
+            for chunk in sequence.as_bytes().chunks(4) {
+                let code = |base: &u8| match base.to_ascii_uppercase() { b'C' => 1, b'A' => 2, b'G' => 3, _ => 0 };
+                data.push(chunk.iter().enumerate().fold(0, |byte, (i, base)| byte | code(base) << (6 - 2 * i)));
+            }
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn reads_2bit_in_either_byte_order() {
+        for big_endian in [false, true] {
+            let bytes = two_bit(&[("chr1", "ACGTTTACGTAC", &[(4, 2)], &[(6, 3)]), ("chr2", "GGA", &[], &[])], big_endian);
+            let genome = Genome::from_2bit(&bytes).unwrap();
+            assert_eq!(genome.get(&SeqId::from("chr1")), Some(&b"ACGTNNacgTAC"[..]));
+            assert_eq!(genome.get(&SeqId::from("chr2")), Some(&b"GGA"[..]));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_2bit() {
+        let bytes = two_bit(&[("chr1", "ACGTTTACGTAC", &[(10, 5)], &[])], false);
+        assert!(Genome::from_2bit(&bytes).is_err());
+        assert!(Genome::from_2bit(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Genome::from_2bit(b"not a 2bit file").is_err());
+    }
+
+    #[test]
+    fn cached_fetches_load_each_block_once() {
+        let genome = Genome::from_fasta(">chr1\nACGTACGTAC\n>chr2\nGGG\n").unwrap();
+        let mut loads = Vec::new();
+        let loader = |range: &GenomicRange| {
+            loads.push(range.to_string());
+            genome.fetch(range).map(|bases| bases.to_vec())
+        };
+        let mut fetcher = CachedFetcher::new(loader, genome.chrom_sizes().into_inner(), 4, 2).unwrap();
+        assert_eq!(fetcher.fetch(&"chr1:3-6".parse().unwrap()).unwrap(), b"GTAC");
+        assert_eq!(fetcher.fetch(&"chr1:9-10".parse().unwrap()).unwrap(), b"AC");
+        // the first block was used least recently, so was evicted
+        assert_eq!(fetcher.fetch(&"chr1:5-5".parse().unwrap()).unwrap(), b"A");
+        assert_eq!(fetcher.fetch(&"chr1:1-1".parse().unwrap()).unwrap(), b"A");
+        assert_eq!((fetcher.hits(), fetcher.misses()), (1, 4));
+        assert!(fetcher.fetch(&"chr2:1-4".parse().unwrap()).is_err());
+        drop(fetcher);
+        assert_eq!(loads, ["chr1:1-4", "chr1:5-8", "chr1:9-10", "chr1:1-4"]);
+    }
+
+    #[test]
+    fn header_templates_need_closed_fields() {
+        let template = HeaderTemplate::try_from("{gene_id}|{{{range}}}").unwrap();
+        assert_eq!(template.fields().collect::<Vec<_>>(), ["gene_id", "range"]);
+        let fields = IndexMap::from([("gene_id".to_string(), "g1".to_string()), ("range".to_string(), "chr1:1-10".to_string())]);
+        assert_eq!(template.render(&fields).unwrap(), "g1|{chr1:1-10}");
+        assert!(HeaderTemplate::try_from("{gene_id}|{range").is_err());
+        assert!(HeaderTemplate::try_from("{gene_id").is_err());
+        assert!(HeaderTemplate::try_from("{}").is_err());
+        assert!(HeaderTemplate::try_from("gene}").is_err());
+    }
+}
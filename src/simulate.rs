@@ -0,0 +1,283 @@
+//! Random generation of reproducible test data
+//!
+//! The generators produce chromosome sizes, gene models, peaks and signal tracks as the
+//! same row types that are read from files, so they can stand in for real inputs.
+//! Each takes a seed, and the same seed always gives the same output.
+
+use std::ops::RangeInclusive;
+use indexmap::IndexMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::format::{Bed6Row, BedGraphRow, Gff3Row, Strand};
+use crate::genome::{ChromSizes, Error, SeqId};
+
+/// Generates `n` chromosomes named `chr1`, `chr2` etc. with lengths drawn uniformly from `lengths`
+pub fn chrom_sizes(n: usize, lengths: RangeInclusive<u64>, seed: u64) -> Result<ChromSizes, Error> {
+    if lengths.is_empty() || *lengths.start() == 0 {
+        return Err(Error::InvalidArguments("chromosome lengths should be a non-empty range above 0".to_string()));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    Ok((1..=n)
+        .map(|i| (SeqId::from(format!("chr{}", i)), rng.random_range(lengths.clone())))
+        .collect())
+}
+
+/// Parameters of the simulated gene models
+///
+/// Lengths are in bases and are drawn uniformly from each range.
+#[derive(Debug, Clone)]
+pub struct GeneModelParams {
+    /// Maximum number of exons per transcript, with fewer exons being more likely
+    pub max_exons: usize,
+    pub exon_len: RangeInclusive<u64>,
+    pub intron_len: RangeInclusive<u64>,
+    pub intergenic_len: RangeInclusive<u64>,
+    /// Untranslated bases at each end of the transcript, before the start and after the stop codon
+    pub utr_len: RangeInclusive<u64>,
+}
+
+impl Default for GeneModelParams {
+    fn default() -> Self {
+        GeneModelParams {
+            max_exons: 12,
+            exon_len: 50..=400,
+            intron_len: 80..=5000,
+            intergenic_len: 1000..=20000,
+            utr_len: 0..=200,
+        }
+    }
+}
+
+/// Generates non-overlapping gene models along each chromosome as GFF3 rows
+///
+/// Each gene has one `mRNA` with `exon` and `CDS` children, linked with `ID` and `Parent` attributes.
+/// Genes are placed one after another until the end of the chromosome is reached, and the rows
+/// are in the order of a sorted GFF3 file, with parents before their children.
+pub fn gene_models(chrom_sizes: &IndexMap<SeqId, u64>, params: &GeneModelParams, seed: u64) -> Result<Vec<Gff3Row<String>>, Error> {
+    if params.max_exons == 0 || params.exon_len.is_empty() || *params.exon_len.start() == 0
+        || params.intron_len.is_empty() || params.intergenic_len.is_empty() || params.utr_len.is_empty() {
+        return Err(Error::InvalidArguments("gene model parameters should have at least one exon and non-empty length ranges".to_string()));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rows = Vec::new();
+    let mut gene_num = 0;
+    for (seqid, &seq_len) in chrom_sizes {
+        // 0-based position of the next base available for a gene
+        let mut pos = rng.random_range(params.intergenic_len.clone());
+        loop {
+            let exons = simulate_exons(pos, params, &mut rng);
+            let end = exons.last().unwrap().1;
+            if end > seq_len {
+                break;
+            }
+            gene_num += 1;
+            let strand = if rng.random_bool(0.5) { Strand::Plus } else { Strand::Minus };
+            let utr5 = rng.random_range(params.utr_len.clone());
+            let utr3 = rng.random_range(params.utr_len.clone());
+            rows.extend(gene_rows(seqid, gene_num, strand, &exons, utr5, utr3));
+            pos = end + rng.random_range(params.intergenic_len.clone());
+        }
+    }
+    Ok(rows)
+}
+
+/// Returns the 0-based, half-open exons of a transcript starting at `start`
+fn simulate_exons(start: u64, params: &GeneModelParams, rng: &mut StdRng) -> Vec<(u64, u64)> {
+    // each extra exon is added with probability 0.8, giving a geometric distribution up to the maximum
+    let mut n = 1;
+    while n < params.max_exons && rng.random_bool(0.8) {
+        n += 1;
+    }
+    let mut exons = Vec::with_capacity(n);
+    let mut pos = start;
+    for i in 0..n {
+        if i > 0 {
+            pos += rng.random_range(params.intron_len.clone());
+        }
+        let end = pos + rng.random_range(params.exon_len.clone());
+        exons.push((pos, end));
+        pos = end;
+    }
+    exons
+}
+
+fn gene_rows(seqid: &SeqId, gene_num: usize, strand: Strand, exons: &[(u64, u64)], utr5: u64, utr3: u64) -> Vec<Gff3Row<String>> {
+    let gene_id = format!("gene{:05}", gene_num);
+    let mrna_id = format!("mRNA{:05}", gene_num);
+    let start = exons.first().unwrap().0;
+    let end = exons.last().unwrap().1;
+    let row = |feature_type: &str, range: (u64, u64), phase: String, attributes: Vec<(&str, String)>| Gff3Row {
+        seqid: seqid.clone(),
+        source: "gannot".to_string(),
+        feature_type: feature_type.to_string(),
+        start: range.0 + 1,
+        end: range.1,
+        score: ".".to_string(),
+        strand,
+        phase,
+        attributes: attributes.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+    };
+
+    let mut rows = vec![
+        row("gene", (start, end), ".".to_string(), vec![("ID", gene_id.clone())]),
+        row("mRNA", (start, end), ".".to_string(), vec![("ID", mrna_id.clone()), ("Parent", gene_id)]),
+    ];
+    for (i, &exon) in exons.iter().enumerate() {
+        // exons are numbered in the direction of transcription
+        let exon_num = if strand == Strand::Minus { exons.len() - i } else { i + 1 };
+        rows.push(row("exon", exon, ".".to_string(), vec![("ID", format!("{}.exon{}", mrna_id, exon_num)), ("Parent", mrna_id.clone())]));
+    }
+
+    // the coding region is the transcript without its UTRs, provided enough remains for a codon
+    let tx_len: u64 = exons.iter().map(|(s, e)| e - s).sum();
+    if utr5 + utr3 + 3 > tx_len {
+        return rows;
+    }
+    // the 3' UTR takes up any partial codon, so the CDS is whole codons
+    let utr3 = utr3 + (tx_len - utr5 - utr3) % 3;
+    let (left_utr, right_utr) = match strand {
+        Strand::Minus => (utr3, utr5),
+        _ => (utr5, utr3),
+    };
+    let cds_start = transcript_to_genome(exons, left_utr);
+    let cds_end = transcript_to_genome(exons, tx_len - right_utr - 1) + 1;
+    let mut cds: Vec<(u64, u64)> = exons.iter()
+        .filter(|(s, e)| *e > cds_start && *s < cds_end)
+        .map(|(s, e)| ((*s).max(cds_start), (*e).min(cds_end)))
+        .collect();
+    if strand == Strand::Minus {
+        cds.reverse();
+    }
+    let mut cds_rows = Vec::with_capacity(cds.len());
+    let mut coding_len = 0;
+    for segment in cds {
+        let phase = (3 - coding_len % 3) % 3;
+        cds_rows.push(row("CDS", segment, phase.to_string(), vec![("ID", format!("{}.cds", mrna_id)), ("Parent", mrna_id.clone())]));
+        coding_len += segment.1 - segment.0;
+    }
+    cds_rows.sort_by_key(|r| r.start);
+    rows.extend(cds_rows);
+    rows
+}
+
+/// Converts a 0-based offset along the spliced exons (left to right) to a genome position
+fn transcript_to_genome(exons: &[(u64, u64)], mut offset: u64) -> u64 {
+    for (start, end) in exons {
+        if offset < end - start {
+            return start + offset;
+        }
+        offset -= end - start;
+    }
+    panic!("offset beyond the end of the transcript");
+}
+
+/// Generates `n` peaks per chromosome with widths drawn uniformly from `widths`
+///
+/// Peaks are placed uniformly at random and may overlap. They are named `peak1`, `peak2` etc.
+/// in sorted order, and have a random score up to 1000 as used by the UCSC browser.
+pub fn peaks(chrom_sizes: &IndexMap<SeqId, u64>, n: usize, widths: RangeInclusive<u64>, seed: u64) -> Result<Vec<Bed6Row>, Error> {
+    if widths.is_empty() || *widths.start() == 0 {
+        return Err(Error::InvalidArguments("peak widths should be a non-empty range above 0".to_string()));
+    }
+    if let Some((seqid, len)) = chrom_sizes.iter().find(|(_, len)| **len < *widths.end()) {
+        return Err(Error::InvalidArguments(format!("{} of length {} is shorter than the maximum peak width", seqid, len)));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rows = Vec::with_capacity(n * chrom_sizes.len());
+    for (seqid, &seq_len) in chrom_sizes {
+        let mut ranges: Vec<(u64, u64)> = (0..n)
+            .map(|_| {
+                let width = rng.random_range(widths.clone());
+                let start = rng.random_range(0..=seq_len - width);
+                (start, start + width)
+            })
+            .collect();
+        ranges.sort();
+        rows.extend(ranges.into_iter().map(|(start, end)| Bed6Row {
+            chrom: seqid.clone(),
+            chrom_start: start,
+            chrom_end: end,
+            name: String::new(),
+            score: rng.random_range(0..=1000),
+            strand: Strand::None,
+        }));
+    }
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.name = format!("peak{}", i + 1);
+    }
+    Ok(rows)
+}
+
+/// Generates a signal track over fixed-width bins covering each chromosome
+///
+/// The values follow a non-negative random walk, so neighbouring bins have similar signal
+/// as in real coverage tracks. The final bin of each chromosome is truncated at its end.
+pub fn signal(chrom_sizes: &IndexMap<SeqId, u64>, bin_size: u64, seed: u64) -> Result<Vec<BedGraphRow<f64>>, Error> {
+    if bin_size == 0 {
+        return Err(Error::InvalidArguments("bin size should be above 0".to_string()));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rows = Vec::new();
+    for (seqid, &seq_len) in chrom_sizes {
+        let mut value: f64 = rng.random_range(0.0..10.0);
+        for start in (0..seq_len).step_by(bin_size as usize) {
+            value = (value + rng.random_range(-1.0..1.0)).max(0.0);
+            rows.push(BedGraphRow {
+                chrom: seqid.clone(),
+                chrom_start: start,
+                chrom_end: (start + bin_size).min(seq_len),
+                data_value: value,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::build_gene_models;
+
+    #[test]
+    fn chrom_sizes_are_reproducible() {
+        let sizes = chrom_sizes(3, 1000..=2000, 1).unwrap();
+        assert_eq!(sizes.keys().map(|s| s.to_string()).collect::<Vec<_>>(), ["chr1", "chr2", "chr3"]);
+        assert!(sizes.values().all(|len| (1000..=2000).contains(len)));
+        assert_eq!(sizes, chrom_sizes(3, 1000..=2000, 1).unwrap());
+        assert!(chrom_sizes(3, 0..=10, 1).is_err());
+    }
+
+    #[test]
+    fn gene_models_build_within_their_chromosomes() {
+        let sizes = chrom_sizes(2, 50_000..=100_000, 2).unwrap();
+        let rows = gene_models(&sizes, &GeneModelParams::default(), 2).unwrap();
+        assert!(rows.iter().all(|row| row.start >= 1 && row.end <= sizes[&row.seqid]));
+        let genes = build_gene_models(rows).unwrap();
+        assert!(!genes.is_empty());
+        for gene in &genes {
+            let transcript = &gene.transcripts[0];
+            assert!(!transcript.exons.is_empty() && !transcript.cds.is_empty());
+            let cds_len: u64 = transcript.cds.iter().map(|row| row.end - row.start + 1).sum();
+            assert_eq!(cds_len % 3, 0);
+        }
+        let params = GeneModelParams { max_exons: 0, ..Default::default() };
+        assert!(gene_models(&sizes, &params, 2).is_err());
+    }
+
+    #[test]
+    fn peaks_and_signal_cover_each_chromosome() {
+        let sizes = chrom_sizes(2, 500..=600, 3).unwrap();
+        let peaks = peaks(&sizes, 4, 10..=50, 3).unwrap();
+        assert_eq!(peaks.len(), 8);
+        assert_eq!(peaks[7].name, "peak8");
+        assert!(peaks.iter().all(|row| row.chrom_end <= sizes[&row.chrom] && row.score <= 1000));
+
+        let signal = signal(&sizes, 100, 3).unwrap();
+        for (seqid, &len) in sizes.iter() {
+            let bins: Vec<_> = signal.iter().filter(|row| &row.chrom == seqid).collect();
+            assert_eq!(bins.len() as u64, len.div_ceil(100));
+            assert_eq!(bins.last().unwrap().chrom_end, len);
+        }
+        assert!(signal.iter().all(|row| row.data_value >= 0.0));
+    }
+}
@@ -0,0 +1,74 @@
+//! Export of GFF3 rows as SQL for DuckDB or SQLite
+//!
+//! [`to_sql`] writes a script that creates and fills two tables: one row per feature with typed
+//! columns, and the attributes in long format, one row per value, so that annotation can be queried
+//! with SQL straight away e.g. `duckdb annotation.db < annotation.sql`.
+
+use std::fmt::Write;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::Error;
+
+/// Quotes a string as an SQL literal
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Writes the rows as an SQL script creating the tables `<table>` and `<table>_attributes`
+///
+/// Features are numbered from 1 in the `id` column, which the `feature_id` of attributes refers to.
+/// Missing scores, strands and phases (`.`) are `NULL`. Attribute values are split on commas, as GFF3
+/// separates multiple values with them. The table name should be a plain identifier.
+pub fn to_sql<T: AsRef<str>>(rows: &[Gff3Row<T>], table: &str) -> Result<String, Error> {
+    let valid = table.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::InvalidArguments(format!("{} is not a valid table name", table)));
+    }
+    let mut sql = String::from("BEGIN TRANSACTION;\n");
+    writeln!(sql, "CREATE TABLE {} (id BIGINT PRIMARY KEY, seqid TEXT NOT NULL, source TEXT NOT NULL, type TEXT NOT NULL, \
+        start BIGINT NOT NULL, \"end\" BIGINT NOT NULL, score DOUBLE, strand TEXT, phase INTEGER);", table).unwrap();
+    writeln!(sql, "CREATE TABLE {0}_attributes (feature_id BIGINT NOT NULL REFERENCES {0}(id), key TEXT NOT NULL, value TEXT NOT NULL);", table).unwrap();
+    for (i, row) in rows.iter().enumerate() {
+        let id = i + 1;
+        let score = row.score.parse::<f64>().ok().filter(|s| s.is_finite()).map_or_else(|| "NULL".to_string(), |s| s.to_string());
+        let strand = match row.strand {
+            Strand::None => "NULL".to_string(),
+            strand => quote(&strand.to_string()),
+        };
+        let phase = row.phase.parse::<u8>().map_or_else(|_| "NULL".to_string(), |p| p.to_string());
+        writeln!(sql, "INSERT INTO {} VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {});", table, id, quote(row.seqid.as_str()),
+            quote(&row.source), quote(row.feature_type.as_ref()), row.start, row.end, score, strand, phase).unwrap();
+        for (key, values) in &row.attributes {
+            for value in values.split(',') {
+                writeln!(sql, "INSERT INTO {}_attributes VALUES ({}, {}, {});", table, id, quote(key), quote(value)).unwrap();
+            }
+        }
+    }
+    sql.push_str("COMMIT;\n");
+    Ok(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+
+    #[test]
+    fn writes_features_and_attributes() {
+        let gff = "\
+chr1\tsrc\tgene\t1\t10\t.\t+\t.\tID=g1;Note=it's,two
+chr1\tsrc\tCDS\t1\t9\t5.5\t.\t0\tParent=g1
+";
+        let sql = to_sql(&parse_gff3(gff, ParseMode::Strict).unwrap(), "features").unwrap();
+        let lines: Vec<_> = sql.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[3], "INSERT INTO features VALUES (1, 'chr1', 'src', 'gene', 1, 10, NULL, '+', NULL);");
+        assert_eq!(lines[5], "INSERT INTO features_attributes VALUES (1, 'Note', 'it''s');");
+        assert_eq!(lines[6], "INSERT INTO features_attributes VALUES (1, 'Note', 'two');");
+        assert_eq!(lines[7], "INSERT INTO features VALUES (2, 'chr1', 'src', 'CDS', 1, 9, 5.5, NULL, 0);");
+        assert_eq!(lines[9], "COMMIT;");
+        assert!(to_sql::<String>(&[], "1features").is_err());
+        assert!(to_sql::<String>(&[], "features; DROP TABLE x").is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Summary statistics of gene models
+//!
+//! The statistics are intended for comparing the quality of annotations e.g. of different assemblies
+//! or annotation pipelines, and can be serialized for reporting.
+
+use serde::Serialize;
+
+use crate::models::Gene;
+
+/// Summary of a distribution of counts or lengths
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub n: usize,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub total: u64,
+}
+
+impl Distribution {
+    pub fn from_values(mut values: Vec<u64>) -> Distribution {
+        values.sort_unstable();
+        let n = values.len();
+        let total = values.iter().sum();
+        let median = match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(values[n / 2] as f64),
+            _ => Some((values[n / 2 - 1] + values[n / 2]) as f64 / 2.0),
+        };
+        Distribution {
+            n,
+            min: values.first().copied(),
+            max: values.last().copied(),
+            mean: (n > 0).then(|| total as f64 / n as f64),
+            median,
+            total,
+        }
+    }
+}
+
+/// Gene model statistics of an annotation
+///
+/// Transcript-level distributions are over all transcripts, and UTR lengths are over coding transcripts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GeneModelStats {
+    pub genes: usize,
+    pub transcripts: usize,
+    pub coding_transcripts: usize,
+    pub transcripts_per_gene: Distribution,
+    pub exons_per_transcript: Distribution,
+    pub exon_lengths: Distribution,
+    pub intron_lengths: Distribution,
+    pub transcript_lengths: Distribution,
+    pub cds_lengths: Distribution,
+    pub utr5_lengths: Distribution,
+    pub utr3_lengths: Distribution,
+    /// The fraction of genes whose transcripts all have a single exon
+    pub single_exon_gene_fraction: Option<f64>,
+}
+
+/// Computes the statistics of a set of gene models
+pub fn gene_model_stats<T>(genes: &[Gene<T>]) -> GeneModelStats {
+    let mut transcripts_per_gene = Vec::new();
+    let mut exons_per_transcript = Vec::new();
+    let mut exon_lengths = Vec::new();
+    let mut intron_lengths = Vec::new();
+    let mut transcript_lengths = Vec::new();
+    let mut cds_lengths = Vec::new();
+    let mut utr5_lengths = Vec::new();
+    let mut utr3_lengths = Vec::new();
+    let mut single_exon_genes = 0;
+
+    for gene in genes {
+        transcripts_per_gene.push(gene.transcripts.len() as u64);
+        if !gene.transcripts.is_empty() && gene.transcripts.iter().all(|t| t.exon_ranges().len() == 1) {
+            single_exon_genes += 1;
+        }
+        for transcript in &gene.transcripts {
+            let exons = transcript.exon_ranges();
+            exons_per_transcript.push(exons.len() as u64);
+            exon_lengths.extend(exons.iter().map(|r| r.range_0halfopen().end - r.range_0halfopen().start));
+            intron_lengths.extend(transcript.introns().iter().map(|r| r.range_0halfopen().end - r.range_0halfopen().start));
+            transcript_lengths.push(transcript.exonic_len());
+            if let Some((utr5, utr3)) = transcript.utr_lens() {
+                cds_lengths.push(transcript.cds_len());
+                utr5_lengths.push(utr5);
+                utr3_lengths.push(utr3);
+            }
+        }
+    }
+
+    GeneModelStats {
+        genes: genes.len(),
+        transcripts: exons_per_transcript.len(),
+        coding_transcripts: cds_lengths.len(),
+        transcripts_per_gene: Distribution::from_values(transcripts_per_gene),
+        exons_per_transcript: Distribution::from_values(exons_per_transcript),
+        exon_lengths: Distribution::from_values(exon_lengths),
+        intron_lengths: Distribution::from_values(intron_lengths),
+        transcript_lengths: Distribution::from_values(transcript_lengths),
+        cds_lengths: Distribution::from_values(cds_lengths),
+        utr5_lengths: Distribution::from_values(utr5_lengths),
+        utr3_lengths: Distribution::from_values(utr3_lengths),
+        single_exon_gene_fraction: (!genes.is_empty()).then(|| single_exon_genes as f64 / genes.len() as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    #[test]
+    fn distributions_of_empty_and_even_inputs() {
+        assert_eq!(Distribution::from_values(vec![]), Distribution::default());
+        let dist = Distribution::from_values(vec![4, 1, 3, 2]);
+        assert_eq!((dist.min, dist.max, dist.median, dist.mean, dist.total), (Some(1), Some(4), Some(2.5), Some(2.5), 10));
+    }
+
+    #[test]
+    fn summarises_gene_models() {
+        let gff = "\
+chr1\t.\tgene\t101\t400\t.\t-\t.\tID=g1
+chr1\t.\tmRNA\t101\t400\t.\t-\t.\tID=t1;Parent=g1
+chr1\t.\texon\t101\t200\t.\t-\t.\tParent=t1
+chr1\t.\texon\t301\t400\t.\t-\t.\tParent=t1
+chr1\t.\tCDS\t151\t200\t.\t-\t2\tParent=t1
+chr1\t.\tCDS\t301\t380\t.\t-\t0\tParent=t1
+chr1\t.\tgene\t1001\t1100\t.\t+\t.\tID=g2
+chr1\t.\tncRNA\t1001\t1100\t.\t+\t.\tID=t2;Parent=g2
+chr1\t.\texon\t1001\t1100\t.\t+\t.\tParent=t2
+chr1\t.\tncRNA\t1001\t1050\t.\t+\t.\tID=t3;Parent=g2
+chr1\t.\texon\t1001\t1050\t.\t+\t.\tParent=t3
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let stats = gene_model_stats(&genes);
+        assert_eq!((stats.genes, stats.transcripts, stats.coding_transcripts), (2, 3, 1));
+        assert_eq!(stats.transcripts_per_gene.median, Some(1.5));
+        assert_eq!(stats.exon_lengths, Distribution::from_values(vec![100, 100, 100, 50]));
+        assert_eq!(stats.intron_lengths.total, 100);
+        assert_eq!(stats.transcript_lengths.max, Some(200));
+        assert_eq!(stats.cds_lengths.total, 130);
+        // the 5' UTR of a minus strand transcript is on its right
+        assert_eq!((stats.utr5_lengths.total, stats.utr3_lengths.total), (20, 50));
+        assert_eq!(stats.single_exon_gene_fraction, Some(0.5));
+        assert_eq!(gene_model_stats::<String>(&[]).single_exon_gene_fraction, None);
+    }
+}
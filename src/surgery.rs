@@ -0,0 +1,505 @@
+//! Editing gene models for curation
+//!
+//! The methods here change the structure of [`Gene`]s and [`Transcript`]s, and then re-derive
+//! whatever depends on it: the bounds of parents, the phases of CDS segments and the `Parent`
+//! attributes. Existing `ID`s are kept, and features that are created, or moved to a new parent,
+//! are given IDs derived from their parent e.g. `<transcript>.exon3` or `<transcript>.cds`.
+//!
+//! Transcripts are edited through their gene, with [`Gene::add_exon`], [`Gene::remove_exon`] and
+//! [`Gene::set_cds`], so that the gene bounds follow. Edits can be made through a [`ChangeLog`] to
+//! record the features they change.
+
+use serde::Serialize;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::{Error, GenomicRange, SeqId};
+use crate::models::{Gene, Transcript};
+
+fn child_row<T: Clone>(template: &Gff3Row<T>, feature_type: T, start: u64, end: u64, id: String, parent: &str) -> Gff3Row<T> {
+    let mut attributes = template.attributes.clone();
+    attributes.retain(|key, _| key != "ID" && key != "Parent" && key != "Name");
+    attributes.insert("ID".to_string(), id);
+    attributes.insert("Parent".to_string(), parent.to_string());
+    attributes.sort_by_cached_key(|key, _| key != "ID" && key != "Parent");
+    Gff3Row {
+        feature_type,
+        start,
+        end,
+        score: ".".to_string(),
+        phase: ".".to_string(),
+        attributes,
+        ..template.clone()
+    }
+}
+
+fn row_id<T>(row: &Gff3Row<T>) -> Result<String, Error> {
+    row.attributes.get("ID").cloned()
+        .ok_or_else(|| Error::InvalidArguments(format!("{}:{}-{} has no ID", row.seqid, row.start, row.end)))
+}
+
+impl<T: Clone + for<'a> From<&'a str>> Transcript<T> {
+    /// Adds an exon, which must be on the same sequence as the transcript and not overlap its other exons
+    ///
+    /// The transcript is extended if needed, but not its gene, so edit it with [`Gene::add_exon`]
+    /// when it has one. Returns the ID of the new exon.
+    pub fn add_exon(&mut self, range: &GenomicRange) -> Result<String, Error> {
+        if *range.seqid() != self.row.seqid {
+            return Err(Error::InvalidArguments(format!("exon on {} can't be added to a transcript on {}", range.seqid(), self.row.seqid)));
+        }
+        let new = range.range_1closed();
+        if self.exons.iter().any(|e| e.start <= *new.end() && *new.start() <= e.end) {
+            return Err(Error::InvalidArguments(format!("exon {}:{}-{} overlaps an existing exon", range.seqid(), new.start(), new.end())));
+        }
+        let tx_id = row_id(&self.row)?;
+        let mut n = self.exons.len() + 1;
+        while self.exons.iter().any(|e| e.attributes.get("ID") == Some(&format!("{}.exon{}", tx_id, n))) {
+            n += 1;
+        }
+        let id = format!("{}.exon{}", tx_id, n);
+        let template = self.exons.first().unwrap_or(&self.row);
+        self.exons.push(child_row(template, T::from("exon"), *new.start(), *new.end(), id.clone(), &tx_id));
+        self.rederive();
+        Ok(id)
+    }
+
+    /// Removes the exon at `index`, in order of position, along with any CDS segments within it
+    pub fn remove_exon(&mut self, index: usize) -> Result<Gff3Row<T>, Error> {
+        if index >= self.exons.len() {
+            return Err(Error::InvalidArguments(format!("transcript has {} exons, so can't remove exon {}", self.exons.len(), index)));
+        }
+        let exon = self.exons.remove(index);
+        self.cds.retain(|c| c.end < exon.start || c.start > exon.end);
+        self.rederive();
+        Ok(exon)
+    }
+
+    /// Sets the CDS to the exonic parts of `range`, which is from the first base of the start codon
+    /// to the last base of the stop codon, or makes the transcript non-coding with `None`
+    ///
+    /// The CDS segments are recreated, with phases for a complete CDS.
+    pub fn set_cds(&mut self, range: Option<&GenomicRange>) -> Result<(), Error> {
+        let Some(range) = range else {
+            self.cds.clear();
+            self.rederive();
+            return Ok(());
+        };
+        if *range.seqid() != self.row.seqid {
+            return Err(Error::InvalidArguments(format!("CDS on {} can't be set on a transcript on {}", range.seqid(), self.row.seqid)));
+        }
+        let tx_id = row_id(&self.row)?;
+        let id = self.cds.first()
+            .and_then(|c| c.attributes.get("ID").cloned())
+            .unwrap_or_else(|| format!("{}.cds", tx_id));
+        let template = self.cds.first().or(self.exons.first()).unwrap_or(&self.row).clone();
+        let bounds = range.range_1closed();
+        let segments: Vec<_> = self.exon_ranges().iter()
+            .map(|e| e.range_1closed())
+            .filter(|e| e.start() <= bounds.end() && bounds.start() <= e.end())
+            .map(|e| ((*e.start()).max(*bounds.start()), (*e.end()).min(*bounds.end())))
+            .collect();
+        if segments.is_empty() {
+            return Err(Error::InvalidArguments("CDS doesn't overlap any exon".to_string()));
+        }
+        self.cds = segments.into_iter()
+            .map(|(start, end)| {
+                let mut row = child_row(&template, T::from("CDS"), start, end, id.clone(), &tx_id);
+                row.phase = "0".to_string();
+                row
+            })
+            .collect();
+        self.rederive();
+        Ok(())
+    }
+}
+
+impl<T> Transcript<T> {
+    /// Re-derives the order of the parts, the `Parent` of each part, the transcript bounds and the CDS phases
+    ///
+    /// The phase of the first CDS segment, in the direction of transcription, is kept so that
+    /// 5' partial CDS are preserved, and the other phases follow from it.
+    pub fn rederive(&mut self) {
+        self.exons.sort_by_key(|r| r.start);
+        self.cds.sort_by_key(|r| r.start);
+        self.others.sort_by_key(|r| r.start);
+        if let Some(id) = self.row.attributes.get("ID") {
+            for row in self.exons.iter_mut().chain(self.cds.iter_mut()).chain(self.others.iter_mut()) {
+                row.attributes.insert("Parent".to_string(), id.clone());
+            }
+        }
+        let parts = || self.exons.iter().chain(self.cds.iter()).chain(self.others.iter());
+        if let (Some(start), Some(end)) = (parts().map(|r| r.start).min(), parts().map(|r| r.end).max()) {
+            self.row.start = start;
+            self.row.end = end;
+        }
+
+        let minus = self.row.strand == Strand::Minus;
+        let mut order: Vec<usize> = (0..self.cds.len()).collect();
+        if minus {
+            order.reverse();
+        }
+        let first_phase = order.first()
+            .and_then(|&i| self.cds[i].phase.parse::<u64>().ok())
+            .unwrap_or(0);
+        // bases from the start of the first complete codon
+        let mut coding_len = 0;
+        for (n, &i) in order.iter().enumerate() {
+            let phase = if n == 0 { first_phase } else { (3 - coding_len % 3) % 3 };
+            let segment = &mut self.cds[i];
+            segment.phase = phase.to_string();
+            let len = segment.end - segment.start + 1;
+            coding_len += if n == 0 { len.saturating_sub(first_phase) } else { len };
+        }
+    }
+}
+
+impl<T: Clone + for<'a> From<&'a str>> Gene<T> {
+    fn transcript_mut(&mut self, transcript_id: &str) -> Result<&mut Transcript<T>, Error> {
+        let gene_id = self.id().unwrap_or_default().to_string();
+        self.transcripts.iter_mut()
+            .find(|t| t.id() == Some(transcript_id))
+            .ok_or_else(|| Error::InvalidArguments(format!("gene {} has no transcript {}", gene_id, transcript_id)))
+    }
+
+    /// Adds an exon to a transcript with [`Transcript::add_exon`], then re-derives the gene
+    pub fn add_exon(&mut self, transcript_id: &str, range: &GenomicRange) -> Result<String, Error> {
+        let id = self.transcript_mut(transcript_id)?.add_exon(range)?;
+        self.rederive();
+        Ok(id)
+    }
+
+    /// Removes an exon of a transcript with [`Transcript::remove_exon`], then re-derives the gene
+    pub fn remove_exon(&mut self, transcript_id: &str, index: usize) -> Result<Gff3Row<T>, Error> {
+        let exon = self.transcript_mut(transcript_id)?.remove_exon(index)?;
+        self.rederive();
+        Ok(exon)
+    }
+
+    /// Sets the CDS of a transcript with [`Transcript::set_cds`], then re-derives the gene
+    pub fn set_cds(&mut self, transcript_id: &str, range: Option<&GenomicRange>) -> Result<(), Error> {
+        self.transcript_mut(transcript_id)?.set_cds(range)?;
+        self.rederive();
+        Ok(())
+    }
+}
+
+impl<T> Gene<T> {
+    /// Re-derives each transcript, then the `Parent` of each transcript and the gene bounds
+    pub fn rederive(&mut self) {
+        for transcript in &mut self.transcripts {
+            transcript.rederive();
+            if let Some(id) = self.row.attributes.get("ID") {
+                transcript.row.attributes.insert("Parent".to_string(), id.clone());
+            }
+        }
+        if let (Some(start), Some(end)) = (self.transcripts.iter().map(|t| t.row.start).min(), self.transcripts.iter().map(|t| t.row.end).max()) {
+            self.row.start = start;
+            self.row.end = end;
+        }
+    }
+
+    /// Moves the transcripts of `other` into this gene, which must be on the same sequence and strand
+    pub fn merge(&mut self, other: Gene<T>) -> Result<(), Error> {
+        if other.row.seqid != self.row.seqid || other.row.strand != self.row.strand {
+            return Err(Error::InvalidArguments("genes can only be merged if they are on the same sequence and strand".to_string()));
+        }
+        self.transcripts.extend(other.transcripts);
+        self.rederive();
+        Ok(())
+    }
+}
+
+impl<T: Clone> Gene<T> {
+    /// Splits the gene before the 1-based position `at`, keeping the part before it and returning the part after
+    ///
+    /// Every part of a transcript must be on one side of the split, so transcripts can be split
+    /// between exons but not within them. The gene is left unchanged if the split fails. Transcripts
+    /// in the new gene, and their parts, are given IDs derived from `new_gene_id` e.g.
+    /// `<new_gene_id>.t1` and `<new_gene_id>.t1.exon1`.
+    pub fn split(&mut self, at: u64, new_gene_id: &str) -> Result<Gene<T>, Error> {
+        // nothing is changed unless the split succeeds
+        let mut parts = self.transcripts.iter().flat_map(|t| t.exons.iter().chain(&t.cds).chain(&t.others));
+        if let Some(part) = parts.find(|r| r.start < at && r.end >= at) {
+            return Err(Error::InvalidArguments(format!("{} feature at {}-{} spans the split", part.seqid, part.start, part.end)));
+        }
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for transcript in &self.transcripts {
+            let split_part = |keep: &dyn Fn(&Gff3Row<T>) -> bool| Transcript {
+                row: transcript.row.clone(),
+                exons: transcript.exons.iter().filter(|r| keep(r)).cloned().collect(),
+                cds: transcript.cds.iter().filter(|r| keep(r)).cloned().collect(),
+                others: transcript.others.iter().filter(|r| keep(r)).cloned().collect(),
+            };
+            let before = split_part(&|r| r.end < at);
+            let after = split_part(&|r| r.start >= at);
+            let has_parts = |t: &Transcript<T>| !(t.exons.is_empty() && t.cds.is_empty() && t.others.is_empty());
+            if has_parts(&before) || transcript.row.end < at {
+                left.push(before);
+            }
+            if has_parts(&after) || transcript.row.start >= at {
+                right.push(after);
+            }
+        }
+        if left.is_empty() || right.is_empty() {
+            return Err(Error::InvalidArguments(format!("splitting at {} would leave a gene without transcripts", at)));
+        }
+
+        let mut new_gene = Gene {
+            row: self.row.clone(),
+            transcripts: right,
+        };
+        new_gene.row.attributes.insert("ID".to_string(), new_gene_id.to_string());
+        new_gene.row.attributes.shift_remove("Name");
+        for (i, transcript) in new_gene.transcripts.iter_mut().enumerate() {
+            let tx_id = format!("{}.t{}", new_gene_id, i + 1);
+            transcript.row.attributes.insert("ID".to_string(), tx_id.clone());
+            transcript.row.attributes.shift_remove("Name");
+            for (n, exon) in transcript.exons.iter_mut().enumerate() {
+                exon.attributes.insert("ID".to_string(), format!("{}.exon{}", tx_id, n + 1));
+            }
+            for cds in &mut transcript.cds {
+                cds.attributes.insert("ID".to_string(), format!("{}.cds", tx_id));
+            }
+            for other in &mut transcript.others {
+                other.attributes.shift_remove("ID");
+            }
+        }
+        new_gene.rederive();
+        self.transcripts = left;
+        self.rederive();
+        Ok(new_gene)
+    }
+}
+
+/// The state of a feature before or after a change
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureState {
+    pub id: Option<String>,
+    pub feature_type: String,
+    pub seqid: SeqId,
+    pub start: u64,
+    pub end: u64,
+    pub strand: Strand,
+    pub phase: String,
+}
+
+impl<T: AsRef<str>> From<&Gff3Row<T>> for FeatureState {
+    fn from(row: &Gff3Row<T>) -> Self {
+        FeatureState {
+            id: row.attributes.get("ID").cloned(),
+            feature_type: row.feature_type.as_ref().to_string(),
+            seqid: row.seqid.clone(),
+            start: row.start,
+            end: row.end,
+            strand: row.strand,
+            phase: row.phase.clone(),
+        }
+    }
+}
+
+/// A feature whose coordinates or phase changed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Modification {
+    pub before: FeatureState,
+    pub after: FeatureState,
+}
+
+/// The features added, removed and modified by one edit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /// The name of the edit e.g. `add_exon`
+    pub operation: String,
+    /// The ID of the transcript or gene that was edited
+    pub target: Option<String>,
+    pub added: Vec<FeatureState>,
+    pub removed: Vec<FeatureState>,
+    pub modified: Vec<Modification>,
+}
+
+/// A record of edits made to gene models, for review of a curation pass
+///
+/// Edits made through the log are applied with the methods above, and the features they
+/// change are recorded. The log can be exported as JSON by serializing it e.g. with `serde_json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ChangeLog {
+    pub changes: Vec<Change>,
+}
+
+fn feature_states<'a, T: AsRef<str> + 'a, I: IntoIterator<Item = &'a Gff3Row<T>>>(rows: I) -> Vec<FeatureState> {
+    rows.into_iter().map(FeatureState::from).collect()
+}
+
+fn transcript_states<T: AsRef<str>>(transcript: &Transcript<T>) -> Vec<FeatureState> {
+    feature_states(std::iter::once(&transcript.row).chain(&transcript.exons).chain(&transcript.cds).chain(&transcript.others))
+}
+
+fn gene_states<T: AsRef<str>>(gene: &Gene<T>) -> Vec<FeatureState> {
+    let mut states = vec![FeatureState::from(&gene.row)];
+    states.extend(gene.transcripts.iter().flat_map(transcript_states));
+    states
+}
+
+impl ChangeLog {
+    /// Records the differences between the features before and after an edit
+    ///
+    /// Features are matched by type and ID, and features sharing an ID, such as CDS segments, by their order.
+    fn record(&mut self, operation: &str, target: Option<String>, before: Vec<FeatureState>, after: Vec<FeatureState>) {
+        let key = |states: &[FeatureState], i: usize| {
+            let state = &states[i];
+            let occurrence = states[..i].iter().filter(|s| s.feature_type == state.feature_type && s.id == state.id).count();
+            (state.feature_type.clone(), state.id.clone(), occurrence)
+        };
+        let before_keys: Vec<_> = (0..before.len()).map(|i| key(&before, i)).collect();
+        let after_keys: Vec<_> = (0..after.len()).map(|i| key(&after, i)).collect();
+        let mut change = Change {
+            operation: operation.to_string(),
+            target,
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+        };
+        for (i, state) in before.iter().enumerate() {
+            match after_keys.iter().position(|k| *k == before_keys[i]) {
+                Some(j) if after[j] != *state => change.modified.push(Modification { before: state.clone(), after: after[j].clone() }),
+                Some(_) => {}
+                None => change.removed.push(state.clone()),
+            }
+        }
+        for (j, state) in after.iter().enumerate() {
+            if !before_keys.contains(&after_keys[j]) {
+                change.added.push(state.clone());
+            }
+        }
+        self.changes.push(change);
+    }
+
+    fn transcript_edit<T, R, F>(&mut self, operation: &str, gene: &mut Gene<T>, transcript_id: &str, edit: F) -> Result<R, Error>
+    where
+        T: AsRef<str>,
+        F: FnOnce(&mut Gene<T>) -> Result<R, Error>,
+    {
+        let before = gene_states(gene);
+        let result = edit(gene)?;
+        self.record(operation, Some(transcript_id.to_string()), before, gene_states(gene));
+        Ok(result)
+    }
+
+    /// Adds an exon with [`Gene::add_exon`]
+    pub fn add_exon<T>(&mut self, gene: &mut Gene<T>, transcript_id: &str, range: &GenomicRange) -> Result<String, Error>
+    where
+        T: AsRef<str> + Clone + for<'a> From<&'a str>,
+    {
+        self.transcript_edit("add_exon", gene, transcript_id, |g| g.add_exon(transcript_id, range))
+    }
+
+    /// Removes an exon with [`Gene::remove_exon`]
+    pub fn remove_exon<T>(&mut self, gene: &mut Gene<T>, transcript_id: &str, index: usize) -> Result<Gff3Row<T>, Error>
+    where
+        T: AsRef<str> + Clone + for<'a> From<&'a str>,
+    {
+        self.transcript_edit("remove_exon", gene, transcript_id, |g| g.remove_exon(transcript_id, index))
+    }
+
+    /// Sets the CDS with [`Gene::set_cds`]
+    pub fn set_cds<T>(&mut self, gene: &mut Gene<T>, transcript_id: &str, range: Option<&GenomicRange>) -> Result<(), Error>
+    where
+        T: AsRef<str> + Clone + for<'a> From<&'a str>,
+    {
+        self.transcript_edit("set_cds", gene, transcript_id, |g| g.set_cds(transcript_id, range))
+    }
+
+    /// Merges genes with [`Gene::merge`]
+    pub fn merge_genes<T: AsRef<str>>(&mut self, gene: &mut Gene<T>, other: Gene<T>) -> Result<(), Error> {
+        let mut before = gene_states(gene);
+        before.extend(gene_states(&other));
+        gene.merge(other)?;
+        self.record("merge_genes", gene.id().map(|id| id.to_string()), before, gene_states(gene));
+        Ok(())
+    }
+
+    /// Splits a gene with [`Gene::split`]
+    pub fn split_gene<T: AsRef<str> + Clone>(&mut self, gene: &mut Gene<T>, at: u64, new_gene_id: &str) -> Result<Gene<T>, Error> {
+        let before = gene_states(gene);
+        let new_gene = gene.split(at, new_gene_id)?;
+        let mut after = gene_states(gene);
+        after.extend(gene_states(&new_gene));
+        self.record("split_gene", gene.id().map(|id| id.to_string()), before, after);
+        Ok(new_gene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    fn gene() -> Gene<String> {
+        let gff = "\
+chr1\t.\tgene\t1\t2000\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t1\t2000\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t1000\t.\t+\t.\tID=t1.exon1;Parent=t1
+chr1\t.\texon\t1500\t2000\t.\t+\t.\tID=t1.exon2;Parent=t1
+chr1\t.\tmRNA\t1\t1000\t.\t+\t.\tID=t2;Parent=g1
+chr1\t.\texon\t1\t1000\t.\t+\t.\tID=t2.exon1;Parent=t2
+";
+        build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap().remove(0)
+    }
+
+    #[test]
+    fn split_between_exons() {
+        let mut gene = gene();
+        let new_gene = gene.split(1200, "g2").unwrap();
+        assert_eq!((gene.row.start, gene.row.end, gene.transcripts.len()), (1, 1000, 2));
+        assert_eq!((new_gene.row.start, new_gene.row.end, new_gene.transcripts.len()), (1500, 2000, 1));
+        assert_eq!(new_gene.transcripts[0].exons[0].attributes["ID"], "g2.t1.exon1");
+        assert_eq!(new_gene.transcripts[0].exons[0].attributes["Parent"], "g2.t1");
+    }
+
+    #[test]
+    fn transcript_edits_update_gene_bounds() {
+        let mut gene = gene();
+        let mut log = ChangeLog::default();
+        let id = log.add_exon(&mut gene, "t2", &"chr1:2100-2500".parse().unwrap()).unwrap();
+        assert_eq!(id, "t2.exon2");
+        assert_eq!((gene.transcripts[1].row.end, gene.row.end), (2500, 2500));
+        let modified: Vec<_> = log.changes[0].modified.iter().map(|m| m.after.id.as_deref().unwrap()).collect();
+        assert_eq!(modified, ["g1", "t2"]);
+
+        gene.set_cds("t1", Some(&"chr1:901-1600".parse().unwrap())).unwrap();
+        let phases: Vec<_> = gene.transcripts[0].cds.iter().map(|c| (c.start, c.end, c.phase.as_str())).collect();
+        assert_eq!(phases, [(901, 1000, "0"), (1500, 1600, "2")]);
+
+        gene.remove_exon("t2", 1).unwrap();
+        assert_eq!(gene.row.end, 2000);
+        assert!(gene.remove_exon("t3", 0).is_err());
+    }
+
+    #[test]
+    fn failed_split_leaves_gene_unchanged() {
+        let mut gene = gene();
+        assert!(gene.split(300, "g2").is_err());
+        assert_eq!(gene.transcripts.len(), 2);
+        assert_eq!(gene.transcripts[0].exons.len(), 2);
+
+        let mut log = ChangeLog::default();
+        assert!(log.split_gene(&mut gene, 300, "g2").is_err());
+        assert_eq!(gene.transcripts.len(), 2);
+        assert!(log.changes.is_empty());
+    }
+
+    #[test]
+    fn split_then_merge_restores_the_gene() {
+        let mut gene = gene();
+        let new_gene = gene.split(1200, "g2").unwrap();
+        let mut log = ChangeLog::default();
+        log.merge_genes(&mut gene, new_gene).unwrap();
+        assert_eq!((gene.row.start, gene.row.end, gene.transcripts.len()), (1, 2000, 3));
+        assert_eq!(gene.transcripts[2].row.attributes["Parent"], "g1");
+        assert_eq!(log.changes[0].operation, "merge_genes");
+
+        let mut other = Gene { row: gene.row.clone(), transcripts: Vec::new() };
+        other.row.strand = Strand::Minus;
+        assert!(gene.merge(other).is_err());
+    }
+}
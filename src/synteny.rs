@@ -0,0 +1,216 @@
+//! Microsynteny between two annotated genomes
+//!
+//! Given orthologous gene pairs, [`find_synteny`] finds blocks where the orthologs are in the same
+//! order in both genomes, allowing a few unrelated genes in between, as in MCScanX. Genes are counted by
+//! their rank along each sequence rather than by bases, so that blocks are found between genomes of
+//! different gene densities. Blocks may be inverted in the second genome, and the gaps between the
+//! blocks along the first genome are breakpoints of synteny.
+
+use indexmap::IndexMap;
+
+use crate::format::Strand;
+use crate::genome::{Error, GenomicRange, SeqId};
+use crate::models::Gene;
+
+/// Parses orthologous gene pairs, as two tab-separated columns of gene IDs in the first and second genome
+///
+/// Blank lines and `#` comments are skipped, as are any further columns.
+pub fn parse_orthologs(text: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut pairs = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t').map(|f| f.trim());
+        match (fields.next(), fields.next()) {
+            (Some(first), Some(second)) if !first.is_empty() && !second.is_empty() => pairs.push((first.to_string(), second.to_string())),
+            _ => return Err(Error::InvalidArguments(format!("line {}: ortholog lines should have 2 gene IDs", line_num + 1))),
+        }
+    }
+    Ok(pairs)
+}
+
+/// Options for [`find_synteny`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntenyOptions {
+    /// The most genes in either genome that can be skipped between consecutive orthologs of a block
+    pub max_gap: usize,
+    /// The fewest ortholog pairs in a block
+    pub min_anchors: usize,
+}
+
+impl Default for SyntenyOptions {
+    fn default() -> Self {
+        SyntenyOptions { max_gap: 25, min_anchors: 5 }
+    }
+}
+
+/// A run of orthologs in the same order in both genomes, as paired ranges spanning them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntenyBlock {
+    pub first: GenomicRange,
+    pub second: GenomicRange,
+    /// [`Strand::Minus`] if the order is reversed in the second genome
+    pub orientation: Strand,
+    /// The ortholog pairs, in order along the first genome
+    pub anchors: Vec<(String, String)>,
+}
+
+/// The synteny blocks, in order along the first genome, with the breakpoints between them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Synteny {
+    pub blocks: Vec<SyntenyBlock>,
+    /// The ranges of the first genome between consecutive blocks on the same sequence, which are empty if
+    /// the blocks are adjacent or overlap
+    pub breakpoints: Vec<GenomicRange>,
+}
+
+/// The sequence and rank along it of each gene with an ID, with its range
+fn rank_genes<T>(genes: &[Gene<T>]) -> IndexMap<&str, (SeqId, usize, GenomicRange)> {
+    let mut ranges: Vec<(GenomicRange, &str)> = genes.iter()
+        .filter_map(|gene| gene.id().map(|id| (gene.range(), id)))
+        .collect();
+    ranges.sort();
+    let mut ranks = IndexMap::new();
+    let mut rank = 0;
+    for (i, (range, id)) in ranges.iter().enumerate() {
+        rank = if i > 0 && ranges[i - 1].0.seqid() == range.seqid() { rank + 1 } else { 0 };
+        ranks.insert(*id, (range.seqid().clone(), rank, range.clone()));
+    }
+    ranks
+}
+
+/// A block being built, from the positions of its anchors
+struct Chain {
+    /// The anchors as indexes of the pairs, with their ranks in each genome
+    anchors: Vec<(usize, usize, usize)>,
+    seqids: (SeqId, SeqId),
+    /// +1 or -1 once the chain has 2 anchors
+    direction: Option<i64>,
+}
+
+impl Chain {
+    /// The gap, as skipped genes in both genomes, if the anchor can extend the chain
+    fn gap(&self, seqids: (&SeqId, &SeqId), first: usize, second: usize, max_gap: usize) -> Option<usize> {
+        let (_, last_first, last_second) = *self.anchors.last()?;
+        if (&self.seqids.0, &self.seqids.1) != seqids || first <= last_first {
+            return None;
+        }
+        let step_first = first - last_first;
+        let step_second = second as i64 - last_second as i64;
+        let direction_ok = match self.direction {
+            Some(direction) => step_second.signum() == direction,
+            None => step_second != 0,
+        };
+        let skipped = (step_first - 1).max((step_second.unsigned_abs() as usize).saturating_sub(1));
+        (direction_ok && skipped <= max_gap).then_some(skipped)
+    }
+}
+
+/// Finds blocks of conserved gene order between two genomes from orthologous pairs of gene IDs
+///
+/// Genes are matched to pairs by their `ID`, and pairs with a gene missing from either genome are
+/// skipped. Each pair extends the block whose last pair it follows most closely, in order along the
+/// first genome, so one-to-many orthologs can be in several blocks.
+pub fn find_synteny<T, U>(first: &[Gene<T>], second: &[Gene<U>], orthologs: &[(String, String)], options: &SyntenyOptions) -> Synteny {
+    let (first_ranks, second_ranks) = (rank_genes(first), rank_genes(second));
+    let mut anchors: Vec<(&SeqId, usize, &SeqId, usize, usize)> = orthologs.iter()
+        .enumerate()
+        .filter_map(|(i, (a, b))| {
+            let (a_seqid, a_rank, _) = first_ranks.get(a.as_str())?;
+            let (b_seqid, b_rank, _) = second_ranks.get(b.as_str())?;
+            Some((a_seqid, *a_rank, b_seqid, *b_rank, i))
+        })
+        .collect();
+    anchors.sort();
+
+    let mut open: Vec<Chain> = Vec::new();
+    let mut closed: Vec<Chain> = Vec::new();
+    for (a_seqid, a_rank, b_seqid, b_rank, i) in anchors {
+        // chains that can no longer be extended are closed
+        let (still_open, done): (Vec<Chain>, Vec<Chain>) = open.into_iter().partition(|chain| {
+            let (_, last_first, _) = *chain.anchors.last().unwrap();
+            chain.seqids.0 == *a_seqid && a_rank - last_first <= options.max_gap + 1
+        });
+        open = still_open;
+        closed.extend(done);
+        let best = open.iter()
+            .enumerate()
+            .filter_map(|(c, chain)| chain.gap((a_seqid, b_seqid), a_rank, b_rank, options.max_gap).map(|gap| (gap, c)))
+            .min();
+        match best {
+            Some((_, c)) => {
+                let chain = &mut open[c];
+                let (_, _, last_second) = *chain.anchors.last().unwrap();
+                chain.direction.get_or_insert((b_rank as i64 - last_second as i64).signum());
+                chain.anchors.push((i, a_rank, b_rank));
+            }
+            None => open.push(Chain { anchors: vec![(i, a_rank, b_rank)], seqids: (a_seqid.clone(), b_seqid.clone()), direction: None }),
+        }
+    }
+    closed.extend(open);
+
+    let mut blocks: Vec<SyntenyBlock> = closed.into_iter()
+        .filter(|chain| chain.anchors.len() >= options.min_anchors.max(1))
+        .map(|chain| {
+            let pairs: Vec<&(String, String)> = chain.anchors.iter().map(|(i, _, _)| &orthologs[*i]).collect();
+            let span = |ranges: Vec<&GenomicRange>| ranges.iter().skip(1).fold(ranges[0].clone(), |span, r| span.combine(r).unwrap());
+            SyntenyBlock {
+                first: span(pairs.iter().map(|(a, _)| &first_ranks[a.as_str()].2).collect()),
+                second: span(pairs.iter().map(|(_, b)| &second_ranks[b.as_str()].2).collect()),
+                orientation: if chain.direction == Some(-1) { Strand::Minus } else { Strand::Plus },
+                anchors: pairs.into_iter().cloned().collect(),
+            }
+        })
+        .collect();
+    blocks.sort_by(|a, b| (&a.first, &a.second).cmp(&(&b.first, &b.second)));
+
+    let breakpoints = blocks.windows(2)
+        .filter(|pair| pair[0].first.seqid() == pair[1].first.seqid())
+        .map(|pair| {
+            let start = pair[0].first.range_0halfopen().end;
+            let end = pair[1].first.range_0halfopen().start.max(start);
+            GenomicRange::from_0halfopen(pair[0].first.seqid().clone(), start..end).unwrap()
+        })
+        .collect();
+    Synteny { blocks, breakpoints }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    /// Genes of 50 bases, 100 bases apart, in the order given
+    fn genes(seqid: &str, ids: &[&str]) -> Vec<Gene<String>> {
+        let gff: String = ids.iter().enumerate()
+            .map(|(i, id)| format!("{}\t.\tgene\t{}\t{}\t.\t+\t.\tID={}\n", seqid, i * 100 + 1, i * 100 + 50, id))
+            .collect();
+        build_gene_models(parse_gff3(&gff, ParseMode::Strict).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn finds_forward_and_inverted_blocks() {
+        let first = genes("chrA", &["a1", "a2", "a3", "a4", "a5", "a6", "a7", "a8"]);
+        let second = genes("chrB", &["b1", "b2", "bx", "b3", "b4", "b8", "b7", "b6", "b5"]);
+        let mut text: String = (1..=8).map(|i| format!("a{}\tb{}\n", i, i)).collect();
+        text.push_str("# unknown genes are skipped\na9\tb9\n");
+        let orthologs = parse_orthologs(&text).unwrap();
+        assert_eq!(orthologs.len(), 9);
+
+        let synteny = find_synteny(&first, &second, &orthologs, &SyntenyOptions { max_gap: 2, min_anchors: 3 });
+        let blocks: Vec<_> = synteny.blocks.iter()
+            .map(|b| (b.first.to_string(), b.second.to_string(), b.orientation, b.anchors.len()))
+            .collect();
+        assert_eq!(blocks, [
+            ("chrA:1-350".to_string(), "chrB:1-450".to_string(), Strand::Plus, 4),
+            ("chrA:401-750".to_string(), "chrB:501-850".to_string(), Strand::Minus, 4),
+        ]);
+        assert_eq!(synteny.breakpoints.iter().map(|r| r.to_string()).collect::<Vec<_>>(), ["chrA:351-400"]);
+
+        let strict = find_synteny(&first, &second, &orthologs, &SyntenyOptions { max_gap: 0, min_anchors: 3 });
+        assert_eq!(strict.blocks.len(), 1);
+        assert!(parse_orthologs("a1\n").is_err());
+    }
+}
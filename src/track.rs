@@ -0,0 +1,299 @@
+//! Per-base score tracks e.g. conservation (phastCons, phyloP) or signal
+//!
+//! A [`ScoreTrack`] holds runs of constant score, as loaded from BedGraph rows or other
+//! [`DataInterval`]s, and answers per-base and summary queries for [`GenomicRange`]s. Runs with outlying
+//! scores, such as artifacts or hotspots in binned signal, can be found by their z-scores.
+
+use std::hash::Hash;
+use indexmap::IndexMap;
+use num_traits::{NumOps, ToPrimitive};
+
+use crate::format::{BedGraphRow, DataInterval};
+use crate::genome::{Error, GenomicRange, SeqId};
+
+/// Runs of constant score along each sequence
+///
+/// Bases that aren't in any run have no score, as with gaps in a bigWig or BedGraph file.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreTrack {
+    // 0-based, half-open runs, sorted and non-overlapping
+    runs: IndexMap<SeqId, Vec<(u64, u64, f64)>>,
+}
+
+/// Summary statistics of the scores over one or more ranges
+///
+/// The statistics are over the bases that have a score, and are `None` when there are none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreSummary {
+    /// The number of bases queried
+    pub bases: u64,
+    /// The number of bases that have a score
+    pub covered: u64,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Default for ScoreSummary {
+    fn default() -> Self {
+        ScoreSummary {
+            bases: 0,
+            covered: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl ScoreSummary {
+    pub fn mean(&self) -> Option<f64> {
+        (self.covered > 0).then(|| self.sum / self.covered as f64)
+    }
+
+    /// The fraction of queried bases that have a score
+    pub fn coverage(&self) -> f64 {
+        if self.bases == 0 { 0.0 } else { self.covered as f64 / self.bases as f64 }
+    }
+
+    /// Combines the statistics of two sets of ranges
+    pub fn combine(&self, other: &ScoreSummary) -> ScoreSummary {
+        let pick = |a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64| match (a, b) {
+            (Some(a), Some(b)) => Some(f(a, b)),
+            (a, b) => a.or(b),
+        };
+        ScoreSummary {
+            bases: self.bases + other.bases,
+            covered: self.covered + other.covered,
+            sum: self.sum + other.sum,
+            min: pick(self.min, other.min, f64::min),
+            max: pick(self.max, other.max, f64::max),
+        }
+    }
+}
+
+/// The baseline that scores are compared to when looking for outliers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Baseline {
+    /// The mean and standard deviation of every run in the track
+    Global,
+    /// The mean and standard deviation of up to `window` runs on either side, on the same sequence
+    Local { window: usize },
+}
+
+/// A run whose score is an outlier from the baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outlier {
+    pub range: GenomicRange,
+    pub score: f64,
+    /// The number of standard deviations from the baseline mean, negative below it
+    pub z_score: f64,
+}
+
+impl Outlier {
+    /// A BedGraph row with the z-score as the value
+    pub fn to_bedgraph_row(&self) -> BedGraphRow<f64> {
+        let range = self.range.range_0halfopen();
+        BedGraphRow {
+            chrom: self.range.seqid().clone(),
+            chrom_start: range.start,
+            chrom_end: range.end,
+            data_value: self.z_score,
+        }
+    }
+}
+
+/// The mean and sample standard deviation from the count, sum and sum of squares
+fn mean_sd(n: usize, sum: f64, sum_sq: f64) -> Option<(f64, f64)> {
+    if n < 2 {
+        return None;
+    }
+    let mean = sum / n as f64;
+    let variance = ((sum_sq - sum * mean) / (n - 1) as f64).max(0.0);
+    (variance > 0.0).then(|| (mean, variance.sqrt()))
+}
+
+impl ScoreTrack {
+    /// Creates a track from the values in `column` of the intervals
+    ///
+    /// Intervals with no value in the column are skipped. Fails if intervals on the same sequence overlap.
+    pub fn from_intervals<T, I>(intervals: I, column: usize) -> Result<ScoreTrack, Error>
+    where
+        T: NumOps + Copy + ToPrimitive,
+        I: IntoIterator<Item = DataInterval<T>>,
+    {
+        let mut runs: IndexMap<SeqId, Vec<(u64, u64, f64)>> = IndexMap::new();
+        for interval in intervals {
+            let Some(value) = interval.values().get(column).copied().flatten() else {
+                continue;
+            };
+            let value = value.to_f64()
+                .ok_or_else(|| Error::InvalidArguments("score can't be converted to f64".to_string()))?;
+            let range = interval.range().range_0halfopen();
+            runs.entry(interval.range().seqid().clone())
+                .or_default()
+                .push((range.start, range.end, value));
+        }
+        for (seqid, seq_runs) in runs.iter_mut() {
+            seq_runs.sort_by_key(|(start, end, _)| (*start, *end));
+            if seq_runs.windows(2).any(|w| w[0].1 > w[1].0) {
+                return Err(Error::InvalidArguments(format!("score intervals on {} overlap", seqid)));
+            }
+        }
+        Ok(ScoreTrack { runs })
+    }
+
+    /// The runs overlapping the range, clipped to it
+    fn clipped_runs<'a>(&'a self, range: &GenomicRange) -> impl Iterator<Item = (u64, u64, f64)> + 'a {
+        let interval = range.range_0halfopen();
+        let runs = self.runs.get(range.seqid()).map(|r| r.as_slice()).unwrap_or_default();
+        let first = runs.partition_point(|(_, end, _)| *end <= interval.start);
+        runs[first..].iter()
+            .take_while(move |(start, _, _)| *start < interval.end)
+            .map(move |(start, end, value)| ((*start).max(interval.start), (*end).min(interval.end), *value))
+    }
+
+    /// Returns the score of each base in the range
+    pub fn values(&self, range: &GenomicRange) -> Vec<Option<f64>> {
+        let interval = range.range_0halfopen();
+        let mut values = vec![None; (interval.end - interval.start) as usize];
+        for (start, end, value) in self.clipped_runs(range) {
+            for v in &mut values[(start - interval.start) as usize..(end - interval.start) as usize] {
+                *v = Some(value);
+            }
+        }
+        values
+    }
+
+    /// Returns the score of a single 0-based position
+    pub fn value_at<T: Into<SeqId>>(&self, seqid: T, pos: u64) -> Option<f64> {
+        let range = GenomicRange::from_0halfopen(seqid, pos..pos + 1).unwrap();
+        self.clipped_runs(&range).next().map(|(_, _, value)| value)
+    }
+
+    /// Summarises the scores over the range
+    pub fn summary(&self, range: &GenomicRange) -> ScoreSummary {
+        let interval = range.range_0halfopen();
+        let mut summary = ScoreSummary {
+            bases: interval.end - interval.start,
+            ..Default::default()
+        };
+        for (start, end, value) in self.clipped_runs(range) {
+            summary = summary.combine(&ScoreSummary {
+                bases: 0,
+                covered: end - start,
+                sum: value * (end - start) as f64,
+                min: Some(value),
+                max: Some(value),
+            });
+        }
+        summary
+    }
+
+    /// Finds the runs whose score is at least `threshold` standard deviations from the baseline, in either direction
+    ///
+    /// Each run is treated as one bin, as in a track of binned signal, whatever its length. A local baseline
+    /// leaves out the run itself. Runs are skipped when their baseline has fewer than 2 runs or no variation.
+    pub fn outliers(&self, baseline: Baseline, threshold: f64) -> Vec<Outlier> {
+        let all = self.runs.values().flatten();
+        let global = mean_sd(all.clone().count(), all.clone().map(|r| r.2).sum(), all.map(|r| r.2 * r.2).sum());
+        let mut outliers = Vec::new();
+        for (seqid, runs) in &self.runs {
+            let mut prefix = vec![(0.0, 0.0)];
+            for (_, _, value) in runs {
+                let (sum, sum_sq) = prefix[prefix.len() - 1];
+                prefix.push((sum + value, sum_sq + value * value));
+            }
+            for (i, (start, end, value)) in runs.iter().enumerate() {
+                let stats = match baseline {
+                    Baseline::Global => global,
+                    Baseline::Local { window } => {
+                        let (lo, hi) = (i.saturating_sub(window), (i + window + 1).min(runs.len()));
+                        mean_sd(
+                            hi - lo - 1,
+                            prefix[hi].0 - prefix[lo].0 - value,
+                            prefix[hi].1 - prefix[lo].1 - value * value,
+                        )
+                    }
+                };
+                let Some((mean, sd)) = stats else { continue };
+                let z_score = (value - mean) / sd;
+                if z_score.abs() >= threshold {
+                    outliers.push(Outlier {
+                        range: GenomicRange::from_0halfopen(seqid.clone(), *start..*end).unwrap(),
+                        score: *value,
+                        z_score,
+                    });
+                }
+            }
+        }
+        outliers
+    }
+
+    /// Summarises the scores over each group of ranges e.g. the exons of each gene
+    ///
+    /// Groups are returned in the order they are first seen. Ranges within a group are
+    /// assumed not to overlap, otherwise the overlapping bases are counted more than once.
+    pub fn summarise_groups<K, I>(&self, ranges: I) -> IndexMap<K, ScoreSummary>
+    where
+        K: Hash + Eq,
+        I: IntoIterator<Item = (K, GenomicRange)>,
+    {
+        let mut groups: IndexMap<K, ScoreSummary> = IndexMap::new();
+        for (key, range) in ranges {
+            let summary = groups.entry(key).or_default();
+            *summary = summary.combine(&self.summary(&range));
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(runs: &[(&str, u64, u64, f64)]) -> ScoreTrack {
+        let intervals = runs.iter().map(|(seqid, start, end, value)| {
+            DataInterval::new(GenomicRange::from_0halfopen(*seqid, *start..*end).unwrap(), vec![Some(*value)])
+        });
+        ScoreTrack::from_intervals(intervals, 0).unwrap()
+    }
+
+    #[test]
+    fn queries_runs_with_gaps() {
+        let track = track(&[("chr1", 20, 30, 4.0), ("chr1", 0, 10, 2.0)]);
+        let range: GenomicRange = "chr1:9-22".parse().unwrap();
+        let values = track.values(&range);
+        assert_eq!(values.len(), 14);
+        assert_eq!((values[1], values[2], values[11], values[12]), (Some(2.0), None, None, Some(4.0)));
+        assert_eq!((track.value_at("chr1", 25), track.value_at("chr1", 15), track.value_at("chr2", 0)), (Some(4.0), None, None));
+
+        let summary = track.summary(&range);
+        assert_eq!((summary.bases, summary.covered, summary.min, summary.max), (14, 4, Some(2.0), Some(4.0)));
+        assert_eq!(summary.mean(), Some(3.0));
+
+        let groups = track.summarise_groups([("a", "chr1:1-10".parse().unwrap()), ("b", range.clone()), ("a", "chr1:21-30".parse().unwrap())]);
+        assert_eq!((groups["a"].covered, groups["a"].sum, groups["a"].coverage()), (20, 60.0, 1.0));
+    }
+
+    #[test]
+    fn overlapping_runs_are_an_error() {
+        let intervals = [("chr1", 0, 10), ("chr1", 5, 15)]
+            .map(|(seqid, start, end)| DataInterval::new(GenomicRange::from_0halfopen(seqid, start..end).unwrap(), vec![Some(1.0)]));
+        assert!(ScoreTrack::from_intervals(intervals, 0).is_err());
+    }
+
+    #[test]
+    fn finds_outlying_bins() {
+        let values = [1.0, 2.0, 1.0, 2.0, 20.0, 1.0, 2.0, 1.0];
+        let runs: Vec<_> = values.iter().enumerate().map(|(i, v)| ("chr1", i as u64 * 10, i as u64 * 10 + 10, *v)).collect();
+        let track = track(&runs);
+        for baseline in [Baseline::Global, Baseline::Local { window: 2 }] {
+            let outliers = track.outliers(baseline, 2.0);
+            assert_eq!(outliers.len(), 1);
+            assert_eq!(outliers[0].range.to_string(), "chr1:41-50");
+            assert!(outliers[0].z_score > 2.0);
+            assert_eq!(outliers[0].to_bedgraph_row().chrom_start, 40);
+        }
+    }
+}
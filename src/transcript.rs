@@ -0,0 +1,231 @@
+//! Coordinate projection through transcript models
+//!
+//! A [`TranscriptMap`] is built from the exons (or CDS segments) of a transcript and maps between
+//! genome coordinates and coordinates along the spliced transcript, counted from its 5' end.
+//! Mapping CDS segments in codons gives protein coordinates. Both directions implement
+//! [`CoordinateMap`], so records can be projected in the same way as with a liftover.
+
+use crate::format::Strand;
+use crate::genome::{Error, GenomicRange, SeqId};
+use crate::liftover::{project_pieces, CoordinateMap, LiftedRange, LiftoverOptions, UnmappedReason};
+
+/// Maps genome coordinates to coordinates along a spliced transcript
+///
+/// Transcript coordinates are 0-based and half-open on a sequence named by the transcript id.
+/// When the map is in codons, as for protein coordinates, a range covers every codon that it overlaps,
+/// and a codon split by an intron is in a single piece.
+#[derive(Debug, Clone)]
+pub struct TranscriptMap {
+    id: SeqId,
+    seqid: SeqId,
+    strand: Strand,
+    // 0-based, half-open, sorted and non-overlapping
+    segments: Vec<(u64, u64)>,
+    codons: bool,
+}
+
+impl TranscriptMap {
+    /// Creates a map from the exons or CDS segments of a transcript, which must be on the same sequence
+    ///
+    /// A transcript with [`Strand::None`] is treated as being on the plus strand.
+    pub fn new<T: Into<SeqId>>(id: T, strand: Strand, segments: &[GenomicRange]) -> Result<TranscriptMap, Error> {
+        let Some(first) = segments.first() else {
+            return Err(Error::InvalidArguments("a transcript needs at least one segment".to_string()));
+        };
+        if segments.iter().any(|s| s.seqid() != first.seqid()) {
+            return Err(Error::InvalidArguments("transcript segments should all be on the same sequence".to_string()));
+        }
+        let mut sorted: Vec<_> = segments.iter()
+            .map(|s| (s.range_0halfopen().start, s.range_0halfopen().end))
+            .collect();
+        sorted.sort();
+        if sorted.windows(2).any(|w| w[0].1 > w[1].0) {
+            return Err(Error::InvalidArguments("transcript segments should not overlap".to_string()));
+        }
+        Ok(TranscriptMap {
+            id: id.into(),
+            seqid: first.seqid().clone(),
+            strand,
+            segments: sorted,
+            codons: false,
+        })
+    }
+
+    /// Maps to codons instead of bases, so that the CDS segments of a transcript map to protein coordinates
+    pub fn in_codons(self) -> TranscriptMap {
+        TranscriptMap {
+            codons: true,
+            ..self
+        }
+    }
+
+    /// The spliced length in bases
+    pub fn len(&self) -> u64 {
+        self.segments.iter().map(|(s, e)| e - s).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the map in the opposite direction, from transcript to genome coordinates
+    pub fn inverse(&self) -> InverseTranscriptMap<'_> {
+        InverseTranscriptMap { map: self }
+    }
+
+    fn reversed(&self) -> bool {
+        self.strand == Strand::Minus
+    }
+
+    /// The segments in the order of transcription, with the transcript offset of each
+    fn transcribed(&self) -> Vec<(u64, (u64, u64))> {
+        let mut segments = self.segments.clone();
+        if self.reversed() {
+            segments.reverse();
+        }
+        let mut offset = 0;
+        segments.into_iter()
+            .map(|(s, e)| {
+                let segment = (offset, (s, e));
+                offset += e - s;
+                segment
+            })
+            .collect()
+    }
+
+    fn piece(&self, seqid: &SeqId, start: u64, end: u64) -> LiftedRange {
+        LiftedRange {
+            range: GenomicRange::from_0halfopen(seqid.clone(), start..end).unwrap(),
+            reversed: self.reversed(),
+        }
+    }
+}
+
+impl CoordinateMap for TranscriptMap {
+    fn map_pieces(&self, range: &GenomicRange) -> Vec<LiftedRange> {
+        if *range.seqid() != self.seqid {
+            return Vec::new();
+        }
+        let interval = range.range_0halfopen();
+        let mut pieces: Vec<_> = self.transcribed().into_iter()
+            .filter(|(_, (s, e))| *s < interval.end && *e > interval.start)
+            .map(|(offset, (s, e))| {
+                let (a, b) = (interval.start.max(s), interval.end.min(e));
+                let (start, end) = if self.reversed() {
+                    (offset + e - b, offset + e - a)
+                } else {
+                    (offset + a - s, offset + b - s)
+                };
+                if self.codons {
+                    self.piece(&self.id, start / 3, end.div_ceil(3))
+                } else {
+                    self.piece(&self.id, start, end)
+                }
+            })
+            .collect();
+        if self.codons {
+            // a codon split across exons is in the pieces of both
+            pieces.dedup_by(|next, piece| {
+                let (a, b) = (piece.range.range_0halfopen(), next.range.range_0halfopen());
+                if b.start > a.end {
+                    return false;
+                }
+                piece.range = GenomicRange::from_0halfopen(self.id.clone(), a.start..b.end.max(a.end)).unwrap();
+                true
+            });
+        }
+        // pieces should be in genome order
+        if self.reversed() {
+            pieces.reverse();
+        }
+        pieces
+    }
+
+    /// As for other maps, with the length of the range in codons when the map is in codons
+    fn project_range(&self, range: &GenomicRange, options: &LiftoverOptions) -> Result<Vec<LiftedRange>, UnmappedReason> {
+        let len = if self.codons { range.len().div_ceil(3) } else { range.len() };
+        project_pieces(self.map_pieces(range), len, options)
+    }
+}
+
+/// Maps transcript (or protein) coordinates back to the genome, as returned by [`TranscriptMap::inverse`]
+#[derive(Debug, Clone, Copy)]
+pub struct InverseTranscriptMap<'a> {
+    map: &'a TranscriptMap,
+}
+
+impl CoordinateMap for InverseTranscriptMap<'_> {
+    fn map_pieces(&self, range: &GenomicRange) -> Vec<LiftedRange> {
+        let map = self.map;
+        if *range.seqid() != map.id {
+            return Vec::new();
+        }
+        let interval = range.range_0halfopen();
+        let (start, end) = if map.codons {
+            (interval.start * 3, (interval.end * 3).min(map.len()))
+        } else {
+            (interval.start, interval.end)
+        };
+        map.transcribed().into_iter()
+            .filter(|(offset, (s, e))| *offset < end && offset + (e - s) > start)
+            .map(|(offset, (s, e))| {
+                let (a, b) = (start.max(offset) - offset, end.min(offset + e - s) - offset);
+                if map.reversed() {
+                    map.piece(&map.seqid, e - b, e - a)
+                } else {
+                    map.piece(&map.seqid, s + a, s + b)
+                }
+            })
+            .collect()
+    }
+
+    /// As for other maps, with the length of the range in bases when the map is in codons
+    fn project_range(&self, range: &GenomicRange, options: &LiftoverOptions) -> Result<Vec<LiftedRange>, UnmappedReason> {
+        let len = if self.map.codons { range.len() * 3 } else { range.len() };
+        project_pieces(self.map_pieces(range), len, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str) -> GenomicRange {
+        text.parse().unwrap()
+    }
+
+    fn map(strand: Strand) -> TranscriptMap {
+        TranscriptMap::new("t1", strand, &[range("chr1:101-110"), range("chr1:201-220")]).unwrap()
+    }
+
+    fn pieces(map: &impl CoordinateMap, text: &str) -> Vec<String> {
+        map.map_pieces(&range(text)).iter().map(|p| p.range.to_string()).collect()
+    }
+
+    #[test]
+    fn maps_across_introns() {
+        assert_eq!(pieces(&map(Strand::Plus), "chr1:106-205"), ["t1:6-10", "t1:11-15"]);
+        assert_eq!(pieces(&map(Strand::Minus), "chr1:106-205"), ["t1:21-25", "t1:16-20"]);
+        assert_eq!(pieces(&map(Strand::Plus).inverse(), "t1:6-15"), ["chr1:106-110", "chr1:201-205"]);
+        assert_eq!(pieces(&map(Strand::Minus).inverse(), "t1:1-5"), ["chr1:216-220"]);
+    }
+
+    #[test]
+    fn codons_split_by_an_intron_are_one_piece() {
+        let map = map(Strand::Plus).in_codons();
+        assert_eq!(pieces(&map, "chr1:101-220"), ["t1:1-10"]);
+        assert_eq!(pieces(&map, "chr1:110-110"), ["t1:4-4"]);
+        assert_eq!(pieces(&map.inverse(), "t1:4-4"), ["chr1:110-110", "chr1:201-202"]);
+    }
+
+    #[test]
+    fn projects_in_codons_with_default_options() {
+        let map = TranscriptMap::new("p1", Strand::Plus, &[range("chr1:1-30")]).unwrap().in_codons();
+        let projected = map.project_range(&range("chr1:1-30"), &LiftoverOptions::default()).unwrap();
+        assert_eq!(projected[0].range, range("p1:1-10"));
+        let options = LiftoverOptions { gap_policy: crate::liftover::GapPolicy::Drop, ..LiftoverOptions::default() };
+        assert!(map.project_range(&range("chr1:4-12"), &options).is_ok());
+        let projected = map.inverse().project_range(&range("p1:2-3"), &LiftoverOptions::default()).unwrap();
+        assert_eq!(projected[0].range, range("chr1:4-9"));
+    }
+}
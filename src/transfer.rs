@@ -0,0 +1,541 @@
+//! Transfer of gene models to a new assembly
+//!
+//! As with Liftoff, [`transfer_genes`] projects each transcript exon by exon through an alignment of
+//! the assemblies, such as chains from [`paf_to_chains`](crate::paf::paf_to_chains), so that exons
+//! are placed even when the introns between them have changed length. Each projected transcript is
+//! checked, and the draft annotation comes with a report of how much of each transcript was transferred
+//! and of any problems, such as a CDS that no longer translates. Small differences in the alignment
+//! around introns are corrected by moving exon boundaries to the nearest splice-site motifs.
+
+use std::fmt;
+
+use crate::format::{Gff3Row, Strand};
+use crate::genome::GenomicRange;
+use crate::liftover::{CoordinateMap, GapPolicy, LiftoverOptions};
+use crate::models::{Gene, Transcript};
+use crate::sequence::{reverse_complement, translate, Genome};
+
+/// Options for [`transfer_genes`]
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    /// Options for lifting each exon, CDS segment and other part, whose gaps are always spanned
+    pub liftover: LiftoverOptions,
+    /// The minimum fraction of the exonic bases of a transcript that must be aligned for it to be transferred
+    pub min_coverage: f64,
+    /// How to snap exon boundaries to splice sites, when the sequence of the new assembly is given
+    pub splice_sites: Option<SpliceSiteOptions>,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        TransferOptions {
+            liftover: LiftoverOptions { min_match: 0.5, ..LiftoverOptions::default() },
+            min_coverage: 0.5,
+            splice_sites: Some(SpliceSiteOptions::default()),
+        }
+    }
+}
+
+/// A problem with a transcript, which is only transferred if it has none of the first three
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferIssue {
+    /// Too few exonic bases are aligned
+    LowCoverage,
+    /// Exons map to different sequences or strands
+    Split,
+    /// Exons are out of order or overlap once mapped
+    ExonsOverlap,
+    /// Some exons, by number in the direction of transcription, couldn't be mapped
+    ExonsMissing(Vec<usize>),
+    /// Some CDS segments couldn't be mapped
+    CdsMissing,
+    /// The CDS length isn't a multiple of 3
+    CdsFrameshift,
+    MissingStartCodon,
+    MissingStopCodon,
+    /// The number of stop codons within the CDS
+    InternalStops(usize),
+    /// The number of introns without splice-site motifs at both ends, after snapping
+    NonCanonicalSplicing(usize),
+}
+
+impl fmt::Display for TransferIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferIssue::LowCoverage => write!(f, "low_coverage"),
+            TransferIssue::Split => write!(f, "split"),
+            TransferIssue::ExonsOverlap => write!(f, "exons_overlap"),
+            TransferIssue::ExonsMissing(exons) => {
+                let exons: Vec<String> = exons.iter().map(|e| e.to_string()).collect();
+                write!(f, "exons_missing:{}", exons.join("|"))
+            }
+            TransferIssue::CdsMissing => write!(f, "cds_missing"),
+            TransferIssue::CdsFrameshift => write!(f, "cds_frameshift"),
+            TransferIssue::MissingStartCodon => write!(f, "missing_start_codon"),
+            TransferIssue::MissingStopCodon => write!(f, "missing_stop_codon"),
+            TransferIssue::InternalStops(n) => write!(f, "internal_stops:{}", n),
+            TransferIssue::NonCanonicalSplicing(n) => write!(f, "non_canonical_splicing:{}", n),
+        }
+    }
+}
+
+/// How a transcript was transferred
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptTransfer {
+    pub gene_id: Option<String>,
+    pub transcript_id: Option<String>,
+    pub source: GenomicRange,
+    /// The range on the new assembly, or `None` if the transcript wasn't transferred
+    pub target: Option<GenomicRange>,
+    /// The fraction of exonic bases aligned
+    pub coverage: f64,
+    pub issues: Vec<TransferIssue>,
+    /// The exon boundaries moved to splice sites
+    pub adjustments: Vec<BoundaryAdjustment>,
+}
+
+/// The transferred gene models, as GFF3 rows, with a report of every transcript
+#[derive(Debug, Clone, Default)]
+pub struct Transfer {
+    pub rows: Vec<Gff3Row<String>>,
+    pub report: Vec<TranscriptTransfer>,
+}
+
+impl Transfer {
+    /// The report as TSV with a header, with the issues and adjustments separated by `,`
+    pub fn report_tsv(&self) -> String {
+        let mut tsv = "gene_id\ttranscript_id\tsource\ttarget\tcoverage\tissues\tadjustments\n".to_string();
+        let join = |items: Vec<String>| if items.is_empty() { ".".to_string() } else { items.join(",") };
+        for transfer in &self.report {
+            tsv.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{:.3}\t{}\t{}\n",
+                transfer.gene_id.as_deref().unwrap_or("."),
+                transfer.transcript_id.as_deref().unwrap_or("."),
+                transfer.source,
+                transfer.target.as_ref().map_or_else(|| ".".to_string(), |t| t.to_string()),
+                transfer.coverage,
+                join(transfer.issues.iter().map(|i| i.to_string()).collect()),
+                join(transfer.adjustments.iter().map(|a| a.to_string()).collect()),
+            ));
+        }
+        tsv
+    }
+}
+
+/// Lifts each row on its own, keeping those that map to a single range
+fn lift_rows<T: Clone, M: CoordinateMap>(rows: &[Gff3Row<T>], map: &M, options: &LiftoverOptions) -> Vec<Option<Gff3Row<T>>> {
+    rows.iter()
+        .map(|row| map.project(row, options).ok().filter(|lifted| lifted.len() == 1).and_then(|mut lifted| lifted.pop()))
+        .collect()
+}
+
+/// Transfers a transcript, returning it with its report, or `None` for the transcript if it can't be transferred
+fn transfer_transcript<T, M>(transcript: &Transcript<T>, map: &M, target: Option<&Genome>, options: &TransferOptions) -> (Option<Transcript<T>>, TranscriptTransfer)
+where
+    T: AsRef<str> + Clone,
+    M: CoordinateMap,
+{
+    let liftover = LiftoverOptions { gap_policy: GapPolicy::Span, ..options.liftover.clone() };
+    let exon_rows = if transcript.exons.is_empty() { &transcript.cds } else { &transcript.exons };
+    let aligned: u64 = exon_rows.iter()
+        .flat_map(|row| map.map_pieces(&GenomicRange::from_gff_row(row)))
+        .map(|piece| piece.range.len())
+        .sum();
+    let exonic_len = transcript.exonic_len();
+    let mut report = TranscriptTransfer {
+        gene_id: transcript.row.attributes.get("Parent").cloned(),
+        transcript_id: transcript.id().map(|id| id.to_string()),
+        source: transcript.range(),
+        target: None,
+        coverage: if exonic_len == 0 { 0.0 } else { aligned as f64 / exonic_len as f64 },
+        issues: Vec::new(),
+        adjustments: Vec::new(),
+    };
+    let lifted_exons = lift_rows(exon_rows, map, &liftover);
+    let exons: Vec<Gff3Row<T>> = lifted_exons.iter().flatten().cloned().collect();
+    if exons.is_empty() || report.coverage < options.min_coverage {
+        report.issues.push(TransferIssue::LowCoverage);
+        return (None, report);
+    }
+    if exons.iter().any(|e| e.seqid != exons[0].seqid || e.strand != exons[0].strand) {
+        report.issues.push(TransferIssue::Split);
+        return (None, report);
+    }
+
+    let mut missing: Vec<usize> = lifted_exons.iter().enumerate().filter(|(_, e)| e.is_none()).map(|(i, _)| i + 1).collect();
+    if transcript.strand() == Strand::Minus {
+        missing = missing.into_iter().map(|i| exon_rows.len() + 1 - i).rev().collect();
+    }
+    // the order of the exons along the transcript is kept, reversed if the strand is flipped
+    let reversed = exons[0].strand != transcript.strand();
+    let mut ordered = exons.clone();
+    if reversed {
+        ordered.reverse();
+    }
+    if ordered.windows(2).any(|pair| pair[0].end >= pair[1].start) {
+        report.issues.push(TransferIssue::ExonsOverlap);
+        return (None, report);
+    }
+    if !missing.is_empty() {
+        report.issues.push(TransferIssue::ExonsMissing(missing));
+    }
+
+    let lifted_cds = if transcript.exons.is_empty() { lifted_exons } else { lift_rows(&transcript.cds, map, &liftover) };
+    let mut cds: Vec<Gff3Row<T>> = lifted_cds.iter().flatten().cloned().collect();
+    if cds.len() < transcript.cds.len() {
+        report.issues.push(TransferIssue::CdsMissing);
+    }
+    let mut others: Vec<Gff3Row<T>> = lift_rows(&transcript.others, map, &liftover).into_iter().flatten().collect();
+    for rows in [&mut cds, &mut others] {
+        rows.sort_by_key(|r| (r.start, r.end));
+    }
+
+    let span = ordered.iter().skip(1).fold(GenomicRange::from_gff_row(&ordered[0]), |span, e| span.combine(&GenomicRange::from_gff_row(e)).unwrap());
+    let range = span.range_1closed();
+    let mut row = Gff3Row { seqid: span.seqid().clone(), start: *range.start(), end: *range.end(), strand: exons[0].strand, ..transcript.row.clone() };
+    row.attributes.insert("transfer_coverage".to_string(), format!("{:.3}", report.coverage));
+    let lifted = Transcript { row, exons: if transcript.exons.is_empty() { Vec::new() } else { ordered }, cds, others };
+    report.target = Some(span);
+
+    let mut lifted = lifted;
+    if let (Some(genome), Some(splice_sites)) = (target, &options.splice_sites) {
+        report.adjustments = snap_splice_sites(&mut lifted, genome, splice_sites);
+        report.target = Some(lifted.range());
+        let non_canonical = splice_site_motifs(&lifted, genome, splice_sites).iter().filter(|(donor, acceptor)| !(donor & acceptor)).count();
+        if non_canonical > 0 {
+            report.issues.push(TransferIssue::NonCanonicalSplicing(non_canonical));
+        }
+    }
+    if let (Some(genome), true) = (target, lifted.is_coding()) {
+        report.issues.extend(check_cds(&lifted, genome));
+    }
+    (Some(lifted), report)
+}
+
+/// The splice-site motifs to look for at either end of introns
+#[derive(Debug, Clone)]
+pub struct SpliceSiteOptions {
+    /// The dinucleotides at the 5' end of introns
+    pub donors: Vec<String>,
+    /// The dinucleotides at the 3' end of introns
+    pub acceptors: Vec<String>,
+    /// The furthest an exon boundary is moved, in bases
+    pub window: u64,
+}
+
+/// GT-AG introns, moving boundaries by up to 5 bases
+impl Default for SpliceSiteOptions {
+    fn default() -> Self {
+        SpliceSiteOptions { donors: vec!["GT".to_string()], acceptors: vec!["AG".to_string()], window: 5 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpliceSite {
+    Donor,
+    Acceptor,
+}
+
+/// An exon boundary moved to a splice site
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundaryAdjustment {
+    /// The number of the intron, from 1 in the direction of transcription
+    pub intron: usize,
+    pub site: SpliceSite,
+    /// The 1-based position of the exonic base next to the intron, before and after
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Written as e.g. `intron2_donor:-3`, with the shift along the forward strand
+impl fmt::Display for BoundaryAdjustment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let site = match self.site {
+            SpliceSite::Donor => "donor",
+            SpliceSite::Acceptor => "acceptor",
+        };
+        write!(f, "intron{}_{}:{:+}", self.intron, site, self.to as i64 - self.from as i64)
+    }
+}
+
+/// The motifs to look for at the left and right ends of introns on the forward strand, and which sites they are
+fn forward_motifs(strand: Strand, options: &SpliceSiteOptions) -> [(Vec<Vec<u8>>, SpliceSite); 2] {
+    let upper = |motifs: &[String]| motifs.iter().map(|m| m.as_bytes().to_ascii_uppercase()).collect::<Vec<_>>();
+    let reversed = |motifs: &[String]| upper(motifs).iter().map(|m| reverse_complement(m)).collect::<Vec<_>>();
+    match strand {
+        Strand::Minus => [(reversed(&options.acceptors), SpliceSite::Acceptor), (reversed(&options.donors), SpliceSite::Donor)],
+        _ => [(upper(&options.donors), SpliceSite::Donor), (upper(&options.acceptors), SpliceSite::Acceptor)],
+    }
+}
+
+/// Whether the sequence has one of the motifs at the 0-based position
+fn has_motif(sequence: &[u8], pos: u64, motifs: &[Vec<u8>]) -> bool {
+    motifs.iter().any(|motif| {
+        let end = pos as usize + motif.len();
+        sequence.get(pos as usize..end).is_some_and(|s| s.eq_ignore_ascii_case(motif))
+    })
+}
+
+/// Whether each intron, in order of position, has a motif at its left and right ends
+fn splice_site_motifs<T>(transcript: &Transcript<T>, genome: &Genome, options: &SpliceSiteOptions) -> Vec<(bool, bool)> {
+    let [(left_motifs, _), (right_motifs, _)] = forward_motifs(transcript.strand(), options);
+    let sequence = genome.get(&transcript.row.seqid).unwrap_or_default();
+    transcript.exons.windows(2)
+        .map(|pair| {
+            // the intron is from the end of the left exon to before the start of the right exon, 0-based
+            let (start, end) = (pair[0].end, pair[1].start - 1);
+            let right_len = right_motifs.first().map_or(2, |m| m.len() as u64);
+            (has_motif(sequence, start, &left_motifs), end >= right_len && has_motif(sequence, end - right_len, &right_motifs))
+        })
+        .collect()
+}
+
+/// Moves the exon boundaries at each end of each intron to the nearest splice-site motif within the window,
+/// returning the boundaries that were moved
+///
+/// Boundaries already at a motif, or with no motif within the window, are left in place. CDS segments
+/// ending at a moved boundary move with it, and the transcript is re-derived, updating its bounds and
+/// the CDS phases. Motifs are given on the strand of the transcript.
+pub fn snap_splice_sites<T>(transcript: &mut Transcript<T>, genome: &Genome, options: &SpliceSiteOptions) -> Vec<BoundaryAdjustment> {
+    let Some(sequence) = genome.get(&transcript.row.seqid) else {
+        return Vec::new();
+    };
+    let [(left_motifs, left_site), (right_motifs, right_site)] = forward_motifs(transcript.strand(), options);
+    let right_len = right_motifs.first().map_or(2, |m| m.len() as u64);
+    let left_len = left_motifs.first().map_or(2, |m| m.len() as u64);
+    transcript.exons.sort_by_key(|r| r.start);
+    let n_introns = transcript.exons.len().saturating_sub(1);
+    let minus = transcript.strand() == Strand::Minus;
+    let intron_number = |i: usize| if minus { n_introns - i } else { i + 1 };
+    // shifts in order of distance, preferring to shorten exons, so moving ends left and starts right
+    let window = options.window as i64;
+    let end_shifts: Vec<i64> = (0..=window).flat_map(|d| if d == 0 { vec![0] } else { vec![-d, d] }).collect();
+    let start_shifts: Vec<i64> = end_shifts.iter().map(|d| -d).collect();
+
+    let mut adjustments = Vec::new();
+    for i in 0..n_introns {
+        // the left exon's end, as the 0-based start of the intron
+        let (left_start, old_end, right_start, right_end) = (transcript.exons[i].start, transcript.exons[i].end, transcript.exons[i + 1].start, transcript.exons[i + 1].end);
+        let new_end = end_shifts.iter()
+            .map(|d| old_end as i64 + d)
+            .filter(|end| *end >= left_start as i64 && *end + ((left_len + right_len) as i64) < right_start as i64)
+            .find(|end| has_motif(sequence, *end as u64, &left_motifs))
+            .map_or(old_end, |end| end as u64);
+        // the right exon's start, with the intron ending 0-based at start - 1
+        let new_start = start_shifts.iter()
+            .map(|d| right_start as i64 + d)
+            .filter(|start| *start <= right_end as i64 && *start - (left_len + right_len) as i64 > new_end as i64)
+            .find(|start| has_motif(sequence, (*start - 1) as u64 - right_len, &right_motifs))
+            .map_or(right_start, |start| start as u64);
+        for (site, from, to) in [(left_site, old_end, new_end), (right_site, right_start, new_start)] {
+            if from != to {
+                adjustments.push(BoundaryAdjustment { intron: intron_number(i), site, from, to });
+            }
+        }
+        for cds in transcript.cds.iter_mut() {
+            if cds.end == old_end { cds.end = new_end }
+            if cds.start == right_start { cds.start = new_start }
+        }
+        transcript.exons[i].end = new_end;
+        transcript.exons[i + 1].start = new_start;
+    }
+    if !adjustments.is_empty() {
+        transcript.rederive();
+    }
+    adjustments.sort_by_key(|a| (a.intron, a.site == SpliceSite::Acceptor));
+    adjustments
+}
+
+/// Checks that the CDS of a transcript is a complete ORF, from a start codon to a stop codon with no stops between
+fn check_cds<T>(transcript: &Transcript<T>, genome: &Genome) -> Vec<TransferIssue> {
+    let mut issues = Vec::new();
+    let Ok(sequence) = transcript.cds_sequence(genome) else {
+        return vec![TransferIssue::CdsMissing];
+    };
+    if sequence.len() % 3 != 0 {
+        issues.push(TransferIssue::CdsFrameshift);
+    }
+    let protein = translate(&sequence);
+    if protein.first() != Some(&b'M') {
+        issues.push(TransferIssue::MissingStartCodon);
+    }
+    if protein.last() != Some(&b'*') {
+        issues.push(TransferIssue::MissingStopCodon);
+    }
+    let internal = protein.iter().take(protein.len().saturating_sub(1)).filter(|aa| **aa == b'*').count();
+    if internal > 0 {
+        issues.push(TransferIssue::InternalStops(internal));
+    }
+    issues
+}
+
+fn owned_type<T: AsRef<str>>(row: Gff3Row<T>) -> Gff3Row<String> {
+    Gff3Row {
+        seqid: row.seqid,
+        source: row.source,
+        feature_type: row.feature_type.as_ref().to_string(),
+        start: row.start,
+        end: row.end,
+        score: row.score,
+        strand: row.strand,
+        phase: row.phase,
+        attributes: row.attributes,
+    }
+}
+
+/// Transfers gene models to a new assembly through a map from the old assembly to the new
+///
+/// Each transcript is transferred if enough of its exonic bases are aligned and its exons map to one
+/// sequence and strand without overlapping, and each gene if any of its transcripts are. Transcripts
+/// mapped to a different sequence or strand from the first of their gene are [`TransferIssue::Split`].
+/// Transferred transcripts have a `transfer_coverage` attribute. With the sequence of the new assembly,
+/// the CDS is checked for a start codon, a stop codon and no internal stops.
+pub fn transfer_genes<T, M>(genes: &[Gene<T>], map: &M, target: Option<&Genome>, options: &TransferOptions) -> Transfer
+where
+    T: AsRef<str> + Clone,
+    M: CoordinateMap,
+{
+    let mut transfer = Transfer::default();
+    for gene in genes {
+        // the transferred transcripts, with the index of their report
+        let mut transcripts = Vec::new();
+        for transcript in &gene.transcripts {
+            let (lifted, mut report) = transfer_transcript(transcript, map, target, options);
+            report.gene_id = gene.id().map(|id| id.to_string()).or(report.gene_id);
+            transcripts.extend(lifted.map(|t| (t, transfer.report.len())));
+            transfer.report.push(report);
+        }
+        // the gene spans its transferred transcripts on the sequence and strand of the first, and the
+        // transcripts mapped elsewhere split the gene, so aren't transferred
+        let Some((first, _)) = transcripts.first() else {
+            continue;
+        };
+        let (seqid, strand) = (first.row.seqid.clone(), first.row.strand);
+        let (transcripts, split): (Vec<_>, Vec<_>) = transcripts.into_iter().partition(|(t, _)| t.row.seqid == seqid && t.row.strand == strand);
+        for (_, i) in split {
+            transfer.report[i].target = None;
+            transfer.report[i].issues.push(TransferIssue::Split);
+        }
+        let transcripts: Vec<Transcript<T>> = transcripts.into_iter().map(|(t, _)| t).collect();
+        let span = transcripts.iter().skip(1).fold(transcripts[0].range(), |span, t| span.combine(&t.range()).unwrap());
+        let range = span.range_1closed();
+        let gene_row = Gff3Row { seqid, start: *range.start(), end: *range.end(), strand, ..gene.row.clone() };
+        transfer.rows.push(owned_type(gene_row));
+        for transcript in transcripts {
+            transfer.rows.push(owned_type(transcript.row));
+            let mut parts: Vec<Gff3Row<T>> = transcript.exons.into_iter().chain(transcript.cds).chain(transcript.others).collect();
+            parts.sort_by_key(|r| (r.start, r.end));
+            transfer.rows.extend(parts.into_iter().map(owned_type));
+        }
+    }
+    transfer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{parse_gff3, ParseMode};
+    use crate::models::build_gene_models;
+
+    fn gene(gff: &str) -> Gene<String> {
+        build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap().remove(0)
+    }
+
+    #[test]
+    fn snapping_prefers_to_shorten_exons() {
+        let mut sequence = vec![b'C'; 40];
+        // donors a base either side of the exon end, and acceptors a base either side of the exon start
+        sequence[9..13].copy_from_slice(b"GTGT");
+        sequence[27..31].copy_from_slice(b"AGAG");
+        let mut genome = Genome::default();
+        genome.insert("chr1", sequence);
+        let mut gene = gene("\
+chr1\t.\tgene\t1\t40\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t1\t40\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t10\t.\t+\t.\tParent=t1
+chr1\t.\texon\t31\t40\t.\t+\t.\tParent=t1
+");
+        let adjustments = snap_splice_sites(&mut gene.transcripts[0], &genome, &SpliceSiteOptions::default());
+        assert_eq!(adjustments, [
+            BoundaryAdjustment { intron: 1, site: SpliceSite::Donor, from: 10, to: 9 },
+            BoundaryAdjustment { intron: 1, site: SpliceSite::Acceptor, from: 31, to: 32 },
+        ]);
+        let exons: Vec<_> = gene.transcripts[0].exons.iter().map(|e| (e.start, e.end)).collect();
+        assert_eq!(exons, [(1, 9), (32, 40)]);
+    }
+
+    #[test]
+    fn transfers_exons_across_a_shorter_intron() {
+        let chains = crate::liftover::parse_chains("chain 100 chr1 200 + 0 90 chrN 300 + 100 170 1\n30\t30\t10\n30\n").unwrap();
+        let liftover = crate::liftover::Liftover::new(chains);
+        let mut sequence = vec![b'C'; 300];
+        let cds = format!("ATG{}TAA", "AAA".repeat(8));
+        sequence[110..130].copy_from_slice(&cds.as_bytes()[..20]);
+        sequence[130..140].copy_from_slice(b"GTCCCCCCAG");
+        sequence[140..150].copy_from_slice(&cds.as_bytes()[20..]);
+        let mut genome = Genome::default();
+        genome.insert("chrN", sequence);
+        let gff = "\
+chr1\t.\tgene\t1\t90\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t1\t90\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t30\t.\t+\t.\tParent=t1
+chr1\t.\texon\t61\t90\t.\t+\t.\tParent=t1
+chr1\t.\tCDS\t11\t30\t.\t+\t0\tID=cds1;Parent=t1
+chr1\t.\tCDS\t61\t70\t.\t+\t1\tID=cds1;Parent=t1
+chr2\t.\tgene\t1\t50\t.\t+\t.\tID=g2
+chr2\t.\tmRNA\t1\t50\t.\t+\t.\tID=t2;Parent=g2
+chr2\t.\texon\t1\t50\t.\t+\t.\tParent=t2
+";
+        let genes = build_gene_models(parse_gff3(gff, ParseMode::Strict).unwrap()).unwrap();
+        let transfer = transfer_genes(&genes, &liftover, Some(&genome), &TransferOptions::default());
+        let rows: Vec<_> = transfer.rows.iter().map(|r| (r.feature_type.as_str(), r.seqid.as_str(), r.start, r.end)).collect();
+        assert_eq!(rows, [
+            ("gene", "chrN", 101, 170),
+            ("mRNA", "chrN", 101, 170),
+            ("exon", "chrN", 101, 130),
+            ("CDS", "chrN", 111, 130),
+            ("CDS", "chrN", 141, 150),
+            ("exon", "chrN", 141, 170),
+        ]);
+        assert_eq!(transfer.report[0].issues, []);
+        assert_eq!(transfer.report[0].coverage, 1.0);
+        assert_eq!((transfer.report[1].target.as_ref(), transfer.report[1].issues.as_slice()), (None, &[TransferIssue::LowCoverage][..]));
+        assert!(transfer.report_tsv().contains("g2\tt2\tchr2:1-50\t.\t0.000\tlow_coverage\t.\n"));
+    }
+
+    #[test]
+    fn broken_cds_is_reported() {
+        let mut genome = Genome::default();
+        genome.insert("chr1", b"ATGAAATAGAAACC".to_vec());
+        let gene = gene("\
+chr1\t.\tgene\t1\t14\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t1\t14\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t14\t.\t+\t.\tParent=t1
+chr1\t.\tCDS\t1\t14\t.\t+\t0\tParent=t1
+");
+        assert_eq!(check_cds(&gene.transcripts[0], &genome), [
+            TransferIssue::CdsFrameshift,
+            TransferIssue::MissingStopCodon,
+            TransferIssue::InternalStops(1),
+        ]);
+    }
+
+    #[test]
+    fn transcripts_mapped_to_another_strand_are_split() {
+        let chains = "chain 100 chr1 200 + 0 50 chrN 100 + 0 50 1\n50\n\nchain 90 chr1 200 + 50 100 chrN 100 - 0 50 2\n50\n";
+        let liftover = crate::liftover::Liftover::new(crate::liftover::parse_chains(chains).unwrap());
+        let gene = gene("\
+chr1\t.\tgene\t1\t90\t.\t+\t.\tID=g1
+chr1\t.\tmRNA\t1\t30\t.\t+\t.\tID=t1;Parent=g1
+chr1\t.\texon\t1\t30\t.\t+\t.\tParent=t1
+chr1\t.\tmRNA\t61\t90\t.\t+\t.\tID=t2;Parent=g1
+chr1\t.\texon\t61\t90\t.\t+\t.\tParent=t2
+");
+        let transfer = transfer_genes(&[gene], &liftover, None, &TransferOptions::default());
+        let rows: Vec<_> = transfer.rows.iter().map(|r| (r.feature_type.as_str(), r.start, r.end, r.strand)).collect();
+        assert_eq!(rows, [("gene", 1, 30, Strand::Plus), ("mRNA", 1, 30, Strand::Plus), ("exon", 1, 30, Strand::Plus)]);
+        assert_eq!(transfer.report[0].target, Some("chrN:1-30".parse().unwrap()));
+        assert_eq!((transfer.report[1].target.as_ref(), transfer.report[1].issues.as_slice()), (None, &[TransferIssue::Split][..]));
+        assert!(transfer.report_tsv().contains("g1\tt2\tchr1:61-90\t.\t1.000\tsplit\t.\n"));
+    }
+}